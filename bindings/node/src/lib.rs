@@ -0,0 +1,123 @@
+use std::sync::Arc;
+
+use napi::{
+    Env, Task,
+    bindgen_prelude::{AsyncTask, Buffer},
+};
+use napi_derive::napi;
+use piper_tts_rust::{Model, OutputFormat, PhonemeGen, SampleFormat, SynthesisOptions, Synthesizer, voices};
+
+fn js_error(error: piper_tts_rust::PiperError) -> napi::Error {
+    napi::Error::from_reason(error.to_string())
+}
+
+/// Where to load the engine from, unset paths fall back to the CLI defaults.
+#[napi(object)]
+pub struct PiperConfig {
+    /// Voice `.onnx` model file or name of a downloaded voice.
+    pub model: String,
+    /// Defaults to the model path with `.json` appended.
+    pub config: Option<String>,
+    /// Directory with the G2P encoder, decoder, tokenizer and vocabulary,
+    /// defaults to `models/g2p`.
+    pub g2p_dir: Option<String>,
+    /// Defaults to `arpabet-mapping.txt`.
+    pub arpabet_mapping: Option<String>,
+    /// Speaker name or id of a multi-speaker voice.
+    pub speaker: Option<String>,
+}
+
+/// Per-call overrides, unset fields fall back to the voice config.
+#[napi(object)]
+pub struct SynthesizeOptions {
+    pub length_scale: Option<f64>,
+    pub noise_scale: Option<f64>,
+    pub noise_w: Option<f64>,
+}
+
+pub struct Load {
+    config: Option<PiperConfig>,
+}
+
+impl Task for Load {
+    type Output = Synthesizer;
+    type JsValue = Piper;
+
+    fn compute(&mut self) -> napi::Result<Synthesizer> {
+        let config = self.config.take().expect("load task computed twice");
+        let dir = config.g2p_dir.as_deref().unwrap_or("models/g2p");
+        let mut phoneme_gen = PhonemeGen::new(
+            format!("{}/decoder_model_mini_bart_g2p.onnx", dir),
+            format!("{}/encoder_model_mini_bart_g2p.onnx", dir),
+            format!("{}/tokenizer.json", dir),
+            format!("{}/vocab.json", dir),
+            config.arpabet_mapping.unwrap_or_else(|| "arpabet-mapping.txt".to_string()),
+        );
+        phoneme_gen.load().map_err(js_error)?;
+
+        let (model_path, config_path) = voices::resolve(&config.model, config.config.as_deref(), &voices::default_dirs())
+            .map_err(js_error)?;
+        let mut model = Model::new(&model_path, &config_path).map_err(js_error)?;
+        if let Some(speaker) = &config.speaker {
+            model.set_speaker(speaker).map_err(js_error)?;
+        }
+        Ok(Synthesizer::new(phoneme_gen, model))
+    }
+
+    fn resolve(&mut self, _env: Env, synthesizer: Synthesizer) -> napi::Result<Piper> {
+        Ok(Piper { synthesizer: Arc::new(synthesizer) })
+    }
+}
+
+pub struct Synthesize {
+    synthesizer: Arc<Synthesizer>,
+    text: String,
+    options: SynthesisOptions,
+}
+
+impl Task for Synthesize {
+    type Output = Vec<u8>;
+    type JsValue = Buffer;
+
+    fn compute(&mut self) -> napi::Result<Vec<u8>> {
+        let waveform = self.synthesizer.synthesize_with(&self.text, &self.options).map_err(js_error)?;
+        OutputFormat::Wav.encode(&waveform, self.synthesizer.sample_rate(), SampleFormat::I16).map_err(js_error)
+    }
+
+    fn resolve(&mut self, _env: Env, wav: Vec<u8>) -> napi::Result<Buffer> {
+        Ok(wav.into())
+    }
+}
+
+/// A loaded voice and G2P model. Synthesis runs on the libuv thread pool,
+/// so concurrent calls don't block the event loop.
+#[napi]
+pub struct Piper {
+    synthesizer: Arc<Synthesizer>,
+}
+
+#[napi]
+impl Piper {
+    /// Loads the models off the main thread.
+    #[napi(ts_return_type = "Promise<Piper>")]
+    pub fn load(config: PiperConfig) -> AsyncTask<Load> {
+        AsyncTask::new(Load { config: Some(config) })
+    }
+
+    #[napi(getter)]
+    pub fn sample_rate(&self) -> u32 {
+        self.synthesizer.sample_rate() as u32
+    }
+
+    /// Synthesizes `text` into the bytes of a 16-bit WAV file.
+    #[napi(ts_return_type = "Promise<Buffer>")]
+    pub fn synthesize(&self, text: String, options: Option<SynthesizeOptions>) -> AsyncTask<Synthesize> {
+        let options = options.map_or_else(SynthesisOptions::default, |options| SynthesisOptions {
+            length_scale: options.length_scale.map(|value| value as f32),
+            noise_scale: options.noise_scale.map(|value| value as f32),
+            noise_w: options.noise_w.map(|value| value as f32),
+            ..Default::default()
+        });
+        AsyncTask::new(Synthesize { synthesizer: self.synthesizer.clone(), text, options })
+    }
+}
@@ -1,6 +1,6 @@
 use piper_tts_rust::{PhonemeGen, Model};
 
-fn main() -> ort::Result<()> {    
+fn main() -> piper_tts_rust::Result<()> {    
     let decoder_path = "models/g2p/decoder_model_mini_bart_g2p.onnx".to_string();
     let encoder_path = "models/g2p/encoder_model_mini_bart_g2p.onnx".to_string();
     let tokenizer_path = "models/g2p/tokenizer.json".to_string();
@@ -17,7 +17,7 @@ fn main() -> ort::Result<()> {
     println!("PhonemeGen loaded in: {:?}", start.elapsed());
 
     let start = std::time::Instant::now();
-    let mut model = Model::new(
+    let model = Model::new(
     "models/en_US-norman-medium.onnx",
     "models/en_US-norman-medium.onnx.json",
     ).expect("Failed to create model");
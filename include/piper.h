@@ -0,0 +1,113 @@
+#ifndef PIPER_H
+#define PIPER_H
+
+/* Generated with cbindgen from src/ffi.rs, do not edit by hand. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+// Result of every fallible call, details are in [`piper_last_error`].
+typedef enum PiperStatus {
+  PIPER_STATUS_OK = 0,
+  // A required pointer was null or a string was not valid UTF-8.
+  PIPER_STATUS_INVALID_ARGUMENT = 1,
+  // The voice or the G2P model could not be loaded.
+  PIPER_STATUS_LOAD_FAILED = 2,
+  PIPER_STATUS_SYNTHESIS_FAILED = 3,
+  // The engine panicked, the handle should not be used anymore.
+  PIPER_STATUS_PANIC = 4,
+} PiperStatus;
+
+// Loaded engine, may be shared between threads.
+typedef struct PiperSynthesizer PiperSynthesizer;
+
+// Where to load the engine from, null paths fall back to the defaults.
+typedef struct PiperConfig {
+  // Voice `.onnx` model file or name of a voice in the default voice
+  // directories. Null for the voice embedded with `embed-voice`.
+  const char *model_path;
+  // Defaults to the model path with `.json` appended.
+  const char *config_path;
+  // Directory with `encoder_model_mini_bart_g2p.onnx`,
+  // `decoder_model_mini_bart_g2p.onnx`, `tokenizer.json` and `vocab.json`.
+  // Defaults to `models/g2p`, or the embedded G2P with `embed-g2p`.
+  const char *g2p_dir;
+  // Defaults to `arpabet-mapping.txt`.
+  const char *arpabet_mapping_path;
+  // Speaker of a multi-speaker voice, negative for the voice's default.
+  int64_t speaker_id;
+} PiperConfig;
+
+// Per-call overrides, negative values fall back to the voice config.
+typedef struct PiperSynthesisOptions {
+  float length_scale;
+  float noise_scale;
+  float noise_w;
+} PiperSynthesisOptions;
+
+// Mono samples in `[-1, 1]`, owned by the library until passed to
+// [`piper_free_audio`].
+typedef struct PiperAudio {
+  float *samples;
+  size_t len;
+  uint32_t sample_rate;
+} PiperAudio;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Loads a voice and the G2P model into a new engine stored in `*out`.
+//
+// # Safety
+//
+// `config` must point to a valid [`PiperConfig`] whose non-null paths are
+// NUL-terminated strings, and `out` must be valid for writes.
+enum PiperStatus piper_create(const struct PiperConfig *config, struct PiperSynthesizer **out);
+
+// Frees an engine created by [`piper_create`], null is ignored.
+//
+// # Safety
+//
+// `synthesizer` must be null or a pointer returned by [`piper_create`] that
+// has not been destroyed yet and is not in use by another thread.
+void piper_destroy(struct PiperSynthesizer *synthesizer);
+
+// Sample rate of the audio returned by [`piper_synthesize`], 0 for null.
+//
+// # Safety
+//
+// `synthesizer` must be null or a live pointer returned by [`piper_create`].
+uint32_t piper_sample_rate(const struct PiperSynthesizer *synthesizer);
+
+// Synthesizes `text` into `*out`, which must later be released with
+// [`piper_free_audio`]. `options` may be null for the voice's defaults.
+//
+// # Safety
+//
+// `synthesizer` must be a live pointer returned by [`piper_create`], `text`
+// a NUL-terminated string, `options` null or valid for reads and `out`
+// valid for writes.
+enum PiperStatus piper_synthesize(const struct PiperSynthesizer *synthesizer,
+                                  const char *text,
+                                  const struct PiperSynthesisOptions *options,
+                                  struct PiperAudio *out);
+
+// Frees the samples of audio returned by [`piper_synthesize`] and resets it,
+// freeing it twice or passing null is harmless.
+//
+// # Safety
+//
+// `audio` must be null or point to a [`PiperAudio`] filled by
+// [`piper_synthesize`] or already freed.
+void piper_free_audio(struct PiperAudio *audio);
+
+// Message of the last error on the calling thread, or null. Valid until the
+// next failing call on the same thread.
+const char *piper_last_error(void);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* PIPER_H */
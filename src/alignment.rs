@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+/// One phoneme id of a chunk and when it is spoken, in seconds from the start
+/// of the audio.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct PhonemeTiming {
+    pub id: i64,
+    /// The symbol the id was mapped from, `_` for the padding between phonemes.
+    pub phoneme: String,
+    pub start: f64,
+    pub duration: f64,
+}
+
+/// The phoneme timings of one inference.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct ChunkAlignment {
+    /// The `^...$` phoneme string synthesized.
+    pub phonemes: String,
+    pub start: f64,
+    pub end: f64,
+    /// Set when the voice has no `durations` output and the chunk's duration
+    /// was split evenly across its phoneme ids.
+    pub estimated: bool,
+    pub timings: Vec<PhonemeTiming>,
+}
@@ -0,0 +1,53 @@
+use std::{borrow::Cow, fs::File};
+
+use memmap2::Mmap;
+
+use crate::error::{PiperError, Result};
+
+/// A model or data file, read from disk or already in memory.
+#[derive(Debug, Clone)]
+pub(crate) enum Asset<'a> {
+    Path(String),
+    Bytes(Cow<'a, [u8]>),
+}
+
+impl Asset<'_> {
+    /// The path, or `<memory>` for in-memory assets, for error messages.
+    pub(crate) fn name(&self) -> &str {
+        match self {
+            Asset::Path(path) => path,
+            Asset::Bytes(_) => "<memory>",
+        }
+    }
+
+    pub(crate) fn read(&self, what: &'static str) -> Result<Cow<'_, [u8]>> {
+        match self {
+            Asset::Path(path) => std::fs::read(path)
+                .map(Cow::Owned)
+                .map_err(|e| PiperError::AssetLoad { what, path: path.clone(), reason: e.to_string() }),
+            Asset::Bytes(bytes) => Ok(Cow::Borrowed(bytes)),
+        }
+    }
+
+    /// Calls `f` with the contents, memory-mapping files instead of reading
+    /// them so large models aren't copied onto the heap.
+    pub(crate) fn map<T>(&self, what: &'static str, f: impl FnOnce(&[u8]) -> T) -> Result<T> {
+        match self {
+            Asset::Path(path) => {
+                let error = |e: std::io::Error| PiperError::AssetLoad { what, path: path.clone(), reason: e.to_string() };
+                let file = File::open(path).map_err(error)?;
+                // SAFETY: the mapping only lives for the call. Truncating the
+                // file meanwhile is the caller's problem, as with any mapped file.
+                let mapping = unsafe { Mmap::map(&file) }.map_err(error)?;
+                Ok(f(&mapping))
+            }
+            Asset::Bytes(bytes) => Ok(f(bytes)),
+        }
+    }
+
+    pub(crate) fn read_to_string(&self, what: &'static str) -> Result<String> {
+        let bytes = self.read(what)?;
+        String::from_utf8(bytes.into_owned())
+            .map_err(|e| PiperError::AssetLoad { what, path: self.name().to_string(), reason: e.to_string() })
+    }
+}
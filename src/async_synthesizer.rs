@@ -0,0 +1,175 @@
+use std::{
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    cancellation::CancellationToken,
+    error::{PiperError, Result},
+    phoneme_gen::PhonemeGen,
+    phonemizer::Phonemizer,
+    synthesizer::{SynthesisOptions, Synthesizer},
+};
+
+/// How long requests waited for a free synthesizer of the pool.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PoolMetrics {
+    pub size: usize,
+    /// Synthesizers currently running a request.
+    pub busy: usize,
+    pub requests: u64,
+    /// Requests that found every synthesizer busy.
+    pub waited: u64,
+    pub total_wait: Duration,
+    pub max_wait: Duration,
+}
+
+impl PoolMetrics {
+    pub fn mean_wait(&self) -> Duration {
+        match self.requests {
+            0 => Duration::ZERO,
+            requests => self.total_wait.div_f64(requests as f64),
+        }
+    }
+}
+
+fn poisoned<T>(_: T) -> PiperError {
+    PiperError::TaskFailed("synthesizer pool lock poisoned".to_string())
+}
+
+struct Pool<P: Phonemizer> {
+    idle: Mutex<Vec<Synthesizer<P>>>,
+    available: Condvar,
+    metrics: Mutex<PoolMetrics>,
+}
+
+impl<P: Phonemizer> Pool<P> {
+    fn acquire(&self) -> Result<Synthesizer<P>> {
+        let start = Instant::now();
+        let mut idle = self.idle.lock().map_err(poisoned)?;
+        let waited = idle.is_empty();
+        while idle.is_empty() {
+            idle = self.available.wait(idle).map_err(poisoned)?;
+        }
+        let synthesizer = idle.pop().expect("pool is not empty");
+        drop(idle);
+
+        let wait = start.elapsed();
+        let mut metrics = self.metrics.lock().map_err(poisoned)?;
+        metrics.busy += 1;
+        metrics.requests += 1;
+        metrics.waited += waited as u64;
+        metrics.total_wait += wait;
+        metrics.max_wait = metrics.max_wait.max(wait);
+        Ok(synthesizer)
+    }
+
+    fn release(&self, synthesizer: Synthesizer<P>) {
+        if let Ok(mut idle) = self.idle.lock() {
+            idle.push(synthesizer);
+        }
+        if let Ok(mut metrics) = self.metrics.lock() {
+            metrics.busy -= 1;
+        }
+        self.available.notify_one();
+    }
+}
+
+/// Returns the synthesizer to the pool even if the request panics.
+struct Lease<'a, P: Phonemizer> {
+    pool: &'a Pool<P>,
+    synthesizer: Option<Synthesizer<P>>,
+}
+
+impl<P: Phonemizer> Drop for Lease<'_, P> {
+    fn drop(&mut self) {
+        if let Some(synthesizer) = self.synthesizer.take() {
+            self.pool.release(synthesizer);
+        }
+    }
+}
+
+/// Shareable handle running [`Synthesizer`]s on tokio's blocking thread pool,
+/// so ONNX inference never stalls the async runtime. Each synthesizer serves
+/// one request at a time, a pool of several serves requests concurrently.
+pub struct AsyncSynthesizer<P: Phonemizer = PhonemeGen> {
+    pool: Arc<Pool<P>>,
+    sample_rate: u64,
+}
+
+impl<P: Phonemizer> Clone for AsyncSynthesizer<P> {
+    fn clone(&self) -> Self {
+        Self {
+            pool: self.pool.clone(),
+            sample_rate: self.sample_rate,
+        }
+    }
+}
+
+impl<P: Phonemizer + Send + 'static> AsyncSynthesizer<P> {
+    pub fn new(synthesizer: Synthesizer<P>) -> Self {
+        Self::from_pool(vec![synthesizer])
+    }
+
+    /// A pool of independently loaded synthesizers, each with its own ONNX
+    /// sessions, so up to `synthesizers.len()` requests run in parallel.
+    pub fn pooled(synthesizers: Vec<Synthesizer<P>>) -> Result<Self> {
+        if synthesizers.is_empty() {
+            return Err(PiperError::InvalidConfig("synthesizer pool must not be empty".to_string()));
+        }
+        Ok(Self::from_pool(synthesizers))
+    }
+
+    fn from_pool(synthesizers: Vec<Synthesizer<P>>) -> Self {
+        Self {
+            sample_rate: synthesizers[0].sample_rate(),
+            pool: Arc::new(Pool {
+                metrics: Mutex::new(PoolMetrics { size: synthesizers.len(), ..Default::default() }),
+                idle: Mutex::new(synthesizers),
+                available: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Sample rate of the voice active when the handle was created.
+    pub fn sample_rate(&self) -> u64 {
+        self.sample_rate
+    }
+
+    pub fn metrics(&self) -> PoolMetrics {
+        self.pool.metrics.lock().map(|metrics| *metrics).unwrap_or_default()
+    }
+
+    /// Runs `f` with exclusive access to a synthesizer of the pool on a
+    /// blocking thread, waiting for one to be free.
+    pub async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Synthesizer<P>) -> Result<T> + Send + 'static,
+    {
+        let pool = self.pool.clone();
+        tokio::task::spawn_blocking(move || {
+            let mut lease = Lease { pool: &pool, synthesizer: Some(pool.acquire()?) };
+            f(lease.synthesizer.as_mut().expect("leased synthesizer"))
+        })
+        .await
+        .map_err(|e| PiperError::TaskFailed(e.to_string()))?
+    }
+
+    pub async fn synthesize(&self, text: &str) -> Result<Vec<f32>> {
+        self.synthesize_with(text, &SynthesisOptions::default()).await
+    }
+
+    /// Dropping the returned future cancels the synthesis, falling back to a
+    /// fresh token when `options` has none.
+    pub async fn synthesize_with(&self, text: &str, options: &SynthesisOptions) -> Result<Vec<f32>> {
+        let text = text.to_string();
+        let mut options = options.clone();
+        let token = options.cancellation.get_or_insert_with(CancellationToken::new).clone();
+
+        let guard = token.drop_guard();
+        let waveform = self.run(move |synthesizer| synthesizer.synthesize_with(&text, &options)).await;
+        guard.disarm();
+        waveform
+    }
+}
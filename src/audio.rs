@@ -0,0 +1,244 @@
+use std::{
+    io::{Cursor, Seek, Write},
+    str::FromStr,
+};
+
+use crate::error::{PiperError, Result};
+
+/// Sample encoding of written WAV files.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SampleFormat {
+    #[default]
+    I16,
+    I24,
+    I32,
+    F32,
+}
+
+impl SampleFormat {
+    fn wav_spec(self, sample_rate: u64, channels: u16) -> hound::WavSpec {
+        let (bits_per_sample, sample_format) = match self {
+            SampleFormat::I16 => (16, hound::SampleFormat::Int),
+            SampleFormat::I24 => (24, hound::SampleFormat::Int),
+            SampleFormat::I32 => (32, hound::SampleFormat::Int),
+            SampleFormat::F32 => (32, hound::SampleFormat::Float),
+        };
+        hound::WavSpec {
+            channels,
+            sample_rate: sample_rate as u32,
+            bits_per_sample,
+            sample_format,
+        }
+    }
+}
+
+impl FromStr for SampleFormat {
+    type Err = PiperError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "i16" | "s16" | "16" => Ok(SampleFormat::I16),
+            "i24" | "s24" | "24" => Ok(SampleFormat::I24),
+            "i32" | "s32" | "32" => Ok(SampleFormat::I32),
+            "f32" | "float" => Ok(SampleFormat::F32),
+            _ => Err(PiperError::InvalidConfig(format!("unknown sample format `{}`", s))),
+        }
+    }
+}
+
+/// Receives audio as it is produced, one inference chunk at a time.
+pub trait AudioSink {
+    fn write_samples(&mut self, samples: &[f32]) -> Result<()>;
+
+    /// Called once after the last chunk.
+    fn finish(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl AudioSink for Vec<f32> {
+    fn write_samples(&mut self, samples: &[f32]) -> Result<()> {
+        self.extend_from_slice(samples);
+        Ok(())
+    }
+}
+
+/// Writes signed 16-bit little-endian PCM to any writer.
+pub struct PcmSink<W: Write> {
+    writer: W,
+    channels: u16,
+}
+
+impl<W: Write> PcmSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer, channels: 1 }
+    }
+
+    /// Writes every sample to `channels` interleaved channels.
+    pub fn with_channels(mut self, channels: u16) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> AudioSink for PcmSink<W> {
+    fn write_samples(&mut self, samples: &[f32]) -> Result<()> {
+        self.writer.write_all(&to_pcm_bytes(&interleave(samples, self.channels)))?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Duplicates mono audio into `channels` interleaved channels.
+pub fn interleave(waveform: &[f32], channels: u16) -> Vec<f32> {
+    waveform.iter()
+        .flat_map(|&sample| std::iter::repeat_n(sample, channels as usize))
+        .collect()
+}
+
+/// Band-limited FFT resampling of a whole waveform, keeping its duration.
+pub fn resample(waveform: &[f32], from: u64, to: u64) -> Result<Vec<f32>> {
+    use rubato::{FftFixedIn, Resampler};
+
+    if from == to || waveform.is_empty() {
+        return Ok(waveform.to_vec());
+    }
+    let resample_error = |err: &dyn std::fmt::Display| PiperError::InvalidConfig(format!("resampling failed: {}", err));
+
+    let mut resampler = FftFixedIn::<f32>::new(from as usize, to as usize, 1024, 2, 1)
+        .map_err(|e| resample_error(&e))?;
+    let expected = (waveform.len() as u64 * to).div_ceil(from) as usize;
+    let delay = resampler.output_delay();
+
+    let mut output = Vec::with_capacity(expected + delay);
+    let mut rest = waveform;
+    while rest.len() >= resampler.input_frames_next() {
+        let (chunk, remaining) = rest.split_at(resampler.input_frames_next());
+        output.extend(resampler.process(&[chunk], None).map_err(|e| resample_error(&e))?.remove(0));
+        rest = remaining;
+    }
+    if !rest.is_empty() {
+        output.extend(resampler.process_partial(Some(&[rest]), None).map_err(|e| resample_error(&e))?.remove(0));
+    }
+    while output.len() < expected + delay {
+        output.extend(resampler.process_partial::<&[f32]>(None, None).map_err(|e| resample_error(&e))?.remove(0));
+    }
+
+    output.drain(..delay);
+    output.truncate(expected);
+    Ok(output)
+}
+
+/// Warns when samples fall outside `[-1, 1]` and will be clipped by an integer conversion.
+pub(crate) fn warn_if_clipping(waveform: &[f32]) {
+    let clipped = waveform.iter().filter(|sample| sample.abs() > 1.0).count();
+    if clipped > 0 {
+        tracing::warn!(
+            "{} samples exceeded full scale and were clipped, consider enabling the limiter (--headroom)",
+            clipped
+        );
+    }
+}
+
+/// Converts to 16-bit PCM, samples are clipped to `[-1, 1]` first.
+pub fn to_pcm_i16(waveform: &[f32]) -> Vec<i16> {
+    warn_if_clipping(waveform);
+    waveform.iter()
+        .map(|&sample| (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)
+        .collect()
+}
+
+pub fn to_pcm_bytes(waveform: &[f32]) -> Vec<u8> {
+    to_pcm_i16(waveform).iter()
+        .flat_map(|s| s.to_le_bytes())
+        .collect()
+}
+
+/// Writes a mono WAV file, samples are clipped to `[-1, 1]` before integer conversion.
+pub fn write_wav_to<W: Write + Seek>(
+    waveform: &[f32],
+    sample_rate: u64,
+    format: SampleFormat,
+    writer: W,
+) -> Result<()> {
+    write_wav_channels_to(waveform, sample_rate, format, 1, writer)
+}
+
+/// Like [`write_wav_to`], copying the mono waveform into every one of `channels`.
+pub fn write_wav_channels_to<W: Write + Seek>(
+    waveform: &[f32],
+    sample_rate: u64,
+    format: SampleFormat,
+    channels: u16,
+    writer: W,
+) -> Result<()> {
+    if channels == 0 {
+        return Err(PiperError::InvalidConfig("at least one channel is required".to_string()));
+    }
+    let mut wav = hound::WavWriter::new(writer, format.wav_spec(sample_rate, channels))?;
+    if format != SampleFormat::F32 {
+        warn_if_clipping(waveform);
+    }
+    let interleaved = interleave(waveform, channels);
+    let clipped = interleaved.iter().map(|sample| sample.clamp(-1.0, 1.0));
+    match format {
+        SampleFormat::I16 => {
+            for sample in clipped {
+                wav.write_sample((sample * i16::MAX as f32) as i16)?;
+            }
+        }
+        SampleFormat::I24 => {
+            const I24_MAX: f32 = ((1 << 23) - 1) as f32;
+            for sample in clipped {
+                wav.write_sample((sample * I24_MAX) as i32)?;
+            }
+        }
+        SampleFormat::I32 => {
+            for sample in clipped {
+                wav.write_sample((sample as f64 * i32::MAX as f64) as i32)?;
+            }
+        }
+        SampleFormat::F32 => {
+            for &sample in &interleaved {
+                wav.write_sample(sample)?;
+            }
+        }
+    }
+    wav.finalize()?;
+    Ok(())
+}
+
+pub fn to_wav_bytes_with_format(waveform: &[f32], sample_rate: u64, format: SampleFormat) -> Result<Vec<u8>> {
+    to_wav_bytes_with_channels(waveform, sample_rate, format, 1)
+}
+
+pub fn to_wav_bytes_with_channels(
+    waveform: &[f32],
+    sample_rate: u64,
+    format: SampleFormat,
+    channels: u16,
+) -> Result<Vec<u8>> {
+    let mut cursor = Cursor::new(Vec::new());
+    write_wav_channels_to(waveform, sample_rate, format, channels, &mut cursor)?;
+    Ok(cursor.into_inner())
+}
+
+pub fn to_wav_bytes(waveform: &[f32], sample_rate: u64) -> Result<Vec<u8>> {
+    to_wav_bytes_with_format(waveform, sample_rate, SampleFormat::I16)
+}
+
+/// Writes a WAV file to a writer that can't seek, such as a socket or stdout,
+/// by assembling it in memory first.
+pub fn write_wav<W: Write>(waveform: &[f32], sample_rate: u64, format: SampleFormat, mut writer: W) -> Result<()> {
+    writer.write_all(&to_wav_bytes_with_format(waveform, sample_rate, format)?)?;
+    writer.flush()?;
+    Ok(())
+}
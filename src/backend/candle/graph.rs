@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+
+use candle_core::{DType, Device, Tensor};
+use ort::tensor::TensorElementType;
+
+use crate::{
+    error::{PiperError, Result},
+    inspect::{fields, read_varint, Field},
+    signature::TensorInfo,
+};
+
+use super::candle_error;
+
+/// ONNX `TensorProto.DataType` values.
+mod data_type {
+    pub const FLOAT: i64 = 1;
+    pub const UINT8: i64 = 2;
+    pub const INT8: i64 = 3;
+    pub const INT32: i64 = 6;
+    pub const INT64: i64 = 7;
+    pub const BOOL: i64 = 9;
+    pub const FLOAT16: i64 = 10;
+    pub const DOUBLE: i64 = 11;
+}
+
+#[derive(Debug, Clone)]
+pub(super) enum Attribute {
+    Float(f32),
+    Int(i64),
+    String(String),
+    Tensor(Tensor),
+    Floats(Vec<f32>),
+    Ints(Vec<i64>),
+}
+
+#[derive(Debug)]
+pub(super) struct Node {
+    pub op_type: String,
+    pub name: String,
+    /// Input names, empty for omitted optional inputs.
+    pub inputs: Vec<String>,
+    pub outputs: Vec<String>,
+    pub attributes: HashMap<String, Attribute>,
+}
+
+impl Node {
+    pub fn int(&self, name: &str, default: i64) -> i64 {
+        match self.attributes.get(name) {
+            Some(Attribute::Int(value)) => *value,
+            _ => default,
+        }
+    }
+
+    pub fn float(&self, name: &str, default: f32) -> f32 {
+        match self.attributes.get(name) {
+            Some(Attribute::Float(value)) => *value,
+            _ => default,
+        }
+    }
+
+    pub fn string(&self, name: &str, default: &str) -> String {
+        match self.attributes.get(name) {
+            Some(Attribute::String(value)) => value.clone(),
+            _ => default.to_string(),
+        }
+    }
+
+    pub fn ints(&self, name: &str) -> Option<&[i64]> {
+        match self.attributes.get(name) {
+            Some(Attribute::Ints(values)) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+/// The main graph of an ONNX model, with its initializers loaded on a device.
+#[derive(Debug)]
+pub(super) struct Graph {
+    pub nodes: Vec<Node>,
+    pub initializers: HashMap<String, Tensor>,
+    pub inputs: Vec<TensorInfo>,
+    pub outputs: Vec<TensorInfo>,
+}
+
+fn utf8(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).into_owned()
+}
+
+/// The values of a repeated varint field, packed or not.
+fn push_varints(values: &mut Vec<i64>, field: &Field<'_>) {
+    match field {
+        Field::Varint(value) => values.push(*value as i64),
+        Field::Bytes(packed) => {
+            let mut pos = 0;
+            while let Some(value) = read_varint(packed, &mut pos) {
+                values.push(value as i64);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The values of a repeated `float` field, packed or not.
+fn push_floats(values: &mut Vec<f32>, field: &Field<'_>) {
+    match field {
+        Field::Fixed32(bits) => values.push(f32::from_bits(*bits)),
+        Field::Bytes(packed) => values.extend(
+            packed.chunks_exact(4).map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        ),
+        _ => {}
+    }
+}
+
+fn element_type(data_type: i64) -> Option<TensorElementType> {
+    Some(match data_type {
+        data_type::FLOAT => TensorElementType::Float32,
+        data_type::UINT8 => TensorElementType::Uint8,
+        data_type::INT8 => TensorElementType::Int8,
+        data_type::INT32 => TensorElementType::Int32,
+        data_type::INT64 => TensorElementType::Int64,
+        data_type::BOOL => TensorElementType::Bool,
+        data_type::FLOAT16 => TensorElementType::Float16,
+        data_type::DOUBLE => TensorElementType::Float64,
+        _ => return None,
+    })
+}
+
+/// The candle type ONNX values of a type are held in. Booleans are `u8` and
+/// every signed integer `i64`, as candle lacks most integer kernels.
+pub(super) fn dtype(data_type: i64) -> Result<DType> {
+    Ok(match data_type {
+        data_type::FLOAT => DType::F32,
+        data_type::DOUBLE => DType::F64,
+        data_type::FLOAT16 => DType::F16,
+        data_type::UINT8 | data_type::BOOL => DType::U8,
+        data_type::INT8 | data_type::INT32 | data_type::INT64 => DType::I64,
+        other => return Err(PiperError::Backend(format!("unsupported tensor data type {}", other))),
+    })
+}
+
+fn tensor(proto: &[u8], device: &Device) -> Result<(String, Tensor)> {
+    let mut name = String::new();
+    let mut dims = Vec::new();
+    let mut kind = 0;
+    let mut raw = None;
+    let mut floats = Vec::new();
+    let mut ints = Vec::new();
+    let mut doubles = Vec::new();
+    for (number, field) in fields(proto) {
+        match (number, field) {
+            (1, field) => push_varints(&mut dims, &field),
+            (2, Field::Varint(value)) => kind = value as i64,
+            (4, field) => push_floats(&mut floats, &field),
+            (5 | 7, field) => push_varints(&mut ints, &field),
+            (8, Field::Bytes(value)) => name = utf8(value),
+            (9, Field::Bytes(value)) => raw = Some(value),
+            (10, Field::Fixed64(bits)) => doubles.push(f64::from_bits(bits)),
+            (10, Field::Bytes(packed)) => doubles.extend(
+                packed.chunks_exact(8).map(|bytes| f64::from_le_bytes(bytes.try_into().unwrap_or_default()))
+            ),
+            (14, Field::Varint(1)) => {
+                return Err(PiperError::Backend(format!("tensor `{}` is stored in external data", name)));
+            }
+            _ => {}
+        }
+    }
+
+    let shape: Vec<usize> = dims.iter().map(|&dim| dim as usize).collect();
+    let tensor = match (kind, raw) {
+        (data_type::INT8, Some(raw)) => Tensor::from_vec(raw.iter().map(|&value| value as i8 as i64).collect::<Vec<_>>(), shape, device),
+        (data_type::INT32, Some(raw)) => Tensor::from_vec(
+            raw.chunks_exact(4).map(|bytes| i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as i64).collect::<Vec<_>>(),
+            shape, device,
+        ),
+        (kind, Some(raw)) => Tensor::from_raw_buffer(raw, dtype(kind)?, &shape, device),
+        (data_type::FLOAT, None) => Tensor::from_vec(floats, shape, device),
+        (data_type::DOUBLE, None) => Tensor::from_vec(doubles, shape, device),
+        (data_type::FLOAT16, None) => Tensor::from_vec(
+            ints.iter().map(|&bits| half_bits(bits as u16)).collect::<Vec<_>>(), shape, device,
+        ).and_then(|tensor| tensor.to_dtype(DType::F16)),
+        (data_type::UINT8 | data_type::BOOL, None) => Tensor::from_vec(
+            ints.iter().map(|&value| value as u8).collect::<Vec<_>>(), shape, device,
+        ),
+        (data_type::INT8 | data_type::INT32 | data_type::INT64, None) => Tensor::from_vec(ints, shape, device),
+        (other, None) => return Err(PiperError::Backend(format!("tensor `{}` has unsupported data type {}", name, other))),
+    };
+    Ok((name, tensor.map_err(candle_error)?))
+}
+
+/// Widens the bits of an IEEE 754 half to `f32`, for `float16` tensors kept
+/// in `int32_data`.
+fn half_bits(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 == 0 { 1.0 } else { -1.0 };
+    let exponent = i32::from((bits >> 10) & 0x1f);
+    let mantissa = f32::from(bits & 0x3ff);
+    sign * match exponent {
+        0 => mantissa * 2f32.powi(-24),
+        0x1f if mantissa == 0.0 => f32::INFINITY,
+        0x1f => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * 2f32.powi(exponent - 15),
+    }
+}
+
+fn attribute(proto: &[u8], device: &Device) -> Result<(String, Option<Attribute>)> {
+    let mut name = String::new();
+    let mut kind = 0;
+    let mut float = 0.0;
+    let mut int = 0;
+    let mut string = String::new();
+    let mut tensor_value = None;
+    let mut floats = Vec::new();
+    let mut ints = Vec::new();
+    for (number, field) in fields(proto) {
+        match (number, field) {
+            (1, Field::Bytes(value)) => name = utf8(value),
+            (2, Field::Fixed32(bits)) => float = f32::from_bits(bits),
+            (3, Field::Varint(value)) => int = value as i64,
+            (4, Field::Bytes(value)) => string = utf8(value),
+            (5, Field::Bytes(value)) => tensor_value = Some(tensor(value, device)?.1),
+            (7, field) => push_floats(&mut floats, &field),
+            (8, field) => push_varints(&mut ints, &field),
+            (20, Field::Varint(value)) => kind = value,
+            _ => {}
+        }
+    }
+    // `AttributeProto.AttributeType`, subgraphs and strings lists aren't used by voices.
+    let attribute = match kind {
+        1 => Some(Attribute::Float(float)),
+        2 => Some(Attribute::Int(int)),
+        3 => Some(Attribute::String(string)),
+        4 => tensor_value.map(Attribute::Tensor),
+        6 => Some(Attribute::Floats(floats)),
+        7 => Some(Attribute::Ints(ints)),
+        _ => None,
+    };
+    Ok((name, attribute))
+}
+
+fn node(proto: &[u8], device: &Device) -> Result<Node> {
+    let mut node = Node {
+        op_type: String::new(),
+        name: String::new(),
+        inputs: Vec::new(),
+        outputs: Vec::new(),
+        attributes: HashMap::new(),
+    };
+    let mut domain = String::new();
+    for (number, field) in fields(proto) {
+        match (number, field) {
+            (1, Field::Bytes(value)) => node.inputs.push(utf8(value)),
+            (2, Field::Bytes(value)) => node.outputs.push(utf8(value)),
+            (3, Field::Bytes(value)) => node.name = utf8(value),
+            (4, Field::Bytes(value)) => node.op_type = utf8(value),
+            (5, Field::Bytes(value)) => {
+                if let (name, Some(attribute)) = attribute(value, device)? {
+                    node.attributes.insert(name, attribute);
+                }
+            }
+            (7, Field::Bytes(value)) => domain = utf8(value),
+            _ => {}
+        }
+    }
+    if !domain.is_empty() && domain != "ai.onnx" {
+        return Err(PiperError::Backend(format!("operator `{}` of domain `{}` is not supported", node.op_type, domain)));
+    }
+    Ok(node)
+}
+
+fn value_info(proto: &[u8]) -> TensorInfo {
+    let mut info = TensorInfo { name: String::new(), tensor: None };
+    for (number, field) in fields(proto) {
+        match (number, field) {
+            (1, Field::Bytes(value)) => info.name = utf8(value),
+            (2, Field::Bytes(type_proto)) => {
+                let tensor_type = fields(type_proto).into_iter().find_map(|(number, field)| match (number, field) {
+                    (1, Field::Bytes(tensor_type)) => Some(tensor_type),
+                    _ => None,
+                });
+                let Some(tensor_type) = tensor_type else {
+                    continue;
+                };
+                let mut element = None;
+                let mut rank = 0;
+                for (number, field) in fields(tensor_type) {
+                    match (number, field) {
+                        (1, Field::Varint(value)) => element = element_type(value as i64),
+                        (2, Field::Bytes(shape)) => rank = fields(shape).len(),
+                        _ => {}
+                    }
+                }
+                info.tensor = element.map(|element| (element, rank));
+            }
+            _ => {}
+        }
+    }
+    info
+}
+
+impl Graph {
+    /// Parses an ONNX `ModelProto`, loading the initializers on `device`.
+    pub fn parse(model: &[u8], device: &Device) -> Result<Self> {
+        let graph = fields(model).into_iter()
+            .find_map(|(number, field)| match (number, field) {
+                (7, Field::Bytes(graph)) => Some(graph),
+                _ => None,
+            })
+            .ok_or_else(|| PiperError::Backend("the model has no graph".to_string()))?;
+
+        let mut parsed = Graph { nodes: Vec::new(), initializers: HashMap::new(), inputs: Vec::new(), outputs: Vec::new() };
+        for (number, field) in fields(graph) {
+            let Field::Bytes(value) = field else {
+                continue;
+            };
+            match number {
+                1 => parsed.nodes.push(node(value, device)?),
+                5 => {
+                    let (name, tensor) = tensor(value, device)?;
+                    parsed.initializers.insert(name, tensor);
+                }
+                11 => parsed.inputs.push(value_info(value)),
+                12 => parsed.outputs.push(value_info(value)),
+                _ => {}
+            }
+        }
+        // Older exporters also list the initializers as graph inputs.
+        parsed.inputs.retain(|input| !parsed.initializers.contains_key(&input.name));
+        Ok(parsed)
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::HashMap;
+
+use candle_core::{DType, Device, Tensor};
+use ndarray::{Array, Dimension};
+
+use crate::{
+    asset::Asset,
+    error::{PiperError, Result},
+    signature::Signature,
+};
+
+use super::{InferenceBackend, InferenceOutput, VoiceInput};
+
+mod graph;
+mod ops;
+
+use graph::Graph;
+
+fn candle_error(error: candle_core::Error) -> PiperError {
+    PiperError::Backend(error.to_string())
+}
+
+fn tensor<T: candle_core::WithDType, D: Dimension>(array: Array<T, D>, device: &Device) -> Result<Tensor> {
+    let shape = array.shape().to_vec();
+    Tensor::from_vec(array.iter().copied().collect::<Vec<_>>(), shape, device).map_err(candle_error)
+}
+
+/// Runs voices with [candle](https://github.com/huggingface/candle) by
+/// interpreting their ONNX graph, on the CPU or on a CUDA or Metal device
+/// shared with other candle models. Experimental: only the operators Piper
+/// exports use are implemented.
+pub struct CandleBackend {
+    graph: Graph,
+    device: Device,
+    signature: Signature,
+    /// Index of the last node reading each value, after which it is dropped.
+    last_use: HashMap<String, usize>,
+}
+
+impl CandleBackend {
+    /// Loads the contents of a voice's `.onnx` model onto `device`.
+    pub fn from_bytes(model: &[u8], device: &Device) -> Result<Self> {
+        let graph = Graph::parse(model, device)?;
+        let signature = Signature::detect(&graph.inputs, &graph.outputs)?;
+        let mut last_use = HashMap::new();
+        for (index, node) in graph.nodes.iter().enumerate() {
+            for input in &node.inputs {
+                last_use.insert(input.clone(), index);
+            }
+        }
+        for output in &graph.outputs {
+            last_use.insert(output.name.clone(), usize::MAX);
+        }
+        Ok(Self { graph, device: device.clone(), signature, last_use })
+    }
+
+    pub fn new(model_path: &str, device: &Device) -> Result<Self> {
+        Self::from_asset(&Asset::Path(model_path.to_string()), device)
+    }
+
+    pub(crate) fn from_asset(model: &Asset<'_>, device: &Device) -> Result<Self> {
+        model.map("model", |bytes| Self::from_bytes(bytes, device))?
+    }
+
+    pub fn device(&self) -> &Device {
+        &self.device
+    }
+
+    fn float_output(&self, value: &Tensor) -> Result<(Vec<i64>, Vec<f32>)> {
+        let shape = value.dims().iter().map(|&dim| dim as i64).collect();
+        let values = value.to_dtype(DType::F32)
+            .and_then(|value| value.flatten_all())
+            .and_then(|value| value.to_vec1::<f32>())
+            .map_err(candle_error)?;
+        Ok((shape, values))
+    }
+}
+
+impl InferenceBackend for CandleBackend {
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn run(&mut self, input: VoiceInput) -> Result<InferenceOutput> {
+        let signature = &self.signature;
+        let scales_dtype = if signature.half_precision { DType::F16 } else { DType::F32 };
+        let mut values: HashMap<String, Tensor> = HashMap::new();
+        values.insert(signature.input.clone(), tensor(input.ids, &self.device)?);
+        values.insert(signature.input_lengths.clone(), tensor(input.lengths, &self.device)?);
+        values.insert(
+            signature.scales.clone(),
+            tensor(input.scales, &self.device)?.to_dtype(scales_dtype).map_err(candle_error)?,
+        );
+        if let (Some(name), Some(sid)) = (&signature.sid, input.sid) {
+            values.insert(name.clone(), tensor(sid, &self.device)?);
+        }
+
+        for (index, node) in self.graph.nodes.iter().enumerate() {
+            let inputs = node.inputs.iter()
+                .map(|name| match name.as_str() {
+                    "" => Ok(None),
+                    name => values.get(name)
+                        .or_else(|| self.graph.initializers.get(name))
+                        .cloned()
+                        .map(Some)
+                        .ok_or_else(|| PiperError::Backend(format!("`{}` reads `{}` before it is computed", node.name, name))),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            let outputs = ops::eval(node, &inputs, &self.device)?;
+            for (name, output) in node.outputs.iter().zip(outputs) {
+                if !name.is_empty() {
+                    values.insert(name.clone(), output);
+                }
+            }
+            for name in &node.inputs {
+                if self.last_use.get(name) == Some(&index) {
+                    values.remove(name);
+                }
+            }
+        }
+
+        let output = values.get(&signature.output).ok_or(PiperError::MissingOutput("output"))?;
+        let (shape, audio) = self.float_output(output)?;
+        let durations = match signature.durations.as_ref().and_then(|name| values.get(name)) {
+            Some(durations) => Some(self.float_output(durations)?.1),
+            None => None,
+        };
+        Ok(InferenceOutput { shape, audio, durations })
+    }
+}
@@ -0,0 +1,857 @@
+use candle_core::{DType, Device, Tensor};
+
+use crate::{
+    backend::SeededRng,
+    error::{PiperError, Result},
+};
+
+use super::{
+    candle_error,
+    graph::{dtype, Attribute, Node},
+};
+
+type CandleResult<T> = candle_core::Result<T>;
+
+/// Values of a seeded `RandomNormalLike` or `RandomUniformLike` node, drawn
+/// on the host so every run gives the same ones.
+fn seeded_random(node: &Node, dims: &[usize], seed: f32, device: &Device) -> CandleResult<Tensor> {
+    let mut rng = SeededRng::new(u64::from(seed.to_bits()));
+    let count = dims.iter().product();
+    let values: Vec<f32> = match node.op_type.as_str() {
+        "RandomNormalLike" => {
+            let (mean, scale) = (node.float("mean", 0.0), node.float("scale", 1.0));
+            (0..count).map(|_| mean + scale * rng.normal()).collect()
+        }
+        _ => {
+            let (low, high) = (node.float("low", 0.0), node.float("high", 1.0));
+            (0..count).map(|_| low + (high - low) * rng.uniform()).collect()
+        }
+    };
+    Tensor::from_vec(values, dims, device)
+}
+
+fn unsupported(node: &Node, what: &str) -> PiperError {
+    PiperError::Backend(format!("`{}` ({}): {} is not supported", node.name, node.op_type, what))
+}
+
+fn input<'a>(node: &Node, inputs: &'a [Option<Tensor>], index: usize) -> Result<&'a Tensor> {
+    inputs.get(index).and_then(Option::as_ref).ok_or_else(|| {
+        PiperError::Backend(format!("`{}` ({}) is missing input {}", node.name, node.op_type, index))
+    })
+}
+
+fn optional(inputs: &[Option<Tensor>], index: usize) -> Option<&Tensor> {
+    inputs.get(index).and_then(Option::as_ref)
+}
+
+/// The values of a small integer tensor, such as a shape or axes.
+fn ints(tensor: &Tensor) -> Result<Vec<i64>> {
+    tensor.flatten_all()
+        .and_then(|tensor| tensor.to_dtype(DType::I64))
+        .and_then(|tensor| tensor.to_vec1::<i64>())
+        .map_err(candle_error)
+}
+
+fn scalar(tensor: &Tensor) -> Result<f64> {
+    tensor.flatten_all()
+        .and_then(|tensor| tensor.to_dtype(DType::F64))
+        .and_then(|tensor| tensor.get(0))
+        .and_then(|value| value.to_scalar::<f64>())
+        .map_err(candle_error)
+}
+
+fn is_float(tensor: &Tensor) -> bool {
+    tensor.dtype().is_float()
+}
+
+/// A negative axis counted from the end of `rank` dimensions.
+fn axis(axis: i64, rank: usize) -> usize {
+    if axis < 0 { (axis + rank as i64).max(0) as usize } else { axis as usize }
+}
+
+fn scalar_like(value: f64, like: &Tensor) -> CandleResult<Tensor> {
+    Tensor::new(value, like.device())?.to_dtype(like.dtype())
+}
+
+fn indices(values: impl IntoIterator<Item = usize>, device: &Device) -> CandleResult<Tensor> {
+    let values: Vec<u32> = values.into_iter().map(|value| value as u32).collect();
+    let len = values.len();
+    Tensor::from_vec(values, len, device)
+}
+
+/// The NumPy broadcast of two shapes.
+fn broadcast_shape(node: &Node, a: &[usize], b: &[usize]) -> Result<Vec<usize>> {
+    let rank = a.len().max(b.len());
+    (0..rank).map(|i| {
+        let a = if i + a.len() >= rank { a[i + a.len() - rank] } else { 1 };
+        let b = if i + b.len() >= rank { b[i + b.len() - rank] } else { 1 };
+        match (a, b) {
+            (a, b) if a == b => Ok(a),
+            (1, other) | (other, 1) => Ok(other),
+            _ => Err(PiperError::Backend(format!(
+                "`{}` ({}): can't broadcast {:?} with {:?}", node.name, node.op_type, a, b
+            ))),
+        }
+    }).collect()
+}
+
+fn neg(x: &Tensor) -> CandleResult<Tensor> {
+    if is_float(x) { x.neg() } else { x.zeros_like()?.sub(x) }
+}
+
+fn abs(x: &Tensor) -> CandleResult<Tensor> {
+    if is_float(x) { x.abs() } else { x.maximum(&neg(x)?) }
+}
+
+fn relu(x: &Tensor) -> CandleResult<Tensor> {
+    if is_float(x) { x.relu() } else { x.maximum(&x.zeros_like()?) }
+}
+
+fn sigmoid(x: &Tensor) -> CandleResult<Tensor> {
+    (x.neg()?.exp()? + 1.0)?.recip()
+}
+
+/// `ln(1 + e^x)`, computed as `max(x, 0) + ln(1 + e^-|x|)` so large inputs don't overflow.
+fn softplus(x: &Tensor) -> CandleResult<Tensor> {
+    x.relu()?.add(&(x.abs()?.neg()?.exp()? + 1.0)?.log()?)
+}
+
+fn softmax(x: &Tensor, dim: usize, log: bool) -> CandleResult<Tensor> {
+    let shifted = x.broadcast_sub(&x.max_keepdim(dim)?)?;
+    let exp = shifted.exp()?;
+    let sum = exp.sum_keepdim(dim)?;
+    match log {
+        true => shifted.broadcast_sub(&sum.log()?),
+        false => exp.broadcast_div(&sum),
+    }
+}
+
+fn pow(x: &Tensor, y: &Tensor) -> CandleResult<Tensor> {
+    if y.elem_count() == 1 {
+        let exponent = y.flatten_all()?.to_dtype(DType::F64)?.get(0)?.to_scalar::<f64>()?;
+        return match exponent {
+            1.0 => Ok(x.clone()),
+            2.0 => x.sqr(),
+            0.5 => x.sqrt(),
+            _ => x.powf(exponent),
+        };
+    }
+    x.broadcast_pow(&y.to_dtype(x.dtype())?)
+}
+
+/// Fold of a variadic elementwise operator such as `Max` or `Sum`.
+fn variadic(
+    node: &Node,
+    inputs: &[Option<Tensor>],
+    op: fn(&Tensor, &Tensor) -> CandleResult<Tensor>,
+) -> Result<Tensor> {
+    let mut result = input(node, inputs, 0)?.clone();
+    for other in inputs[1..].iter().flatten() {
+        result = op(&result, other).map_err(candle_error)?;
+    }
+    Ok(result)
+}
+
+fn matmul(a: &Tensor, b: &Tensor) -> CandleResult<Tensor> {
+    let (a_vector, b_vector) = (a.rank() == 1, b.rank() == 1);
+    let a = if a_vector { a.unsqueeze(0)? } else { a.clone() };
+    let b = if b_vector { b.unsqueeze(1)? } else { b.clone() };
+    let mut product = a.contiguous()?.broadcast_matmul(&b.contiguous()?)?;
+    if b_vector {
+        product = product.squeeze(product.rank() - 1)?;
+    }
+    if a_vector {
+        product = product.squeeze(product.rank() - if b_vector { 1 } else { 2 })?;
+    }
+    Ok(product)
+}
+
+/// Reduction axes from the `axes` input (newer opsets) or attribute,
+/// all of them when there are none.
+fn reduce_axes(node: &Node, inputs: &[Option<Tensor>], rank: usize) -> Result<Vec<usize>> {
+    let axes = match (optional(inputs, 1), node.ints("axes")) {
+        (Some(axes), _) => ints(axes)?,
+        (None, Some(axes)) => axes.to_vec(),
+        (None, None) => Vec::new(),
+    };
+    let mut axes: Vec<usize> = match axes.is_empty() {
+        true if node.int("noop_with_empty_axes", 0) != 0 => Vec::new(),
+        true => (0..rank).collect(),
+        false => axes.iter().map(|&a| axis(a, rank)).collect(),
+    };
+    axes.sort_unstable();
+    axes.dedup();
+    Ok(axes)
+}
+
+fn reduce(node: &Node, inputs: &[Option<Tensor>]) -> Result<Tensor> {
+    let x = input(node, inputs, 0)?;
+    let axes = reduce_axes(node, inputs, x.rank())?;
+    if axes.is_empty() {
+        return Ok(x.clone());
+    }
+    let reduced = match node.op_type.as_str() {
+        "ReduceSum" => x.sum_keepdim(axes.clone()),
+        "ReduceMean" => x.mean_keepdim(axes.clone()),
+        "ReduceL2" => x.sqr().and_then(|x| x.sum_keepdim(axes.clone())).and_then(|x| x.sqrt()),
+        "ReduceSumSquare" => x.sqr().and_then(|x| x.sum_keepdim(axes.clone())),
+        "ReduceMax" | "ReduceMin" => axes.iter().try_fold(x.clone(), |x, &a| match node.op_type.as_str() {
+            "ReduceMax" => x.max_keepdim(a),
+            _ => x.min_keepdim(a),
+        }),
+        _ => return Err(unsupported(node, "the reduction")),
+    }.map_err(candle_error)?;
+    if node.int("keepdims", 1) != 0 {
+        return Ok(reduced);
+    }
+    axes.iter().rev()
+        .try_fold(reduced, |x, &a| x.squeeze(a))
+        .map_err(candle_error)
+}
+
+/// Explicit `pads` of a convolution, as (begin, end) per spatial axis.
+fn conv_pads(node: &Node, spatial: usize) -> Result<Vec<(usize, usize)>> {
+    let auto_pad = node.string("auto_pad", "NOTSET");
+    if auto_pad != "NOTSET" && auto_pad != "VALID" {
+        return Err(unsupported(node, &format!("auto_pad `{}`", auto_pad)));
+    }
+    let pads = node.ints("pads").map(<[i64]>::to_vec).unwrap_or_else(|| vec![0; spatial * 2]);
+    Ok((0..spatial).map(|i| (pads[i] as usize, pads[i + spatial] as usize)).collect())
+}
+
+/// A per-axis attribute that must be the same on every spatial axis, as
+/// candle's convolutions only take one value.
+fn uniform(node: &Node, name: &str, spatial: usize) -> Result<usize> {
+    let values = node.ints(name).map(<[i64]>::to_vec).unwrap_or_else(|| vec![1; spatial]);
+    match values.windows(2).all(|pair| pair[0] == pair[1]) {
+        true => Ok(values.first().copied().unwrap_or(1) as usize),
+        false => Err(unsupported(node, &format!("different `{}` per axis", name))),
+    }
+}
+
+fn add_bias(x: Tensor, bias: Option<&Tensor>) -> CandleResult<Tensor> {
+    match bias {
+        Some(bias) => {
+            let mut shape = vec![1; x.rank()];
+            shape[1] = bias.elem_count();
+            x.broadcast_add(&bias.reshape(shape)?)
+        }
+        None => Ok(x),
+    }
+}
+
+fn conv(node: &Node, inputs: &[Option<Tensor>]) -> Result<Tensor> {
+    let x = input(node, inputs, 0)?;
+    let weight = input(node, inputs, 1)?.contiguous().map_err(candle_error)?;
+    let spatial = x.rank().saturating_sub(2);
+    let pads = conv_pads(node, spatial)?;
+    let stride = uniform(node, "strides", spatial)?;
+    let dilation = uniform(node, "dilations", spatial)?;
+    let groups = node.int("group", 1) as usize;
+
+    let mut x = x.clone();
+    let symmetric = pads.iter().all(|&(begin, end)| begin == end && begin == pads[0].0);
+    if !symmetric {
+        for (i, &(begin, end)) in pads.iter().enumerate() {
+            x = x.pad_with_zeros(2 + i, begin, end).map_err(candle_error)?;
+        }
+    }
+    let padding = if symmetric { pads.first().map_or(0, |pad| pad.0) } else { 0 };
+    let x = x.contiguous().map_err(candle_error)?;
+    let output = match spatial {
+        1 => x.conv1d(&weight, padding, stride, dilation, groups),
+        2 => x.conv2d(&weight, padding, stride, dilation, groups),
+        _ => return Err(unsupported(node, &format!("{}-D convolution", spatial))),
+    };
+    add_bias(output.map_err(candle_error)?, optional(inputs, 2)).map_err(candle_error)
+}
+
+fn conv_transpose(node: &Node, inputs: &[Option<Tensor>]) -> Result<Tensor> {
+    let x = input(node, inputs, 0)?.contiguous().map_err(candle_error)?;
+    let weight = input(node, inputs, 1)?.contiguous().map_err(candle_error)?;
+    if x.rank() != 3 {
+        return Err(unsupported(node, &format!("{}-D transposed convolution", x.rank().saturating_sub(2))));
+    }
+    if node.ints("output_shape").is_some() {
+        return Err(unsupported(node, "output_shape"));
+    }
+    let (begin, end) = conv_pads(node, 1)?[0];
+    let stride = uniform(node, "strides", 1)?;
+    let dilation = uniform(node, "dilations", 1)?;
+    let output_padding = node.ints("output_padding").and_then(|pads| pads.first().copied()).unwrap_or(0) as usize;
+    let groups = node.int("group", 1) as usize;
+
+    // The full output, padded with the positions no input reaches, then cropped by `pads`.
+    let full = x.conv_transpose1d(&weight, 0, 0, stride, dilation, groups)
+        .and_then(|full| full.pad_with_zeros(2, 0, output_padding))
+        .map_err(candle_error)?;
+    let len = full.dim(2).map_err(candle_error)?;
+    let output = full.narrow(2, begin, len.saturating_sub(begin + end))
+        .map_err(candle_error)?;
+    add_bias(output, optional(inputs, 2)).map_err(candle_error)
+}
+
+fn gather(node: &Node, inputs: &[Option<Tensor>]) -> Result<Tensor> {
+    let data = input(node, inputs, 0)?;
+    let index = input(node, inputs, 1)?;
+    let dim = axis(node.int("axis", 0), data.rank());
+    let size = data.dim(dim).map_err(candle_error)? as i64;
+    let positions = ints(index)?.into_iter().map(|i| if i < 0 { i + size } else { i } as usize);
+    let selected = data.index_select(&indices(positions, data.device()).map_err(candle_error)?, dim)
+        .map_err(candle_error)?;
+    let mut shape = data.dims()[..dim].to_vec();
+    shape.extend_from_slice(index.dims());
+    shape.extend_from_slice(&data.dims()[dim + 1..]);
+    selected.reshape(shape).map_err(candle_error)
+}
+
+fn gather_elements(node: &Node, inputs: &[Option<Tensor>]) -> Result<Tensor> {
+    let data = input(node, inputs, 0)?;
+    let index = input(node, inputs, 1)?;
+    let dim = axis(node.int("axis", 0), data.rank());
+    let size = data.dim(dim).map_err(candle_error)?;
+    let mut data = data.clone();
+    for (i, &len) in index.dims().iter().enumerate() {
+        if i != dim && data.dim(i).map_err(candle_error)? != len {
+            data = data.narrow(i, 0, len).map_err(candle_error)?;
+        }
+    }
+    let positions: Vec<i64> = ints(index)?.into_iter().map(|i| if i < 0 { i + size as i64 } else { i }).collect();
+    let index = Tensor::from_vec(positions, index.dims(), data.device()).map_err(candle_error)?;
+    data.contiguous()
+        .and_then(|data| data.gather(&index, dim))
+        .map_err(candle_error)
+}
+
+/// Flat row offsets into the first `k` dimensions of `shape` of the index
+/// tuples in `index`, whose last dimension is `k`.
+fn nd_offsets(index: &Tensor, shape: &[usize]) -> Result<(usize, Vec<usize>)> {
+    let k = index.dims().last().copied().unwrap_or(0);
+    let values = ints(index)?;
+    let offsets = values.chunks(k.max(1))
+        .map(|tuple| tuple.iter().zip(shape).fold(0, |offset, (&i, &dim)| {
+            offset * dim + if i < 0 { (i + dim as i64) as usize } else { i as usize }
+        }))
+        .collect();
+    Ok((k, offsets))
+}
+
+fn gather_nd(node: &Node, inputs: &[Option<Tensor>]) -> Result<Tensor> {
+    let data = input(node, inputs, 0)?;
+    let index = input(node, inputs, 1)?;
+    if node.int("batch_dims", 0) != 0 {
+        return Err(unsupported(node, "batch_dims"));
+    }
+    let (k, offsets) = nd_offsets(index, data.dims())?;
+    let rows: usize = data.dims()[..k].iter().product();
+    let mut rows_shape = vec![rows];
+    rows_shape.extend_from_slice(&data.dims()[k..]);
+    let mut shape = index.dims()[..index.rank() - 1].to_vec();
+    shape.extend_from_slice(&data.dims()[k..]);
+    data.reshape(rows_shape)
+        .and_then(|data| data.index_select(&indices(offsets, data.device())?, 0))
+        .and_then(|selected| selected.reshape(shape))
+        .map_err(candle_error)
+}
+
+fn scatter_nd(node: &Node, inputs: &[Option<Tensor>]) -> Result<Tensor> {
+    let data = input(node, inputs, 0)?;
+    let index = input(node, inputs, 1)?;
+    let updates = input(node, inputs, 2)?;
+    if node.string("reduction", "none") != "none" {
+        return Err(unsupported(node, "reduction"));
+    }
+    if updates.elem_count() == 0 {
+        return Ok(data.clone());
+    }
+    let (k, offsets) = nd_offsets(index, data.dims())?;
+    let rows: usize = data.dims()[..k].iter().product();
+    let row_len: usize = data.dims()[k..].iter().product();
+    let count = offsets.len();
+    let scatter = || -> CandleResult<Tensor> {
+        let rows_index = Tensor::from_vec(offsets.iter().map(|&o| o as i64).collect::<Vec<_>>(), (count, 1), data.device())?
+            .broadcast_as((count, row_len))?
+            .contiguous()?;
+        data.reshape((rows, row_len))?
+            .scatter(&rows_index, &updates.reshape((count, row_len))?.contiguous()?, 0)?
+            .reshape(data.dims())
+    };
+    scatter().map_err(candle_error)
+}
+
+fn non_zero(node: &Node, inputs: &[Option<Tensor>]) -> Result<Tensor> {
+    let x = input(node, inputs, 0)?;
+    let mask = x.ne(&x.zeros_like().map_err(candle_error)?)
+        .and_then(|mask| mask.flatten_all())
+        .and_then(|mask| mask.to_vec1::<u8>())
+        .map_err(candle_error)?;
+    let dims = x.dims();
+    let positions: Vec<usize> = mask.iter().enumerate().filter(|(_, set)| **set != 0).map(|(i, _)| i).collect();
+    let mut coordinates = vec![0i64; dims.len() * positions.len()];
+    for (n, &position) in positions.iter().enumerate() {
+        let mut rest = position;
+        for d in (0..dims.len()).rev() {
+            coordinates[d * positions.len() + n] = (rest % dims[d]) as i64;
+            rest /= dims[d];
+        }
+    }
+    Tensor::from_vec(coordinates, (dims.len(), positions.len()), x.device()).map_err(candle_error)
+}
+
+fn slice(node: &Node, inputs: &[Option<Tensor>]) -> Result<Tensor> {
+    let data = input(node, inputs, 0)?;
+    let (starts, ends) = match (optional(inputs, 1), node.ints("starts")) {
+        (Some(starts), _) => (ints(starts)?, ints(input(node, inputs, 2)?)?),
+        (None, Some(starts)) => (starts.to_vec(), node.ints("ends").unwrap_or_default().to_vec()),
+        (None, None) => return Err(unsupported(node, "a slice without starts")),
+    };
+    let axes = match (optional(inputs, 3), node.ints("axes")) {
+        (Some(axes), _) => ints(axes)?,
+        (None, Some(axes)) => axes.to_vec(),
+        (None, None) => (0..starts.len() as i64).collect(),
+    };
+    let steps = match optional(inputs, 4) {
+        Some(steps) => ints(steps)?,
+        None => vec![1; starts.len()],
+    };
+
+    let mut result = data.clone();
+    for (((&start, &end), &a), &step) in starts.iter().zip(&ends).zip(&axes).zip(&steps) {
+        let dim = axis(a, data.rank());
+        let size = data.dim(dim).map_err(candle_error)? as i64;
+        let resolve = |value: i64| if value < 0 { value + size } else { value };
+        let (start, end) = (resolve(start), resolve(end));
+        if step == 1 {
+            let start = start.clamp(0, size);
+            let end = end.clamp(0, size);
+            result = result.narrow(dim, start as usize, (end - start).max(0) as usize).map_err(candle_error)?;
+            continue;
+        }
+        let (start, end) = match step > 0 {
+            true => (start.clamp(0, size), end.clamp(0, size)),
+            false => (start.clamp(0, size - 1), end.clamp(-1, size - 1)),
+        };
+        let positions = std::iter::successors(Some(start), |&i| Some(i + step))
+            .take_while(|&i| if step > 0 { i < end } else { i > end })
+            .map(|i| i as usize);
+        result = result.index_select(&indices(positions, data.device()).map_err(candle_error)?, dim)
+            .map_err(candle_error)?;
+    }
+    Ok(result)
+}
+
+fn pad(node: &Node, inputs: &[Option<Tensor>]) -> Result<Tensor> {
+    let data = input(node, inputs, 0)?;
+    let rank = data.rank();
+    let pads = match (optional(inputs, 1), node.ints("pads")) {
+        (Some(pads), _) => ints(pads)?,
+        (None, Some(pads)) => pads.to_vec(),
+        (None, None) => return Ok(data.clone()),
+    };
+    let axes: Vec<usize> = match optional(inputs, 3) {
+        Some(axes) => ints(axes)?.into_iter().map(|a| axis(a, rank)).collect(),
+        None => (0..rank).collect(),
+    };
+    let mode = node.string("mode", "constant");
+    let value = match optional(inputs, 2) {
+        Some(value) => scalar(value)?,
+        None => f64::from(node.float("value", 0.0)),
+    };
+
+    let mut result = data.clone();
+    for (i, &dim) in axes.iter().enumerate() {
+        let (begin, end) = (pads[i], pads[i + axes.len()]);
+        let size = result.dim(dim).map_err(candle_error)? as i64;
+        // Negative pads crop.
+        let (crop_begin, crop_end) = ((-begin).max(0), (-end).max(0));
+        if crop_begin > 0 || crop_end > 0 {
+            result = result.narrow(dim, crop_begin as usize, (size - crop_begin - crop_end).max(0) as usize)
+                .map_err(candle_error)?;
+        }
+        let (begin, end) = (begin.max(0) as usize, end.max(0) as usize);
+        if begin == 0 && end == 0 {
+            continue;
+        }
+        let size = result.dim(dim).map_err(candle_error)?;
+        result = match mode.as_str() {
+            "constant" => {
+                let filled = |len: usize| -> CandleResult<Tensor> {
+                    let mut shape = result.dims().to_vec();
+                    shape[dim] = len;
+                    scalar_like(value, &result)?.broadcast_as(shape)?.contiguous()
+                };
+                Tensor::cat(&[filled(begin).map_err(candle_error)?, result.clone(), filled(end).map_err(candle_error)?], dim)
+            }
+            "edge" | "reflect" => {
+                let reflect = mode == "reflect";
+                let source = |i: i64| -> usize {
+                    let last = size as i64 - 1;
+                    match reflect {
+                        true if last > 0 => {
+                            let period = 2 * last;
+                            let i = i.rem_euclid(period);
+                            (if i > last { period - i } else { i }) as usize
+                        }
+                        _ => i.clamp(0, last.max(0)) as usize,
+                    }
+                };
+                let positions = (-(begin as i64)..(size + end) as i64).map(source);
+                indices(positions, result.device()).and_then(|positions| result.index_select(&positions, dim))
+            }
+            other => return Err(unsupported(node, &format!("pad mode `{}`", other))),
+        }.map_err(candle_error)?;
+    }
+    Ok(result)
+}
+
+fn reshape(node: &Node, inputs: &[Option<Tensor>]) -> Result<Tensor> {
+    let data = input(node, inputs, 0)?;
+    let target = ints(input(node, inputs, 1)?)?;
+    let allow_zero = node.int("allowzero", 0) != 0;
+    let mut shape: Vec<usize> = target.iter().enumerate()
+        .map(|(i, &dim)| match dim {
+            0 if !allow_zero => data.dims().get(i).copied().unwrap_or(0),
+            -1 => 1,
+            dim => dim as usize,
+        })
+        .collect();
+    if let Some(hole) = target.iter().position(|&dim| dim == -1) {
+        let known: usize = shape.iter().product();
+        shape[hole] = data.elem_count().checked_div(known).unwrap_or(0);
+    }
+    data.reshape(shape).map_err(candle_error)
+}
+
+fn unsqueeze(node: &Node, inputs: &[Option<Tensor>]) -> Result<Tensor> {
+    let data = input(node, inputs, 0)?;
+    let axes = match optional(inputs, 1) {
+        Some(axes) => ints(axes)?,
+        None => node.ints("axes").unwrap_or_default().to_vec(),
+    };
+    let rank = data.rank() + axes.len();
+    let mut axes: Vec<usize> = axes.iter().map(|&a| axis(a, rank)).collect();
+    axes.sort_unstable();
+    let mut shape = data.dims().to_vec();
+    for a in axes {
+        shape.insert(a, 1);
+    }
+    data.reshape(shape).map_err(candle_error)
+}
+
+fn squeeze(node: &Node, inputs: &[Option<Tensor>]) -> Result<Tensor> {
+    let data = input(node, inputs, 0)?;
+    let axes: Vec<usize> = match (optional(inputs, 1), node.ints("axes")) {
+        (Some(axes), _) => ints(axes)?.into_iter().map(|a| axis(a, data.rank())).collect(),
+        (None, Some(axes)) => axes.iter().map(|&a| axis(a, data.rank())).collect(),
+        (None, None) => (0..data.rank()).filter(|&a| data.dims()[a] == 1).collect(),
+    };
+    let shape: Vec<usize> = data.dims().iter().enumerate()
+        .filter(|(a, _)| !axes.contains(a))
+        .map(|(_, &dim)| dim)
+        .collect();
+    data.reshape(shape).map_err(candle_error)
+}
+
+fn split(node: &Node, inputs: &[Option<Tensor>]) -> Result<Vec<Tensor>> {
+    let data = input(node, inputs, 0)?;
+    let dim = axis(node.int("axis", 0), data.rank());
+    let size = data.dim(dim).map_err(candle_error)?;
+    let sizes: Vec<usize> = match (optional(inputs, 1), node.ints("split")) {
+        (Some(split), _) => ints(split)?.into_iter().map(|len| len as usize).collect(),
+        (None, Some(split)) => split.iter().map(|&len| len as usize).collect(),
+        (None, None) => {
+            let parts = node.int("num_outputs", node.outputs.len() as i64).max(1) as usize;
+            let len = size.div_ceil(parts);
+            (0..parts).map(|i| len.min(size.saturating_sub(i * len))).collect()
+        }
+    };
+    let mut start = 0;
+    sizes.into_iter()
+        .map(|len| {
+            let part = data.narrow(dim, start, len).map_err(candle_error);
+            start += len;
+            part
+        })
+        .collect()
+}
+
+fn range(node: &Node, inputs: &[Option<Tensor>]) -> Result<Tensor> {
+    let start = input(node, inputs, 0)?;
+    let (first, limit, delta) = (scalar(start)?, scalar(input(node, inputs, 1)?)?, scalar(input(node, inputs, 2)?)?);
+    let count = ((limit - first) / delta).ceil().max(0.0) as usize;
+    let device = start.device();
+    let values = (0..count).map(|i| first + i as f64 * delta);
+    match is_float(start) {
+        true => Tensor::from_vec(values.collect::<Vec<_>>(), count, device)
+            .and_then(|range| range.to_dtype(start.dtype())),
+        false => Tensor::from_vec(values.map(|value| value as i64).collect::<Vec<_>>(), count, device),
+    }.map_err(candle_error)
+}
+
+fn cumsum(node: &Node, inputs: &[Option<Tensor>]) -> Result<Tensor> {
+    let x = input(node, inputs, 0)?;
+    let dim = axis(ints(input(node, inputs, 1)?)?.first().copied().unwrap_or(0), x.rank());
+    let (exclusive, reverse) = (node.int("exclusive", 0) != 0, node.int("reverse", 0) != 0);
+    let sum = || -> CandleResult<Tensor> {
+        // candle sums through a matmul, which has no integer kernels.
+        let dtype = x.dtype();
+        let mut values = if is_float(x) { x.clone() } else { x.to_dtype(DType::F64)? };
+        if reverse {
+            values = values.flip(&[dim])?;
+        }
+        let mut sum = values.contiguous()?.cumsum(dim)?;
+        if exclusive {
+            sum = sum.sub(&values)?;
+        }
+        if reverse {
+            sum = sum.flip(&[dim])?;
+        }
+        sum.to_dtype(dtype)
+    };
+    sum().map_err(candle_error)
+}
+
+fn cast(node: &Node, inputs: &[Option<Tensor>]) -> Result<Tensor> {
+    let x = input(node, inputs, 0)?;
+    let to = node.int("to", 0);
+    if to == 9 {
+        return x.ne(&x.zeros_like().map_err(candle_error)?).map_err(candle_error);
+    }
+    x.to_dtype(dtype(to)?).map_err(candle_error)
+}
+
+fn constant(node: &Node) -> Result<Tensor> {
+    let device = Device::Cpu;
+    let value = match node.attributes.get("value") {
+        Some(Attribute::Tensor(tensor)) => return Ok(tensor.clone()),
+        Some(_) | None => node.attributes.iter().find(|(name, _)| name.starts_with("value")),
+    };
+    match value {
+        Some((_, Attribute::Float(value))) => Tensor::new(*value, &device),
+        Some((_, Attribute::Int(value))) => Tensor::new(*value, &device),
+        Some((_, Attribute::Floats(values))) => Tensor::new(values.as_slice(), &device),
+        Some((_, Attribute::Ints(values))) => Tensor::new(values.as_slice(), &device),
+        _ => return Err(unsupported(node, "a constant without a value")),
+    }.map_err(candle_error)
+}
+
+fn layer_norm(node: &Node, inputs: &[Option<Tensor>]) -> Result<Tensor> {
+    let x = input(node, inputs, 0)?;
+    let scale = input(node, inputs, 1)?;
+    let from = axis(node.int("axis", -1), x.rank());
+    let epsilon = f64::from(node.float("epsilon", 1e-5));
+    let axes: Vec<usize> = (from..x.rank()).collect();
+    let normalize = || -> CandleResult<Tensor> {
+        let centered = x.broadcast_sub(&x.mean_keepdim(axes.clone())?)?;
+        let variance = centered.sqr()?.mean_keepdim(axes.clone())?;
+        let normalized = centered.broadcast_div(&(variance + epsilon)?.sqrt()?)?.broadcast_mul(scale)?;
+        match optional(inputs, 2) {
+            Some(bias) => normalized.broadcast_add(bias),
+            None => Ok(normalized),
+        }
+    };
+    normalize().map_err(candle_error)
+}
+
+/// Runs one node on its input values, `None` for omitted optional inputs.
+pub(super) fn eval(node: &Node, inputs: &[Option<Tensor>], device: &Device) -> Result<Vec<Tensor>> {
+    let unary = |op: fn(&Tensor) -> CandleResult<Tensor>| -> Result<Vec<Tensor>> {
+        Ok(vec![op(input(node, inputs, 0)?).map_err(candle_error)?])
+    };
+    let binary = |op: fn(&Tensor, &Tensor) -> CandleResult<Tensor>| -> Result<Vec<Tensor>> {
+        Ok(vec![op(input(node, inputs, 0)?, input(node, inputs, 1)?).map_err(candle_error)?])
+    };
+    let output = match node.op_type.as_str() {
+        "Identity" | "Dropout" => input(node, inputs, 0)?.clone(),
+        "Constant" => constant(node)?.to_device(device).map_err(candle_error)?,
+
+        "Add" => return binary(Tensor::broadcast_add),
+        "Sub" => return binary(Tensor::broadcast_sub),
+        "Mul" => return binary(Tensor::broadcast_mul),
+        "Div" => return binary(Tensor::broadcast_div),
+        "Pow" => return binary(pow),
+        "Equal" => return binary(Tensor::broadcast_eq),
+        "Less" => return binary(Tensor::broadcast_lt),
+        "LessOrEqual" => return binary(Tensor::broadcast_le),
+        "Greater" => return binary(Tensor::broadcast_gt),
+        "GreaterOrEqual" => return binary(Tensor::broadcast_ge),
+        "And" => return binary(Tensor::broadcast_mul),
+        "Or" => return binary(Tensor::broadcast_maximum),
+        "Xor" => return binary(Tensor::broadcast_ne),
+        "MatMul" => return binary(matmul),
+        "Max" => variadic(node, inputs, Tensor::broadcast_maximum)?,
+        "Min" => variadic(node, inputs, Tensor::broadcast_minimum)?,
+        "Sum" => variadic(node, inputs, Tensor::broadcast_add)?,
+        "Mean" => variadic(node, inputs, Tensor::broadcast_add)?
+            .affine(1.0 / inputs.iter().flatten().count() as f64, 0.0)
+            .map_err(candle_error)?,
+
+        "Neg" => return unary(neg),
+        "Abs" => return unary(abs),
+        "Relu" => return unary(relu),
+        "Sqrt" => return unary(Tensor::sqrt),
+        "Exp" => return unary(Tensor::exp),
+        "Log" => return unary(Tensor::log),
+        "Tanh" => return unary(Tensor::tanh),
+        "Sin" => return unary(Tensor::sin),
+        "Cos" => return unary(Tensor::cos),
+        "Erf" => return unary(Tensor::erf),
+        "Ceil" => return unary(Tensor::ceil),
+        "Floor" => return unary(Tensor::floor),
+        "Round" => return unary(Tensor::round),
+        "Sign" => return unary(Tensor::sign),
+        "Reciprocal" => return unary(Tensor::recip),
+        "Sigmoid" => return unary(sigmoid),
+        "Softplus" => return unary(softplus),
+        "Not" => {
+            let x = input(node, inputs, 0)?;
+            x.eq(&x.zeros_like().map_err(candle_error)?).map_err(candle_error)?
+        }
+        "LeakyRelu" => {
+            let x = input(node, inputs, 0)?;
+            let alpha = f64::from(node.float("alpha", 0.01));
+            x.relu().and_then(|positive| positive.sub(&x.neg()?.relu()?.affine(alpha, 0.0)?))
+                .map_err(candle_error)?
+        }
+        "Clip" => {
+            let mut x = input(node, inputs, 0)?.clone();
+            let bound = |index: usize, attribute: &str| -> Result<Option<Tensor>> {
+                match (optional(inputs, index), node.attributes.get(attribute)) {
+                    (Some(bound), _) => Ok(Some(bound.clone())),
+                    (None, Some(Attribute::Float(bound))) => scalar_like(f64::from(*bound), &x).map(Some).map_err(candle_error),
+                    (None, _) => Ok(None),
+                }
+            };
+            let (min, max) = (bound(1, "min")?, bound(2, "max")?);
+            if let Some(min) = min {
+                x = x.broadcast_maximum(&min).map_err(candle_error)?;
+            }
+            if let Some(max) = max {
+                x = x.broadcast_minimum(&max).map_err(candle_error)?;
+            }
+            x
+        }
+        "Where" => {
+            let condition = input(node, inputs, 0)?;
+            let (on_true, on_false) = (input(node, inputs, 1)?, input(node, inputs, 2)?);
+            let shape = broadcast_shape(node, condition.dims(), on_true.dims())?;
+            let shape = broadcast_shape(node, &shape, on_false.dims())?;
+            condition.broadcast_as(shape.as_slice())
+                .and_then(|condition| condition.where_cond(
+                    &on_true.broadcast_as(shape.as_slice())?,
+                    &on_false.broadcast_as(shape.as_slice())?,
+                ))
+                .map_err(candle_error)?
+        }
+        "Softmax" | "LogSoftmax" => {
+            let x = input(node, inputs, 0)?;
+            softmax(x, axis(node.int("axis", -1), x.rank()), node.op_type == "LogSoftmax")
+                .map_err(candle_error)?
+        }
+        "ReduceSum" | "ReduceMean" | "ReduceMax" | "ReduceMin" | "ReduceL2" | "ReduceSumSquare" => reduce(node, inputs)?,
+        "LayerNormalization" => layer_norm(node, inputs)?,
+
+        "Conv" => conv(node, inputs)?,
+        "ConvTranspose" => conv_transpose(node, inputs)?,
+        "Gemm" => {
+            let transpose = |t: &Tensor, flag: &str| match node.int(flag, 0) {
+                0 => Ok(t.clone()),
+                _ => t.t(),
+            };
+            let a = transpose(input(node, inputs, 0)?, "transA").map_err(candle_error)?;
+            let b = transpose(input(node, inputs, 1)?, "transB").map_err(candle_error)?;
+            let (alpha, beta) = (f64::from(node.float("alpha", 1.0)), f64::from(node.float("beta", 1.0)));
+            let product = matmul(&a, &b).and_then(|product| product.affine(alpha, 0.0)).map_err(candle_error)?;
+            match optional(inputs, 2) {
+                Some(c) => c.affine(beta, 0.0).and_then(|c| product.broadcast_add(&c)).map_err(candle_error)?,
+                None => product,
+            }
+        }
+
+        "Shape" => {
+            let dims = input(node, inputs, 0)?.dims();
+            let rank = dims.len() as i64;
+            let clamp = |value: i64| (if value < 0 { value + rank } else { value }).clamp(0, rank) as usize;
+            let (start, end) = (clamp(node.int("start", 0)), clamp(node.int("end", rank)));
+            let shape: Vec<i64> = dims[start..end.max(start)].iter().map(|&dim| dim as i64).collect();
+            let len = shape.len();
+            Tensor::from_vec(shape, len, device).map_err(candle_error)?
+        }
+        "Size" => Tensor::new(input(node, inputs, 0)?.elem_count() as i64, device).map_err(candle_error)?,
+        "Reshape" => reshape(node, inputs)?,
+        "Flatten" => {
+            let x = input(node, inputs, 0)?;
+            let split = axis(node.int("axis", 1), x.rank());
+            let (outer, inner): (usize, usize) = (x.dims()[..split].iter().product(), x.dims()[split..].iter().product());
+            x.reshape((outer, inner)).map_err(candle_error)?
+        }
+        "Squeeze" => squeeze(node, inputs)?,
+        "Unsqueeze" => unsqueeze(node, inputs)?,
+        "Transpose" => {
+            let x = input(node, inputs, 0)?;
+            let perm: Vec<usize> = match node.ints("perm") {
+                Some(perm) => perm.iter().map(|&a| axis(a, x.rank())).collect(),
+                None => (0..x.rank()).rev().collect(),
+            };
+            x.permute(perm).map_err(candle_error)?
+        }
+        "Concat" => {
+            let parts: Vec<&Tensor> = inputs.iter().flatten().collect();
+            let rank = parts.first().map_or(0, |part| part.rank());
+            Tensor::cat(&parts, axis(node.int("axis", 0), rank)).map_err(candle_error)?
+        }
+        "Split" => return split(node, inputs),
+        "Slice" => slice(node, inputs)?,
+        "Pad" => pad(node, inputs)?,
+        "Expand" => {
+            let x = input(node, inputs, 0)?;
+            let target: Vec<usize> = ints(input(node, inputs, 1)?)?.into_iter().map(|dim| dim as usize).collect();
+            let shape = broadcast_shape(node, x.dims(), &target)?;
+            x.broadcast_as(shape).map_err(candle_error)?
+        }
+        "Tile" => {
+            let repeats: Vec<usize> = ints(input(node, inputs, 1)?)?.into_iter().map(|count| count as usize).collect();
+            input(node, inputs, 0)?.repeat(repeats).map_err(candle_error)?
+        }
+        "ConstantOfShape" => {
+            let shape: Vec<usize> = ints(input(node, inputs, 0)?)?.into_iter().map(|dim| dim as usize).collect();
+            let value = match node.attributes.get("value") {
+                Some(Attribute::Tensor(value)) => value.clone(),
+                _ => Tensor::new(0f32, device).map_err(candle_error)?,
+            };
+            value.flatten_all()
+                .and_then(|value| value.get(0))
+                .and_then(|value| value.to_device(device))
+                .and_then(|value| value.broadcast_as(shape))
+                .and_then(|value| value.contiguous())
+                .map_err(candle_error)?
+        }
+        "Range" => range(node, inputs)?,
+        "CumSum" => cumsum(node, inputs)?,
+        "Cast" => cast(node, inputs)?,
+        "Gather" => gather(node, inputs)?,
+        "GatherElements" => gather_elements(node, inputs)?,
+        "GatherND" => gather_nd(node, inputs)?,
+        "ScatterND" => scatter_nd(node, inputs)?,
+        "NonZero" => non_zero(node, inputs)?,
+        "RandomNormalLike" | "RandomUniformLike" => {
+            let like = input(node, inputs, 0)?;
+            let dtype = match node.int("dtype", -1) {
+                -1 => like.dtype(),
+                to => dtype(to)?,
+            };
+            let random = match node.attributes.get("seed") {
+                Some(Attribute::Float(seed)) => seeded_random(node, like.dims(), *seed, device),
+                _ if node.op_type == "RandomNormalLike" => Tensor::randn(
+                    node.float("mean", 0.0), node.float("scale", 1.0), like.dims(), device,
+                ),
+                _ => Tensor::rand(
+                    node.float("low", 0.0), node.float("high", 1.0), like.dims(), device,
+                ),
+            };
+            random.and_then(|random| random.to_dtype(dtype)).map_err(candle_error)?
+        }
+        _ => return Err(unsupported(node, "the operator")),
+    };
+    Ok(vec![output])
+}
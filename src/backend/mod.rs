@@ -0,0 +1,109 @@
+use std::str::FromStr;
+
+use ndarray::{Array1, Array2};
+
+use crate::{
+    asset::Asset,
+    error::{PiperError, Result},
+    session::SessionOptions,
+    signature::Signature,
+};
+
+#[cfg(feature = "candle")]
+pub mod candle;
+mod onnxruntime;
+mod seed;
+#[cfg(feature = "tract")]
+mod tract;
+
+#[cfg_attr(not(feature = "candle"), allow(unused_imports))]
+pub(crate) use seed::SeededRng;
+
+/// Inputs of one voice inference, batched along the first axis.
+#[derive(Debug, Clone)]
+pub struct VoiceInput {
+    pub ids: Array2<i64>,
+    pub lengths: Array1<i64>,
+    /// `noise_scale`, `length_scale` and `noise_w`.
+    pub scales: Array1<f32>,
+    /// Speaker ids, for models with a speaker input.
+    pub sid: Option<Array1<i64>>,
+}
+
+/// Audio of one inference, and the predicted duration frames of every
+/// phoneme id for voices exporting them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InferenceOutput {
+    pub shape: Vec<i64>,
+    pub audio: Vec<f32>,
+    pub durations: Option<Vec<f32>>,
+}
+
+/// Runs a voice model, see [`Backend`] for the implementations.
+pub trait InferenceBackend: Send {
+    /// The model's inputs and outputs.
+    fn signature(&self) -> &Signature;
+
+    fn run(&mut self, input: VoiceInput) -> Result<InferenceOutput>;
+}
+
+/// Inference engine the voices run on. The G2P always runs on ONNX Runtime.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Backend {
+    #[default]
+    OnnxRuntime,
+    /// Pure-Rust [tract](https://github.com/sonos/tract) on the CPU, with the
+    /// `tract` feature. Execution providers and the ONNX Runtime session
+    /// options don't apply.
+    #[cfg(feature = "tract")]
+    Tract,
+    /// [`CandleBackend`](candle::CandleBackend), with the `candle` feature,
+    /// on the first CUDA or Metal device candle is built with, else the CPU.
+    #[cfg(feature = "candle")]
+    Candle,
+}
+
+impl FromStr for Backend {
+    type Err = PiperError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "onnxruntime" | "ort" => Ok(Backend::OnnxRuntime),
+            #[cfg(feature = "tract")]
+            "tract" => Ok(Backend::Tract),
+            #[cfg(feature = "candle")]
+            "candle" => Ok(Backend::Candle),
+            _ => Err(PiperError::InvalidConfig(format!("unknown inference backend `{}`", s))),
+        }
+    }
+}
+
+pub(crate) fn load(model: &Asset<'_>, options: &SessionOptions) -> Result<Box<dyn InferenceBackend>> {
+    let seeded = match options.seed {
+        Some(seed) => {
+            let (bytes, seeded) = model.map("model", |bytes| seed::seed_random_ops(bytes, seed))?;
+            if seeded == 0 {
+                tracing::warn!("`{}` has no random operators to seed", model.name());
+            }
+            Some(Asset::Bytes(bytes.into()))
+        }
+        None => None,
+    };
+    let model = seeded.as_ref().unwrap_or(model);
+
+    match options.backend {
+        Backend::OnnxRuntime => Ok(Box::new(onnxruntime::OrtBackend::new(model, options)?)),
+        #[cfg(feature = "tract")]
+        Backend::Tract => Ok(Box::new(tract::TractBackend::new(model)?)),
+        #[cfg(feature = "candle")]
+        Backend::Candle => {
+            let device = candle_core::Device::cuda_if_available(0)
+                .and_then(|device| match device.is_cpu() {
+                    true => candle_core::Device::metal_if_available(0),
+                    false => Ok(device),
+                })
+                .map_err(|e| PiperError::Backend(e.to_string()))?;
+            Ok(Box::new(candle::CandleBackend::from_asset(model, &device)?))
+        }
+    }
+}
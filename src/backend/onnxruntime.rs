@@ -0,0 +1,101 @@
+use ndarray::Array1;
+use ort::{session::Session, value::ValueType};
+
+use crate::{
+    asset::Asset,
+    error::{PiperError, Result},
+    session::{build_session, SessionOptions},
+    signature::{Signature, TensorInfo},
+};
+
+use super::{InferenceBackend, InferenceOutput, VoiceInput};
+
+fn tensor_info(name: &str, value_type: &ValueType) -> TensorInfo {
+    TensorInfo {
+        name: name.to_string(),
+        tensor: match value_type {
+            ValueType::Tensor { ty, shape, .. } => Some((*ty, shape.len())),
+            _ => None,
+        },
+    }
+}
+
+/// A float input tensor, converted to half precision for `float16` voices.
+#[cfg_attr(not(feature = "fp16"), allow(unused_variables))]
+fn float_input(array: Array1<f32>, half_precision: bool) -> Result<ort::value::DynValue> {
+    #[cfg(feature = "fp16")]
+    if half_precision {
+        return Ok(ort::value::Tensor::from_array(array.mapv(half::f16::from_f32))?.into_dyn());
+    }
+    Ok(ort::value::Tensor::from_array(array)?.into_dyn())
+}
+
+/// Shape and values of a float output, widened to `f32` for `float16` voices.
+fn float_output(value: &ort::value::DynValue) -> Result<(Vec<i64>, Vec<f32>)> {
+    #[cfg(feature = "fp16")]
+    if let Ok((shape, values)) = value.try_extract_tensor::<half::f16>() {
+        return Ok((shape.to_vec(), values.iter().map(|value| value.to_f32()).collect()));
+    }
+    let (shape, values) = value.try_extract_tensor::<f32>()?;
+    Ok((shape.to_vec(), values.to_vec()))
+}
+
+pub(crate) struct OrtBackend {
+    session: Session,
+    signature: Signature,
+    /// The seeded model, reloaded before every inference but the first so
+    /// each one draws the same noise.
+    reload: Option<(Vec<u8>, SessionOptions)>,
+    ran: bool,
+}
+
+impl OrtBackend {
+    pub(crate) fn new(model: &Asset<'_>, options: &SessionOptions) -> Result<Self> {
+        let session = build_session(model, options)?;
+        let inputs: Vec<TensorInfo> = session.inputs.iter()
+            .map(|input| tensor_info(&input.name, &input.input_type))
+            .collect();
+        let outputs: Vec<TensorInfo> = session.outputs.iter()
+            .map(|output| tensor_info(&output.name, &output.output_type))
+            .collect();
+        let signature = Signature::detect(&inputs, &outputs)?;
+        let reload = match options.seed {
+            Some(_) => Some((model.read("model")?.into_owned(), options.clone())),
+            None => None,
+        };
+        Ok(Self { session, signature, reload, ran: false })
+    }
+}
+
+impl InferenceBackend for OrtBackend {
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn run(&mut self, input: VoiceInput) -> Result<InferenceOutput> {
+        if let Some((model, options)) = &self.reload
+            && self.ran
+        {
+            self.session = build_session(&Asset::Bytes(model.as_slice().into()), options)?;
+        }
+        self.ran = true;
+
+        let signature = &self.signature;
+        let mut inputs = ort::inputs!{
+            signature.input.as_str() => ort::value::Tensor::from_array(input.ids)?,
+            signature.input_lengths.as_str() => ort::value::Tensor::from_array(input.lengths)?,
+            signature.scales.as_str() => float_input(input.scales, signature.half_precision)?,
+        };
+        if let (Some(name), Some(sid)) = (&signature.sid, input.sid) {
+            inputs.push((name.as_str().into(), ort::value::Tensor::from_array(sid)?.into()));
+        }
+
+        let outputs = self.session.run(inputs)?;
+        let (shape, audio) = float_output(outputs.get(&signature.output).ok_or(PiperError::MissingOutput("output"))?)?;
+        let durations = match signature.durations.as_ref().and_then(|name| outputs.get(name)) {
+            Some(output) => Some(float_output(output)?.1),
+            None => None,
+        };
+        Ok(InferenceOutput { shape, audio, durations })
+    }
+}
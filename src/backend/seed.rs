@@ -0,0 +1,103 @@
+use crate::inspect::{fields, write_field, Field};
+
+/// Operators drawing random numbers, which take a `seed` attribute.
+const RANDOM_OPS: [&str; 4] = ["RandomNormal", "RandomNormalLike", "RandomUniform", "RandomUniformLike"];
+
+/// SplitMix64, small and good enough for noise.
+#[derive(Debug, Clone)]
+pub(crate) struct SeededRng(u64);
+
+impl SeededRng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut x = self.0;
+        x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        x ^ (x >> 31)
+    }
+
+    /// Uniform in `[0, 1)`.
+    #[cfg_attr(not(feature = "candle"), allow(dead_code))]
+    pub(crate) fn uniform(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Standard normal, by the Box-Muller transform.
+    #[cfg_attr(not(feature = "candle"), allow(dead_code))]
+    pub(crate) fn normal(&mut self) -> f32 {
+        let radius = (-2.0 * (1.0 - self.uniform()).ln()).sqrt();
+        radius * (std::f32::consts::TAU * self.uniform()).cos()
+    }
+}
+
+/// Sets the `seed` attribute of the random operators in the main graph of an
+/// ONNX `ModelProto`, each to its own value drawn from `seed`. Returns the
+/// rewritten model and the number of operators seeded.
+pub(crate) fn seed_random_ops(model: &[u8], seed: u64) -> (Vec<u8>, usize) {
+    const GRAPH: u64 = 7;
+    const NODE: u64 = 1;
+    let mut rng = SeededRng::new(seed);
+    let mut seeded = 0;
+    let mut rewritten = Vec::with_capacity(model.len() + 64);
+    for (number, field) in fields(model) {
+        let (GRAPH, Field::Bytes(graph)) = (number, &field) else {
+            write_field(&mut rewritten, number, &field);
+            continue;
+        };
+        let mut graph_bytes = Vec::with_capacity(graph.len() + 64);
+        for (number, field) in fields(graph) {
+            match (number, &field) {
+                (NODE, Field::Bytes(node)) if is_random(node) => {
+                    // Integers below 2^24 survive the float attribute exactly.
+                    let node = seed_node(node, (rng.next_u64() >> 40) as f32);
+                    write_field(&mut graph_bytes, NODE, &Field::Bytes(&node));
+                    seeded += 1;
+                }
+                _ => write_field(&mut graph_bytes, number, &field),
+            }
+        }
+        write_field(&mut rewritten, GRAPH, &Field::Bytes(&graph_bytes));
+    }
+    (rewritten, seeded)
+}
+
+fn is_random(node: &[u8]) -> bool {
+    const OP_TYPE: u64 = 4;
+    fields(node).into_iter().any(|(number, field)| {
+        matches!(field, Field::Bytes(op_type) if number == OP_TYPE
+            && std::str::from_utf8(op_type).is_ok_and(|op_type| RANDOM_OPS.contains(&op_type)))
+    })
+}
+
+/// `node` with its `seed` attribute replaced by `seed`.
+fn seed_node(node: &[u8], seed: f32) -> Vec<u8> {
+    const ATTRIBUTE: u64 = 5;
+    const NAME: u64 = 1;
+    const FLOAT: u64 = 2;
+    const TYPE: u64 = 20;
+    let is_seed = |attribute: &[u8]| {
+        fields(attribute).into_iter()
+            .any(|(number, field)| matches!(field, Field::Bytes(b"seed") if number == NAME))
+    };
+
+    let mut rewritten = Vec::with_capacity(node.len() + 16);
+    for (number, field) in fields(node) {
+        if let (ATTRIBUTE, Field::Bytes(attribute)) = (number, &field)
+            && is_seed(attribute)
+        {
+            continue;
+        }
+        write_field(&mut rewritten, number, &field);
+    }
+    let mut attribute = Vec::new();
+    write_field(&mut attribute, NAME, &Field::Bytes(b"seed"));
+    write_field(&mut attribute, FLOAT, &Field::Fixed32(seed.to_bits()));
+    // `AttributeProto.AttributeType.FLOAT`
+    write_field(&mut attribute, TYPE, &Field::Varint(1));
+    write_field(&mut rewritten, ATTRIBUTE, &Field::Bytes(&attribute));
+    rewritten
+}
@@ -0,0 +1,122 @@
+use ndarray::{Array, Dimension};
+use ort::tensor::TensorElementType;
+use tract_onnx::prelude::*;
+
+use crate::{
+    asset::Asset,
+    error::{PiperError, Result},
+    signature::{Signature, TensorInfo},
+};
+
+use super::{InferenceBackend, InferenceOutput, VoiceInput};
+
+fn backend_error(error: TractError) -> PiperError {
+    PiperError::Backend(format!("{:#}", error))
+}
+
+fn element_type(datum: DatumType) -> Option<TensorElementType> {
+    Some(match datum {
+        DatumType::Bool => TensorElementType::Bool,
+        DatumType::U8 => TensorElementType::Uint8,
+        DatumType::I8 => TensorElementType::Int8,
+        DatumType::I32 => TensorElementType::Int32,
+        DatumType::I64 => TensorElementType::Int64,
+        DatumType::F16 => TensorElementType::Float16,
+        DatumType::F32 => TensorElementType::Float32,
+        DatumType::F64 => TensorElementType::Float64,
+        DatumType::String => TensorElementType::String,
+        _ => return None,
+    })
+}
+
+fn tensor_info(model: &TypedModel, outlet: OutletId) -> Result<TensorInfo> {
+    let fact = model.outlet_fact(outlet).map_err(backend_error)?;
+    let name = model.outlet_label(outlet).unwrap_or(&model.node(outlet.node).name);
+    Ok(TensorInfo {
+        name: name.to_string(),
+        tensor: element_type(fact.datum_type).map(|element| (element, fact.rank())),
+    })
+}
+
+fn tensor<T: Datum + Copy, D: Dimension>(array: &Array<T, D>) -> Result<Tensor> {
+    let values: Vec<T> = array.iter().copied().collect();
+    Tensor::from_shape(array.shape(), &values).map_err(backend_error)
+}
+
+pub(crate) struct TractBackend {
+    plan: TypedSimplePlan<TypedModel>,
+    /// Input and output names, in the order the plan takes and returns them.
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    signature: Signature,
+}
+
+impl TractBackend {
+    pub(crate) fn new(model: &Asset<'_>) -> Result<Self> {
+        let model = model.map("model", |bytes| tract_onnx::onnx().model_for_read(&mut &bytes[..]))?
+            .and_then(|model| model.into_optimized())
+            .map_err(backend_error)?;
+
+        let inputs = model.input_outlets().map_err(backend_error)?.iter()
+            .map(|&outlet| tensor_info(&model, outlet))
+            .collect::<Result<Vec<_>>>()?;
+        let outputs = model.output_outlets().map_err(backend_error)?.iter()
+            .map(|&outlet| tensor_info(&model, outlet))
+            .collect::<Result<Vec<_>>>()?;
+        let signature = Signature::detect(&inputs, &outputs)?;
+        let inputs = inputs.into_iter().map(|input| input.name).collect();
+        let outputs = outputs.into_iter().map(|output| output.name).collect();
+
+        let plan = model.into_runnable().map_err(backend_error)?;
+        Ok(Self { plan, inputs, outputs, signature })
+    }
+}
+
+impl InferenceBackend for TractBackend {
+    fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    fn run(&mut self, input: VoiceInput) -> Result<InferenceOutput> {
+        let signature = &self.signature;
+        let mut scales = tensor(&input.scales)?;
+        if signature.half_precision {
+            scales = scales.cast_to_dt(DatumType::F16).map_err(backend_error)?.into_owned();
+        }
+
+        let mut values = TVec::new();
+        for name in &self.inputs {
+            let value = if *name == signature.input {
+                tensor(&input.ids)?
+            } else if *name == signature.input_lengths {
+                tensor(&input.lengths)?
+            } else if *name == signature.scales {
+                scales.clone()
+            } else {
+                match &input.sid {
+                    Some(sid) => tensor(sid)?,
+                    None => return Err(PiperError::Backend(format!("no value for input `{}`", name))),
+                }
+            };
+            values.push(value.into_tvalue());
+        }
+
+        let outputs = self.plan.run(values).map_err(backend_error)?;
+        let output = |name: &str| -> Result<Option<(Vec<i64>, Vec<f32>)>> {
+            let position = self.outputs.iter().position(|output| output == name);
+            let Some(value) = position.and_then(|position| outputs.get(position)) else {
+                return Ok(None);
+            };
+            let values = value.cast_to::<f32>().map_err(backend_error)?;
+            let shape = value.shape().iter().map(|&dim| dim as i64).collect();
+            Ok(Some((shape, values.as_slice::<f32>().map_err(backend_error)?.to_vec())))
+        };
+
+        let (shape, audio) = output(&signature.output)?.ok_or(PiperError::MissingOutput("output"))?;
+        let durations = match &signature.durations {
+            Some(name) => output(name)?.map(|(_, durations)| durations),
+            None => None,
+        };
+        Ok(InferenceOutput { shape, audio, durations })
+    }
+}
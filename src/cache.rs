@@ -0,0 +1,90 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use crate::error::Result;
+
+const FLUSH_EVERY_ENTRIES: usize = 64;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Word → IPA phonemes store persisted as a JSON object.
+///
+/// New entries are written back once enough of them pile up or the last
+/// flush is older than 30 seconds, and once more when the cache is dropped.
+pub struct DiskCache {
+    path: PathBuf,
+    entries: BTreeMap<String, Vec<String>>,
+    pending: usize,
+    last_flush: Instant,
+}
+
+impl DiskCache {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let entries = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&path)?)?
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self {
+            path,
+            entries,
+            pending: 0,
+            last_flush: Instant::now(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, word: &str) -> Option<&Vec<String>> {
+        self.entries.get(word)
+    }
+
+    pub fn insert(&mut self, word: String, ipa: Vec<String>) -> Result<()> {
+        self.entries.insert(word, ipa);
+        self.pending += 1;
+
+        if self.pending >= FLUSH_EVERY_ENTRIES || self.last_flush.elapsed() >= FLUSH_INTERVAL {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        if self.pending == 0 {
+            return Ok(());
+        }
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let tmp_path = self.path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_string(&self.entries)?)?;
+        std::fs::rename(&tmp_path, &self.path)?;
+
+        self.pending = 0;
+        self.last_flush = Instant::now();
+        Ok(())
+    }
+}
+
+impl Drop for DiskCache {
+    fn drop(&mut self) {
+        if let Err(err) = self.flush() {
+            tracing::warn!("failed to flush G2P cache `{}`: {}", self.path.display(), err);
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+use crate::error::{PiperError, Result};
+
+/// Shared flag used to abort an in-flight synthesis from another thread.
+///
+/// Clones observe the same flag. Synthesis checks it between sentences and
+/// between G2P decode steps and returns [`PiperError::Cancelled`] once set.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(PiperError::Cancelled);
+        }
+        Ok(())
+    }
+
+    /// Returns a guard that cancels the token when dropped, unless disarmed first.
+    pub fn drop_guard(&self) -> DropGuard {
+        DropGuard { token: Some(self.clone()) }
+    }
+}
+
+pub struct DropGuard {
+    token: Option<CancellationToken>,
+}
+
+impl DropGuard {
+    pub fn disarm(mut self) -> CancellationToken {
+        self.token.take().expect("token is only taken on disarm")
+    }
+}
+
+impl Drop for DropGuard {
+    fn drop(&mut self) {
+        if let Some(token) = &self.token {
+            token.cancel();
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::{
+    io::{self, Read, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::Path,
+    sync::Arc,
+    thread,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    audio::SampleFormat,
+    encoding::OutputFormat,
+    error::{PiperError, Result},
+    synthesizer::{SynthesisOptions, Synthesizer},
+};
+
+/// Largest request accepted, protecting the daemon from bogus length prefixes.
+pub const MAX_REQUEST_BYTES: u32 = 1 << 20;
+
+/// Status byte of a response carrying audio.
+pub const STATUS_OK: u8 = 0;
+/// Status byte of a response carrying an error message.
+pub const STATUS_ERROR: u8 = 1;
+
+/// JSON body of a request frame.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct Request {
+    pub text: String,
+    /// Output format such as `wav` or `pcm`, WAV by default.
+    pub format: Option<String>,
+    #[serde(flatten)]
+    pub options: SynthesisOptions,
+}
+
+fn read_frame(stream: &mut impl Read) -> io::Result<Option<Vec<u8>>> {
+    let mut length = [0; 4];
+    match stream.read_exact(&mut length) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    let length = u32::from_be_bytes(length);
+    if length > MAX_REQUEST_BYTES {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("request of {} bytes is too large", length)));
+    }
+    let mut frame = vec![0; length as usize];
+    stream.read_exact(&mut frame)?;
+    Ok(Some(frame))
+}
+
+fn write_response(stream: &mut impl Write, status: u8, payload: &[u8]) -> io::Result<()> {
+    let length = u32::try_from(payload.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "response does not fit a frame"))?;
+    stream.write_all(&[status])?;
+    stream.write_all(&length.to_be_bytes())?;
+    stream.write_all(payload)?;
+    stream.flush()
+}
+
+fn respond(synthesizer: &Synthesizer, frame: &[u8]) -> Result<Vec<u8>> {
+    let request: Request = serde_json::from_slice(frame)?;
+    let format = match &request.format {
+        Some(format) => format.parse()?,
+        None => OutputFormat::Wav,
+    };
+    let waveform = synthesizer.synthesize_with(&request.text, &request.options)?;
+    format.encode(&waveform, synthesizer.sample_rate(), SampleFormat::I16)
+}
+
+/// Answers requests on one connection until the client hangs up.
+fn handle(synthesizer: &Synthesizer, mut stream: UnixStream) -> io::Result<()> {
+    while let Some(frame) = read_frame(&mut stream)? {
+        match respond(synthesizer, &frame) {
+            Ok(audio) => write_response(&mut stream, STATUS_OK, &audio)?,
+            Err(err) => write_response(&mut stream, STATUS_ERROR, err.to_string().as_bytes())?,
+        }
+    }
+    Ok(())
+}
+
+/// Listens on a Unix socket at `path`, keeping the models loaded for every
+/// client. A request is a big-endian `u32` length followed by a JSON
+/// [`Request`], the response a status byte ([`STATUS_OK`] or
+/// [`STATUS_ERROR`]), a big-endian `u32` length and the encoded audio or the
+/// error message. Connections are served on their own threads and may send
+/// any number of requests.
+pub fn serve(synthesizer: Synthesizer, path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    // A socket file left behind by a daemon that is no longer running.
+    if path.exists() && UnixStream::connect(path).is_err() {
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    tracing::info!("Listening on {}", path.display());
+
+    let synthesizer = Arc::new(synthesizer);
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                tracing::warn!("failed to accept connection: {}", err);
+                continue;
+            }
+        };
+        let synthesizer = synthesizer.clone();
+        thread::spawn(move || {
+            if let Err(err) = handle(&synthesizer, stream) {
+                tracing::warn!("connection failed: {}", err);
+            }
+        });
+    }
+    Ok(())
+}
+
+/// Sends one request to the daemon listening at `path` and returns the audio.
+pub fn request(path: impl AsRef<Path>, request: &Request) -> Result<Vec<u8>> {
+    let mut stream = UnixStream::connect(path)?;
+    let body = serde_json::to_vec(request)?;
+    let length = u32::try_from(body.len()).ok()
+        .filter(|&length| length <= MAX_REQUEST_BYTES)
+        .ok_or_else(|| PiperError::InvalidConfig(format!("request of {} bytes is too large", body.len())))?;
+    stream.write_all(&length.to_be_bytes())?;
+    stream.write_all(&body)?;
+
+    let mut header = [0; 5];
+    stream.read_exact(&mut header)?;
+    let length = u32::from_be_bytes([header[1], header[2], header[3], header[4]]);
+    let mut payload = vec![0; length as usize];
+    stream.read_exact(&mut payload)?;
+    match header[0] {
+        STATUS_OK => Ok(payload),
+        _ => Err(PiperError::TaskFailed(String::from_utf8_lossy(&payload).into_owned())),
+    }
+}
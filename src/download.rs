@@ -0,0 +1,163 @@
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+};
+
+use md5::{Digest, Md5};
+use serde::Deserialize;
+use ureq::{
+    tls::{RootCerts, TlsConfig, TlsProvider},
+    Agent,
+};
+
+use crate::error::{PiperError, Result};
+
+/// Root of the upstream voice repository on Hugging Face.
+pub const VOICES_URL: &str = "https://huggingface.co/rhasspy/piper-voices/resolve/main";
+
+/// Largest `voices.json` accepted.
+const CATALOG_LIMIT: u64 = 64 * 1024 * 1024;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CatalogLanguage {
+    pub code: String,
+    pub family: String,
+    pub region: String,
+    pub name_native: String,
+    pub name_english: String,
+    pub country_english: String,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct CatalogFile {
+    pub size_bytes: u64,
+    pub md5_digest: String,
+}
+
+/// A voice of the upstream `voices.json` manifest, `files` being keyed by
+/// their path in the repository.
+#[derive(Deserialize, Debug, Clone)]
+pub struct CatalogVoice {
+    pub key: String,
+    pub name: String,
+    pub language: CatalogLanguage,
+    pub quality: String,
+    pub num_speakers: u32,
+    #[serde(default)]
+    pub speaker_id_map: HashMap<String, i64>,
+    pub files: HashMap<String, CatalogFile>,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+}
+
+fn agent() -> Agent {
+    let tls = TlsConfig::builder()
+        .provider(TlsProvider::NativeTls)
+        .root_certs(RootCerts::PlatformVerifier)
+        .build();
+    Agent::config_builder().tls_config(tls).build().into()
+}
+
+fn download_error(err: ureq::Error) -> PiperError {
+    PiperError::Download(err.to_string())
+}
+
+/// Fetches the upstream voice manifest, keyed by voice name such as
+/// `en_US-norman-medium`.
+pub fn fetch_catalog() -> Result<HashMap<String, CatalogVoice>> {
+    let catalog = agent().get(format!("{}/voices.json", VOICES_URL))
+        .call()
+        .map_err(download_error)?
+        .body_mut()
+        .with_config()
+        .limit(CATALOG_LIMIT)
+        .read_to_string()
+        .map_err(download_error)?;
+    Ok(serde_json::from_str(&catalog)?)
+}
+
+/// Downloads the model and config of the voice `key` (or one of its aliases)
+/// into `dir`, returning the model path. Files already present with the right
+/// checksum are kept, interrupted downloads are resumed.
+pub fn download_voice(key: &str, dir: impl AsRef<Path>) -> Result<PathBuf> {
+    let catalog = fetch_catalog()?;
+    let voice = catalog.get(key)
+        .or_else(|| catalog.values().find(|voice| voice.aliases.iter().any(|alias| alias == key)))
+        .ok_or_else(|| PiperError::Download(format!("unknown voice `{}`", key)))?;
+
+    fs::create_dir_all(dir.as_ref())?;
+    let agent = agent();
+    let mut model = None;
+    for (path, file) in &voice.files {
+        if !path.ends_with(".onnx") && !path.ends_with(".onnx.json") {
+            continue;
+        }
+        let name = path.rsplit('/').next().unwrap_or(path);
+        let destination = dir.as_ref().join(name);
+        download_file(&agent, &format!("{}/{}", VOICES_URL, path), &destination, file)?;
+        if path.ends_with(".onnx") {
+            model = Some(destination);
+        }
+    }
+    model.ok_or_else(|| PiperError::Download(format!("voice `{}` has no model file", key)))
+}
+
+fn md5_hex(path: &Path) -> Result<String> {
+    let mut hasher = Md5::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher.finalize().iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+fn download_file(agent: &Agent, url: &str, destination: &Path, file: &CatalogFile) -> Result<()> {
+    if destination.exists() && md5_hex(destination)? == file.md5_digest {
+        tracing::info!("{} is up to date", destination.display());
+        return Ok(());
+    }
+
+    let mut partial = destination.as_os_str().to_owned();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+    let mut downloaded = fs::metadata(&partial).map_or(0, |metadata| metadata.len());
+    if downloaded > file.size_bytes {
+        fs::remove_file(&partial)?;
+        downloaded = 0;
+    }
+
+    if downloaded < file.size_bytes {
+        tracing::info!(
+            "Downloading {} ({:.1}/{:.1} MB already there)",
+            destination.display(), downloaded as f64 / 1e6, file.size_bytes as f64 / 1e6
+        );
+        let mut request = agent.get(url);
+        if downloaded > 0 {
+            request = request.header("Range", format!("bytes={}-", downloaded));
+        }
+        let response = request.call().map_err(download_error)?;
+        // Servers ignoring the range send the whole file again.
+        let resumed = response.status() == 206;
+        if !resumed {
+            downloaded = 0;
+        }
+        let mut out = OpenOptions::new().create(true).write(true).append(resumed).truncate(!resumed).open(&partial)?;
+        let mut reader = response.into_body().into_reader();
+        let mut buffer = vec![0; 64 * 1024];
+        loop {
+            let read = reader.read(&mut buffer)?;
+            if read == 0 {
+                break;
+            }
+            out.write_all(&buffer[..read])?;
+            downloaded += read as u64;
+            tracing::trace!(downloaded, total = file.size_bytes, "{}", destination.display());
+        }
+    }
+
+    if md5_hex(&partial)? != file.md5_digest {
+        fs::remove_file(&partial)?;
+        return Err(PiperError::Download(format!("checksum mismatch for {}", url)));
+    }
+    fs::rename(&partial, destination)?;
+    Ok(())
+}
@@ -0,0 +1,19 @@
+/// The ARPAbet mapping plus the G2P files at the absolute paths in
+/// `PIPER_G2P_ENCODER`, `PIPER_G2P_DECODER`, `PIPER_G2P_TOKENIZER` and
+/// `PIPER_G2P_VOCAB` at build time.
+#[cfg(feature = "embed-g2p")]
+pub mod g2p {
+    pub const ENCODER: &[u8] = include_bytes!(env!("PIPER_G2P_ENCODER"));
+    pub const DECODER: &[u8] = include_bytes!(env!("PIPER_G2P_DECODER"));
+    pub const TOKENIZER: &[u8] = include_bytes!(env!("PIPER_G2P_TOKENIZER"));
+    pub const VOCAB: &[u8] = include_bytes!(env!("PIPER_G2P_VOCAB"));
+    pub const ARPABET_MAPPING: &str = include_str!("../arpabet-mapping.txt");
+}
+
+/// The voice at the absolute path in `PIPER_EMBED_VOICE` at build time, with
+/// its `.onnx.json` config next to it.
+#[cfg(feature = "embed-voice")]
+pub mod voice {
+    pub const MODEL: &[u8] = include_bytes!(env!("PIPER_EMBED_VOICE"));
+    pub const CONFIG: &str = include_str!(concat!(env!("PIPER_EMBED_VOICE"), ".json"));
+}
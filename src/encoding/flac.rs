@@ -0,0 +1,305 @@
+use crate::{
+    audio::warn_if_clipping,
+    error::{PiperError, Result},
+};
+
+const BLOCK_SIZE: usize = 4096;
+const MAX_FIXED_ORDER: usize = 4;
+const MAX_RICE_PARAMETER: u32 = 14;
+
+/// MSB-first bit packer.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u64, bits: u32) {
+        for shift in (0..bits).rev() {
+            self.buffer = (self.buffer << 1) | ((value >> shift) & 1);
+            self.bits += 1;
+            if self.bits == 8 {
+                self.bytes.push(self.buffer as u8);
+                self.buffer = 0;
+                self.bits = 0;
+            }
+        }
+    }
+
+    fn write_signed(&mut self, value: i64, bits: u32) {
+        self.write(value as u64 & ((1 << bits) - 1), bits);
+    }
+
+    fn write_unary(&mut self, zeros: u64) {
+        for _ in 0..zeros {
+            self.write(0, 1);
+        }
+        self.write(1, 1);
+    }
+
+    fn align(&mut self) {
+        if self.bits > 0 {
+            self.write(0, 8 - self.bits);
+        }
+    }
+
+    fn into_bytes(mut self) -> Vec<u8> {
+        self.align();
+        self.bytes
+    }
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |crc, &byte| {
+        (0..8).fold(crc ^ byte, |crc, _| match crc & 0x80 {
+            0 => crc << 1,
+            _ => (crc << 1) ^ 0x07,
+        })
+    })
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |crc, &byte| {
+        (0..8).fold(crc ^ ((byte as u16) << 8), |crc, _| match crc & 0x8000 {
+            0 => crc << 1,
+            _ => (crc << 1) ^ 0x8005,
+        })
+    })
+}
+
+/// Frame numbers are coded like UTF-8 extended to 36 bits.
+fn write_utf8_number(writer: &mut BitWriter, value: u64) {
+    if value < 0x80 {
+        writer.write(value, 8);
+        return;
+    }
+    let continuation_bytes = match value {
+        0..0x800 => 1,
+        0x800..0x1_0000 => 2,
+        0x1_0000..0x20_0000 => 3,
+        0x20_0000..0x400_0000 => 4,
+        0x400_0000..0x8000_0000 => 5,
+        _ => 6,
+    };
+    let lead_marker = (0xFF00u64 >> (continuation_bytes + 1)) & 0xFF;
+    writer.write(lead_marker | (value >> (6 * continuation_bytes)), 8);
+    for i in (0..continuation_bytes).rev() {
+        writer.write(0x80 | ((value >> (6 * i)) & 0x3F), 8);
+    }
+}
+
+fn fixed_residual(samples: &[i64], order: usize) -> Vec<i64> {
+    (order..samples.len())
+        .map(|i| {
+            let s = |back: usize| samples[i - back];
+            match order {
+                0 => s(0),
+                1 => s(0) - s(1),
+                2 => s(0) - 2 * s(1) + s(2),
+                3 => s(0) - 3 * s(1) + 3 * s(2) - s(3),
+                _ => s(0) - 4 * s(1) + 6 * s(2) - 4 * s(3) + s(4),
+            }
+        })
+        .collect()
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn rice_cost(residual: &[i64], parameter: u32) -> u64 {
+    residual.iter().map(|&r| (zigzag(r) >> parameter) + 1 + parameter as u64).sum()
+}
+
+/// Writes one mono subframe with the fixed predictor and Rice parameter that
+/// give the smallest residual, falling back to verbatim samples for noise.
+fn write_subframe(writer: &mut BitWriter, samples: &[i64], bits_per_sample: u32) {
+    let (order, residual, parameter, cost) = (0..=MAX_FIXED_ORDER.min(samples.len()))
+        .map(|order| {
+            let residual = fixed_residual(samples, order);
+            let (parameter, cost) = (0..=MAX_RICE_PARAMETER)
+                .map(|parameter| (parameter, rice_cost(&residual, parameter)))
+                .min_by_key(|&(_, cost)| cost)
+                .unwrap_or((0, 0));
+            (order, residual, parameter, cost + (order as u64) * bits_per_sample as u64)
+        })
+        .min_by_key(|&(_, _, _, cost)| cost)
+        .expect("order 0 is always a candidate");
+
+    if cost >= samples.len() as u64 * bits_per_sample as u64 {
+        writer.write(0b0000_0010, 8); // verbatim
+        for &sample in samples {
+            writer.write_signed(sample, bits_per_sample);
+        }
+        return;
+    }
+
+    writer.write(0b0001_0000 | ((order as u64) << 1), 8); // fixed predictor of `order`
+    for &sample in &samples[..order] {
+        writer.write_signed(sample, bits_per_sample);
+    }
+    writer.write(0b00, 2); // Rice coding, 4-bit parameter
+    writer.write(0, 4); // partition order 0
+    writer.write(parameter as u64, 4);
+    for &r in &residual {
+        let value = zigzag(r);
+        writer.write_unary(value >> parameter);
+        writer.write(value & ((1 << parameter) - 1), parameter);
+    }
+}
+
+/// Writes one frame, storing `samples` once per channel.
+fn write_frame(out: &mut Vec<u8>, samples: &[i64], frame_number: u64, bits_per_sample: u32, channels: u16) {
+    let mut header = BitWriter::default();
+    header.write(0b11_1111_1111_1110, 14); // sync code
+    header.write(0, 1); // reserved
+    header.write(0, 1); // fixed block size
+    let block_size_code = if samples.len() == BLOCK_SIZE { 0b1100 } else { 0b0111 };
+    header.write(block_size_code, 4);
+    header.write(0b0000, 4); // sample rate from STREAMINFO
+    header.write(channels as u64 - 1, 4); // independent channels
+    header.write(if bits_per_sample == 24 { 0b110 } else { 0b100 }, 3);
+    header.write(0, 1); // reserved
+    write_utf8_number(&mut header, frame_number);
+    if block_size_code == 0b0111 {
+        header.write(samples.len() as u64 - 1, 16);
+    }
+    let mut header = header.into_bytes();
+    header.push(crc8(&header));
+
+    let mut frame = BitWriter { bytes: header, ..Default::default() };
+    for _ in 0..channels {
+        write_subframe(&mut frame, samples, bits_per_sample);
+    }
+    let frame = frame.into_bytes();
+
+    let crc = crc16(&frame);
+    out.extend_from_slice(&frame);
+    out.extend(crc.to_be_bytes());
+}
+
+/// Encodes mono audio as a FLAC file with 16 or 24-bit samples, copied into
+/// up to 8 channels.
+pub fn encode(waveform: &[f32], sample_rate: u64, bits_per_sample: u32, channels: u16) -> Result<Vec<u8>> {
+    if !matches!(bits_per_sample, 16 | 24) {
+        return Err(PiperError::Encode(format!("flac: unsupported bit depth {}", bits_per_sample)));
+    }
+    if !(1..=8).contains(&channels) {
+        return Err(PiperError::Encode(format!("flac: unsupported channel count {}", channels)));
+    }
+    if sample_rate == 0 || sample_rate >= 1 << 20 {
+        return Err(PiperError::Encode(format!("flac: unsupported sample rate {}", sample_rate)));
+    }
+
+    warn_if_clipping(waveform);
+    let scale = ((1i64 << (bits_per_sample - 1)) - 1) as f64;
+    let samples: Vec<i64> = waveform.iter()
+        .map(|&sample| (sample.clamp(-1.0, 1.0) as f64 * scale) as i64)
+        .collect();
+
+    let mut streaminfo = BitWriter::default();
+    streaminfo.write(BLOCK_SIZE as u64, 16); // min block size
+    streaminfo.write(BLOCK_SIZE as u64, 16); // max block size
+    streaminfo.write(0, 24); // min frame size, unknown
+    streaminfo.write(0, 24); // max frame size, unknown
+    streaminfo.write(sample_rate, 20);
+    streaminfo.write(channels as u64 - 1, 3);
+    streaminfo.write(bits_per_sample as u64 - 1, 5);
+    streaminfo.write(samples.len() as u64, 36);
+    streaminfo.write(0, 64); // MD5, unset
+    streaminfo.write(0, 64);
+    let streaminfo = streaminfo.into_bytes();
+
+    let mut out = b"fLaC".to_vec();
+    out.push(0x80); // last metadata block, STREAMINFO
+    out.extend(&(streaminfo.len() as u32).to_be_bytes()[1..]);
+    out.extend(streaminfo);
+
+    for (frame_number, block) in samples.chunks(BLOCK_SIZE).enumerate() {
+        write_frame(&mut out, block, frame_number as u64, bits_per_sample, channels);
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quantize(waveform: &[f32], bits_per_sample: u32) -> Vec<i32> {
+        let scale = ((1i64 << (bits_per_sample - 1)) - 1) as f64;
+        waveform.iter().map(|&sample| (sample.clamp(-1.0, 1.0) as f64 * scale) as i32).collect()
+    }
+
+    #[test]
+    fn crc_check_values() {
+        assert_eq!(crc8(b"123456789"), 0xF4);
+        assert_eq!(crc16(b"123456789"), 0xFEE8);
+    }
+
+    #[test]
+    fn utf8_frame_numbers() {
+        let encoded = |value| {
+            let mut writer = BitWriter::default();
+            write_utf8_number(&mut writer, value);
+            writer.into_bytes()
+        };
+        assert_eq!(encoded(0x7F), [0x7F]);
+        assert_eq!(encoded(0x80), [0xC2, 0x80]);
+        assert_eq!(encoded(0x1_0000), [0xF0, 0x90, 0x80, 0x80]);
+    }
+
+    #[test]
+    fn streaminfo_layout() {
+        let flac = encode(&[0.0; 10], 22050, 16, 2).unwrap();
+        assert_eq!(&flac[..4], b"fLaC");
+        assert_eq!(flac[4..8], [0x80, 0x00, 0x00, 0x22]);
+
+        let info = &flac[8..42];
+        assert_eq!(info[..4], [0x10, 0x00, 0x10, 0x00]);
+        let packed = u64::from_be_bytes(info[10..18].try_into().unwrap());
+        assert_eq!(packed >> 44, 22050);
+        assert_eq!((packed >> 41) & 0b111, 1);
+        assert_eq!((packed >> 36) & 0b1_1111, 15);
+        assert_eq!(packed & 0xF_FFFF_FFFF, 10);
+    }
+
+    #[test]
+    fn frame_header() {
+        let flac = encode(&[0.0; 10], 22050, 16, 1).unwrap();
+        let header = &flac[42..49];
+        // Sync code, 16-bit block size at the end of the header, sample rate from
+        // STREAMINFO, mono, 16 bits, frame 0 and a block of 10 samples.
+        assert_eq!(header, [0xFF, 0xF8, 0x70, 0x08, 0x00, 0x00, 0x09]);
+        assert_eq!(flac[49], crc8(header));
+        let crc = crc16(&flac[42..flac.len() - 2]);
+        assert_eq!(flac[flac.len() - 2..], crc.to_be_bytes());
+    }
+
+    #[test]
+    fn round_trips_through_a_decoder() {
+        let mut seed = 1u32;
+        let noise: Vec<f32> = (0..3000)
+            .map(|_| {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                (seed >> 8) as f32 / (1 << 23) as f32 - 1.0
+            })
+            .collect();
+        let tone: Vec<f32> = (0..10_000).map(|i| (i as f32 * 0.03).sin() * 0.8).collect();
+
+        for (waveform, bits_per_sample, channels) in [(&tone, 16, 1), (&tone, 24, 2), (&noise, 16, 1), (&vec![0.25; 5], 16, 1)] {
+            let flac = encode(waveform, 16000, bits_per_sample, channels).unwrap();
+            let mut reader = claxon::FlacReader::new(std::io::Cursor::new(flac)).unwrap();
+            let info = reader.streaminfo();
+            assert_eq!((info.sample_rate, info.channels, info.bits_per_sample), (16000, channels as u32, bits_per_sample));
+            assert_eq!(info.samples, Some(waveform.len() as u64));
+
+            let decoded: Vec<i32> = reader.samples().collect::<std::result::Result<_, _>>().unwrap();
+            let expected: Vec<i32> = quantize(waveform, bits_per_sample).into_iter()
+                .flat_map(|sample| std::iter::repeat_n(sample, channels as usize))
+                .collect();
+            assert_eq!(decoded, expected);
+        }
+    }
+}
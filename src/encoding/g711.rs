@@ -0,0 +1,157 @@
+use crate::{audio, error::Result};
+
+/// Sample rate of G.711 telephony audio.
+pub const TELEPHONY_SAMPLE_RATE: u64 = 8_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Companding {
+    MuLaw,
+    ALaw,
+}
+
+impl Companding {
+    fn format_tag(self) -> u16 {
+        match self {
+            Companding::ALaw => 6,
+            Companding::MuLaw => 7,
+        }
+    }
+
+    fn encode_sample(self, sample: i16) -> u8 {
+        match self {
+            Companding::MuLaw => linear_to_mulaw(sample),
+            Companding::ALaw => linear_to_alaw(sample),
+        }
+    }
+}
+
+fn linear_to_mulaw(sample: i16) -> u8 {
+    const BIAS: i32 = 0x21;
+    const CLIP: i32 = 8159;
+    const SEGMENT_ENDS: [i32; 8] = [0x3F, 0x7F, 0xFF, 0x1FF, 0x3FF, 0x7FF, 0xFFF, 0x1FFF];
+
+    let mut value = sample as i32 >> 2;
+    let mask: i32 = if value < 0 {
+        value = -value;
+        0x7F
+    } else {
+        0xFF
+    };
+    let value = value.min(CLIP) + BIAS;
+
+    let Some(segment) = SEGMENT_ENDS.iter().position(|&end| value <= end) else {
+        return (0x7F ^ mask) as u8;
+    };
+    ((((segment << 4) as i32) | ((value >> (segment + 1)) & 0x0F)) ^ mask) as u8
+}
+
+fn linear_to_alaw(sample: i16) -> u8 {
+    const SEGMENT_ENDS: [i32; 8] = [0x1F, 0x3F, 0x7F, 0xFF, 0x1FF, 0x3FF, 0x7FF, 0xFFF];
+
+    let mut value = sample as i32 >> 3;
+    let mask: i32 = if value >= 0 {
+        0xD5
+    } else {
+        value = -value - 1;
+        0x55
+    };
+
+    let Some(segment) = SEGMENT_ENDS.iter().position(|&end| value <= end) else {
+        return (0x7F ^ mask) as u8;
+    };
+    let shift = if segment < 2 { 1 } else { segment };
+    (((segment << 4) as i32 | ((value >> shift) & 0x0F)) ^ mask) as u8
+}
+
+/// Resamples to 8 kHz and encodes a µ-law or A-law WAV file, the format
+/// Asterisk and FreeSWITCH play without transcoding.
+pub fn encode(waveform: &[f32], sample_rate: u64, companding: Companding, channels: u16) -> Result<Vec<u8>> {
+    let resampled = audio::resample(waveform, sample_rate, TELEPHONY_SAMPLE_RATE)?;
+    audio::warn_if_clipping(&resampled);
+    let samples: Vec<u8> = audio::interleave(&resampled, channels).iter()
+        .map(|&sample| companding.encode_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16))
+        .collect();
+
+    let data_len = samples.len() as u32;
+    let mut bytes = Vec::with_capacity(58 + samples.len() + 1);
+    bytes.extend(b"RIFF");
+    bytes.extend((50 + data_len + data_len % 2).to_le_bytes());
+    bytes.extend(b"WAVE");
+    bytes.extend(b"fmt ");
+    bytes.extend(18u32.to_le_bytes());
+    bytes.extend(companding.format_tag().to_le_bytes());
+    bytes.extend(channels.to_le_bytes());
+    bytes.extend((TELEPHONY_SAMPLE_RATE as u32).to_le_bytes());
+    bytes.extend((TELEPHONY_SAMPLE_RATE as u32 * channels as u32).to_le_bytes()); // byte rate
+    bytes.extend(channels.to_le_bytes()); // block align
+    bytes.extend(8u16.to_le_bytes()); // bits per sample
+    bytes.extend(0u16.to_le_bytes()); // extension size
+    // Non-PCM formats carry a `fact` chunk with the sample count.
+    bytes.extend(b"fact");
+    bytes.extend(4u32.to_le_bytes());
+    bytes.extend((data_len / channels as u32).to_le_bytes());
+    bytes.extend(b"data");
+    bytes.extend(data_len.to_le_bytes());
+    bytes.extend(samples);
+    if data_len % 2 == 1 {
+        bytes.push(0);
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mulaw_vectors() {
+        assert_eq!(linear_to_mulaw(0), 0xFF);
+        assert_eq!(linear_to_mulaw(-1), 0x7E);
+        assert_eq!(linear_to_mulaw(i16::MAX), 0x80);
+        assert_eq!(linear_to_mulaw(i16::MIN), 0x00);
+        // Last step of the first segment and first step of the second.
+        assert_eq!(linear_to_mulaw(120), 0xF0);
+        assert_eq!(linear_to_mulaw(124), 0xEF);
+    }
+
+    #[test]
+    fn alaw_vectors() {
+        assert_eq!(linear_to_alaw(0), 0xD5);
+        assert_eq!(linear_to_alaw(-1), 0x55);
+        assert_eq!(linear_to_alaw(i16::MAX), 0xAA);
+        assert_eq!(linear_to_alaw(i16::MIN), 0x2A);
+        assert_eq!(linear_to_alaw(248), 0xDA);
+        assert_eq!(linear_to_alaw(256), 0xC5);
+    }
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn header_pads_odd_data() {
+        let wav = encode(&[0.0, 0.5, -0.5], TELEPHONY_SAMPLE_RATE, Companding::MuLaw, 1).unwrap();
+        assert_eq!(wav.len(), 58 + 3 + 1);
+        assert_eq!(u32_at(&wav, 4) as usize, wav.len() - 8);
+        assert_eq!(&wav[8..16], b"WAVEfmt ");
+        assert_eq!(u16::from_le_bytes([wav[20], wav[21]]), 7);
+        assert_eq!(&wav[38..42], b"fact");
+        assert_eq!(u32_at(&wav, 46), 3);
+        assert_eq!(&wav[50..54], b"data");
+        assert_eq!(u32_at(&wav, 54), 3);
+        assert_eq!(wav[58], 0xFF);
+        assert_eq!(wav[61], 0);
+    }
+
+    #[test]
+    fn header_of_stereo_alaw() {
+        let wav = encode(&[0.0, 0.5], TELEPHONY_SAMPLE_RATE, Companding::ALaw, 2).unwrap();
+        assert_eq!(wav.len(), 58 + 4);
+        assert_eq!(u32_at(&wav, 4) as usize, wav.len() - 8);
+        assert_eq!(u16::from_le_bytes([wav[20], wav[21]]), 6);
+        assert_eq!(u16::from_le_bytes([wav[22], wav[23]]), 2);
+        assert_eq!(u32_at(&wav, 28), 16_000);
+        assert_eq!(u32_at(&wav, 46), 2);
+        assert_eq!(u32_at(&wav, 54), 4);
+    }
+}
@@ -0,0 +1,138 @@
+use std::{path::Path, str::FromStr};
+
+use crate::{
+    audio::{self, SampleFormat},
+    error::{PiperError, Result},
+};
+
+pub mod flac;
+pub mod g711;
+#[cfg(feature = "mp3")]
+pub mod mp3;
+#[cfg(feature = "opus")]
+pub mod opus;
+
+/// Container/codec audio is written in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Wav,
+    /// Headerless signed 16-bit little-endian PCM.
+    Raw,
+    /// Lossless FLAC.
+    Flac,
+    /// 8 kHz µ-law WAV for telephony.
+    MuLaw,
+    /// 8 kHz A-law WAV for telephony.
+    ALaw,
+    /// Constant bitrate MP3.
+    #[cfg(feature = "mp3")]
+    Mp3,
+    /// Opus in an Ogg container.
+    #[cfg(feature = "opus")]
+    Opus,
+}
+
+impl OutputFormat {
+    /// Guesses the format from a file extension, `None` for unknown ones.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?;
+        extension.parse().ok()
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Wav => "wav",
+            OutputFormat::Raw => "pcm",
+            OutputFormat::Flac => "flac",
+            OutputFormat::MuLaw | OutputFormat::ALaw => "wav",
+            #[cfg(feature = "mp3")]
+            OutputFormat::Mp3 => "mp3",
+            #[cfg(feature = "opus")]
+            OutputFormat::Opus => "opus",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            OutputFormat::Wav => "audio/wav",
+            OutputFormat::Raw => "audio/pcm",
+            OutputFormat::Flac => "audio/flac",
+            OutputFormat::MuLaw | OutputFormat::ALaw => "audio/wav",
+            #[cfg(feature = "mp3")]
+            OutputFormat::Mp3 => "audio/mpeg",
+            #[cfg(feature = "opus")]
+            OutputFormat::Opus => "audio/ogg; codecs=opus",
+        }
+    }
+
+    /// Encodes a whole waveform, `sample_format` applies to WAV and FLAC,
+    /// which stores anything above 16-bit as 24-bit samples.
+    pub fn encode(&self, waveform: &[f32], sample_rate: u64, sample_format: SampleFormat) -> Result<Vec<u8>> {
+        self.encode_channels(waveform, sample_rate, sample_format, 1)
+    }
+
+    /// Like [`encode`](Self::encode), duplicating the mono waveform into
+    /// `channels` channels for players that refuse mono input. FLAC takes
+    /// up to 8 channels, MP3 and Opus up to 2.
+    pub fn encode_channels(
+        &self,
+        waveform: &[f32],
+        sample_rate: u64,
+        sample_format: SampleFormat,
+        channels: u16,
+    ) -> Result<Vec<u8>> {
+        let _span = tracing::debug_span!("encode", format = self.extension(), samples = waveform.len()).entered();
+        let max_channels = match self {
+            OutputFormat::Flac => 8,
+            #[cfg(feature = "mp3")]
+            OutputFormat::Mp3 => 2,
+            #[cfg(feature = "opus")]
+            OutputFormat::Opus => 2,
+            _ => u16::MAX,
+        };
+        if channels == 0 || channels > max_channels {
+            return Err(PiperError::InvalidConfig(format!(
+                "{} output supports 1 to {} channels, got {}",
+                self.extension(), max_channels, channels
+            )));
+        }
+
+        match self {
+            OutputFormat::Wav => audio::to_wav_bytes_with_channels(waveform, sample_rate, sample_format, channels),
+            OutputFormat::Raw => Ok(audio::to_pcm_bytes(&audio::interleave(waveform, channels))),
+            OutputFormat::Flac => {
+                let bits_per_sample = match sample_format {
+                    SampleFormat::I16 => 16,
+                    _ => 24,
+                };
+                flac::encode(waveform, sample_rate, bits_per_sample, channels)
+            }
+            OutputFormat::MuLaw => g711::encode(waveform, sample_rate, g711::Companding::MuLaw, channels),
+            OutputFormat::ALaw => g711::encode(waveform, sample_rate, g711::Companding::ALaw, channels),
+            #[cfg(feature = "mp3")]
+            OutputFormat::Mp3 => mp3::encode(waveform, sample_rate, mp3::DEFAULT_BITRATE, channels),
+            #[cfg(feature = "opus")]
+            OutputFormat::Opus => opus::encode(waveform, sample_rate, channels),
+        }
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = PiperError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "wav" => Ok(OutputFormat::Wav),
+            "raw" | "pcm" => Ok(OutputFormat::Raw),
+            "flac" => Ok(OutputFormat::Flac),
+            "mulaw" | "ulaw" | "µlaw" => Ok(OutputFormat::MuLaw),
+            "alaw" => Ok(OutputFormat::ALaw),
+            #[cfg(feature = "mp3")]
+            "mp3" => Ok(OutputFormat::Mp3),
+            #[cfg(feature = "opus")]
+            "opus" | "ogg" => Ok(OutputFormat::Opus),
+            _ => Err(PiperError::InvalidConfig(format!("unknown output format `{}`", s))),
+        }
+    }
+}
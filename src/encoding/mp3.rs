@@ -0,0 +1,137 @@
+use std::io::Write;
+
+use mp3lame_encoder::{Bitrate, Builder, DualPcm, Encoder, FlushNoGap, Mode, MonoPcm, Quality};
+
+use crate::{
+    audio::{self, AudioSink},
+    error::{PiperError, Result},
+};
+
+/// Constant bitrate in kbit/s, plenty for mono speech.
+pub const DEFAULT_BITRATE: u32 = 64;
+/// Space LAME asks for to flush its last frames.
+const FLUSH_BUFFER_SIZE: usize = 7200;
+
+fn encode_error(err: impl std::fmt::Display) -> PiperError {
+    PiperError::Encode(format!("mp3: {}", err))
+}
+
+fn lame_bitrate(bitrate: u32) -> Result<Bitrate> {
+    Ok(match bitrate {
+        8 => Bitrate::Kbps8,
+        16 => Bitrate::Kbps16,
+        24 => Bitrate::Kbps24,
+        32 => Bitrate::Kbps32,
+        40 => Bitrate::Kbps40,
+        48 => Bitrate::Kbps48,
+        64 => Bitrate::Kbps64,
+        80 => Bitrate::Kbps80,
+        96 => Bitrate::Kbps96,
+        112 => Bitrate::Kbps112,
+        128 => Bitrate::Kbps128,
+        160 => Bitrate::Kbps160,
+        192 => Bitrate::Kbps192,
+        224 => Bitrate::Kbps224,
+        256 => Bitrate::Kbps256,
+        320 => Bitrate::Kbps320,
+        _ => return Err(encode_error(format!("unsupported bitrate {} kbit/s", bitrate))),
+    })
+}
+
+/// Encodes mono audio to a constant bitrate MP3 stream with LAME, as mono or
+/// with the audio copied to both stereo channels.
+pub struct Mp3Sink<W: Write> {
+    writer: W,
+    encoder: Encoder,
+    channels: u16,
+    buffer: Vec<u8>,
+    finished: bool,
+}
+
+impl<W: Write> Mp3Sink<W> {
+    pub fn new(writer: W, sample_rate: u64, bitrate: u32, channels: u16) -> Result<Self> {
+        let mode = match channels {
+            1 => Mode::Mono,
+            2 => Mode::JointStereo,
+            _ => return Err(encode_error(format!("unsupported channel count {}", channels))),
+        };
+        let mut builder = Builder::new().ok_or_else(|| encode_error("failed to allocate the LAME encoder"))?;
+        builder.set_sample_rate(sample_rate as u32).map_err(encode_error)?;
+        builder.set_num_channels(channels as u8).map_err(encode_error)?;
+        builder.set_mode(mode).map_err(encode_error)?;
+        builder.set_brate(lame_bitrate(bitrate)?).map_err(encode_error)?;
+        builder.set_quality(Quality::NearBest).map_err(encode_error)?;
+        Ok(Self {
+            writer,
+            encoder: builder.build().map_err(encode_error)?,
+            channels,
+            buffer: Vec::new(),
+            finished: false,
+        })
+    }
+
+    pub fn into_inner(mut self) -> Result<W> {
+        self.finish()?;
+        Ok(self.writer)
+    }
+}
+
+impl<W: Write> AudioSink for Mp3Sink<W> {
+    fn write_samples(&mut self, samples: &[f32]) -> Result<()> {
+        if samples.is_empty() {
+            return Ok(());
+        }
+        audio::warn_if_clipping(samples);
+        self.buffer.clear();
+        // Worst case output size documented by LAME.
+        self.buffer.reserve(mp3lame_encoder::max_required_buffer_size(samples.len()));
+        let encoded = match self.channels {
+            1 => self.encoder.encode_to_vec(MonoPcm(samples), &mut self.buffer),
+            _ => self.encoder.encode_to_vec(DualPcm { left: samples, right: samples }, &mut self.buffer),
+        };
+        encoded.map_err(encode_error)?;
+        self.writer.write_all(&self.buffer)?;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+
+        self.buffer.clear();
+        self.buffer.reserve(FLUSH_BUFFER_SIZE);
+        self.encoder.flush_to_vec::<FlushNoGap>(&mut self.buffer).map_err(encode_error)?;
+        self.writer.write_all(&self.buffer)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+pub fn encode(waveform: &[f32], sample_rate: u64, bitrate: u32, channels: u16) -> Result<Vec<u8>> {
+    let mut sink = Mp3Sink::new(Vec::new(), sample_rate, bitrate, channels)?;
+    sink.write_samples(waveform)?;
+    sink.into_inner()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encodes_frames() {
+        let waveform: Vec<f32> = (0..22050).map(|i| (i as f32 * 0.05).sin() * 0.5).collect();
+        for channels in [1, 2] {
+            let mp3 = encode(&waveform, 22050, DEFAULT_BITRATE, channels).unwrap();
+            assert!(mp3.len() > 1000);
+            // Every MPEG audio frame starts with an 11-bit sync word.
+            assert_eq!((mp3[0], mp3[1] & 0xE0), (0xFF, 0xE0));
+        }
+    }
+
+    #[test]
+    fn rejects_unsupported_bitrates() {
+        assert!(encode(&[0.0; 100], 22050, 65, 1).is_err());
+    }
+}
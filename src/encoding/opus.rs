@@ -0,0 +1,165 @@
+use std::io::Write;
+
+use audiopus::{coder::Encoder, Application, Channels, SampleRate};
+use ogg::writing::{PacketWriteEndInfo, PacketWriter};
+
+use crate::{
+    audio::{self, AudioSink},
+    error::{PiperError, Result},
+};
+
+/// Ogg stream serial number, any value works for a single stream file.
+const STREAM_SERIAL: u32 = 0x5069_7065;
+/// Opus granule positions are always counted at 48 kHz.
+const GRANULE_RATE: u64 = 48_000;
+const FRAME_MS: u64 = 20;
+/// Frames per Ogg page, short pages let streaming clients start playback early.
+const FRAMES_PER_PAGE: u64 = 50;
+const MAX_PACKET_SIZE: usize = 4000;
+
+fn encode_error(err: impl std::fmt::Display) -> PiperError {
+    PiperError::Encode(format!("opus: {}", err))
+}
+
+/// Lowest Opus sample rate at or above the voice rate, 22.05 kHz voices are encoded at 24 kHz.
+fn opus_rate(sample_rate: u64) -> (u64, SampleRate) {
+    [
+        (8_000, SampleRate::Hz8000),
+        (12_000, SampleRate::Hz12000),
+        (16_000, SampleRate::Hz16000),
+        (24_000, SampleRate::Hz24000),
+    ]
+    .into_iter()
+    .find(|&(rate, _)| rate >= sample_rate)
+    .unwrap_or((48_000, SampleRate::Hz48000))
+}
+
+/// Encodes mono audio into a mono or stereo Ogg/Opus stream as 20 ms frames,
+/// writing a page roughly every second so the output can be streamed.
+pub struct OpusSink<W: Write> {
+    writer: PacketWriter<W>,
+    encoder: Encoder,
+    sample_rate: u64,
+    opus_rate: u64,
+    channels: u16,
+    /// Interleaved samples not yet filling a whole frame.
+    pending: Vec<f32>,
+    pre_skip: u64,
+    samples_encoded: u64,
+    frames: u64,
+    finished: bool,
+}
+
+impl<W: Write> OpusSink<W> {
+    pub fn new(writer: W, sample_rate: u64, channels: u16) -> Result<Self> {
+        let (opus_rate, rate) = opus_rate(sample_rate);
+        let opus_channels = match channels {
+            1 => Channels::Mono,
+            2 => Channels::Stereo,
+            _ => return Err(encode_error(format!("unsupported channel count {}", channels))),
+        };
+        let encoder = Encoder::new(rate, opus_channels, Application::Voip).map_err(encode_error)?;
+        let lookahead = encoder.lookahead().map_err(encode_error)? as u64;
+
+        let mut sink = Self {
+            writer: PacketWriter::new(writer),
+            encoder,
+            sample_rate,
+            opus_rate,
+            channels,
+            pending: Vec::new(),
+            pre_skip: lookahead * GRANULE_RATE / opus_rate,
+            samples_encoded: 0,
+            frames: 0,
+            finished: false,
+        };
+        sink.write_headers()?;
+        Ok(sink)
+    }
+
+    fn write_headers(&mut self) -> Result<()> {
+        let mut head = b"OpusHead".to_vec();
+        head.push(1); // version
+        head.push(self.channels as u8);
+        head.extend((self.pre_skip as u16).to_le_bytes());
+        head.extend((self.sample_rate as u32).to_le_bytes());
+        head.extend(0i16.to_le_bytes()); // output gain
+        head.push(0); // mapping family
+        self.writer.write_packet(head.into_boxed_slice(), STREAM_SERIAL, PacketWriteEndInfo::EndPage, 0)?;
+
+        let vendor = concat!("piper-tts-rust ", env!("CARGO_PKG_VERSION"));
+        let mut tags = b"OpusTags".to_vec();
+        tags.extend((vendor.len() as u32).to_le_bytes());
+        tags.extend(vendor.as_bytes());
+        tags.extend(0u32.to_le_bytes()); // user comments
+        self.writer.write_packet(tags.into_boxed_slice(), STREAM_SERIAL, PacketWriteEndInfo::EndPage, 0)?;
+        Ok(())
+    }
+
+    /// Samples per channel in a frame.
+    fn frame_size(&self) -> usize {
+        (self.opus_rate * FRAME_MS / 1000) as usize
+    }
+
+    fn granule(&self) -> u64 {
+        self.pre_skip + self.samples_encoded * GRANULE_RATE / self.opus_rate
+    }
+
+    fn encode_frame(&mut self, frame: &[f32], real_samples: usize, end_info: PacketWriteEndInfo) -> Result<()> {
+        let mut packet = vec![0u8; MAX_PACKET_SIZE];
+        let len = self.encoder.encode_float(frame, &mut packet).map_err(encode_error)?;
+        packet.truncate(len);
+
+        self.samples_encoded += real_samples as u64;
+        self.frames += 1;
+        let end_info = match end_info {
+            PacketWriteEndInfo::NormalPacket if self.frames.is_multiple_of(FRAMES_PER_PAGE) => PacketWriteEndInfo::EndPage,
+            end_info => end_info,
+        };
+        self.writer.write_packet(packet.into_boxed_slice(), STREAM_SERIAL, end_info, self.granule())?;
+        Ok(())
+    }
+
+    pub fn into_inner(mut self) -> Result<W> {
+        self.finish()?;
+        Ok(self.writer.into_inner())
+    }
+}
+
+impl<W: Write> AudioSink for OpusSink<W> {
+    fn write_samples(&mut self, samples: &[f32]) -> Result<()> {
+        let resampled = audio::resample(samples, self.sample_rate, self.opus_rate)?;
+        self.pending.extend(audio::interleave(&resampled, self.channels));
+
+        let frame_size = self.frame_size();
+        let frame_len = frame_size * self.channels as usize;
+        let frames = self.pending.len() / frame_len;
+        let pending = std::mem::take(&mut self.pending);
+        for frame in pending.chunks_exact(frame_len).take(frames) {
+            self.encode_frame(frame, frame_size, PacketWriteEndInfo::NormalPacket)?;
+        }
+        self.pending = pending[frames * frame_len..].to_vec();
+        Ok(())
+    }
+
+    /// Encodes the last partial frame padded with silence and ends the stream.
+    fn finish(&mut self) -> Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+
+        let mut frame = std::mem::take(&mut self.pending);
+        let real_samples = frame.len() / self.channels as usize;
+        frame.resize(self.frame_size() * self.channels as usize, 0.0);
+        self.encode_frame(&frame, real_samples, PacketWriteEndInfo::EndStream)?;
+        self.writer.inner_mut().flush()?;
+        Ok(())
+    }
+}
+
+pub fn encode(waveform: &[f32], sample_rate: u64, channels: u16) -> Result<Vec<u8>> {
+    let mut sink = OpusSink::new(Vec::new(), sample_rate, channels)?;
+    sink.write_samples(waveform)?;
+    sink.into_inner()
+}
@@ -0,0 +1,126 @@
+use std::{collections::HashMap, fs::File, io::Read, path::Path};
+
+use roxmltree::Document;
+use zip::ZipArchive;
+
+use crate::{
+    error::{PiperError, Result},
+    markup::{CodeBlocks, html_text},
+};
+
+/// A chapter of an EPUB, in reading order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chapter {
+    pub title: String,
+    /// Plain text, one paragraph per line.
+    pub text: String,
+}
+
+fn epub_error(reason: impl ToString) -> PiperError {
+    PiperError::Epub(reason.to_string())
+}
+
+fn read_entry(archive: &mut ZipArchive<File>, name: &str) -> Result<String> {
+    let mut entry = archive.by_name(name).map_err(|err| PiperError::Epub(format!("`{}`: {}", name, err)))?;
+    let mut contents = String::new();
+    entry.read_to_string(&mut contents)?;
+    Ok(contents)
+}
+
+/// Archive path of `href` relative to the document at `base`, without any fragment.
+fn resolve_href(base: &str, href: &str) -> String {
+    let href = href.split('#').next().unwrap_or_default().replace("%20", " ");
+    let mut parts: Vec<&str> = base.rsplit_once('/').map_or_else(Vec::new, |(dir, _)| dir.split('/').collect());
+    for part in href.split('/') {
+        match part {
+            ".." => {
+                parts.pop();
+            }
+            "." | "" => {}
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// Chapter titles by archive path, from the EPUB 3 navigation document or the EPUB 2 NCX.
+fn toc_titles(archive: &mut ZipArchive<File>, opf_path: &str, opf: &Document) -> HashMap<String, String> {
+    let mut titles = HashMap::new();
+    let items = || opf.descendants().filter(|node| node.has_tag_name("item"));
+    let nav = items().find(|item| item.attribute("properties").is_some_and(|properties| properties.split_whitespace().any(|p| p == "nav")));
+    let ncx = items().find(|item| item.attribute("media-type") == Some("application/x-dtbncx+xml"));
+
+    if let Some(href) = nav.and_then(|item| item.attribute("href")) {
+        let path = resolve_href(opf_path, href);
+        if let Ok(contents) = read_entry(archive, &path)
+            && let Ok(doc) = Document::parse(&contents)
+        {
+            for link in doc.descendants().filter(|node| node.has_tag_name("a")) {
+                let title = link.descendants().filter(|node| node.is_text()).filter_map(|node| node.text()).collect::<String>();
+                if let Some(target) = link.attribute("href") {
+                    titles.entry(resolve_href(&path, target)).or_insert_with(|| title.trim().to_string());
+                }
+            }
+        }
+    } else if let Some(href) = ncx.and_then(|item| item.attribute("href")) {
+        let path = resolve_href(opf_path, href);
+        if let Ok(contents) = read_entry(archive, &path)
+            && let Ok(doc) = Document::parse(&contents)
+        {
+            for point in doc.descendants().filter(|node| node.has_tag_name("navPoint")) {
+                let title = point.descendants().find(|node| node.has_tag_name("text")).and_then(|node| node.text());
+                let target = point.children().find(|node| node.has_tag_name("content")).and_then(|node| node.attribute("src"));
+                if let (Some(title), Some(target)) = (title, target) {
+                    titles.entry(resolve_href(&path, target)).or_insert_with(|| title.trim().to_string());
+                }
+            }
+        }
+    }
+    titles.retain(|_, title| !title.is_empty());
+    titles
+}
+
+/// Reads the chapters of the EPUB at `path` in spine order. Documents without
+/// any text, such as cover pages, are left out. Titles come from the table of
+/// contents, falling back to the chapter's first heading and `Chapter <n>`.
+pub fn read_chapters(path: impl AsRef<Path>) -> Result<Vec<Chapter>> {
+    let mut archive = ZipArchive::new(File::open(path)?).map_err(epub_error)?;
+    let container = read_entry(&mut archive, "META-INF/container.xml")?;
+    let container = Document::parse(&container).map_err(epub_error)?;
+    let opf_path = container.descendants()
+        .find(|node| node.has_tag_name("rootfile"))
+        .and_then(|node| node.attribute("full-path"))
+        .ok_or_else(|| epub_error("no rootfile in `META-INF/container.xml`"))?
+        .to_string();
+    let opf = read_entry(&mut archive, &opf_path)?;
+    let opf = Document::parse(&opf).map_err(epub_error)?;
+
+    let manifest: HashMap<&str, &str> = opf.descendants()
+        .filter(|node| node.has_tag_name("item"))
+        .filter_map(|node| Some((node.attribute("id")?, node.attribute("href")?)))
+        .collect();
+    let titles = toc_titles(&mut archive, &opf_path, &opf);
+
+    let mut chapters = Vec::new();
+    for itemref in opf.descendants().filter(|node| node.has_tag_name("itemref")) {
+        if itemref.attribute("linear") == Some("no") {
+            continue;
+        }
+        let Some(href) = itemref.attribute("idref").and_then(|id| manifest.get(id)) else {
+            continue;
+        };
+        let path = resolve_href(&opf_path, href);
+        let (text, heading) = html_text(&read_entry(&mut archive, &path)?, CodeBlocks::Read);
+        if text.is_empty() {
+            continue;
+        }
+        let title = titles.get(&path).cloned()
+            .or(heading)
+            .unwrap_or_else(|| format!("Chapter {}", chapters.len() + 1));
+        chapters.push(Chapter { title, text });
+    }
+    if chapters.is_empty() {
+        return Err(epub_error("no chapters with text found"));
+    }
+    Ok(chapters)
+}
@@ -0,0 +1,83 @@
+use thiserror::Error;
+
+pub type Result<T, E = PiperError> = std::result::Result<T, E>;
+
+#[derive(Error, Debug)]
+pub enum PiperError {
+    #[error("failed to load model `{path}`: {source}")]
+    ModelLoad {
+        path: String,
+        #[source]
+        source: ort::Error,
+    },
+
+    #[error("failed to load {what} `{path}`: {reason}")]
+    AssetLoad {
+        what: &'static str,
+        path: String,
+        reason: String,
+    },
+
+    #[error("{0} not loaded, call `load()` first")]
+    NotLoaded(&'static str),
+
+    #[error("tokenizer error: {0}")]
+    Tokenizer(tokenizers::Error),
+
+    #[error("no phonemes generated for `{0}`")]
+    MissingPhoneme(String),
+
+    #[error("invalid configuration: {0}")]
+    InvalidConfig(String),
+
+    #[error("phonemes missing from the voice's phoneme_id_map: {0}")]
+    UnmappedPhonemes(String),
+
+    #[error("unknown speaker `{0}`")]
+    UnknownSpeaker(String),
+
+    #[error("model is not a compatible Piper voice: {0}")]
+    IncompatibleModel(String),
+
+    #[error("inference backend error: {0}")]
+    Backend(String),
+
+    #[error("model output `{0}` is missing")]
+    MissingOutput(&'static str),
+
+    #[error("unexpected tensor shape: {0}")]
+    InvalidShape(String),
+
+    #[error("synthesis cancelled")]
+    Cancelled,
+
+    #[error("synthesis task failed: {0}")]
+    TaskFailed(String),
+
+    #[error("playback error: {0}")]
+    Playback(String),
+
+    #[error("encoding error: {0}")]
+    Encode(String),
+
+    #[error("download failed: {0}")]
+    Download(String),
+
+    #[error("invalid EPUB: {0}")]
+    Epub(String),
+
+    #[error("wav error: {0}")]
+    Wav(#[from] hound::Error),
+
+    #[error("onnx runtime error: {0}")]
+    Ort(#[from] ort::Error),
+
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("json error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    #[error("array shape error: {0}")]
+    Shape(#[from] ndarray::ShapeError),
+}
@@ -0,0 +1,84 @@
+use std::process::Command;
+
+use crate::{
+    error::{PiperError, Result},
+    phoneme_gen::PhonemeGen,
+    phonemizer::{PhonemizedSentence, Phonemizer},
+};
+
+/// Phonemizer running the `espeak-ng` program, which covers the languages of
+/// the upstream Piper voices. Every clause is a separate run, so punctuation
+/// reaches the voice as it does with upstream Piper.
+#[derive(Debug, Clone)]
+pub struct EspeakPhonemizer {
+    voice: String,
+    program: String,
+}
+
+impl EspeakPhonemizer {
+    /// `voice` is an espeak-ng voice such as `de` or `en-us`, as in the
+    /// `espeak.voice` field of Piper voice configs.
+    pub fn new(voice: impl Into<String>) -> Self {
+        Self {
+            voice: voice.into(),
+            program: "espeak-ng".to_string(),
+        }
+    }
+
+    /// Runs `program` instead of `espeak-ng` from the `PATH`.
+    pub fn with_program(mut self, program: impl Into<String>) -> Self {
+        self.program = program.into();
+        self
+    }
+
+    fn clause_to_ipa(&self, clause: &str) -> Result<String> {
+        let output = Command::new(&self.program)
+            .args(["-q", "--ipa", "-v", &self.voice, "--", clause])
+            .output()
+            .map_err(|e| PiperError::AssetLoad { what: "espeak-ng", path: self.program.clone(), reason: e.to_string() })?;
+        if !output.status.success() {
+            return Err(PiperError::InvalidConfig(format!(
+                "espeak-ng failed for voice `{}`: {}",
+                self.voice,
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        let ipa = String::from_utf8_lossy(&output.stdout);
+        // Words read in another language are wrapped in `(en)...(de)` switches.
+        let ipa: String = ipa.split(['(', ')']).step_by(2).collect();
+        Ok(ipa.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+
+    fn sentence_to_ipa(&self, sentence: &str) -> Result<String> {
+        let mut phonemes = String::new();
+        for clause in sentence.split_inclusive(['.', ',', '!', '?', ';', ':']) {
+            let punctuation = clause.chars().last().filter(|c| ".,!?;:".contains(*c));
+            let ipa = self.clause_to_ipa(clause.trim_end_matches(['.', ',', '!', '?', ';', ':']))?;
+            if ipa.is_empty() {
+                continue;
+            }
+            if !phonemes.is_empty() {
+                phonemes.push(' ');
+            }
+            phonemes.push_str(&ipa);
+            phonemes.extend(punctuation);
+        }
+        Ok(phonemes)
+    }
+}
+
+impl Phonemizer for EspeakPhonemizer {
+    fn phonemize(&self, text: &str) -> Result<Vec<PhonemizedSentence>> {
+        PhonemeGen::split_sentences(text)
+            .into_iter()
+            .map(|sentence| {
+                let phonemes = self.sentence_to_ipa(&sentence)?;
+                Ok(PhonemizedSentence { text: sentence, phonemes })
+            })
+            .collect()
+    }
+
+    fn warm_up(&self) -> Result<()> {
+        self.clause_to_ipa("hello").map(|_| ())
+    }
+}
@@ -0,0 +1,268 @@
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString, c_char},
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+use crate::{
+    error::{PiperError, Result},
+    model_handler::Model,
+    phoneme_gen::PhonemeGen,
+    synthesizer::{SynthesisOptions, Synthesizer},
+    voices,
+};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Result of every fallible call, details are in [`piper_last_error`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PiperStatus {
+    Ok = 0,
+    /// A required pointer was null or a string was not valid UTF-8.
+    InvalidArgument = 1,
+    /// The voice or the G2P model could not be loaded.
+    LoadFailed = 2,
+    SynthesisFailed = 3,
+    /// The engine panicked, the handle should not be used anymore.
+    Panic = 4,
+}
+
+/// Where to load the engine from, null paths fall back to the defaults.
+#[repr(C)]
+pub struct PiperConfig {
+    /// Voice `.onnx` model file or name of a voice in the default voice
+    /// directories. Null for the voice embedded with `embed-voice`.
+    pub model_path: *const c_char,
+    /// Defaults to the model path with `.json` appended.
+    pub config_path: *const c_char,
+    /// Directory with `encoder_model_mini_bart_g2p.onnx`,
+    /// `decoder_model_mini_bart_g2p.onnx`, `tokenizer.json` and `vocab.json`.
+    /// Defaults to `models/g2p`, or the embedded G2P with `embed-g2p`.
+    pub g2p_dir: *const c_char,
+    /// Defaults to `arpabet-mapping.txt`.
+    pub arpabet_mapping_path: *const c_char,
+    /// Speaker of a multi-speaker voice, negative for the voice's default.
+    pub speaker_id: i64,
+}
+
+/// Per-call overrides, negative values fall back to the voice config.
+#[repr(C)]
+pub struct PiperSynthesisOptions {
+    pub length_scale: f32,
+    pub noise_scale: f32,
+    pub noise_w: f32,
+}
+
+/// Mono samples in `[-1, 1]`, owned by the library until passed to
+/// [`piper_free_audio`].
+#[repr(C)]
+pub struct PiperAudio {
+    pub samples: *mut f32,
+    pub len: usize,
+    pub sample_rate: u32,
+}
+
+/// Loaded engine, may be shared between threads.
+pub struct PiperSynthesizer(Synthesizer);
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|error| *error.borrow_mut() = Some(message));
+}
+
+/// Runs `f`, turning errors and panics into a status.
+fn guard(f: impl FnOnce() -> std::result::Result<(), (PiperStatus, String)>) -> PiperStatus {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => PiperStatus::Ok,
+        Ok(Err((status, message))) => {
+            set_last_error(message);
+            status
+        }
+        Err(payload) => {
+            let message = payload.downcast_ref::<&str>().map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            set_last_error(format!("panic: {}", message));
+            PiperStatus::Panic
+        }
+    }
+}
+
+fn failed(status: PiperStatus) -> impl Fn(PiperError) -> (PiperStatus, String) {
+    move |error| (status, error.to_string())
+}
+
+/// # Safety
+///
+/// `value` must be null or a valid NUL-terminated string.
+unsafe fn optional_str<'a>(value: *const c_char, name: &str) -> std::result::Result<Option<&'a str>, (PiperStatus, String)> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    unsafe { CStr::from_ptr(value) }.to_str()
+        .map(Some)
+        .map_err(|_| (PiperStatus::InvalidArgument, format!("`{}` is not valid UTF-8", name)))
+}
+
+fn phoneme_gen(g2p_dir: Option<&str>, arpabet_mapping: Option<&str>) -> PhonemeGen {
+    #[cfg(feature = "embed-g2p")]
+    if g2p_dir.is_none() && arpabet_mapping.is_none() {
+        return PhonemeGen::embedded();
+    }
+    let dir = g2p_dir.unwrap_or("models/g2p");
+    PhonemeGen::new(
+        format!("{}/decoder_model_mini_bart_g2p.onnx", dir),
+        format!("{}/encoder_model_mini_bart_g2p.onnx", dir),
+        format!("{}/tokenizer.json", dir),
+        format!("{}/vocab.json", dir),
+        arpabet_mapping.unwrap_or("arpabet-mapping.txt").to_string(),
+    )
+}
+
+fn model(model_path: Option<&str>, config_path: Option<&str>) -> Result<Model> {
+    match model_path {
+        Some(model_path) => {
+            let (model_path, config_path) = voices::resolve(model_path, config_path, &voices::default_dirs())?;
+            Model::new(&model_path, &config_path)
+        }
+        #[cfg(feature = "embed-voice")]
+        None => Model::embedded(&Default::default()),
+        #[cfg(not(feature = "embed-voice"))]
+        None => Err(PiperError::InvalidConfig("no `model_path` given".to_string())),
+    }
+}
+
+/// Loads a voice and the G2P model into a new engine stored in `*out`.
+///
+/// # Safety
+///
+/// `config` must point to a valid [`PiperConfig`] whose non-null paths are
+/// NUL-terminated strings, and `out` must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn piper_create(config: *const PiperConfig, out: *mut *mut PiperSynthesizer) -> PiperStatus {
+    guard(|| {
+        if config.is_null() || out.is_null() {
+            return Err((PiperStatus::InvalidArgument, "`config` and `out` must not be null".to_string()));
+        }
+        let config = unsafe { &*config };
+        let model_path = unsafe { optional_str(config.model_path, "model_path") }?;
+        let config_path = unsafe { optional_str(config.config_path, "config_path") }?;
+        let g2p_dir = unsafe { optional_str(config.g2p_dir, "g2p_dir") }?;
+        let arpabet_mapping = unsafe { optional_str(config.arpabet_mapping_path, "arpabet_mapping_path") }?;
+
+        let mut phoneme_gen = phoneme_gen(g2p_dir, arpabet_mapping);
+        phoneme_gen.load().map_err(failed(PiperStatus::LoadFailed))?;
+        let mut model = model(model_path, config_path).map_err(failed(PiperStatus::LoadFailed))?;
+        if config.speaker_id >= 0 {
+            model.set_speaker_id(config.speaker_id).map_err(failed(PiperStatus::InvalidArgument))?;
+        }
+
+        let synthesizer = Box::new(PiperSynthesizer(Synthesizer::new(phoneme_gen, model)));
+        unsafe { *out = Box::into_raw(synthesizer) };
+        Ok(())
+    })
+}
+
+/// Frees an engine created by [`piper_create`], null is ignored.
+///
+/// # Safety
+///
+/// `synthesizer` must be null or a pointer returned by [`piper_create`] that
+/// has not been destroyed yet and is not in use by another thread.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn piper_destroy(synthesizer: *mut PiperSynthesizer) {
+    if !synthesizer.is_null() {
+        drop(unsafe { Box::from_raw(synthesizer) });
+    }
+}
+
+/// Sample rate of the audio returned by [`piper_synthesize`], 0 for null.
+///
+/// # Safety
+///
+/// `synthesizer` must be null or a live pointer returned by [`piper_create`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn piper_sample_rate(synthesizer: *const PiperSynthesizer) -> u32 {
+    match unsafe { synthesizer.as_ref() } {
+        Some(synthesizer) => synthesizer.0.sample_rate() as u32,
+        None => 0,
+    }
+}
+
+/// Synthesizes `text` into `*out`, which must later be released with
+/// [`piper_free_audio`]. `options` may be null for the voice's defaults.
+///
+/// # Safety
+///
+/// `synthesizer` must be a live pointer returned by [`piper_create`], `text`
+/// a NUL-terminated string, `options` null or valid for reads and `out`
+/// valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn piper_synthesize(
+    synthesizer: *const PiperSynthesizer,
+    text: *const c_char,
+    options: *const PiperSynthesisOptions,
+    out: *mut PiperAudio,
+) -> PiperStatus {
+    guard(|| {
+        if synthesizer.is_null() || out.is_null() {
+            return Err((PiperStatus::InvalidArgument, "`synthesizer` and `out` must not be null".to_string()));
+        }
+        let synthesizer = unsafe { &(*synthesizer).0 };
+        let text = unsafe { optional_str(text, "text") }?
+            .ok_or_else(|| (PiperStatus::InvalidArgument, "`text` must not be null".to_string()))?;
+        let scale = |value: f32| (value >= 0.0).then_some(value);
+        let options = match unsafe { options.as_ref() } {
+            Some(options) => SynthesisOptions {
+                length_scale: scale(options.length_scale),
+                noise_scale: scale(options.noise_scale),
+                noise_w: scale(options.noise_w),
+                ..Default::default()
+            },
+            None => SynthesisOptions::default(),
+        };
+
+        let samples = synthesizer.synthesize_with(text, &options)
+            .map_err(failed(PiperStatus::SynthesisFailed))?
+            .into_boxed_slice();
+        let len = samples.len();
+        unsafe {
+            *out = PiperAudio {
+                samples: Box::into_raw(samples).cast(),
+                len,
+                sample_rate: synthesizer.sample_rate() as u32,
+            };
+        }
+        Ok(())
+    })
+}
+
+/// Frees the samples of audio returned by [`piper_synthesize`] and resets it,
+/// freeing it twice or passing null is harmless.
+///
+/// # Safety
+///
+/// `audio` must be null or point to a [`PiperAudio`] filled by
+/// [`piper_synthesize`] or already freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn piper_free_audio(audio: *mut PiperAudio) {
+    let Some(audio) = (unsafe { audio.as_mut() }) else {
+        return;
+    };
+    if !audio.samples.is_null() {
+        drop(unsafe { Box::from_raw(ptr::slice_from_raw_parts_mut(audio.samples, audio.len)) });
+    }
+    audio.samples = ptr::null_mut();
+    audio.len = 0;
+}
+
+/// Message of the last error on the calling thread, or null. Valid until the
+/// next failing call on the same thread.
+#[unsafe(no_mangle)]
+pub extern "C" fn piper_last_error() -> *const c_char {
+    LAST_ERROR.with(|error| error.borrow().as_ref().map_or(ptr::null(), |message| message.as_ptr()))
+}
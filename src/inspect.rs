@@ -0,0 +1,195 @@
+use crate::{
+    asset::Asset,
+    error::{PiperError, Result},
+    session::{build_session, SessionOptions},
+};
+
+/// An input or output of an ONNX graph, `value_type` as ONNX Runtime prints
+/// it, e.g. `Tensor<i64>(batch_size, phonemes)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueInfo {
+    pub name: String,
+    pub value_type: String,
+}
+
+/// What an ONNX model declares, for debugging voices that fail to load or run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModelInfo {
+    pub producer: String,
+    /// Operator set versions by domain, the empty domain being `ai.onnx`.
+    pub opsets: Vec<(String, i64)>,
+    pub inputs: Vec<ValueInfo>,
+    pub outputs: Vec<ValueInfo>,
+    /// Integer quantization operators in the graph, empty for float models.
+    pub quantized_ops: Vec<String>,
+}
+
+pub fn inspect_model(path: &str) -> Result<ModelInfo> {
+    let bytes = std::fs::read(path)
+        .map_err(|e| PiperError::AssetLoad { what: "model", path: path.to_string(), reason: e.to_string() })?;
+    let session = build_session(&Asset::Bytes(bytes.as_slice().into()), &SessionOptions::default())?;
+    let value_info = |name: &str, value_type: &dyn std::fmt::Display| ValueInfo {
+        name: name.to_string(),
+        value_type: value_type.to_string(),
+    };
+    Ok(ModelInfo {
+        producer: session.metadata()?.producer()?,
+        opsets: opset_imports(&bytes),
+        inputs: session.inputs.iter().map(|input| value_info(&input.name, &input.input_type)).collect(),
+        outputs: session.outputs.iter().map(|output| value_info(&output.name, &output.output_type)).collect(),
+        quantized_ops: quantized_ops(&bytes),
+    })
+}
+
+/// Operators written by ONNX Runtime's dynamic and static quantization.
+const QUANTIZED_OPS: [&str; 8] = [
+    "DynamicQuantizeLinear", "QuantizeLinear", "DequantizeLinear", "MatMulInteger",
+    "ConvInteger", "QLinearConv", "QLinearMatMul", "DynamicQuantizeMatMul",
+];
+
+/// A protobuf field value, by wire type.
+pub(crate) enum Field<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+    Fixed64(u64),
+}
+
+pub(crate) fn read_varint(bytes: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = *bytes.get(*pos)?;
+        *pos += 1;
+        value |= u64::from(byte & 0x7f) << shift;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
+
+pub(crate) fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Appends a field as [`fields`] reads it.
+pub(crate) fn write_field(bytes: &mut Vec<u8>, number: u64, field: &Field<'_>) {
+    let wire_type = match field {
+        Field::Varint(_) => 0,
+        Field::Fixed64(_) => 1,
+        Field::Bytes(_) => 2,
+        Field::Fixed32(_) => 5,
+    };
+    write_varint(bytes, number << 3 | wire_type);
+    match field {
+        Field::Varint(value) => write_varint(bytes, *value),
+        Field::Fixed64(value) => bytes.extend_from_slice(&value.to_le_bytes()),
+        Field::Bytes(value) => {
+            write_varint(bytes, value.len() as u64);
+            bytes.extend_from_slice(value);
+        }
+        Field::Fixed32(value) => bytes.extend_from_slice(&value.to_le_bytes()),
+    }
+}
+
+/// The top-level fields of a protobuf message, stopping at the first
+/// malformed one.
+pub(crate) fn fields(bytes: &[u8]) -> Vec<(u64, Field<'_>)> {
+    let mut fields = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let Some(key) = read_varint(bytes, &mut pos) else {
+            break;
+        };
+        let field = match key & 7 {
+            0 => match read_varint(bytes, &mut pos) {
+                Some(value) => Field::Varint(value),
+                None => break,
+            },
+            1 => {
+                let Some(value) = bytes.get(pos..pos + 8) else {
+                    break;
+                };
+                pos += 8;
+                Field::Fixed64(u64::from_le_bytes(value.try_into().unwrap_or_default()))
+            }
+            5 => {
+                let Some(value) = bytes.get(pos..pos + 4) else {
+                    break;
+                };
+                pos += 4;
+                Field::Fixed32(u32::from_le_bytes(value.try_into().unwrap_or_default()))
+            }
+            2 => {
+                let Some(len) = read_varint(bytes, &mut pos) else {
+                    break;
+                };
+                let Some(value) = bytes.get(pos..pos.saturating_add(len as usize)) else {
+                    break;
+                };
+                pos += len as usize;
+                Field::Bytes(value)
+            }
+            _ => break,
+        };
+        fields.push((key >> 3, field));
+    }
+    fields
+}
+
+/// The quantization operators used by the main graph of an ONNX `ModelProto`,
+/// each listed once in order of first use.
+pub(crate) fn quantized_ops(model: &[u8]) -> Vec<String> {
+    const GRAPH: u64 = 7;
+    const NODE: u64 = 1;
+    const OP_TYPE: u64 = 4;
+    let mut ops = Vec::new();
+    for (number, field) in fields(model) {
+        let (GRAPH, Field::Bytes(graph)) = (number, field) else {
+            continue;
+        };
+        for (number, field) in fields(graph) {
+            let (NODE, Field::Bytes(node)) = (number, field) else {
+                continue;
+            };
+            for (number, field) in fields(node) {
+                if let (OP_TYPE, Field::Bytes(op_type)) = (number, field)
+                    && let Ok(op_type) = std::str::from_utf8(op_type)
+                    && QUANTIZED_OPS.contains(&op_type)
+                    && !ops.iter().any(|op| op == op_type)
+                {
+                    ops.push(op_type.to_string());
+                }
+            }
+        }
+    }
+    ops
+}
+
+/// The `opset_import` entries of an ONNX `ModelProto`, which ONNX Runtime
+/// doesn't expose.
+fn opset_imports(model: &[u8]) -> Vec<(String, i64)> {
+    const OPSET_IMPORT: u64 = 8;
+    fields(model).into_iter()
+        .filter_map(|(number, field)| match (number, field) {
+            (OPSET_IMPORT, Field::Bytes(opset)) => Some(opset),
+            _ => None,
+        })
+        .map(|opset| {
+            let mut domain = String::new();
+            let mut version = 0;
+            for (number, field) in fields(opset) {
+                match (number, field) {
+                    (1, Field::Bytes(name)) => domain = String::from_utf8_lossy(name).into_owned(),
+                    (2, Field::Varint(value)) => version = value as i64,
+                    _ => {}
+                }
+            }
+            (domain, version)
+        })
+        .collect()
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+
+use whatlang::{Detector, Lang};
+
+use crate::error::{PiperError, Result};
+
+/// ISO 639-1 codes of the languages the detector tells apart.
+const LANGUAGES: &[(Lang, &str)] = &[
+    (Lang::Afr, "af"), (Lang::Aka, "ak"), (Lang::Amh, "am"), (Lang::Ara, "ar"), (Lang::Aze, "az"),
+    (Lang::Bel, "be"), (Lang::Ben, "bn"), (Lang::Bul, "bg"), (Lang::Cat, "ca"), (Lang::Ces, "cs"),
+    (Lang::Cmn, "zh"), (Lang::Dan, "da"), (Lang::Deu, "de"), (Lang::Ell, "el"), (Lang::Eng, "en"),
+    (Lang::Epo, "eo"), (Lang::Est, "et"), (Lang::Fin, "fi"), (Lang::Fra, "fr"), (Lang::Guj, "gu"),
+    (Lang::Heb, "he"), (Lang::Hin, "hi"), (Lang::Hrv, "hr"), (Lang::Hun, "hu"), (Lang::Hye, "hy"),
+    (Lang::Ind, "id"), (Lang::Ita, "it"), (Lang::Jav, "jv"), (Lang::Jpn, "ja"), (Lang::Kan, "kn"),
+    (Lang::Kat, "ka"), (Lang::Khm, "km"), (Lang::Kor, "ko"), (Lang::Lat, "la"), (Lang::Lav, "lv"),
+    (Lang::Lit, "lt"), (Lang::Mal, "ml"), (Lang::Mar, "mr"), (Lang::Mkd, "mk"), (Lang::Mya, "my"),
+    (Lang::Nep, "ne"), (Lang::Nld, "nl"), (Lang::Nob, "no"), (Lang::Ori, "or"), (Lang::Pan, "pa"),
+    (Lang::Pes, "fa"), (Lang::Pol, "pl"), (Lang::Por, "pt"), (Lang::Ron, "ro"), (Lang::Rus, "ru"),
+    (Lang::Sin, "si"), (Lang::Slk, "sk"), (Lang::Slv, "sl"), (Lang::Sna, "sn"), (Lang::Spa, "es"),
+    (Lang::Srp, "sr"), (Lang::Swe, "sv"), (Lang::Tam, "ta"), (Lang::Tel, "te"), (Lang::Tgl, "tl"),
+    (Lang::Tha, "th"), (Lang::Tuk, "tk"), (Lang::Tur, "tr"), (Lang::Ukr, "uk"), (Lang::Urd, "ur"),
+    (Lang::Uzb, "uz"), (Lang::Vie, "vi"), (Lang::Yid, "yi"), (Lang::Zul, "zu"),
+];
+
+/// The detector language of an ISO 639-1 code or a voice language code such as `de_DE`.
+fn lang(code: &str) -> Option<Lang> {
+    let code = code.split(['_', '-']).next().unwrap_or_default().to_ascii_lowercase();
+    // Piper names Norwegian Bokmål voices `nb`.
+    let code = if code == "nb" { "no" } else { code.as_str() };
+    LANGUAGES.iter().find(|(_, iso)| *iso == code).map(|(lang, _)| *lang)
+}
+
+/// Most likely ISO 639-1 language of `text`, `None` when the guess is unreliable.
+pub fn detect_language(text: &str) -> Option<&'static str> {
+    let info = whatlang::detect(text).filter(|info| info.is_reliable())?;
+    LANGUAGES.iter().find(|(lang, _)| *lang == info.lang()).map(|(_, iso)| *iso)
+}
+
+/// Picks a voice for each line of mixed-language text by the language it is
+/// written in, see [`Synthesizer::synthesize_routed`](crate::Synthesizer::synthesize_routed).
+/// Only the routed languages are told apart, which keeps short lines from
+/// being mistaken for an unrelated language.
+#[derive(Debug, Clone, Default)]
+pub struct LanguageRouter {
+    routes: HashMap<Lang, String>,
+}
+
+impl LanguageRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Routes `language`, an ISO 639-1 code or a voice language code such as
+    /// `de_DE`, to the voice named `voice`. The first voice routed to a
+    /// language keeps it.
+    pub fn with_route(mut self, language: &str, voice: impl Into<String>) -> Result<Self> {
+        self.add_route(language, voice)?;
+        Ok(self)
+    }
+
+    pub fn add_route(&mut self, language: &str, voice: impl Into<String>) -> Result<()> {
+        let lang = lang(language)
+            .ok_or_else(|| PiperError::InvalidConfig(format!("unknown language `{}`", language)))?;
+        self.routes.entry(lang).or_insert_with(|| voice.into());
+        Ok(())
+    }
+
+    /// Voice routed to the language of `text`, `None` when it can't be told.
+    pub fn voice(&self, text: &str) -> Option<&str> {
+        let detector = Detector::with_allowlist(self.routes.keys().copied().collect());
+        let info = detector.detect(text).filter(|info| info.is_reliable())?;
+        self.routes.get(&info.lang()).map(String::as_str)
+    }
+
+    /// Splits `text` at the line breaks where its language changes, pairing
+    /// each part with its voice. Lines too short to tell, such as headings,
+    /// stay with the part before them, or the one after at the start.
+    /// The voice is `None` only when no line could be told.
+    pub fn route<'a>(&self, text: &'a str) -> Vec<(Option<&str>, &'a str)> {
+        let mut parts = Vec::new();
+        let mut voice: Option<&str> = None;
+        let mut start = 0;
+        let mut offset = 0;
+        for line in text.split_inclusive('\n') {
+            if let Some(line_voice) = self.voice(line) {
+                match voice {
+                    Some(current) if current != line_voice => {
+                        parts.push((voice, &text[start..offset]));
+                        start = offset;
+                        voice = Some(line_voice);
+                    }
+                    Some(_) => {}
+                    None => voice = Some(line_voice),
+                }
+            }
+            offset += line.len();
+        }
+        if start < text.len() {
+            parts.push((voice, &text[start..]));
+        }
+        parts
+    }
+}
@@ -0,0 +1,209 @@
+use std::{collections::HashMap, path::Path};
+
+use crate::{
+    error::{PiperError, Result},
+    phoneme_gen::PhonemeGen,
+    phonemizer::{PhonemizedSentence, Phonemizer},
+};
+
+/// Pronunciation dictionary in CMUdict format: `WORD  PH1 PH2 ...` per line,
+/// `;;;` comments and `WORD(1)` alternates, which are skipped in favour of
+/// the first pronunciation.
+#[derive(Debug, Clone, Default)]
+pub struct Lexicon {
+    entries: HashMap<String, Vec<String>>,
+}
+
+impl Lexicon {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path)
+            .map_err(|e| PiperError::AssetLoad {
+                what: "lexicon",
+                path: path.display().to_string(),
+                reason: e.to_string(),
+            })?;
+        Ok(Self::parse(&String::from_utf8_lossy(&bytes)))
+    }
+
+    pub fn parse(data: &str) -> Self {
+        let mut entries: HashMap<String, Vec<String>> = HashMap::new();
+
+        for line in data.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(";;;") {
+                continue;
+            }
+
+            let mut parts = line.split_whitespace();
+            let Some(word) = parts.next() else {
+                continue;
+            };
+            if word.ends_with(')') && word.contains('(') {
+                continue;
+            }
+
+            let phonemes: Vec<String> = parts.map(|p| p.to_string()).collect();
+            if phonemes.is_empty() {
+                continue;
+            }
+            entries.entry(word.to_lowercase()).or_insert(phonemes);
+        }
+
+        Self { entries }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, word: &str) -> Option<&Vec<String>> {
+        self.entries.get(&word.to_lowercase())
+    }
+
+    pub fn insert(&mut self, word: &str, phonemes: Vec<String>) {
+        self.entries.insert(word.to_lowercase(), phonemes);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pronunciation {
+    Ipa(String),
+    Arpabet(Vec<String>),
+}
+
+impl Pronunciation {
+    /// Treats the text as ARPAbet when every symbol looks like one (`HH EH1 L OW0`),
+    /// and as IPA otherwise.
+    pub fn parse(text: &str) -> Self {
+        let symbols: Vec<&str> = text.split_whitespace().collect();
+        let is_arpabet = !symbols.is_empty() && symbols.iter().all(|symbol| {
+            let letters = symbol.trim_end_matches(|c: char| c.is_ascii_digit());
+            !letters.is_empty() && letters.chars().all(|c| c.is_ascii_uppercase())
+        });
+
+        if is_arpabet {
+            Pronunciation::Arpabet(symbols.iter().map(|s| s.to_string()).collect())
+        } else {
+            Pronunciation::Ipa(text.trim().to_string())
+        }
+    }
+}
+
+/// User-supplied overrides consulted before any other G2P path.
+///
+/// The file has one `word pronunciation` pair per line, the pronunciation
+/// being either IPA or space separated ARPAbet. Lines starting with `#` are comments.
+#[derive(Debug, Clone, Default)]
+pub struct UserLexicon {
+    entries: HashMap<String, Pronunciation>,
+}
+
+impl UserLexicon {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let data = std::fs::read_to_string(path)
+            .map_err(|e| PiperError::AssetLoad {
+                what: "user lexicon",
+                path: path.display().to_string(),
+                reason: e.to_string(),
+            })?;
+        Ok(Self::parse(&data))
+    }
+
+    pub fn parse(data: &str) -> Self {
+        let mut lexicon = Self::default();
+        for line in data.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some((word, pronunciation)) = line.split_once(char::is_whitespace) {
+                lexicon.insert(word, Pronunciation::parse(pronunciation));
+            }
+        }
+        lexicon
+    }
+
+    pub fn extend(&mut self, other: UserLexicon) {
+        self.entries.extend(other.entries);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, word: &str) -> Option<&Pronunciation> {
+        self.entries.get(&word.to_lowercase())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Pronunciation)> {
+        self.entries.iter().map(|(word, pronunciation)| (word.as_str(), pronunciation))
+    }
+
+    pub fn insert(&mut self, word: &str, pronunciation: Pronunciation) {
+        self.entries.insert(word.to_lowercase(), pronunciation);
+    }
+}
+
+/// Phonemizer reading every word from a pronunciation list in the
+/// [`UserLexicon`] format, for voices whose language has no G2P model.
+/// Words missing from the list, or listed in ARPAbet, are left out with a warning.
+#[derive(Debug, Clone, Default)]
+pub struct LexiconPhonemizer {
+    lexicon: UserLexicon,
+}
+
+impl LexiconPhonemizer {
+    pub fn new(lexicon: UserLexicon) -> Self {
+        Self { lexicon }
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        UserLexicon::from_file(path).map(Self::new)
+    }
+
+    fn sentence_to_ipa(&self, sentence: &str) -> String {
+        let mut phonemes = Vec::new();
+        for word in PhonemeGen::split_words(sentence) {
+            let (_, core, trailing) = PhonemeGen::split_punctuation(word);
+            let punctuation: String = trailing.chars().filter(|c| ".,!?;:".contains(*c)).collect();
+            let ipa = match PhonemeGen::phoneme_escape(core) {
+                Some(ipa) => Some(ipa.trim()),
+                None if core.is_empty() => None,
+                None => match self.lexicon.get(core) {
+                    Some(Pronunciation::Ipa(ipa)) => Some(ipa.as_str()),
+                    _ => {
+                        tracing::warn!("no IPA pronunciation for `{}` in the lexicon", core);
+                        None
+                    }
+                },
+            };
+            match ipa {
+                Some(ipa) => phonemes.push(format!("{}{}", ipa, punctuation)),
+                // Punctuation after a skipped word still reaches the voice.
+                None => if let Some(last) = phonemes.last_mut() {
+                    last.push_str(&punctuation);
+                },
+            }
+        }
+        phonemes.join(" ")
+    }
+}
+
+impl Phonemizer for LexiconPhonemizer {
+    fn phonemize(&self, text: &str) -> Result<Vec<PhonemizedSentence>> {
+        Ok(PhonemeGen::split_sentences(text)
+            .into_iter()
+            .map(|sentence| PhonemizedSentence { phonemes: self.sentence_to_ipa(&sentence), text: sentence })
+            .collect())
+    }
+}
@@ -1,5 +1,72 @@
+pub mod error;
+mod asset;
+pub mod alignment;
+pub mod lexicon;
+pub mod loudness;
+pub mod audio;
+pub mod backend;
+pub mod cache;
+pub mod cancellation;
+#[cfg(unix)]
+pub mod daemon;
+pub mod encoding;
+pub mod espeak;
+pub mod inspect;
+pub mod markup;
+#[cfg(any(feature = "embed-g2p", feature = "embed-voice"))]
+pub mod embedded;
 pub mod phoneme_gen;
+pub mod phonemizer;
 pub mod model_handler;
+pub mod pauses;
+pub mod postprocess;
+pub mod session;
+pub mod signature;
+pub mod subtitles;
+pub mod synthesizer;
+pub mod text_norm;
+pub mod time_stretch;
+pub mod viseme;
+pub mod voice_manager;
+pub mod voices;
+#[cfg(feature = "async")]
+pub mod async_synthesizer;
+#[cfg(feature = "server")]
+pub mod server;
+#[cfg(feature = "playback")]
+pub mod playback;
+#[cfg(feature = "speechd")]
+pub mod speechd;
+#[cfg(feature = "download")]
+pub mod download;
+#[cfg(feature = "epub")]
+pub mod epub;
+#[cfg(feature = "detect-language")]
+pub mod language;
+#[cfg(feature = "ffi")]
+pub mod ffi;
 
-pub use phoneme_gen::PhonemeGen;
-pub use model_handler::Model;
\ No newline at end of file
+pub use alignment::{ChunkAlignment, PhonemeTiming};
+pub use audio::{AudioSink, PcmSink, SampleFormat};
+pub use backend::{Backend, InferenceBackend};
+pub use cancellation::CancellationToken;
+pub use encoding::OutputFormat;
+pub use espeak::EspeakPhonemizer;
+pub use markup::{CodeBlocks, Markup};
+#[cfg(feature = "detect-language")]
+pub use language::LanguageRouter;
+pub use error::{PiperError, Result};
+pub use lexicon::LexiconPhonemizer;
+pub use phoneme_gen::{Decoding, ForeignWords, PhonemeGen, WordConfidence};
+pub use phonemizer::{PhonemizedSentence, Phonemizer, PhonemizerConfig};
+pub use model_handler::Model;
+pub use pauses::PauseConfig;
+pub use postprocess::{Gain, PostProcess};
+pub use session::{ExecutionProvider, OptimizationLevel, SessionOptions};
+pub use subtitles::{Cue, SubtitleFormat, WordTiming};
+pub use synthesizer::{Progress, Rate, SynthesisOptions, SynthesisStats, Synthesizer};
+#[cfg(feature = "async")]
+pub use async_synthesizer::{AsyncSynthesizer, PoolMetrics};
+pub use text_norm::TextNormalizer;
+pub use viseme::{Viseme, VisemeEvent};
+pub use voice_manager::VoiceManager;
@@ -0,0 +1,90 @@
+use std::f64::consts::PI;
+
+/// Loudness of silence as far as gating is concerned, per EBU R128.
+const ABSOLUTE_GATE_LUFS: f64 = -70.0;
+const RELATIVE_GATE_LU: f64 = -10.0;
+const BLOCK_SECONDS: f64 = 0.4;
+const STEP_SECONDS: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b: [f64; 3],
+    a: [f64; 3],
+}
+
+impl Biquad {
+    /// Pre-filter modelling the head, a +4 dB shelf above ~1.7 kHz.
+    fn shelf(sample_rate: f64) -> Self {
+        let k = (PI * 1_681.974_450_955_533 / sample_rate).tan();
+        let q = 0.707_175_236_955_419_6;
+        let vh = 10f64.powf(3.999_843_853_973_347 / 20.0);
+        let vb = vh.powf(0.499_666_774_154_541_6);
+        Self {
+            b: [vh + vb * k / q + k * k, 2.0 * (k * k - vh), vh - vb * k / q + k * k],
+            a: [1.0 + k / q + k * k, 2.0 * (k * k - 1.0), 1.0 - k / q + k * k],
+        }
+    }
+
+    /// RLB high-pass weighting out the lowest frequencies.
+    fn high_pass(sample_rate: f64) -> Self {
+        let k = (PI * 38.135_470_876_024_44 / sample_rate).tan();
+        let q = 0.500_327_037_323_877_3;
+        Self {
+            b: [1.0, -2.0, 1.0],
+            a: [1.0 + k / q + k * k, 2.0 * (k * k - 1.0), 1.0 - k / q + k * k],
+        }
+    }
+
+    fn filter(&self, input: &[f64]) -> Vec<f64> {
+        let [b0, b1, b2] = self.b.map(|b| b / self.a[0]);
+        let [_, a1, a2] = self.a.map(|a| a / self.a[0]);
+        let (mut x1, mut x2, mut y1, mut y2) = (0.0, 0.0, 0.0, 0.0);
+        input.iter()
+            .map(|&x| {
+                let y = b0 * x + b1 * x1 + b2 * x2 - a1 * y1 - a2 * y2;
+                (x2, x1, y2, y1) = (x1, x, y1, y);
+                y
+            })
+            .collect()
+    }
+}
+
+fn to_lufs(power: f64) -> f64 {
+    -0.691 + 10.0 * power.log10()
+}
+
+/// Gated integrated loudness (ITU-R BS.1770 / EBU R128) of mono audio in
+/// LUFS, `None` when the audio is silent.
+pub fn integrated_loudness(waveform: &[f32], sample_rate: u64) -> Option<f64> {
+    if waveform.is_empty() {
+        return None;
+    }
+    let rate = sample_rate as f64;
+    let samples: Vec<f64> = waveform.iter().map(|&s| s as f64).collect();
+    let weighted = Biquad::high_pass(rate).filter(&Biquad::shelf(rate).filter(&samples));
+
+    let block = ((BLOCK_SECONDS * rate) as usize).min(weighted.len());
+    let step = ((STEP_SECONDS * rate) as usize).max(1);
+    let powers: Vec<f64> = (0..=weighted.len() - block)
+        .step_by(step)
+        .map(|start| weighted[start..start + block].iter().map(|s| s * s).sum::<f64>() / block as f64)
+        .filter(|&power| to_lufs(power) > ABSOLUTE_GATE_LUFS)
+        .collect();
+    if powers.is_empty() {
+        return None;
+    }
+
+    let relative_gate = to_lufs(powers.iter().sum::<f64>() / powers.len() as f64) + RELATIVE_GATE_LU;
+    let gated: Vec<f64> = powers.into_iter().filter(|&power| to_lufs(power) > relative_gate).collect();
+    Some(to_lufs(gated.iter().sum::<f64>() / gated.len() as f64))
+}
+
+/// Scales the waveform so its integrated loudness is `target` LUFS, silent
+/// audio is left untouched.
+pub fn normalize(waveform: &mut [f32], sample_rate: u64, target: f64) {
+    let Some(loudness) = integrated_loudness(waveform, sample_rate) else {
+        return;
+    };
+    let gain = 10f64.powf((target - loudness) / 20.0) as f32;
+    waveform.iter_mut().for_each(|sample| *sample *= gain);
+}
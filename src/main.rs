@@ -0,0 +1,1506 @@
+use std::{io::{BufRead, IsTerminal}, path::PathBuf, time::Duration};
+
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand};
+use piper_tts_rust::{Backend, CodeBlocks, Decoding, ExecutionProvider, Gain, Markup, Model, OptimizationLevel, OutputFormat, PhonemeGen, PcmSink, Result, PauseConfig, PostProcess, Rate, SampleFormat, SessionOptions, SubtitleFormat, SynthesisOptions, Synthesizer, TextNormalizer};
+use piper_tts_rust::text_norm::EmojiHandling;
+use serde::Deserialize;
+use tracing_subscriber::{fmt::format::FmtSpan, EnvFilter};
+
+#[derive(Parser, Debug)]
+#[command(name = "piper-tts-rust", version, about = "Piper TTS with a neural G2P front-end")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// TOML file of default flag values, defaults to
+    /// `$XDG_CONFIG_HOME/piper-tts-rust/config.toml`
+    #[arg(long, global = true, env = "PIPER_CONFIG_FILE")]
+    config_file: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Synthesize text into a WAV file or play it
+    Synth(Box<SynthArgs>),
+    /// Synthesize every `.txt` file of a directory into an audio file
+    SynthDir(Box<SynthDirArgs>),
+    /// Synthesize every row of a CSV or TSV file, writing a manifest of the results
+    SynthCsv(Box<SynthCsvArgs>),
+    /// Synthesize an EPUB into one audio file per chapter, resuming an interrupted run
+    #[cfg(feature = "epub")]
+    Audiobook(Box<AudiobookArgs>),
+    /// Run the HTTP server
+    #[cfg(feature = "server")]
+    Serve(Box<ServeArgs>),
+    /// Keep the models loaded and synthesize requests sent over a Unix socket
+    #[cfg(unix)]
+    Daemon(Box<DaemonArgs>),
+    /// List the audio output devices available for playback
+    #[cfg(feature = "playback")]
+    Devices,
+    /// Run as a speech-dispatcher output module, talking its protocol on stdin and stdout
+    #[cfg(feature = "speechd")]
+    Speechd(Box<SpeechdArgs>),
+    /// Download a voice from the upstream Piper voice repository
+    #[cfg(feature = "download")]
+    Download(DownloadArgs),
+    /// List the voices found in the voices directories
+    Voices(VoicesArgs),
+    /// Print a voice's ONNX inputs and outputs and its config
+    Inspect(InspectArgs),
+    /// Upstream `piper` command line, used when the first argument is a flag
+    #[command(hide = true)]
+    Piper(Box<PiperArgs>),
+}
+
+#[derive(Args, Debug)]
+struct VoiceArgs {
+    /// Path to the Piper voice `.onnx` model, or the name of a voice in the
+    /// voices directories such as `en_US-norman-medium`
+    #[arg(short, long, visible_alias = "voice", required = cfg!(not(feature = "embed-voice")))]
+    model: Option<String>,
+    /// Path to the voice config, defaults to `<model>.json`
+    #[arg(short, long)]
+    config: Option<String>,
+    /// Directory searched for voices by name before `./voices` and `$XDG_DATA_HOME/piper`
+    #[arg(long)]
+    voices_dir: Vec<PathBuf>,
+    /// Memory-map the ONNX models instead of reading them, lowering the startup memory peak
+    #[arg(long)]
+    mmap: bool,
+    /// Run a dummy inference through every model after loading, so the first request is fast
+    #[arg(long)]
+    warm_up: bool,
+    /// Defaults to `models/g2p/encoder_model_mini_bart_g2p.onnx`, or the
+    /// embedded G2P files unless any of them is given
+    #[arg(long)]
+    g2p_encoder: Option<String>,
+    /// Defaults to `models/g2p/decoder_model_mini_bart_g2p.onnx`
+    #[arg(long)]
+    g2p_decoder: Option<String>,
+    /// Optional `decoder_with_past` export enabling the decoder KV-cache
+    #[arg(long)]
+    g2p_decoder_with_past: Option<String>,
+    /// Defaults to `models/g2p/tokenizer.json`
+    #[arg(long)]
+    g2p_tokenizer: Option<String>,
+    /// Defaults to `models/g2p/vocab.json`
+    #[arg(long)]
+    g2p_vocab: Option<String>,
+    /// Defaults to `arpabet-mapping.txt`
+    #[arg(long)]
+    arpabet_mapping: Option<String>,
+    /// Decode G2P pronunciations with a beam search over this many candidates, 1 decodes greedily
+    #[arg(long, default_value_t = 1)]
+    g2p_beam_width: usize,
+    /// Beam search length penalty, higher values favour longer pronunciations
+    #[arg(long, default_value_t = 1.0)]
+    g2p_length_penalty: f32,
+    /// Number of word pronunciations kept in memory, 0 disables the cache
+    #[arg(long, default_value_t = 4096)]
+    g2p_cache_size: usize,
+    /// CMUdict-style ARPAbet dictionary consulted before the neural G2P
+    #[arg(long)]
+    lexicon: Option<String>,
+    /// Words with letters outside the English alphabet missing from the lexicons
+    /// are guessed by the G2P (`guess`), spelled out (`spell`) or read by
+    /// espeak-ng in another language (`espeak:<voice>`, e.g. `espeak:de`)
+    #[arg(long, default_value = "guess")]
+    foreign_words: String,
+    /// User pronunciation overrides, one `word IPA-or-ARPAbet` pair per line
+    #[arg(long)]
+    user_lexicon: Option<String>,
+    /// JSON file persisting word pronunciations between runs
+    #[arg(long)]
+    g2p_cache_file: Option<String>,
+    /// Locale of dates and times in the input text, e.g. `en-US` or `en-GB`
+    #[arg(long, default_value = "en-US")]
+    locale: String,
+    /// Extra `abbreviation expansion` pairs, one per line, overriding the built-in table
+    #[arg(long)]
+    abbreviations: Option<String>,
+    /// Read the input text as written, without expanding dates, times, ...
+    #[arg(long)]
+    no_normalize: bool,
+    /// Emoji are read by their description (`describe`) or left out (`strip`)
+    #[arg(long, default_value = "describe")]
+    emoji: EmojiHandling,
+    /// Extra `emoji description` pairs, one per line, overriding the built-in table
+    #[arg(long)]
+    emoji_descriptions: Option<String>,
+    /// Markup stripped from the input text: `plain`, `markdown` or `html`
+    #[arg(long, default_value = "plain")]
+    markup: Markup,
+    /// Code blocks in Markdown or HTML input: `skip`, `mention` or `read`
+    #[arg(long, default_value = "skip")]
+    code_blocks: CodeBlocks,
+    /// Insert short pauses after commas, semicolons, colons and dashes
+    #[arg(long)]
+    clause_pauses: bool,
+    /// Seconds of silence after every sentence
+    #[arg(long, alias = "sentence_silence")]
+    sentence_silence: Option<f32>,
+    /// Synthesize sentences longer than this many phonemes clause by clause
+    #[arg(long)]
+    max_clause_phonemes: Option<usize>,
+    /// Fail on phonemes the voice has no id for instead of dropping them
+    #[arg(long)]
+    strict_phonemes: bool,
+    /// Speed the audio up or slow it down after synthesis, keeping the pitch, 0.5 to 2.0
+    #[arg(long)]
+    tempo: Option<f32>,
+    /// Shift the pitch by this many semitones, -12 to 12
+    #[arg(long, allow_hyphen_values = true)]
+    pitch: Option<f32>,
+    /// Resample the output to this rate in Hz, e.g. 16000 or 48000
+    #[arg(long)]
+    sample_rate: Option<u64>,
+    /// Normalize the output to this integrated loudness in LUFS, e.g. -16
+    #[arg(long, allow_hyphen_values = true)]
+    loudness: Option<f64>,
+    /// Soft-limit peaks to this many dB below full scale, e.g. 1.0
+    #[arg(long)]
+    headroom: Option<f32>,
+    /// Output volume in dB (`-6dB`) or as a linear factor (`0.5`)
+    #[arg(long, allow_hyphen_values = true)]
+    volume: Option<Gain>,
+    /// Fade the start of the audio in over this many milliseconds
+    #[arg(long)]
+    fade_in: Option<u64>,
+    /// Fade the end of the audio out over this many milliseconds
+    #[arg(long)]
+    fade_out: Option<u64>,
+    /// Apply the fades to every synthesized chunk, not just the whole audio
+    #[arg(long)]
+    fade_chunks: bool,
+    /// Speaker name or id for multi-speaker voices
+    #[arg(short, long)]
+    speaker: Option<String>,
+    /// ONNX execution provider: `cpu`, `auto`, `auto:<provider>,...` or a provider enabled at build time
+    #[arg(long, default_value = "cpu")]
+    device: ExecutionProvider,
+    /// Threads used within an ONNX operator, 0 uses one per core
+    #[arg(long, default_value_t = piper_tts_rust::session::DEFAULT_INTRA_THREADS)]
+    intra_threads: usize,
+    /// Threads running independent ONNX operators with `--parallel-execution`, 0 lets ONNX Runtime decide
+    #[arg(long, default_value_t = 0)]
+    inter_threads: usize,
+    /// ONNX Runtime graph optimizations: `disable`, `basic`, `extended` or `all`
+    #[arg(long, default_value = "all")]
+    optimization_level: OptimizationLevel,
+    /// Run independent branches of the ONNX graphs concurrently
+    #[arg(long)]
+    parallel_execution: bool,
+    /// Let GPU execution providers compute in half precision
+    #[arg(long)]
+    fp16: bool,
+    /// Engine running the voice: `onnxruntime`, or `tract` or `candle` when enabled at build time
+    #[arg(long, default_value = "onnxruntime")]
+    backend: Backend,
+    /// Seed the voice's noise so the same text always gives the same audio,
+    /// at the cost of reloading the voice for every sentence on ONNX Runtime
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Directory where TensorRT engines are cached between runs
+    #[cfg(feature = "tensorrt")]
+    #[arg(long)]
+    trt_engine_cache: Option<std::path::PathBuf>,
+}
+
+impl VoiceArgs {
+    fn execution_provider(&self) -> ExecutionProvider {
+        #[cfg(feature = "tensorrt")]
+        fn with_engine_cache(provider: ExecutionProvider, dir: &Option<std::path::PathBuf>) -> ExecutionProvider {
+            match provider {
+                ExecutionProvider::TensorRT { device_id, .. } => ExecutionProvider::TensorRT {
+                    device_id,
+                    engine_cache_dir: dir.clone(),
+                },
+                ExecutionProvider::Auto { priority } => ExecutionProvider::Auto {
+                    priority: priority.into_iter()
+                        .map(|provider| with_engine_cache(provider, dir))
+                        .collect(),
+                },
+                other => other,
+            }
+        }
+
+        #[cfg(feature = "tensorrt")]
+        return with_engine_cache(self.device.clone(), &self.trt_engine_cache);
+        #[cfg(not(feature = "tensorrt"))]
+        self.device.clone()
+    }
+
+    fn session_options(&self) -> SessionOptions {
+        SessionOptions {
+            execution_provider: self.execution_provider(),
+            memory_map: self.mmap,
+            intra_threads: self.intra_threads,
+            inter_threads: self.inter_threads,
+            optimization_level: self.optimization_level,
+            parallel_execution: self.parallel_execution,
+            fp16: self.fp16,
+            backend: self.backend,
+            seed: self.seed,
+        }
+    }
+
+    fn voice_dirs(&self) -> Vec<PathBuf> {
+        voices_dirs(&self.voices_dir)
+    }
+
+    /// Model and config paths of `--model`, looking it up by name if it is not a file.
+    fn voice_files(&self) -> Result<(String, String)> {
+        let model = self.model.as_deref()
+            .ok_or_else(|| piper_tts_rust::PiperError::InvalidConfig("no `--model` given".to_string()))?;
+        piper_tts_rust::voices::resolve(model, self.config.as_deref(), &self.voice_dirs())
+    }
+
+    fn phoneme_gen(&self) -> PhonemeGen {
+        #[cfg(feature = "embed-g2p")]
+        if [&self.g2p_encoder, &self.g2p_decoder, &self.g2p_tokenizer, &self.g2p_vocab, &self.arpabet_mapping]
+            .iter()
+            .all(|file| file.is_none())
+        {
+            return PhonemeGen::embedded();
+        }
+        let file = |file: &Option<String>, default: &str| file.clone().unwrap_or_else(|| default.to_string());
+        PhonemeGen::new(
+            file(&self.g2p_decoder, "models/g2p/decoder_model_mini_bart_g2p.onnx"),
+            file(&self.g2p_encoder, "models/g2p/encoder_model_mini_bart_g2p.onnx"),
+            file(&self.g2p_tokenizer, "models/g2p/tokenizer.json"),
+            file(&self.g2p_vocab, "models/g2p/vocab.json"),
+            file(&self.arpabet_mapping, "arpabet-mapping.txt"),
+        )
+    }
+
+    fn load(&self) -> Result<Synthesizer> {
+        let session_options = self.session_options();
+
+        let mut phoneme_gen = self.phoneme_gen()
+        .with_session_options(session_options.clone())
+        .with_cache_capacity(self.g2p_cache_size);
+        if let Some(path) = &self.g2p_decoder_with_past {
+            phoneme_gen = phoneme_gen.with_decoder_with_past(path.clone());
+        }
+        if let Some(path) = &self.lexicon {
+            phoneme_gen = phoneme_gen.with_lexicon(path);
+        }
+        phoneme_gen = phoneme_gen.with_foreign_words(self.foreign_words.parse()?);
+        if self.g2p_beam_width > 1 {
+            phoneme_gen = phoneme_gen.with_decoding(Decoding::Beam {
+                width: self.g2p_beam_width,
+                length_penalty: self.g2p_length_penalty,
+            });
+        }
+        if self.no_normalize {
+            phoneme_gen = phoneme_gen.with_normalizer(None);
+        } else {
+            let mut normalizer = TextNormalizer::for_locale(&self.locale)?.with_emoji(self.emoji);
+            if let Some(path) = &self.abbreviations {
+                normalizer = normalizer.with_abbreviations_file(path)?;
+            }
+            if let Some(path) = &self.emoji_descriptions {
+                normalizer = normalizer.with_emoji_file(path)?;
+            }
+            phoneme_gen = phoneme_gen.with_normalizer(Some(normalizer));
+        }
+        if let Some(path) = &self.user_lexicon {
+            phoneme_gen = phoneme_gen.with_user_lexicon(path);
+        }
+        if let Some(path) = &self.g2p_cache_file {
+            phoneme_gen = phoneme_gen.with_disk_cache(path);
+        }
+        phoneme_gen.load()?;
+
+        let mut model = match &self.model {
+            Some(_) => {
+                let (model_path, config_path) = self.voice_files()?;
+                Model::with_options(&model_path, &config_path, &session_options)?
+            }
+            #[cfg(feature = "embed-voice")]
+            None => Model::embedded(&session_options)?,
+            #[cfg(not(feature = "embed-voice"))]
+            None => unreachable!("`--model` is required without an embedded voice"),
+        };
+        if let Some(speaker) = &self.speaker {
+            model.set_speaker(speaker)?;
+        }
+        model.set_strict(self.strict_phonemes);
+
+        let mut synthesizer = Synthesizer::new(phoneme_gen, model);
+        if let Some(max_phonemes) = self.max_clause_phonemes {
+            synthesizer = synthesizer.with_clause_chunking(max_phonemes);
+        }
+        let mut pauses = PauseConfig::default();
+        if self.clause_pauses {
+            pauses = pauses.with_clause_pauses();
+        }
+        if let Some(seconds) = self.sentence_silence {
+            pauses = pauses.with_sentence_silence(Duration::from_secs_f32(seconds.max(0.0)));
+        }
+        synthesizer = synthesizer.with_pauses(pauses).with_markup(self.markup, self.code_blocks);
+        let mut post_process = PostProcess::default();
+        if let Some(tempo) = self.tempo {
+            post_process = post_process.with_tempo(tempo);
+        }
+        if let Some(semitones) = self.pitch {
+            post_process = post_process.with_pitch_shift(semitones);
+        }
+        if let Some(sample_rate) = self.sample_rate {
+            post_process = post_process.with_sample_rate(sample_rate);
+        }
+        if let Some(lufs) = self.loudness {
+            post_process = post_process.with_loudness(lufs);
+        }
+        if let Some(gain) = self.volume {
+            post_process = post_process.with_gain(gain);
+        }
+        if let Some(headroom_db) = self.headroom {
+            post_process = post_process.with_limiter(headroom_db);
+        }
+        if let Some(ms) = self.fade_in {
+            post_process = post_process.with_fade_in(Duration::from_millis(ms));
+        }
+        if let Some(ms) = self.fade_out {
+            post_process = post_process.with_fade_out(Duration::from_millis(ms));
+        }
+        if self.fade_chunks {
+            post_process = post_process.with_chunk_fades();
+        }
+        synthesizer = synthesizer.with_post_process(post_process);
+        if self.warm_up {
+            synthesizer.warm_up()?;
+        }
+        Ok(synthesizer)
+    }
+}
+
+#[derive(Args, Debug)]
+struct ScaleArgs {
+    /// Override the voice's noise_scale
+    #[arg(long, alias = "noise_scale")]
+    noise_scale: Option<f32>,
+    /// Override the voice's length_scale
+    #[arg(long, alias = "length_scale")]
+    length_scale: Option<f32>,
+    /// Override the voice's noise_w
+    #[arg(long, alias = "noise_w")]
+    noise_w: Option<f32>,
+    /// Speaking rate, e.g. 1.25 or +25% to speak faster, -10% to speak slower
+    #[arg(long, allow_hyphen_values = true)]
+    rate: Option<Rate>,
+}
+
+impl ScaleArgs {
+    fn options(&self) -> SynthesisOptions {
+        SynthesisOptions {
+            noise_scale: self.noise_scale,
+            length_scale: self.length_scale,
+            noise_w: self.noise_w,
+            rate: self.rate,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum Speaker {
+    Id(i64),
+    Name(String),
+}
+
+/// One record of `--json-input`, unset fields fall back to the command line.
+#[derive(Deserialize, Debug, Default)]
+struct JsonLine {
+    text: String,
+    output_file: Option<String>,
+    #[serde(alias = "speaker_id")]
+    speaker: Option<Speaker>,
+    noise_scale: Option<f32>,
+    length_scale: Option<f32>,
+    noise_w: Option<f32>,
+}
+
+impl JsonLine {
+    fn options(&self, scales: &ScaleArgs) -> SynthesisOptions {
+        SynthesisOptions {
+            noise_scale: self.noise_scale.or(scales.noise_scale),
+            length_scale: self.length_scale.or(scales.length_scale),
+            noise_w: self.noise_w.or(scales.noise_w),
+            rate: scales.rate,
+            ..Default::default()
+        }
+    }
+
+    /// Runs `f` with the record's speaker selected, then goes back to the previous one.
+    fn with_speaker<T>(&self, synthesizer: &mut Synthesizer, f: impl FnOnce(&Synthesizer) -> Result<T>) -> Result<T> {
+        let previous = synthesizer.model.speaker_id();
+        let selected = match &self.speaker {
+            None => return f(synthesizer),
+            Some(Speaker::Id(id)) => synthesizer.model.set_speaker_id(*id),
+            Some(Speaker::Name(name)) => synthesizer.model.set_speaker(name),
+        };
+        let result = selected.and_then(|()| f(synthesizer));
+        synthesizer.model.set_speaker_id(previous.unwrap_or(0))?;
+        result
+    }
+}
+
+#[derive(Args, Debug)]
+struct SynthArgs {
+    #[command(flatten)]
+    voice: VoiceArgs,
+    #[command(flatten)]
+    scales: ScaleArgs,
+    /// Text to synthesize
+    #[arg(required_unless_present = "stdin")]
+    text: Option<String>,
+    /// Synthesize every line read from stdin as soon as it is read, into
+    /// numbered output files (`output-1.wav`, ...) or played, keeping the
+    /// models loaded between lines
+    #[arg(long, conflicts_with_all = ["text", "subtitles", "alignment", "visemes"])]
+    stdin: bool,
+    /// Read `--stdin` as JSON lines, e.g. `{"text": "...", "output_file": "a.wav",
+    /// "speaker": 3, "length_scale": 1.1}`
+    #[arg(long, alias = "json_input", requires = "stdin")]
+    json_input: bool,
+    /// Output file, defaults to `output.wav` unless `--play` is given
+    #[arg(short, long)]
+    output: Option<String>,
+    /// Write raw signed 16-bit little-endian PCM to stdout as it is synthesized
+    #[arg(long, alias = "output_raw", conflicts_with = "output")]
+    output_raw: bool,
+    /// Output file format, guessed from the `--output` extension when not given
+    #[arg(long)]
+    output_format: Option<OutputFormat>,
+    /// WAV sample format: `i16`, `i24`, `i32` or `f32`
+    #[arg(long, default_value = "i16")]
+    sample_format: SampleFormat,
+    /// Copy the mono audio into this many channels, e.g. 2 for stereo
+    #[arg(long, default_value_t = 1)]
+    channels: u16,
+    /// Also write subtitles with one cue per sentence, `.srt` or `.vtt`
+    #[arg(long, conflicts_with = "output_raw")]
+    subtitles: Option<String>,
+    /// Also write the id and timing of every phoneme as JSON
+    #[arg(long, conflicts_with_all = ["output_raw", "subtitles"])]
+    alignment: Option<String>,
+    /// Also write a timed stream of Oculus visemes as JSON, for lip sync
+    #[arg(long, conflicts_with_all = ["output_raw", "subtitles"])]
+    visemes: Option<String>,
+    /// Print synthesis progress to stderr
+    #[arg(long)]
+    progress: bool,
+    /// List the G2P pronunciations with a mean token log-probability below
+    /// this threshold, e.g. `-0.5`, as candidates for the user lexicon
+    #[arg(long, allow_hyphen_values = true)]
+    low_confidence: Option<f32>,
+    /// Play the audio through the default output device
+    #[cfg(feature = "playback")]
+    #[arg(long)]
+    play: bool,
+    /// Output device to play on, by name or part of it, see the `devices` command
+    #[cfg(feature = "playback")]
+    #[arg(long, requires = "play")]
+    playback_device: Option<String>,
+    /// Another voice, by name or path, reading the lines written in its
+    /// language while `--model` reads the rest; repeat for more languages
+    #[cfg(feature = "detect-language")]
+    #[arg(long, conflicts_with_all = ["stdin", "output_raw", "subtitles", "alignment", "visemes"])]
+    language_voice: Vec<String>,
+}
+
+#[derive(Args, Debug)]
+struct SynthDirArgs {
+    #[command(flatten)]
+    voice: VoiceArgs,
+    #[command(flatten)]
+    scales: ScaleArgs,
+    /// Directory searched recursively for `.txt` files
+    input: PathBuf,
+    /// Directory the audio is written to, mirroring the layout of the input
+    output: PathBuf,
+    /// Output file format
+    #[arg(long, default_value = "wav")]
+    output_format: OutputFormat,
+    /// Files synthesized at the same time, each job loading its own copy of the models
+    #[arg(short, long, default_value_t = 1)]
+    jobs: usize,
+}
+
+#[derive(Args, Debug)]
+struct SynthCsvArgs {
+    #[command(flatten)]
+    voice: VoiceArgs,
+    #[command(flatten)]
+    scales: ScaleArgs,
+    /// File with a header and `id` and `text` columns, optionally `voice`,
+    /// `speaker` and `rate`; tab-separated when it ends in `.tsv`
+    input: PathBuf,
+    /// Directory the audio files, named by `id`, are written to
+    output: PathBuf,
+    /// Manifest of output paths and durations, defaults to `manifest.csv` in the output directory
+    #[arg(long)]
+    manifest: Option<PathBuf>,
+    /// Output file format
+    #[arg(long, default_value = "wav")]
+    output_format: OutputFormat,
+}
+
+#[cfg(feature = "epub")]
+#[derive(Args, Debug)]
+struct AudiobookArgs {
+    #[command(flatten)]
+    voice: VoiceArgs,
+    #[command(flatten)]
+    scales: ScaleArgs,
+    /// EPUB file to read
+    input: PathBuf,
+    /// Directory the chapters, `playlist.m3u` and `chapters.ffmetadata` are written to
+    output: PathBuf,
+    /// Output file format
+    #[arg(long, default_value = "wav")]
+    output_format: OutputFormat,
+}
+
+#[cfg(feature = "server")]
+#[derive(Args, Debug)]
+struct ServeArgs {
+    #[command(flatten)]
+    voice: VoiceArgs,
+    /// Address to listen on
+    #[arg(long, default_value = "127.0.0.1:5000")]
+    addr: std::net::SocketAddr,
+    /// Extra voice selectable through the request's `voice`, as `NAME=MODEL`
+    /// or the name of a voice in the voices directories; `--model` is
+    /// available under its file name
+    #[arg(long = "add-voice", value_name = "NAME[=MODEL]")]
+    voices: Vec<String>,
+    /// Most voices kept loaded, the least recently used are unloaded first
+    #[arg(long, default_value_t = piper_tts_rust::voice_manager::DEFAULT_MAX_LOADED)]
+    max_loaded_voices: usize,
+    /// Requests synthesized in parallel, each slot loading its own copy of the models
+    #[arg(long, default_value_t = 1)]
+    pool_size: usize,
+}
+
+#[cfg(unix)]
+#[derive(Args, Debug)]
+struct DaemonArgs {
+    #[command(flatten)]
+    voice: VoiceArgs,
+    /// Path of the Unix socket to listen on
+    #[arg(long, default_value = "/tmp/piper-tts-rust.sock")]
+    socket: PathBuf,
+}
+
+#[cfg(feature = "speechd")]
+#[derive(Args, Debug)]
+struct SpeechdArgs {
+    #[command(flatten)]
+    voice: VoiceArgs,
+    /// Output device to play on, by name or part of it, see the `devices` command
+    #[arg(long)]
+    playback_device: Option<String>,
+}
+
+#[cfg(feature = "download")]
+#[derive(Args, Debug)]
+struct DownloadArgs {
+    /// Voice name, e.g. `en_US-norman-medium`
+    voice: String,
+    /// Directory to download the model and config into
+    #[arg(long, default_value = "voices")]
+    dir: String,
+}
+
+#[derive(Args, Debug)]
+struct VoicesArgs {
+    /// List the voices available for download from the upstream repository instead
+    #[cfg(feature = "download")]
+    #[arg(long)]
+    remote: bool,
+    /// Directory searched before `./voices` and `$XDG_DATA_HOME/piper`
+    #[arg(long)]
+    voices_dir: Vec<PathBuf>,
+    /// Only list voices whose language code starts with this, e.g. `en` or `de_DE`
+    #[arg(long)]
+    language: Option<String>,
+}
+
+/// The flags of the upstream `piper` binary, reading text from stdin.
+#[derive(Args, Debug)]
+struct PiperArgs {
+    #[command(flatten)]
+    voice: VoiceArgs,
+    #[command(flatten)]
+    scales: ScaleArgs,
+    /// Write all of stdin into this WAV file
+    #[arg(short = 'f', long = "output_file", alias = "output-file")]
+    output_file: Option<String>,
+    /// Write one WAV file per line into this directory, defaults to the current directory
+    #[arg(short = 'd', long = "output_dir", alias = "output-dir", conflicts_with = "output_file")]
+    output_dir: Option<PathBuf>,
+    /// Stream raw signed 16-bit PCM to stdout, line by line
+    #[arg(long = "output-raw", alias = "output_raw", conflicts_with_all = ["output_file", "output_dir"])]
+    output_raw: bool,
+    /// Read stdin as JSON lines with `text` and optionally `output_file`,
+    /// `speaker` (or `speaker_id`) and scale fields
+    #[arg(long = "json-input", alias = "json_input")]
+    json_input: bool,
+    /// Run on a GPU execution provider when one was enabled at build time
+    #[arg(long = "use-cuda", alias = "use_cuda")]
+    use_cuda: bool,
+    /// Accepted for compatibility, the G2P does not use eSpeak
+    #[arg(long = "espeak_data", alias = "espeak-data", hide = true)]
+    espeak_data: Option<String>,
+    /// Accepted for compatibility, Arabic diacritization is not supported
+    #[arg(long = "tashkeel_model", alias = "tashkeel-model", hide = true)]
+    tashkeel_model: Option<String>,
+    /// Print every input line to stderr before synthesizing it
+    #[arg(long)]
+    debug: bool,
+    /// Don't print the real-time factor of every line
+    #[arg(short, long)]
+    quiet: bool,
+}
+
+#[derive(Args, Debug)]
+struct InspectArgs {
+    /// Path to the `.onnx` model
+    model: String,
+    /// Path to the voice config, defaults to `<model>.json`
+    #[arg(long)]
+    config: Option<String>,
+}
+
+fn synth(args: SynthArgs) -> Result<()> {
+    let mut synthesizer = args.voice.load()?;
+    if args.stdin {
+        return synth_lines(&args, &mut synthesizer);
+    }
+    let text = args.text.as_deref().expect("clap requires the text without `--stdin`");
+    #[cfg(feature = "detect-language")]
+    if !args.language_voice.is_empty() {
+        return synth_routed(&args, synthesizer, text);
+    }
+    synth_text(&args, &synthesizer, text, &args.scales.options(), args.output.clone())
+}
+
+/// Language code of the voice config at `path`, e.g. `de_DE`.
+#[cfg(feature = "detect-language")]
+fn voice_language(path: &str) -> Result<String> {
+    let config: piper_tts_rust::model_handler::Config = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    Ok(config.language.code)
+}
+
+#[cfg(feature = "detect-language")]
+fn synth_routed(args: &SynthArgs, synthesizer: Synthesizer, text: &str) -> Result<()> {
+    let (model, config) = args.voice.voice_files()?;
+    let active = voice_name(&model);
+    let dirs = args.voice.voice_dirs();
+    let mut voices = piper_tts_rust::VoiceManager::new(args.language_voice.len() + 1)
+        .with_session_options(args.voice.session_options());
+    let mut router = piper_tts_rust::LanguageRouter::new()
+        .with_route(&synthesizer.model.config.language.code, &active)?;
+    voices.add_voice(&active, model, Some(config));
+    for voice in &args.language_voice {
+        let (model, config) = piper_tts_rust::voices::resolve(voice, None, &dirs)?;
+        let name = voice_name(&model);
+        router.add_route(&voice_language(&config)?, &name)?;
+        voices.add_voice(name, model, Some(config));
+    }
+
+    let mut synthesizer = synthesizer.with_voices(voices, active);
+    let waveform = synthesizer.synthesize_routed(text, &args.scales.options(), &router)?;
+    write_output(args, &synthesizer, &waveform, args.output.clone())
+}
+
+/// Name a voice model is registered under in a voice manager, its file stem.
+fn voice_name(model: &str) -> String {
+    std::path::Path::new(model).file_stem()
+        .map_or_else(|| model.to_string(), |stem| stem.to_string_lossy().into_owned())
+}
+
+/// `path` with `-<number>` appended to the file stem.
+fn numbered_path(path: &str, number: usize) -> String {
+    let path = std::path::Path::new(path);
+    let stem = path.file_stem().map_or_else(String::new, |stem| stem.to_string_lossy().into_owned());
+    let file_name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, number, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, number),
+    };
+    path.with_file_name(file_name).to_string_lossy().into_owned()
+}
+
+fn synth_lines(args: &SynthArgs, synthesizer: &mut Synthesizer) -> Result<()> {
+    #[cfg(feature = "playback")]
+    let play_only = args.play && args.output.is_none();
+    #[cfg(not(feature = "playback"))]
+    let play_only = false;
+    let format = args.output_format
+        .or_else(|| args.output.as_deref().and_then(OutputFormat::from_path))
+        .unwrap_or_default();
+    let template = args.output.clone().unwrap_or_else(|| format!("output.{}", format.extension()));
+
+    let mut number = 0;
+    for line in std::io::stdin().lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        number += 1;
+        // A bad line shouldn't take the models down with it.
+        if let Err(err) = synth_line(args, synthesizer, &line, number, &template, play_only) {
+            eprintln!("Error: line {}: {}", number, err);
+        }
+    }
+    Ok(())
+}
+
+fn synth_line(args: &SynthArgs, synthesizer: &mut Synthesizer, line: &str, number: usize, template: &str, play_only: bool) -> Result<()> {
+    let record = match args.json_input {
+        true => serde_json::from_str(line)?,
+        false => JsonLine { text: line.to_string(), ..Default::default() },
+    };
+    let output = record.output_file.clone()
+        .or_else(|| (!args.output_raw && !play_only).then(|| numbered_path(template, number)));
+    let options = record.options(&args.scales);
+    record.with_speaker(synthesizer, |synthesizer| synth_text(args, synthesizer, &record.text, &options, output))
+}
+
+fn synth_text(args: &SynthArgs, synthesizer: &Synthesizer, text: &str, options: &SynthesisOptions, output: Option<String>) -> Result<()> {
+    if let Some(threshold) = args.low_confidence {
+        for word in synthesizer.phonemizer.low_confidence_words(text, threshold)? {
+            tracing::warn!("unsure of `{}`, guessed {} ({:.2})", word.word, word.ipa, word.confidence);
+        }
+    }
+
+    if args.output_raw {
+        let mut sink = PcmSink::new(std::io::stdout().lock()).with_channels(args.channels);
+        return synthesizer.synthesize_to_sink(text, options, &mut sink);
+    }
+
+    // Without an output file, audio is played as each sentence is synthesized.
+    #[cfg(feature = "playback")]
+    if args.play && output.is_none() && args.subtitles.is_none() && args.alignment.is_none() && args.visemes.is_none() {
+        let mut sink = piper_tts_rust::playback::PlaybackSink::new(synthesizer.sample_rate(), args.playback_device.as_deref())?;
+        return synthesizer.synthesize_to_sink(text, options, &mut sink);
+    }
+
+    let waveform = if let Some(path) = &args.subtitles {
+        let format = SubtitleFormat::from_path(path).unwrap_or_default();
+        let (waveform, cues) = synthesizer.synthesize_with_cues(text, options)?;
+        std::fs::write(path, format.render(&cues))?;
+        println!("Subtitles created successfully at: {}", path);
+        waveform
+    } else if args.alignment.is_some() || args.visemes.is_some() {
+        let (waveform, alignment) = synthesizer.synthesize_with_alignment(text, options)?;
+        if let Some(path) = &args.alignment {
+            std::fs::write(path, serde_json::to_string_pretty(&alignment)?)?;
+            println!("Alignment created successfully at: {}", path);
+        }
+        if let Some(path) = &args.visemes {
+            std::fs::write(path, serde_json::to_string_pretty(&piper_tts_rust::viseme::visemes(&alignment))?)?;
+            println!("Visemes created successfully at: {}", path);
+        }
+        waveform
+    } else if args.progress {
+        let waveform = synthesizer.synthesize_with_progress(text, options, |progress| {
+            eprint!(
+                "\r{}/{} sentences, {:.1}s of audio",
+                progress.sentences_completed, progress.sentences_total, progress.audio_seconds
+            );
+        })?;
+        eprintln!();
+        waveform
+    } else {
+        synthesizer.synthesize_with(text, options)?
+    };
+    write_output(args, synthesizer, &waveform, output)
+}
+
+/// Plays `waveform` if asked to and writes it to `output`.
+fn write_output(args: &SynthArgs, synthesizer: &Synthesizer, waveform: &[f32], output: Option<String>) -> Result<()> {
+    #[cfg(feature = "playback")]
+    if args.play {
+        piper_tts_rust::playback::play_on(waveform, synthesizer.sample_rate(), args.playback_device.as_deref())?;
+    }
+
+    let format = args.output_format
+        .or_else(|| output.as_deref().and_then(OutputFormat::from_path))
+        .unwrap_or_default();
+    let output = output.unwrap_or_else(|| format!("output.{}", format.extension()));
+    match format {
+        OutputFormat::Wav if args.channels == 1 => synthesizer.model.write_wav_file_with_format(waveform, synthesizer.sample_rate(), &output, args.sample_format),
+        format => {
+            std::fs::write(&output, format.encode_channels(waveform, synthesizer.sample_rate(), args.sample_format, args.channels)?)?;
+            println!("{} file created successfully at: {}", format.extension().to_uppercase(), output);
+            Ok(())
+        }
+    }
+}
+
+/// Record of one line of `piper` input, `None` for blank lines.
+fn piper_record(args: &PiperArgs, line: &str) -> Result<Option<JsonLine>> {
+    if line.trim().is_empty() {
+        return Ok(None);
+    }
+    match args.json_input {
+        true => Ok(Some(serde_json::from_str(line)?)),
+        false => Ok(Some(JsonLine { text: line.to_string(), ..Default::default() })),
+    }
+}
+
+/// Synthesizes a record, reporting the real-time factor like upstream `piper`.
+fn piper_synthesize(args: &PiperArgs, synthesizer: &mut Synthesizer, record: &JsonLine) -> Result<Vec<f32>> {
+    let options = record.options(&args.scales);
+    let (waveform, stats) = record.with_speaker(synthesizer, |synthesizer| synthesizer.synthesize_with_stats(&record.text, &options))?;
+    if !args.quiet {
+        eprintln!(
+            "Real-time factor: {:.3} (infer={:.3} sec, audio={:.3} sec)",
+            stats.real_time_factor(), stats.total().as_secs_f64(), stats.audio.as_secs_f64()
+        );
+    }
+    Ok(waveform)
+}
+
+fn piper_write(synthesizer: &Synthesizer, waveform: &[f32], path: impl AsRef<std::path::Path>) -> Result<()> {
+    Ok(std::fs::write(path, OutputFormat::Wav.encode(waveform, synthesizer.sample_rate(), SampleFormat::I16)?)?)
+}
+
+fn piper(mut args: PiperArgs) -> Result<()> {
+    if args.espeak_data.is_some() {
+        tracing::warn!("--espeak_data is ignored, phonemes come from the neural G2P");
+    }
+    if args.tashkeel_model.is_some() {
+        tracing::warn!("--tashkeel_model is ignored, Arabic diacritization is not supported");
+    }
+    if args.use_cuda {
+        args.voice.device = ExecutionProvider::Auto { priority: ExecutionProvider::default_priority() };
+    }
+    args.voice.sentence_silence.get_or_insert(0.2);
+    let mut synthesizer = args.voice.load()?;
+
+    // Everything for `--output_file`, in input order.
+    let mut joined = Vec::new();
+    for line in std::io::stdin().lock().lines() {
+        let line = line?;
+        if args.debug {
+            eprintln!("Input: {}", line);
+        }
+        let record = match piper_record(&args, &line) {
+            Ok(Some(record)) => record,
+            Ok(None) => continue,
+            Err(err) => {
+                tracing::warn!("skipping line: {}", err);
+                continue;
+            }
+        };
+        if args.output_raw && record.output_file.is_none() {
+            let options = record.options(&args.scales);
+            record.with_speaker(&mut synthesizer, |synthesizer| {
+                let mut sink = PcmSink::new(std::io::stdout().lock());
+                synthesizer.synthesize_to_sink(&record.text, &options, &mut sink)
+            })?;
+            continue;
+        }
+        let waveform = piper_synthesize(&args, &mut synthesizer, &record)?;
+        let path = match (&record.output_file, &args.output_file) {
+            (Some(path), _) => PathBuf::from(path),
+            (None, Some(_)) => {
+                joined.extend(waveform);
+                continue;
+            }
+            (None, None) => {
+                let timestamp = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+                let dir = args.output_dir.clone().unwrap_or_else(|| PathBuf::from("."));
+                dir.join(format!("{}.wav", timestamp.as_nanos()))
+            }
+        };
+        piper_write(&synthesizer, &waveform, &path)?;
+        println!("{}", path.display());
+    }
+
+    if let Some(path) = &args.output_file {
+        piper_write(&synthesizer, &joined, path)?;
+    }
+    Ok(())
+}
+
+/// Every `.txt` file under `dir`, depth first.
+fn text_files(dir: &std::path::Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            text_files(&path, files)?;
+        } else if path.extension().is_some_and(|extension| extension == "txt") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Synthesizes one file of `synth-dir`, returning the output path and seconds of audio.
+fn synth_file(args: &SynthDirArgs, synthesizer: &Synthesizer, file: &std::path::Path) -> Result<(PathBuf, f64)> {
+    let relative = file.strip_prefix(&args.input).unwrap_or(file);
+    let output = args.output.join(relative).with_extension(args.output_format.extension());
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let text = std::fs::read_to_string(file)?;
+    let waveform = synthesizer.synthesize_with(&text, &args.scales.options())?;
+    std::fs::write(&output, args.output_format.encode(&waveform, synthesizer.sample_rate(), SampleFormat::I16)?)?;
+    Ok((output, waveform.len() as f64 / synthesizer.sample_rate() as f64))
+}
+
+fn synth_dir(args: SynthDirArgs) -> Result<()> {
+    let mut files = Vec::new();
+    text_files(&args.input, &mut files)?;
+    files.sort();
+    // Every job needs models of its own, a shared synthesizer runs one inference at a time.
+    let synthesizers = (0..args.jobs.clamp(1, files.len().max(1)))
+        .map(|_| args.voice.load())
+        .collect::<Result<Vec<_>>>()?;
+
+    let start = std::time::Instant::now();
+    let queue = std::sync::Mutex::new(files.iter());
+    let failures = std::sync::Mutex::new(Vec::new());
+    let audio_seconds = std::sync::Mutex::new(0.0);
+    std::thread::scope(|scope| {
+        for synthesizer in &synthesizers {
+            let (args, queue, failures, audio_seconds) = (&args, &queue, &failures, &audio_seconds);
+            scope.spawn(move || loop {
+                let Some(file) = queue.lock().unwrap_or_else(|e| e.into_inner()).next() else {
+                    break;
+                };
+                match synth_file(args, synthesizer, file) {
+                    Ok((output, seconds)) => {
+                        println!("{} -> {} ({:.1}s)", file.display(), output.display(), seconds);
+                        *audio_seconds.lock().unwrap_or_else(|e| e.into_inner()) += seconds;
+                    }
+                    Err(err) => failures.lock().unwrap_or_else(|e| e.into_inner()).push((file, err)),
+                }
+            });
+        }
+    });
+
+    let failures = failures.into_inner().unwrap_or_else(|e| e.into_inner());
+    let audio_seconds = audio_seconds.into_inner().unwrap_or_else(|e| e.into_inner());
+    println!(
+        "{} of {} files synthesized, {:.1}s of audio in {:.1}s",
+        files.len() - failures.len(), files.len(), audio_seconds, start.elapsed().as_secs_f64()
+    );
+    for (file, err) in &failures {
+        eprintln!("Failed: {}: {}", file.display(), err);
+    }
+    match failures.len() {
+        0 => Ok(()),
+        failed => Err(piper_tts_rust::PiperError::TaskFailed(format!("{} of {} files failed", failed, files.len()))),
+    }
+}
+
+/// One row of `synth-csv` input.
+#[derive(Deserialize, Debug)]
+struct CsvRow {
+    id: String,
+    text: String,
+    #[serde(default)]
+    voice: Option<String>,
+    #[serde(default)]
+    speaker: Option<String>,
+    /// Speaking rate, 2.0 twice as fast.
+    #[serde(default)]
+    rate: Option<Rate>,
+}
+
+#[derive(serde::Serialize, Debug)]
+struct ManifestRow<'a> {
+    id: &'a str,
+    path: String,
+    duration: f64,
+}
+
+fn synth_row(args: &SynthCsvArgs, synthesizer: &mut Synthesizer, row: &CsvRow, default_voice: &str) -> Result<(PathBuf, f64)> {
+    if row.id.is_empty() || row.id.contains(['/', '\\']) {
+        return Err(piper_tts_rust::PiperError::InvalidConfig(format!("`{}` can't be used as a file name", row.id)));
+    }
+    synthesizer.set_voice(row.voice.as_deref().unwrap_or(default_voice))?;
+    let voice_length_scale = synthesizer.model.config.inference.length_scale;
+    let record = JsonLine {
+        text: row.text.clone(),
+        speaker: row.speaker.clone().map(Speaker::Name),
+        length_scale: row.rate.map(|rate| args.scales.length_scale.unwrap_or(voice_length_scale) / rate.0),
+        ..Default::default()
+    };
+    let options = record.options(&args.scales);
+    let waveform = record.with_speaker(synthesizer, |synthesizer| synthesizer.synthesize_with(&record.text, &options))?;
+    let output = args.output.join(format!("{}.{}", row.id, args.output_format.extension()));
+    std::fs::write(&output, args.output_format.encode(&waveform, synthesizer.sample_rate(), SampleFormat::I16)?)?;
+    Ok((output, waveform.len() as f64 / synthesizer.sample_rate() as f64))
+}
+
+fn synth_csv(args: SynthCsvArgs) -> Result<()> {
+    let delimiter = match args.input.extension().is_some_and(|extension| extension == "tsv") {
+        true => b'\t',
+        false => b',',
+    };
+    let mut reader = csv::ReaderBuilder::new().delimiter(delimiter).from_path(&args.input)
+        .map_err(|err| piper_tts_rust::PiperError::InvalidConfig(err.to_string()))?;
+    let rows = reader.deserialize::<CsvRow>()
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .map_err(|err| piper_tts_rust::PiperError::InvalidConfig(err.to_string()))?;
+
+    // The voices of all rows are registered up front and loaded as they come up.
+    let (model, config) = args.voice.voice_files()?;
+    let default_voice = voice_name(&model);
+    let dirs = args.voice.voice_dirs();
+    let mut voices = piper_tts_rust::VoiceManager::new(piper_tts_rust::voice_manager::DEFAULT_MAX_LOADED)
+        .with_session_options(args.voice.session_options())
+        .with_voice(&default_voice, model, Some(config));
+    let mut names: Vec<&str> = rows.iter().filter_map(|row| row.voice.as_deref()).collect();
+    names.sort();
+    names.dedup();
+    for name in names.into_iter().filter(|&name| name != default_voice) {
+        let (model, config) = piper_tts_rust::voices::resolve(name, None, &dirs)?;
+        voices.add_voice(name, model, Some(config));
+    }
+    let mut synthesizer = args.voice.load()?.with_voices(voices, default_voice.clone());
+
+    std::fs::create_dir_all(&args.output)?;
+    let manifest = args.manifest.clone().unwrap_or_else(|| args.output.join("manifest.csv"));
+    let mut writer = csv::Writer::from_path(&manifest)
+        .map_err(|err| piper_tts_rust::PiperError::InvalidConfig(err.to_string()))?;
+    let mut failed = 0;
+    for row in &rows {
+        match synth_row(&args, &mut synthesizer, row, &default_voice) {
+            Ok((path, duration)) => {
+                println!("{} -> {} ({:.1}s)", row.id, path.display(), duration);
+                writer.serialize(ManifestRow { id: &row.id, path: path.to_string_lossy().into_owned(), duration })
+                    .map_err(|err| piper_tts_rust::PiperError::InvalidConfig(err.to_string()))?;
+            }
+            Err(err) => {
+                eprintln!("Failed: {}: {}", row.id, err);
+                failed += 1;
+            }
+        }
+    }
+    writer.flush()?;
+    println!("{} of {} rows synthesized, manifest written to {}", rows.len() - failed, rows.len(), manifest.display());
+    match failed {
+        0 => Ok(()),
+        failed => Err(piper_tts_rust::PiperError::TaskFailed(format!("{} of {} rows failed", failed, rows.len()))),
+    }
+}
+
+/// A finished chapter, kept in `audiobook.json` so an interrupted run can resume.
+#[cfg(feature = "epub")]
+#[derive(Deserialize, serde::Serialize, Debug)]
+struct ChapterFile {
+    index: usize,
+    title: String,
+    file: String,
+    duration: f64,
+}
+
+/// `title` cut down to characters that are safe in a file name.
+#[cfg(feature = "epub")]
+fn file_title(title: &str) -> String {
+    let title: String = title.chars()
+        .filter(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_'))
+        .take(60)
+        .collect();
+    match title.trim() {
+        "" => "chapter".to_string(),
+        title => title.to_string(),
+    }
+}
+
+/// Escapes the characters FFmpeg's metadata format treats specially.
+#[cfg(feature = "epub")]
+fn ffmetadata_escape(value: &str) -> String {
+    value.chars().fold(String::new(), |mut escaped, c| {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+        escaped
+    })
+}
+
+#[cfg(feature = "epub")]
+fn audiobook(args: AudiobookArgs) -> Result<()> {
+    let chapters = piper_tts_rust::epub::read_chapters(&args.input)?;
+    std::fs::create_dir_all(&args.output)?;
+    let state_path = args.output.join("audiobook.json");
+    let mut done: Vec<ChapterFile> = std::fs::read_to_string(&state_path).ok()
+        .and_then(|state| serde_json::from_str(&state).ok())
+        .unwrap_or_default();
+    done.retain(|finished| {
+        chapters.get(finished.index).is_some_and(|chapter| chapter.title == finished.title)
+            && args.output.join(&finished.file).exists()
+    });
+
+    let synthesizer = match done.len() < chapters.len() {
+        true => Some(args.voice.load()?),
+        false => None,
+    };
+    for (index, chapter) in chapters.iter().enumerate() {
+        let number = index + 1;
+        if done.iter().any(|finished| finished.index == index) {
+            println!("Chapter {}/{}: {} (already done)", number, chapters.len(), chapter.title);
+            continue;
+        }
+        let synthesizer = synthesizer.as_ref().expect("loaded while chapters remain");
+        println!("Chapter {}/{}: {}", number, chapters.len(), chapter.title);
+        let waveform = synthesizer.synthesize_with_progress(&chapter.text, &args.scales.options(), |progress| {
+            eprint!(
+                "\r  {}/{} sentences, {:.1}s of audio",
+                progress.sentences_completed, progress.sentences_total, progress.audio_seconds
+            );
+        })?;
+        eprintln!();
+
+        // Written under a temporary name, so a crash never leaves a truncated chapter behind.
+        let file = format!("{:03} {}.{}", number, file_title(&chapter.title), args.output_format.extension());
+        let partial = args.output.join(format!("{}.part", file));
+        std::fs::write(&partial, args.output_format.encode(&waveform, synthesizer.sample_rate(), SampleFormat::I16)?)?;
+        std::fs::rename(&partial, args.output.join(&file))?;
+        let duration = waveform.len() as f64 / synthesizer.sample_rate() as f64;
+        done.push(ChapterFile { index, title: chapter.title.clone(), file, duration });
+        std::fs::write(&state_path, serde_json::to_string_pretty(&done)?)?;
+    }
+    done.sort_by_key(|finished| finished.index);
+
+    let mut playlist = String::from("#EXTM3U\n");
+    let mut metadata = String::from(";FFMETADATA1\n");
+    let mut start_ms = 0;
+    for finished in &done {
+        playlist.push_str(&format!("#EXTINF:{},{}\n{}\n", finished.duration.round(), finished.title, finished.file));
+        let end_ms = start_ms + (finished.duration * 1000.0).round() as u64;
+        metadata.push_str(&format!(
+            "[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            start_ms, end_ms, ffmetadata_escape(&finished.title)
+        ));
+        start_ms = end_ms;
+    }
+    std::fs::write(args.output.join("playlist.m3u"), playlist)?;
+    std::fs::write(args.output.join("chapters.ffmetadata"), metadata)?;
+    println!("Audiobook written to {}", args.output.display());
+    Ok(())
+}
+
+#[cfg(feature = "server")]
+fn serve(args: ServeArgs) -> Result<()> {
+    let synthesizers = (0..args.pool_size.max(1))
+        .map(|_| serve_synthesizer(&args))
+        .collect::<Result<Vec<_>>>()?;
+    let synthesizer = piper_tts_rust::AsyncSynthesizer::pooled(synthesizers)?;
+    tokio::runtime::Runtime::new()?
+        .block_on(piper_tts_rust::server::serve_pooled(synthesizer, args.addr))
+}
+
+#[cfg(feature = "server")]
+fn serve_synthesizer(args: &ServeArgs) -> Result<Synthesizer> {
+    let mut synthesizer = args.voice.load()?;
+    if !args.voices.is_empty() {
+        let dirs = args.voice.voice_dirs();
+        let (model, config) = args.voice.voice_files()?;
+        let default_name = voice_name(&model);
+        let mut voices = piper_tts_rust::VoiceManager::new(args.max_loaded_voices)
+            .with_session_options(args.voice.session_options())
+            .with_voice(&default_name, model, Some(config));
+        for voice in &args.voices {
+            let (name, model) = voice.split_once('=').unwrap_or((voice, voice));
+            let (model, config) = piper_tts_rust::voices::resolve(model, None, &dirs)?;
+            voices.add_voice(name, model, Some(config));
+        }
+        synthesizer = synthesizer.with_voices(voices, default_name);
+    }
+    Ok(synthesizer)
+}
+
+#[cfg(feature = "playback")]
+fn devices() -> Result<()> {
+    for name in piper_tts_rust::playback::output_devices()? {
+        println!("{}", name);
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn daemon(args: DaemonArgs) -> Result<()> {
+    piper_tts_rust::daemon::serve(args.voice.load()?, &args.socket)
+}
+
+#[cfg(feature = "speechd")]
+fn speechd(args: SpeechdArgs) -> Result<()> {
+    let voice = match &args.voice.model {
+        Some(_) => {
+            let (model, _) = args.voice.voice_files()?;
+            voice_name(&model)
+        }
+        None => "piper".to_string(),
+    };
+    piper_tts_rust::speechd::run_module(&voice, args.playback_device.clone(), || args.voice.load())
+}
+
+#[cfg(feature = "download")]
+fn download(args: DownloadArgs) -> Result<()> {
+    let model = piper_tts_rust::download::download_voice(&args.voice, &args.dir)?;
+    println!("Voice downloaded successfully at: {}", model.display());
+    Ok(())
+}
+
+/// `extra` followed by the default voice directories.
+fn voices_dirs(extra: &[PathBuf]) -> Vec<PathBuf> {
+    extra.iter().cloned().chain(piper_tts_rust::voices::default_dirs()).collect()
+}
+
+fn voices(args: VoicesArgs) -> Result<()> {
+    #[cfg(feature = "download")]
+    if args.remote {
+        return remote_voices(&args);
+    }
+    for voice in piper_tts_rust::voices::discover(&voices_dirs(&args.voices_dir)) {
+        let language = std::fs::read_to_string(&voice.config).ok()
+            .and_then(|config| serde_json::from_str::<serde_json::Value>(&config).ok())
+            .and_then(|config| config["language"]["code"].as_str().map(str::to_string))
+            .unwrap_or_default();
+        if args.language.as_ref().is_some_and(|prefix| !language.starts_with(prefix.as_str())) {
+            continue;
+        }
+        println!("{:<40} {:<8} {}", voice.name, language, voice.model.display());
+    }
+    Ok(())
+}
+
+#[cfg(feature = "download")]
+fn remote_voices(args: &VoicesArgs) -> Result<()> {
+    let catalog = piper_tts_rust::download::fetch_catalog()?;
+    let mut voices: Vec<_> = catalog.values()
+        .filter(|voice| args.language.as_ref().is_none_or(|language| voice.language.code.starts_with(language.as_str())))
+        .collect();
+    voices.sort_by(|a, b| a.key.cmp(&b.key));
+    for voice in voices {
+        let speakers = match voice.num_speakers {
+            1 => "1 speaker".to_string(),
+            n => format!("{} speakers", n),
+        };
+        println!(
+            "{:<40} {} ({}), {}, {}",
+            voice.key, voice.language.name_english, voice.language.country_english, voice.quality, speakers
+        );
+    }
+    Ok(())
+}
+
+fn inspect(args: InspectArgs) -> Result<()> {
+    let info = piper_tts_rust::inspect::inspect_model(&args.model)?;
+    println!("Model: {}", args.model);
+    println!("  Producer: {}", info.producer);
+    for (domain, version) in &info.opsets {
+        println!("  Opset: {} {}", if domain.is_empty() { "ai.onnx" } else { domain }, version);
+    }
+    for input in &info.inputs {
+        println!("  Input: {} {}", input.name, input.value_type);
+    }
+    for output in &info.outputs {
+        println!("  Output: {} {}", output.name, output.value_type);
+    }
+    if !info.quantized_ops.is_empty() {
+        println!("  Quantized: {}", info.quantized_ops.join(", "));
+    }
+
+    let config_path = args.config.unwrap_or_else(|| {
+        piper_tts_rust::voices::config_path(std::path::Path::new(&args.model)).to_string_lossy().into_owned()
+    });
+    println!("Config: {}", config_path);
+    let config: piper_tts_rust::model_handler::Config = match std::fs::read_to_string(&config_path)
+        .map_err(piper_tts_rust::PiperError::from)
+        .and_then(|config| Ok(serde_json::from_str(&config)?))
+    {
+        Ok(config) => config,
+        Err(err) => {
+            println!("  Error: {}", err);
+            return Ok(());
+        }
+    };
+    println!("  Sample rate: {} Hz, quality: {}", config.audio.sample_rate, config.audio.quality);
+    println!("  Language: {} ({}, {})", config.language.code, config.language.name_english, config.language.country_english);
+    println!("  Speakers: {}", config.num_speakers);
+    let mut speakers: Vec<_> = config.speaker_id_map.iter().collect();
+    speakers.sort_by_key(|(_, id)| **id);
+    for (name, id) in speakers {
+        println!("    {}: {}", id, name);
+    }
+    println!("  Phoneme inventory: {} symbols", config.phoneme_id_map.len());
+    println!(
+        "  Inference: noise_scale {}, length_scale {}, noise_w {}",
+        config.inference.noise_scale, config.inference.length_scale, config.inference.noise_w
+    );
+    Ok(())
+}
+
+/// `$XDG_CONFIG_HOME/piper-tts-rust/config.toml` (`~/.config/...` when unset).
+fn default_config_file() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("piper-tts-rust").join("config.toml"))
+}
+
+/// `--config-file` or `PIPER_CONFIG_FILE`, read before clap parses the
+/// command line since the file changes how it is parsed.
+fn config_file_arg(argv: &[std::ffi::OsString]) -> Option<PathBuf> {
+    let mut args = argv.iter();
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("--") => break,
+            Some("--config-file") => return args.next().map(PathBuf::from),
+            Some(arg) => {
+                if let Some(path) = arg.strip_prefix("--config-file=") {
+                    return Some(PathBuf::from(path));
+                }
+            }
+            None => {}
+        }
+    }
+    std::env::var_os("PIPER_CONFIG_FILE").filter(|path| !path.is_empty()).map(PathBuf::from)
+}
+
+/// Lets a `PIPER_<FLAG>` environment variable such as `PIPER_DEVICE=auto`
+/// set any flag, with `PIPER_VOICE` and `PIPER_DATA_DIR` for `--model` and
+/// `--voices-dir`. They override the config file and the command line
+/// overrides them.
+fn with_env_vars(command: clap::Command) -> clap::Command {
+    command.mut_subcommands(|subcommand| {
+        subcommand.mut_args(|arg| {
+            let name = match arg.get_long() {
+                None => return arg,
+                Some("model") => "PIPER_VOICE".to_string(),
+                Some("voices-dir") => "PIPER_DATA_DIR".to_string(),
+                Some(long) => format!("PIPER_{}", long.to_ascii_uppercase().replace('-', "_")),
+            };
+            arg.env(name)
+        })
+    })
+}
+
+/// The settings of `--config-file` or of the default config file, which
+/// may be missing.
+fn load_config_file(argv: &[std::ffi::OsString]) -> Result<toml::Table> {
+    let (path, explicit) = match config_file_arg(argv) {
+        Some(path) => (path, true),
+        None => match default_config_file() {
+            Some(path) => (path, false),
+            None => return Ok(toml::Table::new()),
+        },
+    };
+    let data = match std::fs::read_to_string(&path) {
+        Ok(data) => data,
+        Err(e) if !explicit && e.kind() == std::io::ErrorKind::NotFound => return Ok(toml::Table::new()),
+        Err(e) => return Err(piper_tts_rust::PiperError::AssetLoad {
+            what: "config file",
+            path: path.display().to_string(),
+            reason: e.to_string(),
+        }),
+    };
+    toml::from_str(&data)
+        .map_err(|e| piper_tts_rust::PiperError::InvalidConfig(format!("config file `{}`: {}", path.display(), e)))
+}
+
+/// Makes every setting, keyed by the long name of a flag (`voices-dir` or
+/// `voices_dir`), the default of that flag in each command taking it, so
+/// the command line still overrides it.
+fn with_config_defaults(mut command: clap::Command, config: &toml::Table) -> Result<clap::Command> {
+    for (key, value) in config {
+        let setting = |value: &toml::Value| match value {
+            toml::Value::String(value) => Ok(value.clone()),
+            toml::Value::Integer(_) | toml::Value::Float(_) | toml::Value::Boolean(_) => Ok(value.to_string()),
+            _ => Err(piper_tts_rust::PiperError::InvalidConfig(format!("config setting `{}` is not a string, number or boolean", key))),
+        };
+        let values = match value {
+            toml::Value::Array(items) => items.iter().map(setting).collect::<Result<Vec<_>>>()?,
+            value => vec![setting(value)?],
+        };
+
+        let long = key.replace('_', "-");
+        let mut known = false;
+        command = command.mut_subcommands(|subcommand| {
+            let id = subcommand.get_arguments()
+                .find(|arg| {
+                    arg.get_long() == Some(long.as_str())
+                        || arg.get_all_aliases().is_some_and(|aliases| aliases.contains(&long.as_str()))
+                })
+                .map(|arg| arg.get_id().clone());
+            match id {
+                Some(id) => {
+                    known = true;
+                    subcommand.mut_arg(id, |arg| arg.required(false).default_values(values.clone()))
+                }
+                None => subcommand,
+            }
+        });
+        if !known {
+            return Err(piper_tts_rust::PiperError::InvalidConfig(format!("unknown config setting `{}`", key)));
+        }
+    }
+    Ok(command)
+}
+
+fn main() {
+    // Invoked like upstream `piper`, with flags and no subcommand.
+    let mut argv: Vec<std::ffi::OsString> = std::env::args_os().collect();
+    if argv.get(1).and_then(|arg| arg.to_str())
+        .is_some_and(|arg| arg.starts_with('-') && !["-h", "--help", "-V", "--version"].contains(&arg))
+    {
+        argv.insert(1, "piper".into());
+    }
+    let cli = match load_config_file(&argv).and_then(|config| with_config_defaults(with_env_vars(Cli::command()), &config)) {
+        Ok(command) => Cli::from_arg_matches(&command.get_matches_from(argv)).unwrap_or_else(|err| err.exit()),
+        Err(err) => {
+            eprintln!("Error: {}", err);
+            std::process::exit(1);
+        }
+    };
+
+    // Diagnostics go to stderr, `RUST_LOG=piper_tts_rust=debug` adds the
+    // timings of the G2P, inference and encoding spans.
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("warn,piper_tts_rust=info")))
+        .with_span_events(FmtSpan::CLOSE)
+        .with_target(false)
+        .without_time()
+        .init();
+
+    let result = match cli.command {
+        Command::Synth(args) => synth(*args),
+        Command::SynthDir(args) => synth_dir(*args),
+        Command::SynthCsv(args) => synth_csv(*args),
+        #[cfg(feature = "epub")]
+        Command::Audiobook(args) => audiobook(*args),
+        #[cfg(feature = "server")]
+        Command::Serve(args) => serve(*args),
+        #[cfg(unix)]
+        Command::Daemon(args) => daemon(*args),
+        #[cfg(feature = "playback")]
+        Command::Devices => devices(),
+        #[cfg(feature = "speechd")]
+        Command::Speechd(args) => speechd(*args),
+        #[cfg(feature = "download")]
+        Command::Download(args) => download(args),
+        Command::Voices(args) => voices(args),
+        Command::Inspect(args) => inspect(args),
+        Command::Piper(args) => piper(*args),
+    };
+
+    if let Err(err) = result {
+        eprintln!("Error: {}", err);
+        std::process::exit(1);
+    }
+}
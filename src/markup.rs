@@ -0,0 +1,407 @@
+use std::{borrow::Cow, str::FromStr, sync::LazyLock};
+
+use regex::Regex;
+
+use crate::error::{PiperError, Result};
+
+/// Markup of the input text, flattened to plain sentences before it is normalized.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Markup {
+    /// Read as written.
+    #[default]
+    Plain,
+    Markdown,
+    Html,
+}
+
+impl FromStr for Markup {
+    type Err = PiperError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "plain" | "text" | "txt" => Ok(Markup::Plain),
+            "markdown" | "md" => Ok(Markup::Markdown),
+            "html" | "htm" | "xhtml" => Ok(Markup::Html),
+            _ => Err(PiperError::InvalidConfig(format!("unknown markup `{}`", s))),
+        }
+    }
+}
+
+/// What becomes of code blocks (fenced Markdown blocks, HTML `<pre>`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CodeBlocks {
+    #[default]
+    Skip,
+    /// Replaced with a spoken "Code block."
+    Mention,
+    /// Read out line by line.
+    Read,
+}
+
+impl FromStr for CodeBlocks {
+    type Err = PiperError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "skip" => Ok(CodeBlocks::Skip),
+            "mention" => Ok(CodeBlocks::Mention),
+            "read" => Ok(CodeBlocks::Read),
+            _ => Err(PiperError::InvalidConfig(format!("unknown code block handling `{}`", s))),
+        }
+    }
+}
+
+const CODE_MENTION: &str = "Code block.";
+
+impl Markup {
+    /// Flattens `text` into plain text, one block per line. Headings, list
+    /// items and table rows become sentences of their own, links are reduced
+    /// to their text and images are dropped.
+    pub fn strip(self, text: &str, code_blocks: CodeBlocks) -> Cow<'_, str> {
+        match self {
+            Markup::Plain => Cow::Borrowed(text),
+            Markup::Markdown => Cow::Owned(markdown_text(text, code_blocks)),
+            Markup::Html => Cow::Owned(html_text(text, code_blocks).0),
+        }
+    }
+}
+
+fn new_line(text: &mut String) {
+    if !text.is_empty() && !text.ends_with('\n') {
+        text.push('\n');
+    }
+}
+
+/// Ends the text so far with a full stop, unless it already has punctuation.
+/// The comma after the last table cell gives way to the full stop.
+fn end_sentence(text: &mut String) {
+    let end = text.trim_end_matches(|c: char| c == ',' || c.is_whitespace()).len();
+    if text[..end].chars().last().is_some_and(char::is_alphanumeric) {
+        text.truncate(end);
+        text.push('.');
+    }
+}
+
+/// Whitespace collapsed within lines and empty lines removed.
+fn tidy(text: &str) -> String {
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .map(|line| line.trim_end_matches(',').to_string())
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Replaces the character references and the common named entities.
+pub(crate) fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';').filter(|&end| end <= 10) else {
+            decoded.push('&');
+            rest = &rest[1..];
+            continue;
+        };
+        let entity = &rest[1..end];
+        let character = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" | "rsquo" => Some('\''),
+            "nbsp" => Some(' '),
+            "mdash" => Some('—'),
+            "ndash" => Some('–'),
+            "hellip" => Some('…'),
+            _ => entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X"))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|decimal| decimal.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match character {
+            Some(character) => {
+                decoded.push(character);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+const BLOCK_TAGS: &[&str] = &[
+    "p", "div", "li", "tr", "h1", "h2", "h3", "h4", "h5", "h6", "blockquote", "section", "article", "hr", "dt",
+    "dd", "caption", "table", "ul", "ol", "pre",
+];
+
+/// `rest` after the closing tag of `name`, or nothing when it is never closed.
+fn skip_element<'a>(rest: &'a str, name: &str) -> &'a str {
+    let close = format!("</{}", name);
+    rest.to_ascii_lowercase().find(&close)
+        .and_then(|index| rest[index..].find('>').map(|end| &rest[index + end + 1..]))
+        .unwrap_or("")
+}
+
+/// Text of an HTML document and its first `h1` to `h3` heading, skipping
+/// `head`, `script` and `style`. Parsed by hand, as pages and books are not
+/// always well-formed XML.
+pub(crate) fn html_text(html: &str, code_blocks: CodeBlocks) -> (String, Option<String>) {
+    let mut text = String::new();
+    let mut heading: Option<String> = None;
+    let mut in_heading = false;
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        // Line breaks in the source are just whitespace, blocks come from the tags.
+        let chunk = decode_entities(&rest[..start]).replace(['\r', '\n'], " ");
+        text.push_str(&chunk);
+        if in_heading {
+            heading.get_or_insert_with(String::new).push_str(&chunk);
+        }
+        rest = &rest[start..];
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            rest = comment.find("-->").map_or("", |end| &comment[end + 3..]);
+            continue;
+        }
+        let Some(end) = rest.find('>') else {
+            rest = "";
+            break;
+        };
+        let tag = &rest[1..end];
+        rest = &rest[end + 1..];
+        let closing = tag.starts_with('/');
+        let name = tag.trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        let opening = !closing && !tag.ends_with('/');
+        if opening && matches!(name.as_str(), "head" | "script" | "style" | "template") {
+            rest = skip_element(rest, &name);
+            continue;
+        }
+        if opening && name == "pre" && code_blocks != CodeBlocks::Read {
+            rest = skip_element(rest, &name);
+            end_sentence(&mut text);
+            new_line(&mut text);
+            if code_blocks == CodeBlocks::Mention {
+                text.push_str(CODE_MENTION);
+                text.push('\n');
+            }
+            continue;
+        }
+        if matches!(name.as_str(), "h1" | "h2" | "h3") {
+            in_heading = !closing && heading.is_none();
+        }
+        if closing && matches!(name.as_str(), "td" | "th") {
+            text.push_str(", ");
+        } else if name == "br" {
+            text.push('\n');
+        } else if BLOCK_TAGS.contains(&name.as_str()) {
+            end_sentence(&mut text);
+            text.push('\n');
+        }
+    }
+    text.push_str(&decode_entities(rest).replace(['\r', '\n'], " "));
+    end_sentence(&mut text);
+
+    let heading = heading.map(|heading| heading.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|heading| !heading.is_empty());
+    (tidy(&text), heading)
+}
+
+static FENCE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s{0,3}(```|~~~)").unwrap());
+static HEADING: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s{0,3}#{1,6}\s+(.*?)(\s+#+)?\s*$").unwrap());
+static RULE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s{0,3}(=+|-+|(\*\s*){3,}|(_\s*){3,})\s*$").unwrap());
+static BLOCKQUOTE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s{0,3}(>\s?)+").unwrap());
+static LIST_ITEM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*([-*+]|\d{1,9}[.)])\s+(\[[ xX]\]\s+)?").unwrap());
+static LINK_DEFINITION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s{0,3}\[[^\]]+\]:\s*\S+").unwrap());
+static TABLE_SEPARATOR: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*\|?(\s*:?-+:?\s*\|)+\s*(:?-+:?\s*)?$").unwrap());
+
+/// Inline markup and its replacement, applied in order.
+static INLINE: LazyLock<Vec<(Regex, &'static str)>> = LazyLock::new(|| {
+    [
+        (r"!\[[^\]]*\]\([^)]*\)", ""),
+        (r"\[([^\]]+)\]\([^)]*\)", "$1"),
+        (r"\[([^\]]+)\]\[[^\]]*\]", "$1"),
+        (r"<(https?|mailto):[^>]+>", ""),
+        (r"`+([^`]+?)`+", "$1"),
+        (r"\*\*(\S(?:.*?\S)?)\*\*", "$1"),
+        (r"__(\S(?:.*?\S)?)__", "$1"),
+        (r"\*(\S(?:.*?\S)?)\*", "$1"),
+        (r"\b_(\S(?:.*?\S)?)_\b", "$1"),
+        (r"~~(.+?)~~", "$1"),
+        (r"</?[A-Za-z][^>]*>", ""),
+    ]
+    .into_iter()
+    .map(|(pattern, replacement)| (Regex::new(pattern).unwrap(), replacement))
+    .collect()
+});
+
+static ESCAPE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\\([\\`*_{}\[\]()#+\-.!|>~])").unwrap());
+
+/// Private use code points escaped characters hide behind while the inline markup is removed.
+const ESCAPED: u32 = 0xE000;
+
+fn markdown_inline(text: &str) -> String {
+    let escaped = ESCAPE.replace_all(text, |captures: &regex::Captures| {
+        captures[1].chars().filter_map(|c| char::from_u32(ESCAPED + c as u32)).collect::<String>()
+    });
+    let text = INLINE.iter().fold(escaped.into_owned(), |text, (pattern, replacement)| {
+        pattern.replace_all(&text, *replacement).into_owned()
+    });
+    let text: String = text.chars()
+        .map(|c| match c as u32 {
+            code @ ESCAPED..0xE080 => char::from_u32(code - ESCAPED).unwrap_or(c),
+            _ => c,
+        })
+        .collect();
+    decode_entities(&text)
+}
+
+fn markdown_text(markdown: &str, code_blocks: CodeBlocks) -> String {
+    let mut text = String::new();
+    let mut fenced = false;
+    for line in markdown.lines() {
+        if FENCE.is_match(line) {
+            fenced = !fenced;
+            end_sentence(&mut text);
+            new_line(&mut text);
+            if fenced && code_blocks == CodeBlocks::Mention {
+                text.push_str(CODE_MENTION);
+                text.push('\n');
+            }
+            continue;
+        }
+        if fenced {
+            if code_blocks == CodeBlocks::Read {
+                text.push_str(line.trim());
+                text.push('\n');
+            }
+            continue;
+        }
+        if line.trim().is_empty() || RULE.is_match(line) || LINK_DEFINITION.is_match(line) || TABLE_SEPARATOR.is_match(line) {
+            end_sentence(&mut text);
+            new_line(&mut text);
+            continue;
+        }
+
+        let line = BLOCKQUOTE.replace(line, "");
+        let block = if let Some(captures) = HEADING.captures(&line) {
+            Some(markdown_inline(&captures[1]))
+        } else if let Some(marker) = LIST_ITEM.find(&line) {
+            Some(markdown_inline(&line[marker.end()..]))
+        } else if line.trim_start().starts_with('|') {
+            let cells: Vec<String> = line.trim().trim_matches('|').split('|')
+                .map(|cell| markdown_inline(cell.trim()))
+                .filter(|cell| !cell.is_empty())
+                .collect();
+            Some(cells.join(", "))
+        } else {
+            None
+        };
+        match block {
+            Some(block) => {
+                end_sentence(&mut text);
+                new_line(&mut text);
+                text.push_str(&block);
+                end_sentence(&mut text);
+                text.push('\n');
+            }
+            // Paragraph text, soft-wrapped lines run on.
+            None => {
+                if !text.is_empty() && !text.ends_with('\n') {
+                    text.push(' ');
+                }
+                text.push_str(&markdown_inline(line.trim()));
+            }
+        }
+    }
+    end_sentence(&mut text);
+    tidy(&text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn markdown(text: &str) -> String {
+        Markup::Markdown.strip(text, CodeBlocks::Skip).into_owned()
+    }
+
+    fn html(text: &str) -> String {
+        Markup::Html.strip(text, CodeBlocks::Skip).into_owned()
+    }
+
+    #[test]
+    fn markdown_headings_and_lists() {
+        assert_eq!(markdown("# Title\nSome text\n## Next ##"), "Title.\nSome text.\nNext.");
+        assert_eq!(markdown("Shopping:\n- eggs\n* milk\n1. bread\n- [x] done"), "Shopping:\neggs.\nmilk.\nbread.\ndone.");
+    }
+
+    #[test]
+    fn markdown_paragraphs_run_on() {
+        assert_eq!(markdown("A soft\nwrapped line\n\nNew paragraph"), "A soft wrapped line.\nNew paragraph.");
+        assert_eq!(markdown("> quoted\n\n---\n\nafter"), "quoted.\nafter.");
+    }
+
+    #[test]
+    fn markdown_links_images_and_inline_code() {
+        assert_eq!(markdown("See [the docs](https://example.com) ![logo](logo.png)now"), "See the docs now.");
+        assert_eq!(markdown("Run `cargo test` with **care** and _style_"), "Run cargo test with care and style.");
+        assert_eq!(markdown("A \\*literal\\* star &amp; more"), "A *literal* star & more.");
+        assert_eq!(markdown("[ref][1]\n\n[1]: https://example.com"), "ref.");
+    }
+
+    #[test]
+    fn markdown_tables() {
+        assert_eq!(markdown("| a | b |\n|---|---|\n| 1 | 2 |"), "a, b.\n1, 2.");
+    }
+
+    #[test]
+    fn markdown_code_blocks() {
+        let text = "Before\n```rust\nlet x = 1;\n```\nAfter";
+        assert_eq!(Markup::Markdown.strip(text, CodeBlocks::Skip), "Before.\nAfter.");
+        assert_eq!(Markup::Markdown.strip(text, CodeBlocks::Mention), "Before.\nCode block.\nAfter.");
+        assert_eq!(Markup::Markdown.strip(text, CodeBlocks::Read), "Before.\nlet x = 1;\nAfter.");
+    }
+
+    #[test]
+    fn html_blocks_and_entities() {
+        assert_eq!(html("<h1>Title</h1><p>One &amp; two&nbsp;&#x41;&#66;</p><p>Line<br>break</p>"), "Title.\nOne & two AB.\nLine\nbreak.");
+        assert_eq!(html("<table><tr><td>a</td><td>b</td></tr></table>"), "a, b.");
+        assert_eq!(html("AT&T &bogus; &"), "AT&T &bogus; &");
+    }
+
+    #[test]
+    fn html_skips_scripts_styles_and_comments() {
+        let page = "<html><head><title>T</title></head><body><script>var x = '<p>';</script><STYLE>p {}</STYLE><!-- note --><p>Text</p></body></html>";
+        assert_eq!(html(page), "Text.");
+    }
+
+    #[test]
+    fn html_code_blocks() {
+        let page = "<p>Before</p><pre>let x = 1;</pre><p>After</p>";
+        assert_eq!(Markup::Html.strip(page, CodeBlocks::Skip), "Before.\nAfter.");
+        assert_eq!(Markup::Html.strip(page, CodeBlocks::Mention), "Before.\nCode block.\nAfter.");
+        assert_eq!(Markup::Html.strip(page, CodeBlocks::Read), "Before.\nlet x = 1;\nAfter.");
+    }
+
+    #[test]
+    fn unclosed_html() {
+        assert_eq!(html("<p>Text<script>never closed"), "Text.");
+        assert_eq!(html("<p>Text <b"), "Text.");
+        assert_eq!(html("<p>Text<!-- open comment"), "Text.");
+    }
+
+    #[test]
+    fn first_heading() {
+        let (_, heading) = html_text("<h2>Chapter  One</h2><h1>Later</h1><p>Text</p>", CodeBlocks::Skip);
+        assert_eq!(heading.as_deref(), Some("Chapter One"));
+    }
+}
@@ -1,9 +1,68 @@
 use ndarray::{Array1, Array2};
-use ort::{
-    session::{builder::GraphOptimizationLevel, Session}, tensor::Shape, Error
-};
+use ort::tensor::Shape;
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, io::Write};
+use std::{collections::HashMap, path::Path, sync::Mutex};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::{
+    asset::Asset,
+    audio::{self, AudioSink, SampleFormat},
+    backend::{self, InferenceBackend, VoiceInput},
+    error::{PiperError, Result},
+    inspect::quantized_ops,
+    phonemizer::PhonemizerConfig,
+    session::{ExecutionProvider, SessionOptions},
+    signature::Signature,
+};
+
+pub use crate::backend::InferenceOutput;
+
+pub type ModelInput = (Array2<i64>, Array1<i64>, Array1<f32>);
+
+/// Called with the output shape and samples of each inference chunk, and the
+/// phoneme durations when the model reports them.
+type ChunkCallback<'a> = dyn FnMut(&[i64], &[f32], Option<Vec<PhonemeDuration>>) -> Result<()> + 'a;
+
+/// Samples the decoder generates per predicted duration frame.
+const HOP_LENGTH: f32 = 256.0;
+
+/// Longest phoneme id sequence run in one inference, longer inputs are split.
+pub const DEFAULT_MAX_PHONEME_IDS: usize = 2048;
+
+/// Most utterances run together in one batched inference.
+pub const DEFAULT_MAX_BATCH_SIZE: usize = 8;
+
+/// Largest deviation from the final sample still treated as padded tail.
+const PADDING_TOLERANCE: f32 = 1e-3;
+
+/// The decoder turns the zeroed latent frames of a padded batch item into a
+/// near constant tail, which is cut after the last sample that differs from it.
+///
+/// Only a guess for voices without a `durations` output: a quiet ending within
+/// the tolerance of the final sample is cut along with the padding, and a
+/// signal that is constant throughout is returned as is.
+fn trim_padded_tail(samples: &[f32]) -> &[f32] {
+    let Some(&tail) = samples.last() else {
+        return samples;
+    };
+    match samples.iter().rposition(|sample| (sample - tail).abs() > PADDING_TOLERANCE) {
+        Some(i) => &samples[..i + 1],
+        None => samples,
+    }
+}
+
+/// Samples spoken for `frames` predicted duration frames.
+fn frames_to_samples(frames: f32) -> usize {
+    (frames.max(0.0) * HOP_LENGTH).round() as usize
+}
+
+/// How long one phoneme id is spoken, reported by voices exported with an
+/// extra `durations` output holding the predicted frames per input id.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct PhonemeDuration {
+    pub id: i64,
+    pub samples: usize,
+}
 
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Audio {
@@ -11,7 +70,7 @@ pub struct Audio {
     pub quality: String,
 }
 
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
 pub struct Inference {
     pub noise_scale: f32,
     pub length_scale: f32,
@@ -28,52 +87,336 @@ pub struct Language {
     pub country_english: String,
 }
 
+/// The espeak-ng settings of upstream Piper configs.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Espeak {
+    pub voice: String,
+}
+
+fn default_num_speakers() -> u32 {
+    1
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 pub struct Config {
     pub audio: Audio,
     pub inference: Inference,
     pub phoneme_id_map: HashMap<String, Vec<i64>>,
     pub language: Language,
+    #[serde(default = "default_num_speakers")]
+    pub num_speakers: u32,
+    #[serde(default)]
+    pub speaker_id_map: HashMap<String, i64>,
+    #[serde(default)]
+    pub espeak: Option<Espeak>,
+    /// Front end of this voice, when it isn't the synthesizer's own.
+    #[serde(default)]
+    pub phonemizer: Option<PhonemizerConfig>,
 }
 
 pub struct Model  {
     pub config: Config,
-    model: Session,
+    /// `phoneme_id_map` with NFC normalized keys, built once at load time.
+    phoneme_map: HashMap<String, Vec<i64>>,
+    max_symbol_len: usize,
+    backend: Mutex<Box<dyn InferenceBackend>>,
+    signature: Signature,
+    quantized: bool,
+    speaker_id: Option<i64>,
+    strict: bool,
+    max_phoneme_ids: usize,
+    max_batch_size: usize,
 }
 
 impl Model {
-    pub fn new(model_path: &str, config_path: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let config: Config = serde_json::from_str(&std::fs::read_to_string(config_path)?)?;
-        
-        let model = Session::builder()?
-            .with_optimization_level(GraphOptimizationLevel::Level3)?
-            .with_intra_threads(4)?
-            .commit_from_file(model_path)?;
+    pub fn new(model_path: &str, config_path: &str) -> Result<Self> {
+        Self::with_options(model_path, config_path, &SessionOptions::default())
+    }
+
+    pub fn with_options(
+        model_path: &str,
+        config_path: &str,
+        session_options: &SessionOptions,
+    ) -> Result<Self> {
+        let config = std::fs::read_to_string(config_path)?;
+        let mut model = Self::from_asset(&Asset::Path(model_path.to_string()), &config, session_options)?;
+        if let Some(phonemizer) = &mut model.config.phonemizer
+            && let Some(dir) = Path::new(config_path).parent()
+        {
+            phonemizer.resolve_paths(dir);
+        }
+        Ok(model)
+    }
+
+    /// Loads a voice from the contents of its `.onnx` model and `.onnx.json`
+    /// config, without touching the filesystem.
+    pub fn from_bytes(model: &[u8], config: &str) -> Result<Self> {
+        Self::from_bytes_with_options(model, config, &SessionOptions::default())
+    }
+
+    pub fn from_bytes_with_options(model: &[u8], config: &str, session_options: &SessionOptions) -> Result<Self> {
+        Self::from_asset(&Asset::Bytes(model.into()), config, session_options)
+    }
+
+    /// The voice compiled into the binary with the `embed-voice` feature, see
+    /// [`embedded`](crate::embedded).
+    #[cfg(feature = "embed-voice")]
+    pub fn embedded(session_options: &SessionOptions) -> Result<Self> {
+        use crate::embedded::voice;
+        Self::from_bytes_with_options(voice::MODEL, voice::CONFIG, session_options)
+    }
+
+    fn from_asset(model: &Asset<'_>, config: &str, session_options: &SessionOptions) -> Result<Self> {
+        let quantized = !model.map("model", quantized_ops)?.is_empty();
+        if quantized && session_options.execution_provider != ExecutionProvider::Cpu {
+            tracing::warn!(
+                "`{}` is quantized, the `{}` execution provider may run its integer operators on the CPU",
+                model.name(), session_options.execution_provider.name()
+            );
+        }
+        let mut model = Self::from_backend(backend::load(model, session_options)?, config)?;
+        model.quantized = quantized;
+        Ok(model)
+    }
+
+    /// Runs the voice on a custom [`InferenceBackend`], with the contents of
+    /// its `.onnx.json` config.
+    pub fn from_backend(backend: Box<dyn InferenceBackend>, config: &str) -> Result<Self> {
+        let mut config: Config = serde_json::from_str(config)?;
+        if let Some(PhonemizerConfig::Espeak { voice: voice @ None }) = &mut config.phonemizer {
+            *voice = config.espeak.as_ref().map(|espeak| espeak.voice.clone());
+        }
+        let signature = backend.signature().clone();
+        if config.num_speakers > 1 && signature.sid.is_none() {
+            tracing::warn!("config lists {} speakers but the model has no speaker id input", config.num_speakers);
+        }
+
+        let phoneme_map: HashMap<String, Vec<i64>> = config.phoneme_id_map.iter()
+            .map(|(symbol, ids)| (symbol.nfc().collect(), ids.clone()))
+            .collect();
+        let max_symbol_len = phoneme_map.keys().map(|symbol| symbol.chars().count()).max().unwrap_or(1);
 
         Ok(Model {
             config,
-            model,
+            phoneme_map,
+            max_symbol_len,
+            backend: Mutex::new(backend),
+            signature,
+            quantized: false,
+            speaker_id: None,
+            strict: false,
+            max_phoneme_ids: DEFAULT_MAX_PHONEME_IDS,
+            max_batch_size: DEFAULT_MAX_BATCH_SIZE,
         })
     }
 
+    /// In strict mode symbols missing from `phoneme_id_map` are an error,
+    /// otherwise they are dropped with a warning.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Caps the phoneme ids passed to a single inference, see [`DEFAULT_MAX_PHONEME_IDS`].
+    pub fn set_max_phoneme_ids(&mut self, max_phoneme_ids: usize) {
+        self.max_phoneme_ids = max_phoneme_ids.max(8);
+    }
+
+    pub fn max_phoneme_ids(&self) -> usize {
+        self.max_phoneme_ids
+    }
+
+    /// Caps the utterances run together by [`process_ipa_batch_with_scales`](Self::process_ipa_batch_with_scales).
+    pub fn set_max_batch_size(&mut self, max_batch_size: usize) {
+        self.max_batch_size = max_batch_size.max(1);
+    }
+
+    pub fn max_batch_size(&self) -> usize {
+        self.max_batch_size
+    }
+
+    /// The session input and output names detected at load time.
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    /// Whether the voice is an integer-quantized export, see
+    /// [`ModelInfo::quantized_ops`](crate::inspect::ModelInfo::quantized_ops).
+    pub fn is_quantized(&self) -> bool {
+        self.quantized
+    }
+
+    pub fn is_multi_speaker(&self) -> bool {
+        self.config.num_speakers > 1
+    }
+
+    pub fn speaker_id(&self) -> Option<i64> {
+        self.speaker_id
+    }
+
+    pub fn set_speaker_id(&mut self, speaker_id: i64) -> Result<()> {
+        if speaker_id < 0 || speaker_id >= self.config.num_speakers as i64 {
+            return Err(PiperError::UnknownSpeaker(speaker_id.to_string()));
+        }
+        self.speaker_id = Some(speaker_id);
+        Ok(())
+    }
+
+    /// Selects a speaker by its name in `speaker_id_map` or by its numeric id.
+    pub fn set_speaker(&mut self, speaker: &str) -> Result<()> {
+        match self.config.speaker_id_map.get(speaker) {
+            Some(&speaker_id) => self.set_speaker_id(speaker_id),
+            None => {
+                let speaker_id = speaker.parse::<i64>()
+                    .map_err(|_| PiperError::UnknownSpeaker(speaker.to_string()))?;
+                self.set_speaker_id(speaker_id)
+            }
+        }
+    }
+
+    /// Maps an IPA string to phoneme ids, matching the longest `phoneme_id_map`
+    /// key at each position so symbols spanning several codepoints survive.
+    ///
+    /// Both the input and the keys are NFC normalized, precomposed characters
+    /// missing from the map are retried in their decomposed form. `_` pads are
+    /// re-inserted after every phoneme the way Piper expects them.
     pub fn ipa_string_to_phoneme_ids(
         &self,
         ipa_string: &str,
-    ) -> Result<Vec<i64>, Error> {
-        let phoneme_ids = ipa_string
-            .chars()
-            .filter_map(|c| self.config.phoneme_id_map.get(&c.to_string()))
-            .flat_map(|ids| ids.iter())
-            .cloned()
-            .collect::<Vec<i64>>();
-        
+    ) -> Result<Vec<i64>> {
+        let phoneme_map = &self.phoneme_map;
+        let max_symbol_len = self.max_symbol_len;
+        let pad = phoneme_map.get("_");
+
+        // Characters paired with their position in the normalized input. Dashes only
+        // mark pauses for the synthesizer unless the voice has an id for them.
+        let keep_dashes = phoneme_map.contains_key("—");
+        let mut chars: Vec<(usize, char)> = ipa_string.nfc().enumerate()
+            .filter(|&(_, c)| c != '_' && (c != '—' || keep_dashes))
+            .collect();
+        let mut phoneme_ids = Vec::with_capacity(chars.len() * 2);
+        let mut unmapped: Vec<(usize, char)> = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let longest = (1..=max_symbol_len.min(chars.len() - i)).rev()
+                .find_map(|len| {
+                    let symbol: String = chars[i..i + len].iter().map(|&(_, c)| c).collect();
+                    phoneme_map.get(&symbol).map(|ids| (len, ids))
+                });
+
+            match longest {
+                Some((len, ids)) => {
+                    phoneme_ids.extend_from_slice(ids);
+                    if let Some(pad) = pad && chars[i].1 != '$' {
+                        phoneme_ids.extend_from_slice(pad);
+                    }
+                    i += len;
+                }
+                None => {
+                    let (position, c) = chars[i];
+                    let decomposed: Vec<char> = c.to_string().nfd().collect();
+                    if decomposed.len() > 1 {
+                        chars.splice(i..=i, decomposed.into_iter().map(|c| (position, c)));
+                    } else {
+                        unmapped.push((position, c));
+                        i += 1;
+                    }
+                }
+            }
+        }
+
+        if !unmapped.is_empty() {
+            self.report_unmapped(&unmapped)?;
+        }
+
         Ok(phoneme_ids)
     }
 
+    fn report_unmapped(&self, unmapped: &[(usize, char)]) -> Result<()> {
+        let mut symbols: Vec<(char, Vec<usize>)> = Vec::new();
+        for &(position, c) in unmapped {
+            match symbols.iter_mut().find(|(symbol, _)| *symbol == c) {
+                Some((_, positions)) => positions.push(position),
+                None => symbols.push((c, vec![position])),
+            }
+        }
+
+        if self.strict {
+            let details = symbols.iter()
+                .map(|(symbol, positions)| {
+                    let positions: Vec<String> = positions.iter().map(|p| p.to_string()).collect();
+                    format!("`{}` (U+{:04X}) at {}", symbol, *symbol as u32, positions.join(", "))
+                })
+                .collect::<Vec<String>>()
+                .join("; ");
+            return Err(PiperError::UnmappedPhonemes(details));
+        }
+
+        let counts = symbols.iter()
+            .map(|(symbol, positions)| format!("`{}` x{}", symbol, positions.len()))
+            .collect::<Vec<String>>()
+            .join(", ");
+        tracing::warn!("dropped {} phonemes missing from the voice: {}", unmapped.len(), counts);
+        Ok(())
+    }
+
+    /// Splits ids longer than `max_phoneme_ids` into chunks, cutting after the
+    /// last sentence end or else the last word boundary that fits. Every chunk
+    /// is wrapped in `^`/`$` so it is synthesized as a complete utterance.
+    fn split_phoneme_ids(&self, phoneme_ids: Vec<i64>) -> Vec<Vec<i64>> {
+        if phoneme_ids.len() <= self.max_phoneme_ids {
+            return vec![phoneme_ids];
+        }
+
+        let id = |symbol: &str| self.config.phoneme_id_map.get(symbol).and_then(|ids| ids.first().copied());
+        let (bos, eos, pad, space) = (id("^"), id("$"), id("_"), id(" "));
+        // Leaves room for the `^`, `_` and `$` added around each chunk and a trailing pad.
+        let window = self.max_phoneme_ids - 4;
+
+        let mut chunks: Vec<Vec<i64>> = Vec::new();
+        let mut rest = &phoneme_ids[..];
+        while rest.len() > window {
+            let last_position = |boundary: Option<i64>| {
+                rest[..window].iter().rposition(|&id| Some(id) == boundary).map(|i| i + 1)
+            };
+            let mut cut = last_position(eos).or_else(|| last_position(space)).unwrap_or(window);
+            if pad.is_some() && rest.get(cut).copied() == pad {
+                cut += 1;
+            }
+            chunks.push(rest[..cut].to_vec());
+            rest = &rest[cut..];
+        }
+        if !rest.is_empty() {
+            chunks.push(rest.to_vec());
+        }
+
+        for chunk in &mut chunks {
+            if let Some(bos) = bos && chunk.first() != Some(&bos) {
+                let prefix: Vec<i64> = std::iter::once(bos).chain(pad).collect();
+                chunk.splice(0..0, prefix);
+            }
+            if let Some(eos) = eos && chunk.last() != Some(&eos) {
+                chunk.push(eos);
+            }
+        }
+        chunks
+    }
+
     pub fn prepare_input(
         &self,
         phonemes_ids: Vec<i64>,
-    ) -> Result<(Array2<i64>, Array1<i64>, Array1<f32>), Box<dyn std::error::Error>> {
+    ) -> Result<ModelInput> {
+        self.prepare_input_with_scales(phonemes_ids, &self.config.inference)
+    }
+
+    pub fn prepare_input_with_scales(
+        &self,
+        phonemes_ids: Vec<i64>,
+        scales: &Inference,
+    ) -> Result<ModelInput> {
         let phonemes_len = phonemes_ids.len();
         let phonems_ids_array = Array2::<i64>::from_shape_vec(
             [1, phonemes_len], 
@@ -88,9 +431,9 @@ impl Model {
         let scales_array = Array1::<f32>::from_shape_vec(
             [3], 
             vec![
-                self.config.inference.noise_scale.clone(),
-                self.config.inference.length_scale.clone(),
-                self.config.inference.noise_w.clone()
+                scales.noise_scale,
+                scales.length_scale,
+                scales.noise_w
             ]
         )?;
 
@@ -98,33 +441,238 @@ impl Model {
     }
 
     pub fn run_inference(
-        &mut self,
+        &self,
         phonemes_ids: Vec<i64>
-    ) -> Result<ort::session::SessionOutputs, Box<dyn std::error::Error>> {
-        let (phonems_ids_array, phonems_len_array, scales_array) = self.prepare_input(phonemes_ids)?;
+    ) -> Result<InferenceOutput> {
+        self.run_inference_with_scales(phonemes_ids, &self.config.inference)
+    }
 
-        let phonems_ids_tensor = ort::value::Tensor::from_array(phonems_ids_array)?;
-        let phonems_len_tensor = ort::value::Tensor::from_array(phonems_len_array)?;
-        let scales_tensor = ort::value::Tensor::from_array(scales_array)?;
+    /// Runs one inference. The session is locked for its duration, so a
+    /// shared model serializes concurrent calls instead of failing them.
+    pub fn run_inference_with_scales(
+        &self,
+        phonemes_ids: Vec<i64>,
+        scales: &Inference,
+    ) -> Result<InferenceOutput> {
+        let _span = tracing::debug_span!("inference", phonemes = phonemes_ids.len()).entered();
+        let (phonems_ids_array, phonems_len_array, scales_array) = self.prepare_input_with_scales(phonemes_ids, scales)?;
 
-        let inputs = ort::inputs!{
-            "input" => phonems_ids_tensor,
-            "input_lengths" => phonems_len_tensor,
-            "scales" => scales_tensor,
-        };
+        let sid = self.signature.sid.as_ref()
+            .map(|_| Array1::<i64>::from_elem(1, self.speaker_id.unwrap_or(0)));
+        self.backend()?.run(VoiceInput {
+            ids: phonems_ids_array,
+            lengths: phonems_len_array,
+            scales: scales_array,
+            sid,
+        })
+    }
+
+    /// Runs a short dummy utterance so ONNX Runtime allocates its buffers and
+    /// picks its kernels before the first real request.
+    pub fn warm_up(&self) -> Result<()> {
+        let id = |symbol: &str| self.config.phoneme_id_map.get(symbol).and_then(|ids| ids.first().copied());
+        let mut ids: Vec<i64> = self.config.phoneme_id_map.values().flatten().copied().collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids.truncate(8);
+        let phoneme_ids: Vec<i64> = id("^").into_iter().chain(ids).chain(id("$")).collect();
+        self.run_inference(phoneme_ids).map(|_| ())
+    }
 
-        Ok(self.model.run(inputs)?)
+    fn backend(&self) -> Result<std::sync::MutexGuard<'_, Box<dyn InferenceBackend>>> {
+        self.backend.lock().map_err(|_| PiperError::TaskFailed("model session lock poisoned".to_string()))
     }
 
     pub fn process_ipa_string(
-        &mut self,
+        &self,
+        ipa_string: &str,
+    ) -> Result<(Shape, Vec<f32>)> {
+        self.process_ipa_string_with_scales(ipa_string, &self.config.inference)
+    }
+
+    pub fn process_ipa_string_with_scales(
+        &self,
         ipa_string: &str,
-    ) -> Result<(Shape, Vec<f32>), Box<dyn std::error::Error>> {
+        scales: &Inference,
+    ) -> Result<(Shape, Vec<f32>)> {
+        let mut shape: Option<Vec<i64>> = None;
+        let mut waveform = Vec::new();
+        self.process_chunks(ipa_string, scales, &mut |chunk_shape, chunk_waveform, _| {
+            shape.get_or_insert_with(|| chunk_shape.to_vec());
+            waveform.extend_from_slice(chunk_waveform);
+            Ok(())
+        })?;
+
+        let mut shape = shape.unwrap_or_else(|| vec![1, 1, 0]);
+        if let Some(samples) = shape.last_mut() {
+            *samples = waveform.len() as i64;
+        }
+        Ok((Shape::new(shape), waveform))
+    }
+
+    /// Like [`process_ipa_string_with_scales`](Self::process_ipa_string_with_scales),
+    /// also returning the duration of every phoneme id, `None` when the model
+    /// has no `durations` output.
+    pub fn process_ipa_string_with_durations(
+        &self,
+        ipa_string: &str,
+        scales: &Inference,
+    ) -> Result<(Vec<f32>, Option<Vec<PhonemeDuration>>)> {
+        let mut waveform = Vec::new();
+        let mut durations = Some(Vec::new());
+        self.process_chunks(ipa_string, scales, &mut |_, chunk_waveform, chunk_durations| {
+            waveform.extend_from_slice(chunk_waveform);
+            durations = durations.take().zip(chunk_durations).map(|(mut all, chunk)| {
+                all.extend(chunk);
+                all
+            });
+            Ok(())
+        })?;
+        Ok((waveform, durations))
+    }
+
+    pub fn process_ipa_string_with_sink(
+        &self,
+        ipa_string: &str,
+        sink: &mut impl AudioSink,
+    ) -> Result<()> {
+        self.process_ipa_string_with_sink_and_scales(ipa_string, sink, &self.config.inference)
+    }
+
+    /// Pushes the audio of every sub-chunk to `sink` as soon as its inference
+    /// completes instead of collecting the whole waveform first.
+    pub fn process_ipa_string_with_sink_and_scales(
+        &self,
+        ipa_string: &str,
+        sink: &mut impl AudioSink,
+        scales: &Inference,
+    ) -> Result<()> {
+        self.process_chunks(ipa_string, scales, &mut |_, chunk_waveform, _| sink.write_samples(chunk_waveform))?;
+        sink.finish()
+    }
+
+    fn process_chunks(
+        &self,
+        ipa_string: &str,
+        scales: &Inference,
+        on_chunk: &mut ChunkCallback,
+    ) -> Result<()> {
         let phoneme_ids = self.ipa_string_to_phoneme_ids(ipa_string)?;
-        let outputs = self.run_inference(phoneme_ids)?;
-        let (waveform_tensor_shape, waveform_tensor) = outputs["output"].try_extract_tensor::<f32>()?;
-        
-        Ok((waveform_tensor_shape.clone(), waveform_tensor.to_vec()))
+        for chunk in self.split_phoneme_ids(phoneme_ids) {
+            let ids = chunk.clone();
+            let output = self.run_inference_with_scales(chunk, scales)?;
+            let durations = output.durations
+                .filter(|frames| frames.len() == ids.len())
+                .map(|frames| ids.iter().zip(frames)
+                    .map(|(&id, frames)| PhonemeDuration { id, samples: frames_to_samples(frames) })
+                    .collect());
+            on_chunk(&output.shape, &output.audio, durations)?;
+        }
+        Ok(())
+    }
+
+    /// Runs several phoneme id sequences as one `[batch, time]` inference, padding
+    /// the shorter ones, and returns the audio of each with the padding trimmed.
+    /// Each item is cut to the length of its predicted `durations`; voices without
+    /// that output fall back to guessing the padding from a constant tail.
+    pub fn run_batch_inference_with_scales(
+        &self,
+        batch: &[Vec<i64>],
+        scales: &Inference,
+    ) -> Result<Vec<Vec<f32>>> {
+        let batch_size = batch.len();
+        let max_len = batch.iter().map(Vec::len).max().unwrap_or(0);
+        let _span = tracing::debug_span!("inference", batch = batch_size, phonemes = max_len).entered();
+        let pad = self.config.phoneme_id_map.get("_").and_then(|ids| ids.first().copied()).unwrap_or(0);
+
+        let mut ids = Vec::with_capacity(batch_size * max_len);
+        for item in batch {
+            ids.extend_from_slice(item);
+            ids.resize(ids.len() + max_len - item.len(), pad);
+        }
+        let phonems_ids_array = Array2::<i64>::from_shape_vec([batch_size, max_len], ids)?;
+        let phonems_len_array = Array1::<i64>::from_iter(batch.iter().map(|item| item.len() as i64));
+        let scales_array = Array1::<f32>::from_vec(vec![scales.noise_scale, scales.length_scale, scales.noise_w]);
+
+        let sid = self.signature.sid.as_ref()
+            .map(|_| Array1::<i64>::from_elem(batch_size, self.speaker_id.unwrap_or(0)));
+        let InferenceOutput { shape, audio: samples, durations } = self.backend()?.run(VoiceInput {
+            ids: phonems_ids_array,
+            lengths: phonems_len_array,
+            scales: scales_array,
+            sid,
+        })?;
+        if shape.first().copied() != Some(batch_size as i64) {
+            return Err(PiperError::InvalidShape(format!(
+                "expected a batch of {} waveforms, got shape {:?}", batch_size, shape
+            )));
+        }
+
+        // The padded tail depends on how much audio each item produces, so its
+        // length comes from the predicted durations, padded ids predicting none.
+        let item_lens: Option<Vec<usize>> = durations
+            .filter(|frames| frames.len() == batch_size * max_len)
+            .map(|frames| frames.chunks(max_len.max(1))
+                .zip(batch)
+                .map(|(row, item)| row[..item.len()].iter().map(|&frames| frames_to_samples(frames)).sum())
+                .collect());
+
+        let item_len = samples.len() / batch_size;
+        Ok(samples.chunks(item_len.max(1))
+            .take(batch_size)
+            .enumerate()
+            .map(|(i, samples)| match &item_lens {
+                Some(lens) => samples[..lens[i].min(samples.len())].to_vec(),
+                None => trim_padded_tail(samples).to_vec(),
+            })
+            .collect())
+    }
+
+    /// Synthesizes independent utterances in batches of up to
+    /// [`max_batch_size`](Self::max_batch_size), grouping inputs of similar
+    /// length to limit padding. Inputs over `max_phoneme_ids` run on their own.
+    pub fn process_ipa_batch_with_scales(
+        &self,
+        ipa_strings: &[&str],
+        scales: &Inference,
+    ) -> Result<Vec<Vec<f32>>> {
+        let mut waveforms = vec![Vec::new(); ipa_strings.len()];
+        let mut batchable = Vec::new();
+        for (i, ipa_string) in ipa_strings.iter().enumerate() {
+            let phoneme_ids = self.ipa_string_to_phoneme_ids(ipa_string)?;
+            if phoneme_ids.len() > self.max_phoneme_ids {
+                waveforms[i] = self.process_ipa_string_with_scales(ipa_string, scales)?.1;
+            } else if !phoneme_ids.is_empty() {
+                batchable.push((i, phoneme_ids));
+            }
+        }
+
+        batchable.sort_by_key(|(_, phoneme_ids)| phoneme_ids.len());
+        for group in batchable.chunks(self.max_batch_size) {
+            let batch: Vec<Vec<i64>> = group.iter().map(|(_, phoneme_ids)| phoneme_ids.clone()).collect();
+            let outputs = self.run_batch_inference_with_scales(&batch, scales)?;
+            for ((i, _), waveform) in group.iter().zip(outputs) {
+                waveforms[*i] = waveform;
+            }
+        }
+        Ok(waveforms)
+    }
+
+    pub fn write_wav(
+        &self,
+        waveform: &[f32],
+        sample_rate: u64,
+        writer: impl std::io::Write,
+    ) -> Result<()> {
+        audio::write_wav(waveform, sample_rate, SampleFormat::I16, writer)
+    }
+
+    pub fn to_wav_bytes(
+        &self,
+        waveform: &[f32],
+        sample_rate: u64,
+    ) -> Result<Vec<u8>> {
+        audio::to_wav_bytes(waveform, sample_rate)
     }
 
     pub fn write_wav_file(
@@ -132,40 +680,166 @@ impl Model {
         waveform: &[f32],
         sample_rate: u64,
         output_path: &str,
-    ) -> std::io::Result<()> {
+    ) -> Result<()> {
+        self.write_wav_file_with_format(waveform, sample_rate, output_path, SampleFormat::I16)
+    }
+
+    pub fn write_wav_file_with_format(
+        &self,
+        waveform: &[f32],
+        sample_rate: u64,
+        output_path: &str,
+        format: SampleFormat,
+    ) -> Result<()> {
         let start = std::time::Instant::now();
-        let mut file = std::fs::File::create(output_path)
-            .expect("Failed to create output file");
-
-        let header: Vec<u8> = vec![
-            "RIFF".as_bytes().to_vec(),
-            (36 + waveform.len() as u32 * 2).to_le_bytes().to_vec(),
-            "WAVE".as_bytes().to_vec(),
-            "fmt ".as_bytes().to_vec(),
-            (16u32).to_le_bytes().to_vec(), // Subchunk1Size
-            (1u16).to_le_bytes().to_vec(), // AudioFormat (PCM)
-            (1u16).to_le_bytes().to_vec(), // NumChannels (Mono)
-            (sample_rate.clone() as u32).to_le_bytes().to_vec(), // SampleRate
-            (sample_rate.clone() as u32 * 2).to_le_bytes().to_vec(), // ByteRate
-            (2u16).to_le_bytes().to_vec(), // BlockAlign
-            (16u16).to_le_bytes().to_vec(), // BitsPerSample
-            "data".as_bytes().to_vec(),
-            (waveform.len() as u32 * 2).to_le_bytes().to_vec(), // Subchunk2Size
-        ].concat();
-        file.write_all(&header)
-            .expect("Failed to write WAV header");
-
-        let samples: Vec<i16> = waveform.iter()
-            .map(|&sample| (sample * i16::MAX as f32) as i16)
-            .collect();
-        file.write_all(&samples.iter().flat_map(|s| s.to_le_bytes()).collect::<Vec<u8>>())
-            .expect("Failed to write WAV samples");
+        let file = std::io::BufWriter::new(std::fs::File::create(output_path)?);
+
+        audio::write_wav_to(waveform, sample_rate, format, file)?;
 
-        println!("WAV file created successfully at: {}", output_path);
-        println!("WAV file creation took: {:?}", start.elapsed());
+        tracing::info!(elapsed = ?start.elapsed(), "WAV file created successfully at: {}", output_path);
 
         Ok(())
     }
 }
 
     
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::InferenceOutput;
+
+    /// Stands in for a voice where only the config is exercised.
+    struct NoInference(Signature);
+
+    impl InferenceBackend for NoInference {
+        fn signature(&self) -> &Signature {
+            &self.0
+        }
+
+        fn run(&mut self, _input: VoiceInput) -> Result<InferenceOutput> {
+            Err(PiperError::NotLoaded("voice"))
+        }
+    }
+
+    fn model() -> Model {
+        let config = r#"{
+            "audio": {"sample_rate": 22050, "quality": "medium"},
+            "inference": {"noise_scale": 0.667, "length_scale": 1.0, "noise_w": 0.8},
+            "phoneme_id_map": {
+                "_": [0], "^": [1], "$": [2], " ": [3], "a": [4], "t": [5], "ʃ": [6], "tʃ": [7],
+                "e": [8], "\u0301": [9]
+            },
+            "language": {
+                "code": "en_US", "family": "en", "region": "US", "name_native": "English",
+                "name_english": "English", "country_english": "United States"
+            }
+        }"#;
+        let signature = Signature {
+            input: "input".to_string(),
+            input_lengths: "input_lengths".to_string(),
+            scales: "scales".to_string(),
+            sid: None,
+            output: "output".to_string(),
+            durations: None,
+            half_precision: false,
+        };
+        Model::from_backend(Box::new(NoInference(signature)), config).unwrap()
+    }
+
+    #[test]
+    fn longest_symbols_win() {
+        let model = model();
+        assert_eq!(model.ipa_string_to_phoneme_ids("^tʃa$").unwrap(), [1, 0, 7, 0, 4, 0, 2]);
+        assert_eq!(model.ipa_string_to_phoneme_ids("ʃta").unwrap(), [6, 0, 5, 0, 4, 0]);
+    }
+
+    #[test]
+    fn pads_are_reinserted() {
+        let model = model();
+        assert_eq!(model.ipa_string_to_phoneme_ids("a_t a").unwrap(), [4, 0, 5, 0, 3, 0, 4, 0]);
+        assert_eq!(model.ipa_string_to_phoneme_ids("a—t").unwrap(), [4, 0, 5, 0]);
+    }
+
+    #[test]
+    fn precomposed_characters_are_decomposed() {
+        let model = model();
+        assert_eq!(model.ipa_string_to_phoneme_ids("té").unwrap(), [5, 0, 8, 0, 9, 0]);
+        assert_eq!(model.ipa_string_to_phoneme_ids("te\u{301}").unwrap(), [5, 0, 8, 0, 9, 0]);
+    }
+
+    #[test]
+    fn unmapped_symbols() {
+        let mut model = model();
+        assert_eq!(model.ipa_string_to_phoneme_ids("axtx").unwrap(), [4, 0, 5, 0]);
+        model.set_strict(true);
+        let err = model.ipa_string_to_phoneme_ids("axtx").unwrap_err();
+        assert!(matches!(&err, PiperError::UnmappedPhonemes(details) if details == "`x` (U+0078) at 1, 3"), "{}", err);
+    }
+
+    #[test]
+    fn trims_constant_tail() {
+        assert_eq!(trim_padded_tail(&[0.5, -0.5, 0.2, 0.01, 0.01, 0.01]), [0.5, -0.5, 0.2]);
+    }
+
+    #[test]
+    fn keeps_constant_signal() {
+        assert_eq!(trim_padded_tail(&[0.25; 4]), [0.25; 4]);
+        assert_eq!(trim_padded_tail(&[]), [0.0; 0]);
+    }
+
+    /// `^`, `_` and then `a`, `t` and spaces with their pads, ending in `$`.
+    fn sentence_ids(len: usize) -> Vec<i64> {
+        let mut ids = vec![1, 0];
+        ids.extend([4, 0, 5, 0, 3, 0].into_iter().cycle().take(len - 3));
+        ids.push(2);
+        ids
+    }
+
+    fn assert_chunks(model: &Model, ids: Vec<i64>) -> Vec<Vec<i64>> {
+        let content = |ids: &[i64]| ids.iter().copied().filter(|&id| id > 2).collect::<Vec<i64>>();
+        let chunks = model.split_phoneme_ids(ids.clone());
+        for chunk in &chunks {
+            assert!(chunk.len() <= model.max_phoneme_ids(), "{:?}", chunk);
+            assert_eq!((chunk.first(), chunk.last()), (Some(&1), Some(&2)), "{:?}", chunk);
+        }
+        assert_eq!(content(&chunks.concat()), content(&ids));
+        chunks
+    }
+
+    #[test]
+    fn short_sequences_are_one_chunk() {
+        let mut model = model();
+        model.set_max_phoneme_ids(16);
+        assert_eq!(assert_chunks(&model, sentence_ids(16)), [sentence_ids(16)]);
+    }
+
+    #[test]
+    fn long_sequences_are_split() {
+        let mut model = model();
+        model.set_max_phoneme_ids(16);
+        assert_eq!(assert_chunks(&model, sentence_ids(17)).len(), 2);
+        assert!(assert_chunks(&model, sentence_ids(48)).len() >= 3);
+        assert!(assert_chunks(&model, sentence_ids(160)).len() >= 10);
+    }
+
+    #[test]
+    fn chunks_end_at_word_boundaries() {
+        let mut model = model();
+        model.set_max_phoneme_ids(16);
+        for chunk in &assert_chunks(&model, sentence_ids(48))[..2] {
+            // Cut after a space and its pad.
+            assert_eq!(&chunk[chunk.len() - 3..], [3, 0, 2], "{:?}", chunk);
+        }
+    }
+
+    #[test]
+    fn words_longer_than_a_chunk_are_cut() {
+        let mut model = model();
+        model.set_max_phoneme_ids(16);
+        let mut ids = vec![1, 0];
+        ids.extend([4, 0].repeat(20));
+        ids.push(2);
+        assert!(assert_chunks(&model, ids).len() >= 3);
+    }
+}
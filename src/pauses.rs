@@ -0,0 +1,130 @@
+use std::{collections::HashMap, sync::LazyLock, time::Duration};
+
+use regex::{Captures, Regex};
+
+/// Longest pause a `[pause ...]` marker can ask for, longer ones are clamped
+/// so text from a request can't make the synthesizer allocate unbounded silence.
+pub const MAX_PAUSE: Duration = Duration::from_secs(10);
+
+static PAUSE_MARKUP: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\[pause(?:\s+(\d+(?:\.\d+)?)\s*(ms|s))?\]|\.\.\.|…").unwrap()
+});
+
+/// Silence inserted for the pause markup in plain text: `[pause 300ms]`,
+/// `[pause 1.5s]`, `[pause]` and ellipses, and after punctuation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PauseConfig {
+    /// Length of a `[pause]` without a duration.
+    pub default_pause: Duration,
+    /// Length of the pause for `...` and `…`, `None` leaves ellipses to the voice.
+    pub ellipsis: Option<Duration>,
+    /// Punctuation after which sentences are split and silence is inserted,
+    /// e.g. `,` or `—` for dashes. Empty by default, leaving pauses to the voice.
+    pub punctuation: HashMap<char, Duration>,
+    /// Silence after every sentence, which then gets an inference of its own.
+    pub sentence_silence: Option<Duration>,
+}
+
+impl Default for PauseConfig {
+    fn default() -> Self {
+        Self {
+            default_pause: Duration::from_millis(500),
+            ellipsis: Some(Duration::from_millis(400)),
+            punctuation: HashMap::new(),
+            sentence_silence: None,
+        }
+    }
+}
+
+/// A run of text followed by the pause that was marked up after it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextSegment<'a> {
+    pub text: &'a str,
+    pub pause: Option<Duration>,
+}
+
+impl PauseConfig {
+    /// Breathing room at clause boundaries: commas, semicolons, colons and dashes.
+    pub fn with_clause_pauses(mut self) -> Self {
+        self.punctuation.extend([
+            (',', Duration::from_millis(150)),
+            (';', Duration::from_millis(250)),
+            (':', Duration::from_millis(250)),
+            ('—', Duration::from_millis(250)),
+        ]);
+        self
+    }
+
+    pub fn with_sentence_silence(mut self, silence: Duration) -> Self {
+        self.sentence_silence = Some(silence);
+        self
+    }
+
+    pub fn with_punctuation_pause(mut self, punctuation: char, pause: Duration) -> Self {
+        self.punctuation.insert(punctuation, pause);
+        self
+    }
+
+    fn marked_pause(&self, captures: &Captures) -> Option<Duration> {
+        if !captures[0].starts_with('[') {
+            return self.ellipsis;
+        }
+        let Some(amount) = captures.get(1).and_then(|m| m.as_str().parse::<f64>().ok()) else {
+            return Some(self.default_pause);
+        };
+        let seconds = match captures[2].to_ascii_lowercase().as_str() {
+            "ms" => amount / 1000.0,
+            _ => amount,
+        };
+        Some(Duration::try_from_secs_f64(seconds).map_or(MAX_PAUSE, |pause| pause.min(MAX_PAUSE)))
+    }
+
+    /// Splits `text` at its pause markers, the markers themselves are removed
+    /// unless they are ellipses left to the voice.
+    pub fn split<'a>(&self, text: &'a str) -> Vec<TextSegment<'a>> {
+        let mut segments = Vec::new();
+        let mut start = 0;
+        for captures in PAUSE_MARKUP.captures_iter(text) {
+            let Some(pause) = self.marked_pause(&captures) else {
+                continue;
+            };
+            let marker = captures.get(0).unwrap();
+            segments.push(TextSegment { text: &text[start..marker.start()], pause: Some(pause) });
+            start = marker.end();
+        }
+        segments.push(TextSegment { text: &text[start..], pause: None });
+        segments
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pauses(text: &str) -> Vec<Option<Duration>> {
+        PauseConfig::default().split(text).iter().map(|segment| segment.pause).collect()
+    }
+
+    #[test]
+    fn marked_pauses() {
+        assert_eq!(pauses("a [pause 300ms] b [pause 1.5s] c [pause] d"), [
+            Some(Duration::from_millis(300)),
+            Some(Duration::from_millis(1500)),
+            Some(Duration::from_millis(500)),
+            None,
+        ]);
+    }
+
+    #[test]
+    fn long_pauses_are_clamped() {
+        assert_eq!(pauses("a [pause 100000s] b"), [Some(MAX_PAUSE), None]);
+        assert_eq!(pauses("a [pause 20000ms] b"), [Some(MAX_PAUSE), None]);
+    }
+
+    #[test]
+    fn overflowing_pauses_do_not_panic() {
+        assert_eq!(pauses("a [pause 20000000000000000000s] b"), [Some(MAX_PAUSE), None]);
+        let digits = "9".repeat(400);
+        assert_eq!(pauses(&format!("a [pause {digits}s] b")), [Some(MAX_PAUSE), None]);
+    }
+}
@@ -1,24 +1,152 @@
-use std::collections::HashMap;
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    num::NonZeroUsize,
+    path::PathBuf,
+    str::FromStr,
+    sync::{LazyLock, Mutex, MutexGuard, PoisonError},
+};
+
+use lru::LruCache;
+use ndarray::{Array2, Array3, Axis};
+use ort::{session::Session, value::DynValue};
+use serde::Serialize;
+use unicode_normalization::{UnicodeNormalization, char::is_combining_mark};
+use unicode_segmentation::UnicodeSegmentation;
 
-use ndarray::{Array2, Array3};
-use ort::{
-    session::{builder::GraphOptimizationLevel, Session}
+use crate::{
+    asset::Asset,
+    cache::DiskCache,
+    cancellation::CancellationToken,
+    error::{PiperError, Result},
+    espeak::EspeakPhonemizer,
+    lexicon::{Lexicon, Pronunciation, UserLexicon},
+    phonemizer::{PhonemizedSentence, Phonemizer},
+    session::{build_session, SessionOptions},
+    text_norm::{default_abbreviations, TextNormalizer, TERMINAL_ABBREVIATIONS},
 };
 
+/// What becomes of foreign words, those with letters outside the English
+/// alphabet that no lexicon knows, which the G2P was never trained on.
+#[derive(Default)]
+pub enum ForeignWords {
+    /// Guessed by the G2P like any other word, with a warning.
+    #[default]
+    Guess,
+    /// Spelled out letter by letter, without diacritics.
+    Spell,
+    /// Read by another phonemizer, e.g. an [`EspeakPhonemizer`] for their language.
+    Phonemizer(Box<dyn Phonemizer + Send + Sync>),
+}
+
+impl FromStr for ForeignWords {
+    type Err = PiperError;
+
+    /// `guess`, `spell` or `espeak:<voice>`, e.g. `espeak:de`.
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "guess" => Ok(ForeignWords::Guess),
+            "spell" => Ok(ForeignWords::Spell),
+            _ => match s.split_once(':') {
+                Some((program, voice)) if program.eq_ignore_ascii_case("espeak") && !voice.is_empty() => {
+                    Ok(ForeignWords::Phonemizer(Box::new(EspeakPhonemizer::new(voice))))
+                }
+                _ => Err(PiperError::InvalidConfig(format!("unknown foreign word handling `{}`", s))),
+            },
+        }
+    }
+}
+
+/// How the G2P decoder picks the phonemes of a word.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Decoding {
+    /// The most likely phoneme at every step.
+    #[default]
+    Greedy,
+    /// Keeps the `width` most likely phoneme sequences at every step and picks
+    /// the finished one with the best log-probability divided by
+    /// `length ^ length_penalty`, so a higher penalty favours longer sequences.
+    /// Runs the plain decoder, without the `decoder_with_past`.
+    Beam { width: usize, length_penalty: f32 },
+}
+
+/// A word the G2P pronounced, with how sure it was of the pronunciation.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WordConfidence {
+    pub word: String,
+    pub ipa: String,
+    /// Mean log-probability of the decoded phoneme tokens, 0 being certain.
+    pub confidence: f32,
+}
+
+/// Token ids, vocabulary entries and mean token log-probability of a decoded word.
+struct Decoded {
+    ids: Vec<usize>,
+    tokens: Vec<String>,
+    confidence: f32,
+}
+
+/// IPA names of the letters, for spelling foreign words.
+const LETTER_IPA: [&str; 26] = [
+    "ˈeɪ", "bˈiː", "sˈiː", "dˈiː", "ˈiː", "ˈɛf", "dʒˈiː", "ˈeɪtʃ", "ˈaɪ", "dʒˈeɪ", "kˈeɪ", "ˈɛl", "ˈɛm",
+    "ˈɛn", "ˈoʊ", "pˈiː", "kjˈuː", "ˈɑːɹ", "ˈɛs", "tˈiː", "jˈuː", "vˈiː", "dˈʌbəljuː", "ˈɛks", "wˈaɪ", "zˈiː",
+];
+
 pub struct PhonemeGen {
-    decoder_path: String,
-    encoder_path: String,
-    tokenizer_path: String,
-    vocab_path: String,
-    arpabet_mapping_path: String,
-
-    encoder: Option<Session>,
-    decoder: Option<Session>,
+    decoder_source: Asset<'static>,
+    encoder_source: Asset<'static>,
+    tokenizer_source: Asset<'static>,
+    vocab_source: Asset<'static>,
+    arpabet_mapping_source: Asset<'static>,
+    decoder_with_past_source: Option<Asset<'static>>,
+    disk_cache_path: Option<PathBuf>,
+    lexicon_path: Option<PathBuf>,
+    user_lexicon_path: Option<PathBuf>,
+    session_options: SessionOptions,
+
+    encoder: Option<Mutex<Session>>,
+    decoder: Option<Mutex<Session>>,
+    decoder_with_past: Option<Mutex<Session>>,
     tokenizer: Option<tokenizers::Tokenizer>,
     arpabet_mapping: Option<HashMap<String, String>>,
     pub vocab: Option<(HashMap<String, usize>, HashMap<usize, String>)>,
+    cache: Option<Mutex<LruCache<String, Vec<String>>>>,
+    disk_cache: Option<Mutex<DiskCache>>,
+    lexicon: Option<Lexicon>,
+    user_lexicon: UserLexicon,
+    normalizer: Option<TextNormalizer>,
+    foreign_words: ForeignWords,
+    decoding: Decoding,
+}
+
+const DEFAULT_CACHE_CAPACITY: usize = 4096;
+
+fn lock_session<'a>(session: &'a Mutex<Session>, what: &'static str) -> Result<MutexGuard<'a, Session>> {
+    session.lock().map_err(|_| PiperError::TaskFailed(format!("{} session lock poisoned", what)))
 }
 
+/// A panic while a cache was locked leaves at worst a missing entry.
+fn lock_cache<T>(cache: &Mutex<T>) -> MutexGuard<'_, T> {
+    cache.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Abbreviations that precede a name or number and never end a sentence, the
+/// lowercased entries of the text normalizer's embedded table.
+static NON_TERMINAL_ABBREVIATIONS: LazyLock<HashSet<String>> = LazyLock::new(|| {
+    default_abbreviations().into_keys()
+        .map(|abbreviation| abbreviation.to_lowercase())
+        .filter(|abbreviation| abbreviation.ends_with('.') && !TERMINAL_ABBREVIATIONS.contains(&abbreviation.as_str()))
+        .collect()
+});
+
+/// Abbreviations that only continue the sentence when a number follows, so
+/// `No. 5` is kept together while `I said no.` still ends a sentence.
+const NUMBER_ABBREVIATIONS: [&str; 1] = ["no."];
+
+const ARPABET_VOWELS: [&str; 15] = [
+    "AA", "AE", "AH", "AO", "AW", "AY", "EH", "ER", "EY", "IH", "IY", "OW", "OY", "UH", "UW",
+];
+
 impl PhonemeGen {
     pub fn new(
         decoder_path: String,
@@ -26,40 +154,259 @@ impl PhonemeGen {
         tokenizer_path: String,
         vocab_path: String,
         arpabet_mapping_path: String,
+    ) -> Self {
+        Self::from_assets(
+            Asset::Path(decoder_path),
+            Asset::Path(encoder_path),
+            Asset::Path(tokenizer_path),
+            Asset::Path(vocab_path),
+            Asset::Path(arpabet_mapping_path),
+        )
+    }
+
+    /// G2P model, tokenizer, vocabulary and ARPAbet mapping compiled into the
+    /// binary with the `embed-g2p` feature, see [`embedded`](crate::embedded).
+    #[cfg(feature = "embed-g2p")]
+    pub fn embedded() -> Self {
+        use crate::embedded::g2p;
+        Self::from_bytes(g2p::DECODER, g2p::ENCODER, g2p::TOKENIZER, g2p::VOCAB, g2p::ARPABET_MAPPING.as_bytes())
+    }
+
+    /// Like [`new`](Self::new) with the file contents instead of paths, e.g.
+    /// for assets fetched over the network or decrypted at runtime.
+    pub fn from_bytes(
+        decoder: impl Into<Cow<'static, [u8]>>,
+        encoder: impl Into<Cow<'static, [u8]>>,
+        tokenizer: impl Into<Cow<'static, [u8]>>,
+        vocab: impl Into<Cow<'static, [u8]>>,
+        arpabet_mapping: impl Into<Cow<'static, [u8]>>,
+    ) -> Self {
+        Self::from_assets(
+            Asset::Bytes(decoder.into()),
+            Asset::Bytes(encoder.into()),
+            Asset::Bytes(tokenizer.into()),
+            Asset::Bytes(vocab.into()),
+            Asset::Bytes(arpabet_mapping.into()),
+        )
+    }
+
+    fn from_assets(
+        decoder_source: Asset<'static>,
+        encoder_source: Asset<'static>,
+        tokenizer_source: Asset<'static>,
+        vocab_source: Asset<'static>,
+        arpabet_mapping_source: Asset<'static>,
     ) -> Self {
         Self {
-            decoder_path,
-            encoder_path,
-            tokenizer_path,
-            arpabet_mapping_path,
-            vocab_path,
+            decoder_source,
+            encoder_source,
+            tokenizer_source,
+            vocab_source,
+            arpabet_mapping_source,
+            decoder_with_past_source: None,
+            disk_cache_path: None,
+            lexicon_path: None,
+            user_lexicon_path: None,
+            session_options: SessionOptions::default(),
             encoder: None,
             decoder: None,
+            decoder_with_past: None,
             tokenizer: None,
             vocab: None,
             arpabet_mapping: None,
+            cache: NonZeroUsize::new(DEFAULT_CACHE_CAPACITY).map(|capacity| Mutex::new(LruCache::new(capacity))),
+            disk_cache: None,
+            lexicon: None,
+            user_lexicon: UserLexicon::default(),
+            normalizer: Some(TextNormalizer::default()),
+            foreign_words: ForeignWords::default(),
+            decoding: Decoding::default(),
+        }
+    }
+
+    /// Replaces the default (`en-US`) text normalizer, `None` feeds text to the G2P as written.
+    pub fn with_normalizer(mut self, normalizer: Option<TextNormalizer>) -> Self {
+        self.normalizer = normalizer;
+        self
+    }
+
+    fn normalize(&self, text: &str) -> String {
+        match &self.normalizer {
+            Some(normalizer) => normalizer.normalize(text),
+            None => text.to_string(),
+        }
+    }
+
+    /// Loads user pronunciation overrides (word → IPA or ARPAbet) on `load()`.
+    pub fn with_user_lexicon(mut self, path: impl Into<PathBuf>) -> Self {
+        self.user_lexicon_path = Some(path.into());
+        self
+    }
+
+    /// Overrides the pronunciation of `word` with an IPA string, taking
+    /// precedence over the lexicon, the caches and the neural G2P.
+    pub fn add_pronunciation(&mut self, word: &str, ipa: &str) {
+        self.user_lexicon.insert(word, Pronunciation::Ipa(ipa.to_string()));
+    }
+
+    fn user_pronunciation(&self, key: &str) -> Option<Vec<String>> {
+        match self.user_lexicon.get(key)? {
+            Pronunciation::Ipa(ipa) => Some(vec![ipa.clone()]),
+            Pronunciation::Arpabet(arpabet) => match self.arpabet_to_ipa(arpabet.clone()) {
+                Ok(ipa) => Some(ipa),
+                Err(err) => {
+                    tracing::warn!("ignoring user lexicon entry `{}`: {}", key, err);
+                    None
+                }
+            },
+        }
+    }
+
+    /// Returns the first ARPAbet symbol of `lexicon` missing from `mapping`,
+    /// with the word it belongs to. Stress digits are ignored.
+    fn unknown_arpabet_symbol<'a>(lexicon: &'a UserLexicon, mapping: &HashMap<String, String>) -> Option<(&'a str, &'a str)> {
+        lexicon.iter().find_map(|(word, pronunciation)| match pronunciation {
+            Pronunciation::Ipa(_) => None,
+            Pronunciation::Arpabet(arpabet) => arpabet.iter()
+                .find(|symbol| !mapping.contains_key(symbol.as_str())
+                    && !mapping.contains_key(symbol.trim_end_matches(|c: char| c.is_ascii_digit())))
+                .map(|symbol| (word, symbol.as_str())),
+        })
+    }
+
+    /// Sets how words are decoded unless a call says otherwise, see [`Decoding`].
+    pub fn with_decoding(mut self, decoding: Decoding) -> Self {
+        self.decoding = decoding;
+        self
+    }
+
+    /// Sets what becomes of foreign words, see [`ForeignWords`].
+    pub fn with_foreign_words(mut self, foreign_words: ForeignWords) -> Self {
+        self.foreign_words = foreign_words;
+        self
+    }
+
+    /// Whether `word` has letters outside the English alphabet, such as `Müller` or `Москва`.
+    fn is_foreign(word: &str) -> bool {
+        word.chars().any(|c| c.is_alphabetic() && !c.is_ascii())
+    }
+
+    /// `word` spelled out, diacritics removed and letters outside the Latin alphabet left out.
+    fn spell(word: &str) -> Vec<String> {
+        let letters: Vec<&str> = word.nfd()
+            .filter(|c| !is_combining_mark(*c))
+            .filter(char::is_ascii_alphabetic)
+            .map(|c| LETTER_IPA[(c.to_ascii_lowercase() as u8 - b'a') as usize])
+            .collect();
+        vec![letters.join(" ")]
+    }
+
+    /// Reads a foreign word as set by [`with_foreign_words`](Self::with_foreign_words),
+    /// `None` leaving it to the G2P.
+    fn foreign_pronunciation(&self, word: &str) -> Result<Option<Vec<String>>> {
+        match &self.foreign_words {
+            ForeignWords::Guess => {
+                tracing::warn!("`{}` looks foreign, its pronunciation is a guess", word);
+                Ok(None)
+            }
+            ForeignWords::Spell => Ok(Some(Self::spell(word))),
+            ForeignWords::Phonemizer(phonemizer) => {
+                let ipa = phonemizer.phonemize(word)?.iter()
+                    .map(|sentence| sentence.phonemes.trim_end_matches(['.', ',', '!', '?', ';', ':']))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                Ok(Some(vec![ipa]))
+            }
         }
     }
 
-    pub fn load(&mut self) -> ort::Result<()> {
-        let encoder_model = Session::builder()?
-            .with_optimization_level(GraphOptimizationLevel::Level3)?
-            .with_intra_threads(4)?
-            .commit_from_file(&self.encoder_path)?;
+    /// Looks words up in a CMUdict-style ARPAbet dictionary before running
+    /// the neural G2P, which is then only used for out-of-vocabulary words.
+    pub fn with_lexicon(mut self, path: impl Into<PathBuf>) -> Self {
+        self.lexicon_path = Some(path.into());
+        self
+    }
 
-        let decoder_model = Session::builder()?
-            .with_optimization_level(GraphOptimizationLevel::Level3)?
-            .with_intra_threads(4)?
-            .commit_from_file(&self.decoder_path)?;
+    /// Sets how many word pronunciations are kept in memory, `0` disables the cache.
+    pub fn with_cache_capacity(mut self, capacity: usize) -> Self {
+        self.cache = NonZeroUsize::new(capacity).map(|capacity| Mutex::new(LruCache::new(capacity)));
+        self
+    }
 
-        let tokenizer = tokenizers::Tokenizer::from_file(&self.tokenizer_path)
-            .expect("Failed to load tokenizer");
+    /// Persists pronunciations to a JSON file which is read back on `load()`.
+    pub fn with_disk_cache(mut self, path: impl Into<PathBuf>) -> Self {
+        self.disk_cache_path = Some(path.into());
+        self
+    }
+
+    pub fn clear_cache(&self) {
+        if let Some(cache) = &self.cache {
+            lock_cache(cache).clear();
+        }
+    }
+
+    pub fn flush_cache(&self) -> Result<()> {
+        match &self.disk_cache {
+            Some(disk_cache) => lock_cache(disk_cache).flush(),
+            None => Ok(()),
+        }
+    }
+
+    fn cache_key(word: &str) -> String {
+        word.trim().to_lowercase()
+    }
+
+    fn cached(&self, key: &str) -> Option<Vec<String>> {
+        if let Some(ipa) = self.cache.as_ref().and_then(|cache| lock_cache(cache).get(key).cloned()) {
+            return Some(ipa);
+        }
+
+        let ipa = self.disk_cache.as_ref().and_then(|disk_cache| lock_cache(disk_cache).get(key).cloned())?;
+        self.remember(key, &ipa);
+        Some(ipa)
+    }
+
+    fn remember(&self, key: &str, ipa: &[String]) {
+        if let Some(cache) = &self.cache {
+            lock_cache(cache).put(key.to_string(), ipa.to_vec());
+        }
+    }
+
+    pub fn with_session_options(mut self, session_options: SessionOptions) -> Self {
+        self.session_options = session_options;
+        self
+    }
+
+    /// Uses a `decoder_with_past` export to reuse the key/value cache between
+    /// decode steps. The plain decoder must then export its `present.*` outputs.
+    pub fn with_decoder_with_past(mut self, decoder_with_past_path: String) -> Self {
+        self.decoder_with_past_source = Some(Asset::Path(decoder_with_past_path));
+        self
+    }
+
+    /// Like [`with_decoder_with_past`](Self::with_decoder_with_past) with the model's bytes.
+    pub fn with_decoder_with_past_bytes(mut self, decoder_with_past: impl Into<Cow<'static, [u8]>>) -> Self {
+        self.decoder_with_past_source = Some(Asset::Bytes(decoder_with_past.into()));
+        self
+    }
+
+    pub fn load(&mut self) -> Result<()> {
+        let encoder_model = build_session(&self.encoder_source, &self.session_options)?;
+        let decoder_model = build_session(&self.decoder_source, &self.session_options)?;
+        let decoder_with_past_model = self.decoder_with_past_source.as_ref()
+            .map(|source| build_session(source, &self.session_options))
+            .transpose()?;
+
+        let tokenizer = tokenizers::Tokenizer::from_bytes(self.tokenizer_source.read("tokenizer")?)
+            .map_err(|e| PiperError::AssetLoad {
+                what: "tokenizer",
+                path: self.tokenizer_source.name().to_string(),
+                reason: e.to_string(),
+            })?;
 
         let vocab = {
-            let vocab_data = std::fs::read_to_string(&self.vocab_path)
-                .expect("Failed to read vocabulary file");
-            let vocab_map: HashMap<String, usize> = serde_json::from_str(&vocab_data)
-                .expect("Failed to parse vocabulary JSON");
+            let vocab_data = self.vocab_source.read_to_string("vocabulary")?;
+            let vocab_map: HashMap<String, usize> = serde_json::from_str(&vocab_data)?;
 
             let mut reverse_vocab_map: HashMap<usize, String> = HashMap::new();
 
@@ -71,32 +418,57 @@ impl PhonemeGen {
         };
 
         let arpabet_mapping = {
-            let bytes = std::fs::read(self.arpabet_mapping_path.clone())
-                .expect("Failed to read the ARPAbet mapping file");
-            let mapping_data = String::from_utf8_lossy(&bytes).to_string();
-
-            let mut arpabet_to_ipa: HashMap<String, String> = HashMap::new();
-
-            for line in mapping_data.lines() {
-                let parts: Vec<&str> = line.split(", ").collect();
-                if parts.len() != 2 {
-                    continue;
-                }
-                let arpabet = parts[0].trim().to_string();
-                let ipa = parts[1].trim().to_string();
-                arpabet_to_ipa.insert(arpabet, ipa);
-            }
-            arpabet_to_ipa
+            let bytes = self.arpabet_mapping_source.read("ARPAbet mapping")?;
+            Self::parse_arpabet_mapping(&String::from_utf8_lossy(&bytes))
         };
 
-        self.encoder = Some(encoder_model);
-        self.decoder = Some(decoder_model);
+        self.encoder = Some(Mutex::new(encoder_model));
+        self.decoder = Some(Mutex::new(decoder_model));
+        self.decoder_with_past = decoder_with_past_model.map(Mutex::new);
+        if let Some(path) = &self.disk_cache_path {
+            self.disk_cache = Some(Mutex::new(DiskCache::open(path)?));
+        }
+        if let Some(path) = &self.lexicon_path {
+            self.lexicon = Some(Lexicon::from_file(path)?);
+        }
+        if let Some(path) = &self.user_lexicon_path {
+            let user_lexicon = UserLexicon::from_file(path)?;
+            if let Some((word, symbol)) = Self::unknown_arpabet_symbol(&user_lexicon, &arpabet_mapping) {
+                return Err(PiperError::AssetLoad {
+                    what: "user lexicon",
+                    path: path.display().to_string(),
+                    reason: format!("unknown ARPAbet symbol `{}` in the entry for `{}`", symbol, word),
+                });
+            }
+            self.user_lexicon.extend(user_lexicon);
+        }
         self.tokenizer = Some(tokenizer);
         self.vocab = Some(vocab);
         self.arpabet_mapping = Some(arpabet_mapping);
         Ok(())
     }
     
+    /// Parses `ARPAbet, IPA` lines, skipping anything else.
+    fn parse_arpabet_mapping(mapping_data: &str) -> HashMap<String, String> {
+        let mut arpabet_to_ipa: HashMap<String, String> = HashMap::new();
+
+        for line in mapping_data.lines() {
+            let parts: Vec<&str> = line.split(", ").collect();
+            if parts.len() != 2 {
+                continue;
+            }
+            let arpabet = parts[0].trim().to_string();
+            let ipa = parts[1].trim().to_string();
+            arpabet_to_ipa.insert(arpabet, ipa);
+        }
+        arpabet_to_ipa
+    }
+
+    fn log_sum_exp(logits: &[f32]) -> f32 {
+        let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        logits.iter().map(|logit| (logit - max).exp()).sum::<f32>().ln() + max
+    }
+
     fn argmax(slice: &[f32]) -> usize {
         let mut best = 0usize;
         let mut best_val = f32::NEG_INFINITY;
@@ -110,167 +482,731 @@ impl PhonemeGen {
     }
 
     pub fn word_to_tokens(
-        &mut self,
+        &self,
         word: &str,
-    ) -> Result<(Vec<usize>, Vec<String>), Box<dyn std::error::Error + Send + Sync>> {        
-        let encoded = self.tokenizer.as_mut().unwrap().encode(word, true)?;
-        let input_ids: Vec<i64> = encoded.get_ids().iter().map(|&id| id as i64).collect();
-        let attention_mask: Vec<i64> = vec![1; input_ids.len()];
+    ) -> Result<(Vec<usize>, Vec<String>)> {        
+        let mut batch = self.words_to_tokens(&[word])?;
+        Ok(batch.remove(0))
+    }
 
-        let input_array = Array2::<i64>::from_shape_vec([1, input_ids.len()], input_ids)?;
-        let attention_mask_array = Array2::<i64>::from_shape_vec([1, attention_mask.len()], attention_mask)?;
+    /// Runs the encoder and decoder once for the whole batch, padding the
+    /// token sequences to the longest word.
+    pub fn words_to_tokens(
+        &self,
+        words: &[&str],
+    ) -> Result<Vec<(Vec<usize>, Vec<String>)>> {
+        self.words_to_tokens_with(words, self.decoding)
+    }
+
+    /// Like [`words_to_tokens`](Self::words_to_tokens) with `decoding` for this call.
+    pub fn words_to_tokens_with(
+        &self,
+        words: &[&str],
+        decoding: Decoding,
+    ) -> Result<Vec<(Vec<usize>, Vec<String>)>> {
+        Ok(self.decode_words(words, decoding, None)?
+            .into_iter()
+            .map(|decoded| (decoded.ids, decoded.tokens))
+            .collect())
+    }
+
+    fn decode_words(
+        &self,
+        words: &[&str],
+        decoding: Decoding,
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<Vec<Decoded>> {
+        if words.is_empty() {
+            return Ok(Vec::new());
+        }
+        let _span = tracing::debug_span!("g2p", words = words.len()).entered();
+
+        let pad_id = 1i64;  // <pad>
+        let encodings = self.tokenizer.as_ref()
+            .ok_or(PiperError::NotLoaded("tokenizer"))?
+            .encode_batch(words.to_vec(), true)
+            .map_err(PiperError::Tokenizer)?;
+
+        let batch_size = encodings.len();
+        let max_len = encodings.iter().map(|e| e.get_ids().len()).max().unwrap_or(0);
+        let mut input_ids: Vec<i64> = Vec::with_capacity(batch_size * max_len);
+        let mut attention_mask: Vec<i64> = Vec::with_capacity(batch_size * max_len);
+        for encoding in &encodings {
+            let ids = encoding.get_ids();
+            input_ids.extend(ids.iter().map(|&id| id as i64));
+            input_ids.extend(std::iter::repeat_n(pad_id, max_len - ids.len()));
+            attention_mask.extend(std::iter::repeat_n(1, ids.len()));
+            attention_mask.extend(std::iter::repeat_n(0, max_len - ids.len()));
+        }
+
+        let input_array = Array2::<i64>::from_shape_vec([batch_size, max_len], input_ids)?;
+        let attention_mask_array = Array2::<i64>::from_shape_vec([batch_size, max_len], attention_mask)?;
         
-        let input_ids_tensor = ort::value::Tensor::from_array(input_array);
-        let attention_mask_tensor = ort::value::Tensor::from_array(attention_mask_array.clone());
+        let input_ids_tensor = ort::value::Tensor::from_array(input_array)?;
+        let attention_mask_tensor = ort::value::Tensor::from_array(attention_mask_array.clone())?;
 
         let encoder_output_array = {
-            let encoder_outputs = self.encoder.as_mut().unwrap().run(vec![
-                ("input_ids", input_ids_tensor?),
-                ("attention_mask", attention_mask_tensor?),
-            ])?;
+            let mut encoder = lock_session(self.encoder.as_ref().ok_or(PiperError::NotLoaded("encoder"))?, "encoder")?;
+            let encoder_outputs = encoder.run(ort::inputs!{
+                    "input_ids" => input_ids_tensor,
+                    "attention_mask" => attention_mask_tensor,
+                })?;
 
             let (encoder_output_shape, encoder_output_tensor) = encoder_outputs.get("last_hidden_state")
-                .expect("Failed to get encoder output")
+                .ok_or(PiperError::MissingOutput("last_hidden_state"))?
                 .try_extract_tensor::<f32>()?;
 
             Array3::<f32>::from_shape_vec(
-                [1, encoder_output_shape[1] as usize, encoder_output_shape[2] as usize],
+                [batch_size, encoder_output_shape[1] as usize, encoder_output_shape[2] as usize],
                 encoder_output_tensor.to_vec()
             )?
         };
 
-        let (token_ids, tokens) = self.greedy_decode(
-            &encoder_output_array,
-            &attention_mask_array,
-            50,
-        )?;
-        Ok((token_ids, tokens))
+        match decoding {
+            Decoding::Beam { width, length_penalty } if width > 1 => self.beam_decode(
+                &encoder_output_array,
+                &attention_mask_array,
+                50,
+                width,
+                length_penalty,
+                cancellation,
+            ),
+            _ => self.greedy_decode(
+                &encoder_output_array,
+                &attention_mask_array,
+                50,
+                cancellation,
+            ),
+        }
+    }
+
+    /// A decoded sequence without its special tokens.
+    fn decoded(&self, ids: &[i64], confidence: f32) -> Result<Decoded> {
+        let vocab = &self.vocab.as_ref().ok_or(PiperError::NotLoaded("vocabulary"))?.1;
+        let (ids, tokens) = ids.iter()
+            .filter(|&&id| id > 2) // <s>, <pad> and </s>
+            .map(|&id| {
+                let id = id as usize;
+                (id, vocab.get(&id).cloned().unwrap_or_else(|| format!("<{}>", id)))
+            })
+            .unzip();
+        Ok(Decoded { ids, tokens, confidence })
+    }
+
+    /// Beam search over `width` hypotheses per word, all of them decoded as one
+    /// batch of `words * width` rows.
+    fn beam_decode(
+        &self,
+        encoder_output: &Array3<f32>,
+        encoder_attention_mask: &Array2<i64>,
+        max_len: usize,
+        width: usize,
+        length_penalty: f32,
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<Vec<Decoded>> {
+        let bos_id = 2i64;  // </s> is used as BOS for BART decoder
+        let eos_id = 2i64;  // </s>
+        let pad_id = 1i64;  // <pad>
+
+        let batch_size = encoder_output.shape()[0];
+        let rows: Vec<usize> = (0..batch_size).flat_map(|b| std::iter::repeat_n(b, width)).collect();
+        let encoder_output_value = ort::value::Value::from_array(encoder_output.select(Axis(0), &rows))?;
+        let encoder_attention_mask_value = ort::value::Value::from_array(encoder_attention_mask.select(Axis(0), &rows))?;
+        let mut decoder = lock_session(self.decoder.as_ref().ok_or(PiperError::NotLoaded("decoder"))?, "decoder")?;
+
+        // Live hypotheses per word as (ids, summed log-probability), and the finished
+        // ones as (length-normalized score, summed log-probability, ids).
+        let mut alive: Vec<Vec<(Vec<i64>, f32)>> = vec![vec![(vec![bos_id], 0.0)]; batch_size];
+        let mut finished: Vec<Vec<(f32, f32, Vec<i64>)>> = vec![Vec::new(); batch_size];
+        let normalized = |ids: &[i64], score: f32| score / ((ids.len() - 1) as f32).powf(length_penalty);
+
+        for step in 0..max_len {
+            if let Some(token) = cancellation {
+                token.check()?;
+            }
+            if (0..batch_size).all(|b| alive[b].is_empty() || finished[b].len() >= width) {
+                break;
+            }
+
+            // Words short of `width` live hypotheses are padded with dummy rows.
+            let seq_len = step + 1;
+            let mut decoder_ids: Vec<i64> = Vec::with_capacity(batch_size * width * seq_len);
+            for hypotheses in &alive {
+                for k in 0..width {
+                    match hypotheses.get(k) {
+                        Some((ids, _)) => decoder_ids.extend(ids),
+                        None => decoder_ids.extend(std::iter::repeat_n(pad_id, seq_len)),
+                    }
+                }
+            }
+            let dec_array = Array2::<i64>::from_shape_vec([batch_size * width, seq_len], decoder_ids)?;
+            let outputs = decoder.run(ort::inputs!{
+                "encoder_attention_mask" => &encoder_attention_mask_value,
+                "input_ids" => ort::value::Value::from_array(dec_array)?,
+                "encoder_hidden_states" => &encoder_output_value,
+            })?;
+            let (shape, flat_logits) = outputs
+                .get("logits")
+                .ok_or(PiperError::MissingOutput("logits"))?
+                .try_extract_tensor::<f32>()?;
+            if shape.len() != 3 || shape[0] as usize != batch_size * width {
+                return Err(PiperError::InvalidShape(format!("logits {:?}", shape)));
+            }
+            let vocab_size = shape[2] as usize;
+            let cur_decoder_seq_len = shape[1] as usize;
+
+            for b in 0..batch_size {
+                if finished[b].len() >= width {
+                    alive[b].clear();
+                    continue;
+                }
+                let mut candidates: Vec<(usize, i64, f32)> = Vec::new();
+                for (k, (_, score)) in alive[b].iter().enumerate() {
+                    let row = b * width + k;
+                    let start = (row * cur_decoder_seq_len + cur_decoder_seq_len - 1) * vocab_size;
+                    let logits = &flat_logits[start..start + vocab_size];
+                    let log_sum = Self::log_sum_exp(logits);
+                    let mut top: Vec<(i64, f32)> = logits.iter().enumerate()
+                        .map(|(id, logit)| (id as i64, score + logit - log_sum))
+                        .collect();
+                    top.sort_unstable_by(|a, b| b.1.total_cmp(&a.1));
+                    candidates.extend(top.into_iter().take(width).map(|(id, score)| (k, id, score)));
+                }
+                candidates.sort_unstable_by(|a, b| b.2.total_cmp(&a.2));
+
+                let mut next = Vec::with_capacity(width);
+                for (k, id, score) in candidates {
+                    if next.len() >= width || finished[b].len() >= width {
+                        break;
+                    }
+                    let mut ids = alive[b][k].0.clone();
+                    ids.push(id);
+                    if id == eos_id {
+                        finished[b].push((normalized(&ids, score), score, ids));
+                    } else {
+                        next.push((ids, score));
+                    }
+                }
+                alive[b] = next;
+            }
+        }
+
+        // Words that never finished within `max_len` fall back to their best live hypothesis.
+        (0..batch_size)
+            .map(|b| {
+                let best = finished[b].iter()
+                    .max_by(|x, y| x.0.total_cmp(&y.0))
+                    .map(|(_, score, ids)| (ids, *score))
+                    .or_else(|| alive[b].iter().max_by(|x, y| x.1.total_cmp(&y.1)).map(|(ids, score)| (ids, *score)));
+                match best {
+                    Some((ids, score)) => self.decoded(ids, score / (ids.len() - 1).max(1) as f32),
+                    None => self.decoded(&[], 0.0),
+                }
+            })
+            .collect()
     }
 
     fn greedy_decode(
-        &mut self,
+        &self,
         encoder_output: &Array3<f32>,
         encoder_attention_mask: &Array2<i64>,
         max_len: usize,
-    ) -> Result<(Vec<usize>, Vec<String>), Box<dyn std::error::Error + Send + Sync>> {
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<Vec<Decoded>> {
         let bos_id = 2i64;  // </s> is used as BOS for BART decoder
         let eos_id = 2i64;  // </s>
         let pad_id = 1i64;  // <pad>
         let s_id = 0i64;    // <s>
 
-        let mut decoder_ids: Vec<i64> = vec![bos_id];
-        let mut decoded_ids: Vec<usize> = Vec::new();
-        let mut decoded_tokens: Vec<String> = Vec::new();
+        let batch_size = encoder_output.shape()[0];
+        let mut decoder_ids: Vec<Vec<i64>> = vec![vec![bos_id]; batch_size];
+        let mut decoded: Vec<(Vec<usize>, Vec<String>)> = vec![(Vec::new(), Vec::new()); batch_size];
+        // Summed log-probability and number of the chosen tokens.
+        let mut log_probs: Vec<(f32, usize)> = vec![(0.0, 0); batch_size];
+        let mut finished = vec![false; batch_size];
+
+        let encoder_output_value = ort::value::Value::from_array(encoder_output.clone())?;
+        let encoder_attention_mask_value = ort::value::Value::from_array(encoder_attention_mask.clone())?;
+        let use_cache = self.decoder_with_past.is_some();
+        let mut past_key_values: Vec<(String, DynValue)> = Vec::new();
+        let mut decoder = lock_session(self.decoder.as_ref().ok_or(PiperError::NotLoaded("decoder"))?, "decoder")?;
+        let mut decoder_with_past = self.decoder_with_past.as_ref()
+            .map(|session| lock_session(session, "decoder_with_past"))
+            .transpose()?;
 
         for _step in 0..max_len {
-            let seq_len = decoder_ids.len();
-            let dec_array = Array2::<i64>::from_shape_vec([1, seq_len], decoder_ids.clone())
-                .expect("Failed to create decoder Array2");
-            let dec_input_value = ort::value::Value::from_array(dec_array)?;
-            let encoder_output_value = ort::value::Value::from_array(encoder_output.clone())?;
-            let encoder_attention_mask_value = ort::value::Value::from_array(encoder_attention_mask.clone())?;
-            let inputs = ort::inputs!{
-                "encoder_attention_mask" => encoder_attention_mask_value,
-                "input_ids" => dec_input_value,
-                "encoder_hidden_states" => encoder_output_value,
+            if let Some(token) = cancellation {
+                token.check()?;
+            }
+            let outputs = match decoder_with_past.as_mut() {
+                Some(decoder_with_past) if !past_key_values.is_empty() => {
+                    let last_ids = decoder_ids.iter().map(|ids| ids[ids.len() - 1]).collect();
+                    let dec_array = Array2::<i64>::from_shape_vec([batch_size, 1], last_ids)?;
+                    let mut inputs = ort::inputs!{
+                        "encoder_attention_mask" => &encoder_attention_mask_value,
+                        "input_ids" => ort::value::Value::from_array(dec_array)?,
+                    };
+                    let accepts = |name: &str| decoder_with_past.inputs.iter().any(|input| input.name == name);
+                    if accepts("encoder_hidden_states") {
+                        inputs.push(("encoder_hidden_states".into(), (&encoder_output_value).into()));
+                    }
+                    for (name, value) in &past_key_values {
+                        if accepts(name) {
+                            inputs.push((name.clone().into(), value.into()));
+                        }
+                    }
+                    decoder_with_past.run(inputs)?
+                }
+                _ => {
+                    let seq_len = decoder_ids[0].len();
+                    let dec_array = Array2::<i64>::from_shape_vec(
+                        [batch_size, seq_len],
+                        decoder_ids.concat(),
+                    )?;
+                    let inputs = ort::inputs!{
+                        "encoder_attention_mask" => &encoder_attention_mask_value,
+                        "input_ids" => ort::value::Value::from_array(dec_array)?,
+                        "encoder_hidden_states" => &encoder_output_value,
+                    };
+                    decoder.run(inputs)?
+                }
             };
-            let outputs = self.decoder.as_mut().unwrap().run(inputs)?;
             let (shape, flat_logits) = outputs
                 .get("logits")
-                .expect("No 'logits' output")
+                .ok_or(PiperError::MissingOutput("logits"))?
                 .try_extract_tensor::<f32>()?;
-            if shape.len() != 3 {
-                panic!("Unexpected logits shape: {:?}", shape);
+            if shape.len() != 3 || shape[0] as usize != batch_size {
+                return Err(PiperError::InvalidShape(format!("logits {:?}", shape)));
             }
             let vocab_size = shape[2] as usize;
             let cur_decoder_seq_len = shape[1] as usize;
-            let start = (cur_decoder_seq_len - 1) * vocab_size;
-            let end = start + vocab_size;
-            let last_logits_slice = &flat_logits[start..end];
 
-            let next_id_usize = PhonemeGen::argmax(last_logits_slice);
-            let next_id = next_id_usize as i64;
-            decoder_ids.push(next_id);
+            for (b, ids) in decoder_ids.iter_mut().enumerate() {
+                if finished[b] {
+                    ids.push(pad_id);
+                    continue;
+                }
+
+                let start = (b * cur_decoder_seq_len + cur_decoder_seq_len - 1) * vocab_size;
+                let end = start + vocab_size;
+                let last_logits_slice = &flat_logits[start..end];
 
-            let tok_str = self.vocab.as_ref().unwrap().1.get(&next_id_usize)
-                .cloned()
-                .unwrap_or_else(|| format!("<{}>", next_id_usize));
+                let next_id_usize = PhonemeGen::argmax(last_logits_slice);
+                let next_id = next_id_usize as i64;
+                ids.push(next_id);
+                log_probs[b].0 += last_logits_slice[next_id_usize] - Self::log_sum_exp(last_logits_slice);
+                log_probs[b].1 += 1;
+
+                if next_id == eos_id {
+                    finished[b] = true;
+                    continue;
+                }
+
+                if next_id != bos_id && next_id != pad_id && next_id != eos_id && next_id != s_id {
+                    let tok_str = self.vocab.as_ref()
+                        .ok_or(PiperError::NotLoaded("vocabulary"))?
+                        .1.get(&next_id_usize)
+                        .cloned()
+                        .unwrap_or_else(|| format!("<{}>", next_id_usize));
+                    decoded[b].0.push(next_id_usize);
+                    decoded[b].1.push(tok_str);
+                }
+            }
 
-            if next_id == eos_id {
+            if finished.iter().all(|&done| done) {
                 break;
             }
 
-            if next_id != bos_id && next_id != pad_id && next_id != eos_id && next_id != s_id {
-                decoded_ids.push(next_id_usize);
-                decoded_tokens.push(tok_str);
+            if use_cache {
+                for (name, value) in outputs {
+                    let Some(suffix) = name.strip_prefix("present") else {
+                        continue;
+                    };
+                    let past_name = format!("past_key_values{}", suffix);
+                    match past_key_values.iter_mut().find(|(existing, _)| *existing == past_name) {
+                        Some(entry) => entry.1 = value,
+                        None => past_key_values.push((past_name, value)),
+                    }
+                }
             }
         }
 
-        Ok((decoded_ids, decoded_tokens))
+        Ok(decoded.into_iter()
+            .zip(log_probs)
+            .map(|((ids, tokens), (log_prob, steps))| Decoded { ids, tokens, confidence: log_prob / steps.max(1) as f32 })
+            .collect())
+    }
+
+    fn is_arpabet_vowel(symbol: &str) -> bool {
+        ARPABET_VOWELS.contains(&symbol)
+    }
+
+    /// Whether the consonants can start an English syllable (`S T R`, `P L`, ...).
+    fn is_legal_onset(cluster: &[&str]) -> bool {
+        match cluster {
+            [single] => *single != "NG",
+            [first, second] => {
+                let liquid_glide = matches!(*second, "L" | "R" | "W" | "Y");
+                (liquid_glide && matches!(*first, "P" | "B" | "T" | "D" | "K" | "G" | "F" | "TH" | "SH")
+                    && !matches!((*first, *second), ("T" | "D", "L")))
+                    || (*first == "S" && matches!(*second, "P" | "T" | "K" | "M" | "N" | "L" | "W" | "F"))
+            }
+            [first, second, third] => {
+                *first == "S" && matches!(*second, "P" | "T" | "K") && matches!(*third, "L" | "R" | "W" | "Y")
+            }
+            _ => false,
+        }
     }
 
-    pub fn arpabet_to_ipa(&self, word: Vec<String>) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
-        if let Some(mapping) = &self.arpabet_mapping {
-            let mut ipa_phonemes = Vec::new();
-            for phoneme in word {
-                if let Some(ipa) = mapping.get(&phoneme) {
-                    ipa_phonemes.push(ipa.clone());
-                } else {
-                    ipa_phonemes.push(phoneme); // Fallback to original if no mapping found
+    /// Maps ARPAbet to IPA, turning the stress digits of vowels into `ˈ`/`ˌ`
+    /// placed at the start of their syllable using the maximal onset principle.
+    pub fn arpabet_to_ipa(&self, word: Vec<String>) -> Result<Vec<String>> {
+        let mapping = self.arpabet_mapping.as_ref()
+            .ok_or(PiperError::NotLoaded("ARPAbet mapping"))?;
+
+        let bases: Vec<&str> = word.iter()
+            .map(|phoneme| phoneme.trim_end_matches(|c: char| c.is_ascii_digit()))
+            .collect();
+        let mut ipa_phonemes: Vec<String> = word.iter().zip(&bases)
+            .map(|(phoneme, base)| {
+                mapping.get(phoneme)
+                    .or_else(|| mapping.get(*base))
+                    .map(|ipa| ipa.replace(['ˈ', 'ˌ'], ""))
+                    .unwrap_or_else(|| phoneme.clone()) // Fallback to original if no mapping found
+            })
+            .collect();
+
+        let mut syllable_start = 0;
+        for (i, phoneme) in word.iter().enumerate() {
+            if !Self::is_arpabet_vowel(bases[i]) {
+                continue;
+            }
+
+            let mark = match phoneme.chars().last() {
+                Some('1') => Some('ˈ'),
+                Some('2') => Some('ˌ'),
+                _ => None,
+            };
+            if let Some(mark) = mark {
+                let mut onset = i;
+                while onset > syllable_start && Self::is_legal_onset(&bases[onset - 1..i]) {
+                    onset -= 1;
                 }
+                ipa_phonemes[onset].insert(0, mark);
             }
-            Ok(ipa_phonemes)
-        } else {
-            Err("ARPAbet mapping not loaded".into())
+            syllable_start = i + 1;
         }
+
+        Ok(ipa_phonemes)
     }
 
     pub fn process_word(
-        &mut self,
+        &self,
         word: &str,
-    ) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<Vec<String>> {
+        let mut batch = self.process_words(&[word])?;
+        Ok(batch.remove(0))
+    }
+
+    pub fn process_words(
+        &self,
+        words: &[&str],
+    ) -> Result<Vec<Vec<String>>> {
+        self.phonemize_words(words, self.decoding, None)
+    }
+
+    /// Like [`process_words`](Self::process_words) with `decoding` for this
+    /// call, which bypasses the caches unless it is the default one.
+    pub fn process_words_with(
+        &self,
+        words: &[&str],
+        decoding: Decoding,
+    ) -> Result<Vec<Vec<String>>> {
+        self.phonemize_words(words, decoding, None)
+    }
+
+    /// Decodes the words of `text` that no lexicon covers, bypassing the
+    /// caches, and reports how sure the G2P is of each pronunciation.
+    pub fn confidences(&self, text: &str) -> Result<Vec<WordConfidence>> {
+        let mut words: Vec<String> = Vec::new();
+        for sentence in Self::split_sentences(&self.normalize(text)) {
+            for word in Self::split_words(&sentence) {
+                let (_, core, _) = Self::split_punctuation(word);
+                if !core.chars().any(char::is_alphanumeric) || Self::phoneme_escape(core).is_some() {
+                    continue;
+                }
+                let key = Self::cache_key(core);
+                let in_lexicon = self.user_pronunciation(&key).is_some()
+                    || self.lexicon.as_ref().is_some_and(|lexicon| lexicon.get(&key).is_some());
+                let foreign_fallback = Self::is_foreign(&key) && !matches!(self.foreign_words, ForeignWords::Guess);
+                if !in_lexicon && !foreign_fallback && !words.contains(&key) {
+                    words.push(key);
+                }
+            }
+        }
+
+        let mut confidences = Vec::with_capacity(words.len());
+        for batch in words.chunks(64) {
+            let batch: Vec<&str> = batch.iter().map(String::as_str).collect();
+            for (word, decoded) in batch.iter().zip(self.decode_words(&batch, self.decoding, None)?) {
+                confidences.push(WordConfidence {
+                    word: word.to_string(),
+                    ipa: self.arpabet_to_ipa(decoded.tokens)?.concat(),
+                    confidence: decoded.confidence,
+                });
+            }
+        }
+        Ok(confidences)
+    }
+
+    /// The words of `text` the G2P pronounces with a confidence below
+    /// `threshold`, e.g. `-0.5`, which are worth adding to the user lexicon.
+    pub fn low_confidence_words(&self, text: &str, threshold: f32) -> Result<Vec<WordConfidence>> {
+        let mut words = self.confidences(text)?;
+        words.retain(|word| word.confidence < threshold);
+        Ok(words)
+    }
+
+    fn phonemize_words(
+        &self,
+        words: &[&str],
+        decoding: Decoding,
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<Vec<Vec<String>>> {
         if self.encoder.is_none() || self.decoder.is_none() || self.tokenizer.is_none() {
-            return Err("Models and tokenizer not loaded".into());
+            return Err(PiperError::NotLoaded("models and tokenizer"));
         }
-        
-        let tokens = self.word_to_tokens(word)?;
-        if tokens.0.is_empty() {
-            return Err("No tokens generated".into());
+
+        // The caches hold what the default decoding made of a word.
+        let use_cache = decoding == self.decoding;
+        let keys: Vec<String> = words.iter().map(|word| Self::cache_key(word)).collect();
+        let mut ipa_words: Vec<Option<Vec<String>>> = keys.iter()
+            .map(|key| match self.user_pronunciation(key) {
+                Some(ipa) => Some(ipa),
+                None if use_cache => self.cached(key),
+                None => None,
+            })
+            .collect();
+
+        let mut misses: Vec<&str> = Vec::new();
+        for (key, ipa) in keys.iter().zip(&ipa_words) {
+            if ipa.is_none() && !misses.contains(&key.as_str()) {
+                misses.push(key);
+            }
         }
 
-        let ipa_phonemes = self.arpabet_to_ipa(tokens.1)?;
-        Ok(ipa_phonemes)
+        let mut phonemized: HashMap<&str, Vec<String>> = HashMap::new();
+        let mut out_of_vocabulary: Vec<&str> = Vec::new();
+        for word in misses {
+            match self.lexicon.as_ref().and_then(|lexicon| lexicon.get(word)).cloned() {
+                Some(arpabet) => {
+                    let ipa = self.arpabet_to_ipa(arpabet)?;
+                    self.remember(word, &ipa);
+                    phonemized.insert(word, ipa);
+                }
+                None if Self::is_foreign(word) => match self.foreign_pronunciation(word)? {
+                    Some(ipa) => {
+                        self.remember(word, &ipa);
+                        phonemized.insert(word, ipa);
+                    }
+                    None => out_of_vocabulary.push(word),
+                },
+                None => out_of_vocabulary.push(word),
+            }
+        }
+
+        let batch = self.decode_words(&out_of_vocabulary, decoding, cancellation)?;
+        for (word, decoded) in out_of_vocabulary.iter().zip(batch) {
+            if decoded.ids.is_empty() {
+                return Err(PiperError::MissingPhoneme(word.to_string()));
+            }
+            let ipa = self.arpabet_to_ipa(decoded.tokens)?;
+            if use_cache {
+                self.remember(word, &ipa);
+                if let Some(disk_cache) = &self.disk_cache {
+                    lock_cache(disk_cache).insert(word.to_string(), ipa.clone())?;
+                }
+            }
+            phonemized.insert(word, ipa);
+        }
+
+        for (key, ipa) in keys.iter().zip(ipa_words.iter_mut()) {
+            if ipa.is_none() {
+                *ipa = phonemized.get(key.as_str()).cloned();
+            }
+        }
+        Ok(ipa_words.into_iter().flatten().collect())
+    }
+
+    /// Splits on whitespace, keeping `[[...]]` phoneme escapes together even
+    /// when they contain spaces.
+    pub(crate) fn split_words(text: &str) -> Vec<&str> {
+        let mut words = Vec::new();
+        let mut rest = text.trim_start();
+        while !rest.is_empty() {
+            let mut end = match rest.strip_prefix("[[") {
+                Some(escape) => escape.find("]]").map(|i| i + 4).unwrap_or(0),
+                None => 0,
+            };
+            end += rest[end..].find(char::is_whitespace).unwrap_or(rest.len() - end);
+            words.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+        words
     }
 
+    /// Returns the IPA enclosed in a `[[...]]` escape, if `word` is one.
+    pub(crate) fn phoneme_escape(word: &str) -> Option<&str> {
+        word.strip_prefix("[[")?.strip_suffix("]]")
+    }
+
+    /// Maps full-width and typographic punctuation to the ASCII forms the voices know.
+    fn ascii_punctuation(c: char) -> char {
+        match c {
+            '。' | '．' | '｡' => '.',
+            '！' => '!',
+            '？' => '?',
+            '，' | '、' => ',',
+            '；' => ';',
+            '：' => ':',
+            '“' | '”' | '„' | '«' | '»' | '「' | '」' | '『' | '』' => '"',
+            '‘' | '’' => '\'',
+            '（' => '(',
+            '）' => ')',
+            c => c,
+        }
+    }
+
+    /// Whether a full stop after the last word of `sentence` is an abbreviation
+    /// or an initial rather than the end of the sentence, `next` being the text after it.
+    fn ends_with_abbreviation(sentence: &str, next: &str) -> bool {
+        let Some(last_word) = sentence.split_whitespace().last() else {
+            return false;
+        };
+        let last_word = last_word.trim_start_matches(|c: char| c.is_ascii_punctuation());
+        let lower = last_word.to_lowercase();
+        if NON_TERMINAL_ABBREVIATIONS.contains(&lower) {
+            return true;
+        }
+        if NUMBER_ABBREVIATIONS.contains(&lower.as_str()) {
+            return next.trim_start().starts_with(|c: char| c.is_ascii_digit());
+        }
+
+        // A single capital initial, e.g. `J.` in `J. R. R. Tolkien`, except for
+        // `I` and `A` which are just as likely to be words ending a sentence.
+        let mut chars = last_word.chars();
+        matches!(
+            (chars.next(), chars.next(), chars.next()),
+            (Some(c), Some('.'), None) if c.is_uppercase() && c != 'I' && c != 'A'
+        )
+    }
+
+    /// Splits text into sentences following the Unicode sentence boundary rules,
+    /// which handle closing quotes and brackets as well as non-ASCII terminators,
+    /// without splitting after abbreviations and initials.
     pub fn text_to_sentences(
         &self,
         text: &str,
-    ) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
-        let sentence_endings = vec![".", "!", "?"];
+    ) -> Result<Vec<String>> {
+        Ok(Self::split_sentences(text))
+    }
+
+    pub(crate) fn split_sentences(text: &str) -> Vec<String> {
         let mut sentences: Vec<String> = Vec::new();
 
         let mut current_sentence = String::new();
-        for word in text.split_whitespace() {
-            if sentence_endings.iter().any(|&ending| word.ends_with(ending)) {
-                current_sentence.push_str(word);
-                sentences.push(current_sentence.trim().to_string());
-                current_sentence.clear();
-            } else {
-                current_sentence.push_str(word);
-                current_sentence.push(' ');
+        let mut segments = text.split_sentence_bounds().peekable();
+        while let Some(segment) = segments.next() {
+            current_sentence.push_str(segment);
+            // Never split inside a `[[...]]` escape, IPA may contain dots.
+            if current_sentence.matches("[[").count() > current_sentence.matches("]]").count() {
+                continue;
             }
+            if Self::ends_with_abbreviation(&current_sentence, segments.peek().copied().unwrap_or_default()) {
+                continue;
+            }
+
+            let sentence = Self::split_words(&current_sentence).join(" ")
+                .chars().map(Self::ascii_punctuation).collect::<String>();
+            if !sentence.is_empty() {
+                sentences.push(sentence);
+            }
+            current_sentence.clear();
+        }
+
+        let sentence = Self::split_words(&current_sentence).join(" ");
+        if !sentence.is_empty() {
+            sentences.push(sentence.chars().map(Self::ascii_punctuation).collect());
         }
+        sentences
+    }
 
-        if !current_sentence.is_empty() {
-            sentences.push(current_sentence.trim().to_string());
+    /// Splits a word into its leading punctuation, core and trailing punctuation,
+    /// keeping the brackets of `[[...]]` escapes in the core.
+    pub(crate) fn split_punctuation(word: &str) -> (&str, &str, &str) {
+        let start = word.find(|c: char| !c.is_ascii_punctuation() || c == '[')
+            .unwrap_or(word.len());
+        let end = word.char_indices().rev()
+            .find(|&(_, c)| !c.is_ascii_punctuation() || c == ']')
+            .map(|(i, c)| i + c.len_utf8())
+            .unwrap_or(start)
+            .max(start);
+        (&word[..start], &word[start..end], &word[end..])
+    }
+
+    /// Phonemizes a single sentence into plain IPA, keeping its punctuation.
+    pub fn phonemize_sentence(
+        &self,
+        sentence: &str,
+    ) -> Result<String> {
+        self.sentence_to_ipa(sentence, None)
+    }
+
+    fn sentence_to_ipa(
+        &self,
+        sentence: &str,
+        cancellation: Option<&CancellationToken>,
+    ) -> Result<String> {
+        let mut processed_sentence: String = String::new();
+
+        let words: Vec<(&str, String)> = Self::split_words(sentence).into_iter()
+            .map(|word| {
+                // Spaced dashes are kept as `—` so pauses can be mapped to them.
+                if word.chars().all(|c| matches!(c, '-' | '–' | '—')) {
+                    return ("", "—".to_string());
+                }
+                let (_, core, trailing) = Self::split_punctuation(word);
+                let punctuation = trailing.chars().filter(|c| ".,!?;:".contains(*c)).collect();
+                (core, punctuation)
+            })
+            .collect();
+        let bare_words: Vec<&str> = words.iter()
+            .map(|(word, _)| *word)
+            .filter(|word| !word.is_empty() && Self::phoneme_escape(word).is_none())
+            .collect();
+        let mut phonemized = self.phonemize_words(&bare_words, self.decoding, cancellation)?.into_iter();
+
+        for (word, punctuation) in words {
+            match Self::phoneme_escape(word) {
+                Some(ipa) => processed_sentence.push_str(ipa.trim()),
+                None if word.is_empty() => {}
+                None => {
+                    let token_phonemes = phonemized.next().unwrap_or_default();
+                    processed_sentence.push_str(&token_phonemes.join(""));
+                }
+            }
+
+            processed_sentence.push_str(&punctuation);
+            processed_sentence.push(' ');
         }
-        Ok(sentences)
+
+        Ok(processed_sentence.trim().to_string())
     }
 
     pub fn process_senteces(
-        &mut self,
+        &self,
         sentences: Vec<String>,
-    ) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+    ) -> Result<Vec<String>> {
         let mut processed_sentences: Vec<String> = Vec::new();
         for sentence in sentences {
             let bos = "^";
@@ -280,39 +1216,133 @@ impl PhonemeGen {
             let mut processed_sentence: String = String::new();
 
             processed_sentence.push_str(bos);
-            for word in sentence.split_whitespace() {                
-                let punctuation = word.chars().last().map(|c| if c.is_ascii_punctuation() { c } else { ' ' }).unwrap_or(' ');
-                let word_without_punctuation = word.trim_end_matches(punctuation);
-
-                let token_phonemes = self.process_word(word_without_punctuation)?;
-
-                if !token_phonemes.is_empty() {
-                    processed_sentence.push_str(&token_phonemes.join(""));
-                }            
-                if punctuation != ' ' {
-                    processed_sentence.push(punctuation);
-                }
-                processed_sentence.push(' ');
-            }
-            processed_sentence = processed_sentence.trim().to_string()
-                .chars().map(|c| c.to_string()).collect::<Vec<String>>().join(pad);
-
+            processed_sentence.push_str(
+                &self.phonemize_sentence(&sentence)?
+                    .chars().map(|c| c.to_string()).collect::<Vec<String>>().join(pad)
+            );
             processed_sentence.push_str(pad);
             processed_sentence.push_str(eos);
 
-            if !processed_sentence.is_empty() {
-                processed_sentences.push(processed_sentence);
-            }
+            processed_sentences.push(processed_sentence);
         }
 
         Ok(processed_sentences)
     }
 
+    /// Phonemizes `text`, passing `[[...]]` escapes straight through as IPA.
     pub fn process_text(
-        &mut self,
+        &self,
         text: &str,
-    ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
-        let sentences = self.text_to_sentences(text)?;
-        Ok(self.process_senteces(sentences).unwrap().join(""))
+    ) -> Result<String> {
+        let sentences = self.text_to_sentences(&self.normalize(text))?;
+        Ok(self.process_senteces(sentences)?.join(""))
+    }
+}
+
+impl Phonemizer for PhonemeGen {
+    fn phonemize(&self, text: &str) -> Result<Vec<PhonemizedSentence>> {
+        self.phonemize_cancellable(text, None)
+    }
+
+    fn phonemize_cancellable(&self, text: &str, cancellation: Option<&CancellationToken>) -> Result<Vec<PhonemizedSentence>> {
+        self.text_to_sentences(&self.normalize(text))?
+            .into_iter()
+            .map(|sentence| {
+                if let Some(token) = cancellation {
+                    token.check()?;
+                }
+                let phonemes = self.sentence_to_ipa(&sentence, cancellation)?;
+                Ok(PhonemizedSentence { text: sentence, phonemes })
+            })
+            .collect()
     }
-}
\ No newline at end of file
+
+    /// Decodes a short word through the encoder and decoder, and the
+    /// `decoder_with_past` when set, bypassing the caches.
+    fn warm_up(&self) -> Result<()> {
+        self.words_to_tokens(&["hello"]).map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_ends_a_sentence_unless_a_number_follows() {
+        assert_eq!(PhonemeGen::split_sentences("See No. 5 for details."), ["See No. 5 for details."]);
+        assert_eq!(PhonemeGen::split_sentences("The answer is no. We left."), ["The answer is no.", "We left."]);
+    }
+
+    #[test]
+    fn titles_and_initials_do_not_split() {
+        assert_eq!(PhonemeGen::split_sentences("Dr. Smith met J. Doe. He left."), ["Dr. Smith met J. Doe.", "He left."]);
+        assert_eq!(PhonemeGen::split_sentences("See fig. 3 and vol. 2 now."), ["See fig. 3 and vol. 2 now."]);
+    }
+
+    #[test]
+    fn terminal_abbreviations_end_sentences() {
+        assert_eq!(PhonemeGen::split_sentences("He works at Acme Inc. She does not."), ["He works at Acme Inc.", "She does not."]);
+    }
+
+    #[test]
+    fn terminal_abbreviations_end_normalized_sentences() {
+        let split = |text: &str| PhonemeGen::split_sentences(&TextNormalizer::default().normalize(text));
+        assert_eq!(split("He works at Acme Inc. She does not."), ["He works at Acme incorporated.", "She does not."]);
+        assert_eq!(split("Dr. Smith met J. Doe. He left."), ["doctor Smith met J. Doe.", "He left."]);
+        assert_eq!(split("Smith & Co. sells tea."), ["Smith & company sells tea."]);
+    }
+
+    #[test]
+    fn finds_unknown_arpabet_symbols() {
+        let mapping: HashMap<String, String> = [("K", "k"), ("AE", "æ"), ("T", "t")]
+            .into_iter().map(|(arpabet, ipa)| (arpabet.to_string(), ipa.to_string())).collect();
+        let lexicon = UserLexicon::parse("cat K AE1 T\nkat ˈkæt\n");
+        assert_eq!(PhonemeGen::unknown_arpabet_symbol(&lexicon, &mapping), None);
+        let lexicon = UserLexicon::parse("cat K AX1 T\n");
+        assert_eq!(PhonemeGen::unknown_arpabet_symbol(&lexicon, &mapping), Some(("cat", "AX1")));
+    }
+
+    fn with_arpabet_mapping() -> PhonemeGen {
+        let mapping = include_str!("../arpabet-mapping.txt");
+        let mut phoneme_gen = PhonemeGen::from_bytes(Vec::new(), Vec::new(), Vec::new(), Vec::new(), mapping.as_bytes());
+        phoneme_gen.arpabet_mapping = Some(PhonemeGen::parse_arpabet_mapping(mapping));
+        phoneme_gen
+    }
+
+    fn to_ipa(phoneme_gen: &PhonemeGen, arpabet: &str) -> Vec<String> {
+        phoneme_gen.arpabet_to_ipa(arpabet.split_whitespace().map(String::from).collect()).unwrap()
+    }
+
+    #[test]
+    fn legal_onsets() {
+        assert!(PhonemeGen::is_legal_onset(&["B"]));
+        assert!(!PhonemeGen::is_legal_onset(&["NG"]));
+        assert!(PhonemeGen::is_legal_onset(&["P", "L"]));
+        assert!(!PhonemeGen::is_legal_onset(&["T", "L"]));
+        assert!(PhonemeGen::is_legal_onset(&["S", "T", "R"]));
+        assert!(!PhonemeGen::is_legal_onset(&["K", "S", "T", "R"]));
+    }
+
+    #[test]
+    fn stress_marks_start_the_syllable() {
+        let phoneme_gen = with_arpabet_mapping();
+        assert_eq!(to_ipa(&phoneme_gen, "AH0 B AW1 T"), ["ə", "ˈb", "aʊ", "t"]);
+        assert_eq!(to_ipa(&phoneme_gen, "EH1 K S T R AH0"), ["ˈɛ", "k", "s", "t", "ɹ", "ə"]);
+        assert_eq!(to_ipa(&phoneme_gen, "IH0 K S T R IY1 M"), ["ɪ", "k", "ˈs", "t", "ɹ", "iː", "m"]);
+        assert_eq!(to_ipa(&phoneme_gen, "AE1 T L AE2 S"), ["ˈa", "t", "ˌl", "a", "s"]);
+    }
+
+    #[test]
+    fn unstressed_words_have_no_marks() {
+        let phoneme_gen = with_arpabet_mapping();
+        assert_eq!(to_ipa(&phoneme_gen, "DH AH0"), ["ð", "ə"]);
+        assert_eq!(to_ipa(&phoneme_gen, "S IH0 NG"), ["s", "ɪ", "ŋ"]);
+    }
+
+    #[test]
+    fn arpabet_needs_the_mapping() {
+        let phoneme_gen = PhonemeGen::from_bytes(Vec::new(), Vec::new(), Vec::new(), Vec::new(), Vec::new());
+        assert!(matches!(phoneme_gen.arpabet_to_ipa(vec!["K".to_string()]), Err(PiperError::NotLoaded(_))));
+    }
+}
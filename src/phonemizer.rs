@@ -0,0 +1,125 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cancellation::CancellationToken,
+    error::{PiperError, Result},
+    espeak::EspeakPhonemizer,
+    lexicon::LexiconPhonemizer,
+    phoneme_gen::PhonemeGen,
+    session::SessionOptions,
+};
+
+/// One sentence of input text and its IPA, without `^`/`$` markers or `_` padding.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PhonemizedSentence {
+    pub text: String,
+    pub phonemes: String,
+}
+
+impl PhonemizedSentence {
+    /// The sentence in the `^...$` form the voice models are fed with.
+    pub fn to_model_input(&self) -> String {
+        format!("^{}$", self.phonemes)
+    }
+}
+
+/// Text to IPA front end of the synthesis pipeline.
+///
+/// [`PhonemeGen`](crate::PhonemeGen) is the neural implementation, other
+/// backends such as espeak-ng or a plain lexicon can be plugged into a
+/// [`Synthesizer`](crate::Synthesizer) by implementing this trait.
+///
+/// Phonemizers take `&self` so a [`Synthesizer`](crate::Synthesizer) can be
+/// shared between threads, state such as caches needs interior mutability.
+pub trait Phonemizer {
+    fn phonemize(&self, text: &str) -> Result<Vec<PhonemizedSentence>>;
+
+    /// Like [`phonemize`](Phonemizer::phonemize), checking `token` between
+    /// steps. Backends that can't be interrupted just phonemize by default.
+    fn phonemize_cancellable(&self, text: &str, _token: Option<&CancellationToken>) -> Result<Vec<PhonemizedSentence>> {
+        self.phonemize(text)
+    }
+
+    /// Runs the backend once so the first real request doesn't pay for lazy
+    /// allocations. Does nothing by default.
+    fn warm_up(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Front end a voice declares in the `phonemizer` section of its config, e.g.
+/// `{"type": "espeak", "voice": "de"}`, used for that voice in place of the
+/// [`Synthesizer`](crate::Synthesizer)'s own phonemizer.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum PhonemizerConfig {
+    /// A neural G2P with an ARPAbet to IPA mapping, see [`PhonemeGen`].
+    Neural {
+        encoder: String,
+        decoder: String,
+        #[serde(default)]
+        decoder_with_past: Option<String>,
+        tokenizer: String,
+        vocab: String,
+        arpabet_mapping: String,
+        #[serde(default)]
+        lexicon: Option<String>,
+    },
+    /// The `espeak-ng` program, see [`EspeakPhonemizer`]. The voice defaults
+    /// to the `espeak.voice` of the Piper config.
+    Espeak {
+        #[serde(default)]
+        voice: Option<String>,
+    },
+    /// A `word IPA` pronunciation list, see [`LexiconPhonemizer`].
+    Lexicon { path: String },
+}
+
+impl PhonemizerConfig {
+    /// Makes relative paths relative to `dir`, the directory of the voice config.
+    pub fn resolve_paths(&mut self, dir: &Path) {
+        let resolve = |path: &mut String| {
+            if Path::new(path.as_str()).is_relative() {
+                *path = dir.join(path.as_str()).to_string_lossy().into_owned();
+            }
+        };
+        match self {
+            PhonemizerConfig::Neural { encoder, decoder, decoder_with_past, tokenizer, vocab, arpabet_mapping, lexicon } => {
+                [encoder, decoder, tokenizer, vocab, arpabet_mapping].into_iter().for_each(resolve);
+                decoder_with_past.iter_mut().chain(lexicon.iter_mut()).for_each(resolve);
+            }
+            PhonemizerConfig::Espeak { .. } => {}
+            PhonemizerConfig::Lexicon { path } => resolve(path),
+        }
+    }
+
+    /// Loads the front end, the neural G2P with `session_options`.
+    pub fn load(&self, session_options: &SessionOptions) -> Result<Box<dyn Phonemizer + Send + Sync>> {
+        match self {
+            PhonemizerConfig::Neural { encoder, decoder, decoder_with_past, tokenizer, vocab, arpabet_mapping, lexicon } => {
+                let mut phoneme_gen = PhonemeGen::new(
+                    decoder.clone(), encoder.clone(),
+                    tokenizer.clone(), vocab.clone(),
+                    arpabet_mapping.clone(),
+                )
+                .with_session_options(session_options.clone());
+                if let Some(path) = decoder_with_past {
+                    phoneme_gen = phoneme_gen.with_decoder_with_past(path.clone());
+                }
+                if let Some(path) = lexicon {
+                    phoneme_gen = phoneme_gen.with_lexicon(path);
+                }
+                phoneme_gen.load()?;
+                Ok(Box::new(phoneme_gen))
+            }
+            PhonemizerConfig::Espeak { voice } => {
+                let voice = voice.clone()
+                    .ok_or_else(|| PiperError::InvalidConfig("no espeak voice for the `espeak` phonemizer".to_string()))?;
+                Ok(Box::new(EspeakPhonemizer::new(voice)))
+            }
+            PhonemizerConfig::Lexicon { path } => Ok(Box::new(LexiconPhonemizer::from_file(path)?)),
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Condvar, Mutex},
+    time::Duration,
+};
+
+use cpal::{
+    traits::{DeviceTrait, HostTrait, StreamTrait},
+    FromSample, SampleFormat, SampleRate, SizedSample, StreamConfig, SupportedStreamConfig,
+};
+
+use crate::{
+    audio::{self, AudioSink},
+    cancellation::CancellationToken,
+    error::{PiperError, Result},
+};
+
+/// Audio buffered before playback starts.
+const DEFAULT_LOOKAHEAD: Duration = Duration::from_millis(200);
+
+fn playback_error(err: impl std::fmt::Display) -> PiperError {
+    PiperError::Playback(err.to_string())
+}
+
+fn output_config(device: &cpal::Device, sample_rate: u32) -> Result<SupportedStreamConfig> {
+    let exact = device.supported_output_configs()
+        .map_err(playback_error)?
+        .filter_map(|range| range.try_with_sample_rate(SampleRate(sample_rate)))
+        .min_by_key(|config| (config.sample_format() != SampleFormat::F32, config.channels()));
+
+    match exact {
+        Some(config) => Ok(config),
+        None => device.default_output_config().map_err(playback_error),
+    }
+}
+
+/// Playback queue shared with the audio callback.
+#[derive(Default)]
+struct Queue {
+    samples: VecDeque<f32>,
+    /// Set once enough audio is buffered, cleared again on an underrun so
+    /// playback resumes only after the buffer has refilled.
+    playing: bool,
+    finished: bool,
+}
+
+type SharedQueue = Arc<(Mutex<Queue>, Condvar)>;
+
+fn build_stream<T>(device: &cpal::Device, config: &StreamConfig, queue: SharedQueue) -> Result<cpal::Stream>
+where
+    T: SizedSample + FromSample<f32>,
+{
+    let channels = config.channels as usize;
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            let (lock, drained) = &*queue;
+            let mut queue = lock.lock().unwrap_or_else(|e| e.into_inner());
+            for frame in data.chunks_mut(channels) {
+                let sample = match queue.playing {
+                    true => queue.samples.pop_front().unwrap_or(0.0),
+                    false => 0.0,
+                };
+                frame.fill(T::from_sample(sample));
+            }
+            if queue.samples.is_empty() {
+                queue.playing = queue.finished;
+                drained.notify_all();
+            }
+        },
+        |err| tracing::error!("playback stream error: {}", err),
+        None,
+    ).map_err(playback_error)
+}
+
+/// Plays audio as it is synthesized, so the first sentence is heard while the
+/// rest is still being generated. Playback starts once `lookahead` of audio is
+/// buffered, which absorbs small gaps between inferences.
+pub struct PlaybackSink {
+    queue: SharedQueue,
+    // Kept alive for as long as audio is played.
+    _stream: cpal::Stream,
+    sample_rate: u64,
+    device_rate: u64,
+    lookahead: usize,
+    cancellation: Option<CancellationToken>,
+}
+
+impl PlaybackSink {
+    /// Opens the output device matching `device`, or the default one.
+    pub fn new(sample_rate: u64, device: Option<&str>) -> Result<Self> {
+        let device = match device {
+            Some(name) => find_device(name)?,
+            None => cpal::default_host().default_output_device()
+                .ok_or_else(|| PiperError::Playback("no default output device".to_string()))?,
+        };
+        let supported = output_config(&device, sample_rate as u32)?;
+        let config = supported.config();
+        let queue = SharedQueue::default();
+
+        let stream = match supported.sample_format() {
+            SampleFormat::F32 => build_stream::<f32>(&device, &config, queue.clone()),
+            SampleFormat::I16 => build_stream::<i16>(&device, &config, queue.clone()),
+            SampleFormat::U16 => build_stream::<u16>(&device, &config, queue.clone()),
+            SampleFormat::I32 => build_stream::<i32>(&device, &config, queue.clone()),
+            other => Err(PiperError::Playback(format!("unsupported sample format {:?}", other))),
+        }?;
+        stream.play().map_err(playback_error)?;
+
+        let device_rate = config.sample_rate.0 as u64;
+        Ok(Self {
+            queue,
+            _stream: stream,
+            sample_rate,
+            device_rate,
+            lookahead: (DEFAULT_LOOKAHEAD.as_secs_f64() * device_rate as f64) as usize,
+            cancellation: None,
+        })
+    }
+
+    pub fn with_lookahead(mut self, lookahead: Duration) -> Self {
+        self.lookahead = (lookahead.as_secs_f64() * self.device_rate as f64) as usize;
+        self
+    }
+
+    /// Silences the buffered audio and fails with
+    /// [`PiperError::Cancelled`] once `token` is cancelled, instead of
+    /// playing it to the end.
+    pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+        self.cancellation = Some(token);
+        self
+    }
+
+    fn check_cancelled(&self, queue: &mut Queue) -> Result<()> {
+        match &self.cancellation {
+            Some(token) if token.is_cancelled() => {
+                queue.samples.clear();
+                Err(PiperError::Cancelled)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl AudioSink for PlaybackSink {
+    fn write_samples(&mut self, samples: &[f32]) -> Result<()> {
+        let resampled = audio::resample(samples, self.sample_rate, self.device_rate)?;
+        let mut queue = self.queue.0.lock().unwrap_or_else(|e| e.into_inner());
+        self.check_cancelled(&mut queue)?;
+        queue.samples.extend(resampled);
+        if queue.samples.len() >= self.lookahead {
+            queue.playing = true;
+        }
+        Ok(())
+    }
+
+    /// Plays whatever is still buffered and waits until it has been heard.
+    fn finish(&mut self) -> Result<()> {
+        let (lock, drained) = &*self.queue;
+        let mut queue = lock.lock().unwrap_or_else(|e| e.into_inner());
+        queue.finished = true;
+        queue.playing = true;
+        while !queue.samples.is_empty() {
+            self.check_cancelled(&mut queue)?;
+            queue = drained.wait_timeout(queue, Duration::from_millis(100))
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        drop(queue);
+
+        // Let the device drain the last buffer before the stream is dropped.
+        std::thread::sleep(Duration::from_millis(200));
+        Ok(())
+    }
+}
+
+/// Names of the output devices of the default audio host.
+pub fn output_devices() -> Result<Vec<String>> {
+    let host = cpal::default_host();
+    Ok(host.output_devices()
+        .map_err(playback_error)?
+        .filter_map(|device| device.name().ok())
+        .collect())
+}
+
+/// Finds an output device by exact name, or else by a case-insensitive
+/// substring such as `usb`.
+fn find_device(name: &str) -> Result<cpal::Device> {
+    let host = cpal::default_host();
+    let devices: Vec<(String, cpal::Device)> = host.output_devices()
+        .map_err(playback_error)?
+        .filter_map(|device| Some((device.name().ok()?, device)))
+        .collect();
+
+    let lower = name.to_lowercase();
+    let position = devices.iter().position(|(device_name, _)| device_name == name)
+        .or_else(|| devices.iter().position(|(device_name, _)| device_name.to_lowercase().contains(&lower)));
+    match position {
+        Some(i) => Ok(devices.into_iter().nth(i).expect("position is in bounds").1),
+        None => Err(PiperError::Playback(format!("no output device matching `{}`", name))),
+    }
+}
+
+pub fn play(waveform: &[f32], sample_rate: u64) -> Result<()> {
+    play_on(waveform, sample_rate, None)
+}
+
+/// Plays through the output device matching `device`, or the default one.
+pub fn play_on(waveform: &[f32], sample_rate: u64, device: Option<&str>) -> Result<()> {
+    let mut sink = PlaybackSink::new(sample_rate, device)?;
+    sink.write_samples(waveform)?;
+    sink.finish()
+}
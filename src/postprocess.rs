@@ -0,0 +1,313 @@
+use std::{collections::VecDeque, str::FromStr, time::Duration};
+
+use crate::{
+    audio,
+    error::{PiperError, Result},
+    loudness,
+    time_stretch::{self, PITCH_RANGE, TEMPO_RANGE},
+};
+
+/// How far the limiter looks ahead so the gain is already down at a peak.
+const LIMITER_LOOKAHEAD_SECONDS: f64 = 0.005;
+/// Time for the limiter gain to recover most of the way back to unity.
+const LIMITER_RELEASE_SECONDS: f64 = 0.05;
+
+/// Largest boost `Gain` allows, beyond this it only amplifies clipping.
+const MAX_GAIN_DB: f32 = 24.0;
+
+/// Output volume as a linear factor, parsed from `6dB`, `-3 dB` or `0.5`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Gain(pub f32);
+
+impl Gain {
+    pub fn from_db(db: f32) -> Self {
+        Self(10f32.powf(db / 20.0))
+    }
+
+    /// The factor clamped to silence up to +24 dB.
+    pub fn linear(self) -> f32 {
+        self.0.clamp(0.0, 10f32.powf(MAX_GAIN_DB / 20.0))
+    }
+}
+
+impl FromStr for Gain {
+    type Err = PiperError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || PiperError::InvalidConfig(format!("invalid gain `{}`, expected e.g. `-3dB` or `0.5`", s));
+        let s = s.trim();
+        let lower = s.to_ascii_lowercase();
+        match lower.strip_suffix("db") {
+            Some(db) => db.trim().parse().map(Gain::from_db).map_err(|_| invalid()),
+            None => match s.parse::<f32>() {
+                Ok(factor) if factor >= 0.0 => Ok(Gain(factor)),
+                _ => Err(invalid()),
+            },
+        }
+    }
+}
+
+/// Optional processing applied to the synthesized waveform before it is
+/// returned or encoded, every stage is off by default.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PostProcess {
+    /// Speed the audio up (`> 1`) or slow it down without changing the pitch,
+    /// between 0.5 and 2.0.
+    pub tempo: Option<f32>,
+    /// Shift the pitch by this many semitones, between -12 and 12.
+    pub pitch_semitones: Option<f32>,
+    /// Resample to this rate instead of returning the voice's native rate.
+    pub sample_rate: Option<u64>,
+    /// Normalize the integrated loudness to this many LUFS, e.g. -16 for
+    /// podcasts or -23 for EBU R128 broadcast. Only applied to whole waveforms,
+    /// audio streamed to a sink is left as is.
+    pub loudness: Option<f64>,
+    /// Soft-limit peaks to this many dB below full scale so nothing clips.
+    pub headroom_db: Option<f32>,
+    /// Volume change applied after loudness normalization.
+    pub gain: Option<Gain>,
+    /// Ramp the start of the audio up from silence over this long.
+    pub fade_in: Option<Duration>,
+    /// Ramp the end of the audio down to silence over this long.
+    pub fade_out: Option<Duration>,
+    /// Also fade every synthesized chunk in and out, not just the whole audio.
+    pub fade_chunks: bool,
+}
+
+impl PostProcess {
+    pub fn with_tempo(mut self, tempo: f32) -> Self {
+        self.tempo = Some(tempo);
+        self
+    }
+
+    pub fn with_pitch_shift(mut self, semitones: f32) -> Self {
+        self.pitch_semitones = Some(semitones);
+        self
+    }
+
+    pub fn with_sample_rate(mut self, sample_rate: u64) -> Self {
+        self.sample_rate = Some(sample_rate);
+        self
+    }
+
+    pub fn with_loudness(mut self, lufs: f64) -> Self {
+        self.loudness = Some(lufs);
+        self
+    }
+
+    pub fn with_gain(mut self, gain: Gain) -> Self {
+        self.gain = Some(gain);
+        self
+    }
+
+    pub fn with_limiter(mut self, headroom_db: f32) -> Self {
+        self.headroom_db = Some(headroom_db);
+        self
+    }
+
+    pub fn with_fade_in(mut self, duration: Duration) -> Self {
+        self.fade_in = Some(duration);
+        self
+    }
+
+    pub fn with_fade_out(mut self, duration: Duration) -> Self {
+        self.fade_out = Some(duration);
+        self
+    }
+
+    pub fn with_chunk_fades(mut self) -> Self {
+        self.fade_chunks = true;
+        self
+    }
+
+    /// Sample rate of the processed audio for a voice running at `sample_rate`.
+    pub fn output_sample_rate(&self, sample_rate: u64) -> u64 {
+        self.sample_rate.unwrap_or(sample_rate)
+    }
+
+    /// Factor durations change by, less than one when the tempo is raised.
+    pub fn time_scale(&self) -> f64 {
+        self.tempo.map_or(1.0, |tempo| 1.0 / tempo as f64)
+    }
+
+    pub fn apply(&self, waveform: Vec<f32>, sample_rate: u64) -> Result<Vec<f32>> {
+        let mut waveform = self.process(waveform, sample_rate)?;
+        let sample_rate = self.output_sample_rate(sample_rate);
+        self.fade_in(&mut waveform, sample_rate);
+        self.fade_out(&mut waveform, sample_rate);
+        Ok(waveform)
+    }
+
+    /// Every stage but the fades, which only apply to the ends of the audio.
+    pub(crate) fn process(&self, waveform: Vec<f32>, sample_rate: u64) -> Result<Vec<f32>> {
+        self.process_stages(waveform, sample_rate, self.loudness)
+    }
+
+    /// Like [`process`](Self::process) for one chunk of streamed audio, leaving
+    /// out loudness normalization since it measures the whole waveform.
+    pub(crate) fn process_chunk(&self, waveform: Vec<f32>, sample_rate: u64) -> Result<Vec<f32>> {
+        self.process_stages(waveform, sample_rate, None)
+    }
+
+    fn process_stages(&self, waveform: Vec<f32>, sample_rate: u64, loudness: Option<f64>) -> Result<Vec<f32>> {
+        let mut waveform = waveform;
+        if let Some(tempo) = self.tempo {
+            if !TEMPO_RANGE.contains(&tempo) {
+                return Err(PiperError::InvalidConfig(format!("tempo {} is outside 0.5 to 2.0", tempo)));
+            }
+            waveform = time_stretch::time_stretch(&waveform, sample_rate, tempo);
+        }
+        if let Some(semitones) = self.pitch_semitones {
+            if !PITCH_RANGE.contains(&semitones) {
+                return Err(PiperError::InvalidConfig(format!("pitch shift of {} semitones is outside -12 to 12", semitones)));
+            }
+            waveform = time_stretch::pitch_shift(&waveform, sample_rate, semitones)?;
+        }
+        if let Some(target) = self.sample_rate {
+            waveform = audio::resample(&waveform, sample_rate, target)?;
+        }
+        if let Some(lufs) = loudness {
+            loudness::normalize(&mut waveform, self.output_sample_rate(sample_rate), lufs);
+        }
+        if let Some(gain) = self.gain {
+            let factor = gain.linear();
+            waveform.iter_mut().for_each(|sample| *sample *= factor);
+        }
+        if let Some(headroom_db) = self.headroom_db {
+            limit(&mut waveform, self.output_sample_rate(sample_rate), headroom_db);
+        }
+        Ok(waveform)
+    }
+
+    pub(crate) fn fade_in(&self, waveform: &mut [f32], sample_rate: u64) {
+        if let Some(duration) = self.fade_in {
+            let len = fade_len(waveform, sample_rate, duration);
+            apply_ramp(&mut waveform[..len], false);
+        }
+    }
+
+    pub(crate) fn fade_out(&self, waveform: &mut [f32], sample_rate: u64) {
+        if let Some(duration) = self.fade_out {
+            let len = fade_len(waveform, sample_rate, duration);
+            let start = waveform.len() - len;
+            apply_ramp(&mut waveform[start..], true);
+        }
+    }
+}
+
+fn fade_len(waveform: &[f32], sample_rate: u64, duration: Duration) -> usize {
+    ((duration.as_secs_f64() * sample_rate as f64) as usize).min(waveform.len())
+}
+
+/// Raised-cosine ramp from silence to full level, or back down when `falling`.
+fn apply_ramp(samples: &mut [f32], falling: bool) {
+    let len = samples.len();
+    for (i, sample) in samples.iter_mut().enumerate() {
+        let position = if falling { len - i } else { i + 1 };
+        let gain = 0.5 - 0.5 * (std::f32::consts::PI * position as f32 / (len + 1) as f32).cos();
+        *sample *= gain;
+    }
+}
+
+/// Look-ahead peak limiter keeping `|sample|` below `-headroom_db` dBFS. The
+/// gain ramps down over the look-ahead window before a peak and recovers
+/// smoothly afterwards, so limiting doesn't add clicks.
+fn limit(waveform: &mut [f32], sample_rate: u64, headroom_db: f32) {
+    let ceiling = 10f32.powf(-headroom_db.abs() / 20.0);
+    if waveform.iter().all(|sample| sample.abs() <= ceiling) {
+        return;
+    }
+    let lookahead = ((LIMITER_LOOKAHEAD_SECONDS * sample_rate as f64) as usize).max(1);
+    let release = (-1.0 / (LIMITER_RELEASE_SECONDS * sample_rate as f64)).exp() as f32;
+
+    // Gain each sample needs on its own, padded so the window can run past the end.
+    let needed: Vec<f32> = waveform.iter()
+        .map(|sample| (ceiling / sample.abs()).min(1.0))
+        .chain(std::iter::repeat_n(1.0, lookahead))
+        .collect();
+
+    // Minimum over the next `lookahead` samples, then an exponential release.
+    let mut window = VecDeque::new();
+    let mut held = 1.0f32;
+    let mut envelope = Vec::with_capacity(needed.len());
+    for (i, &gain) in needed.iter().enumerate() {
+        while window.back().is_some_and(|&j: &usize| needed[j] >= gain) {
+            window.pop_back();
+        }
+        window.push_back(i);
+        if window[0] + lookahead <= i {
+            window.pop_front();
+        }
+        if i + 1 >= lookahead {
+            held = needed[window[0]].min(1.0 - (1.0 - held) * release);
+            envelope.push(held);
+        }
+    }
+
+    // Averaging over the same window turns the steps into ramps, never above the
+    // gain a peak inside the window needs.
+    let mut sum = 0.0f64;
+    for (i, sample) in waveform.iter_mut().enumerate() {
+        sum += envelope[i] as f64;
+        if i >= lookahead {
+            sum -= envelope[i - lookahead] as f64;
+        }
+        let gain = (sum / (i + 1).min(lookahead) as f64) as f32;
+        *sample = (*sample * gain).clamp(-ceiling, ceiling);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u64 = 16000;
+
+    fn tone(len: usize, amplitude: f32) -> Vec<f32> {
+        (0..len).map(|i| (i as f32 * 0.07).sin() * amplitude).collect()
+    }
+
+    fn peak(waveform: &[f32]) -> f32 {
+        waveform.iter().fold(0.0, |peak, sample| peak.max(sample.abs()))
+    }
+
+    #[test]
+    fn limiter_keeps_peaks_below_the_ceiling() {
+        for headroom_db in [0.0, 1.0, 6.0] {
+            let ceiling = 10f32.powf(-headroom_db / 20.0);
+            let mut waveform = tone(4000, 0.3);
+            waveform[1000] = 1.8;
+            waveform[2500] = -2.5;
+            waveform[3999] = 3.0;
+            limit(&mut waveform, SAMPLE_RATE, headroom_db);
+            assert!(peak(&waveform) <= ceiling, "headroom {} dB: peak {}", headroom_db, peak(&waveform));
+        }
+    }
+
+    #[test]
+    fn limiter_leaves_quiet_signals_alone() {
+        let waveform = tone(4000, 0.5);
+        let mut limited = waveform.clone();
+        limit(&mut limited, SAMPLE_RATE, 3.0);
+        assert_eq!(limited, waveform);
+    }
+
+    #[test]
+    fn limiter_only_touches_audio_near_peaks() {
+        let mut waveform = tone(16000, 0.3);
+        waveform[8000] = 2.0;
+        let original = waveform.clone();
+        limit(&mut waveform, SAMPLE_RATE, 1.0);
+        // Well before the look-ahead window and long after the release the gain is back at unity.
+        assert_eq!(waveform[..7000], original[..7000]);
+        assert!(waveform[15000..].iter().zip(&original[15000..]).all(|(a, b)| (a - b).abs() < 1e-3));
+    }
+
+    #[test]
+    fn streamed_chunks_skip_loudness_normalization() {
+        let post_process = PostProcess::default().with_loudness(-16.0);
+        let waveform = tone(SAMPLE_RATE as usize, 0.05);
+        assert_eq!(post_process.process_chunk(waveform.clone(), SAMPLE_RATE).unwrap(), waveform);
+        assert_ne!(post_process.process(waveform.clone(), SAMPLE_RATE).unwrap(), waveform);
+    }
+}
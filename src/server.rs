@@ -0,0 +1,483 @@
+use std::net::SocketAddr;
+
+use axum::{
+    extract::{
+        Form, Query, State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    async_synthesizer::AsyncSynthesizer,
+    audio::{AudioSink, SampleFormat},
+    cancellation::CancellationToken,
+    encoding::OutputFormat,
+    error::{PiperError, Result},
+    synthesizer::{SynthesisOptions, SynthesisStats, Synthesizer},
+};
+
+#[derive(Clone)]
+pub struct AppState {
+    synthesizer: AsyncSynthesizer,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SpeechRequest {
+    pub model: String,
+    pub input: String,
+    pub voice: String,
+    pub response_format: Option<String>,
+    pub speed: Option<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponseFormat {
+    Wav,
+    Pcm,
+    Flac,
+    #[cfg(feature = "mp3")]
+    Mp3,
+    #[cfg(feature = "opus")]
+    Opus,
+}
+
+impl ResponseFormat {
+    const SUPPORTED: &'static str = match (cfg!(feature = "mp3"), cfg!(feature = "opus")) {
+        (true, true) => "`wav`, `pcm`, `flac`, `mp3` or `opus`",
+        (true, false) => "`wav`, `pcm`, `flac` or `mp3`",
+        (false, true) => "`wav`, `pcm`, `flac` or `opus`",
+        (false, false) => "`wav`, `pcm` or `flac`",
+    };
+
+    fn parse(format: Option<&str>) -> std::result::Result<Self, ApiError> {
+        match format {
+            None | Some("wav") => Ok(ResponseFormat::Wav),
+            Some("pcm") => Ok(ResponseFormat::Pcm),
+            Some("flac") => Ok(ResponseFormat::Flac),
+            #[cfg(feature = "mp3")]
+            Some("mp3") => Ok(ResponseFormat::Mp3),
+            #[cfg(feature = "opus")]
+            Some("opus") => Ok(ResponseFormat::Opus),
+            Some(other) => Err(ApiError::invalid_request(
+                format!("Unsupported response_format `{}`, expected {}", other, Self::SUPPORTED),
+                "response_format",
+            )),
+        }
+    }
+
+    fn output_format(&self) -> OutputFormat {
+        match self {
+            ResponseFormat::Wav => OutputFormat::Wav,
+            ResponseFormat::Pcm => OutputFormat::Raw,
+            ResponseFormat::Flac => OutputFormat::Flac,
+            #[cfg(feature = "mp3")]
+            ResponseFormat::Mp3 => OutputFormat::Mp3,
+            #[cfg(feature = "opus")]
+            ResponseFormat::Opus => OutputFormat::Opus,
+        }
+    }
+
+    fn content_type(&self) -> &'static str {
+        self.output_format().content_type()
+    }
+}
+
+#[derive(Serialize, Debug)]
+struct ApiErrorBody {
+    message: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    param: Option<&'static str>,
+    code: Option<&'static str>,
+}
+
+#[derive(Debug)]
+pub struct ApiError {
+    status: StatusCode,
+    body: ApiErrorBody,
+}
+
+impl ApiError {
+    fn invalid_request(message: String, param: &'static str) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            body: ApiErrorBody {
+                message,
+                kind: "invalid_request_error",
+                param: Some(param),
+                code: None,
+            },
+        }
+    }
+
+    fn server_error(message: String) -> Self {
+        Self {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            body: ApiErrorBody {
+                message,
+                kind: "server_error",
+                param: None,
+                code: None,
+            },
+        }
+    }
+}
+
+impl From<PiperError> for ApiError {
+    fn from(err: PiperError) -> Self {
+        ApiError::server_error(err.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = serde_json::json!({ "error": self.body });
+        (self.status, Json(body)).into_response()
+    }
+}
+
+/// `Server-Timing` header value of a synthesis, in milliseconds.
+fn server_timing(stats: &SynthesisStats) -> String {
+    format!(
+        "phonemize;dur={:.1}, inference;dur={:.1}",
+        stats.phonemization.as_secs_f64() * 1000.0,
+        stats.inference.as_secs_f64() * 1000.0
+    )
+}
+
+async fn speech(
+    State(state): State<AppState>,
+    Json(request): Json<SpeechRequest>,
+) -> std::result::Result<Response, ApiError> {
+    let format = ResponseFormat::parse(request.response_format.as_deref())?;
+    if request.input.trim().is_empty() {
+        return Err(ApiError::invalid_request("`input` must not be empty".to_string(), "input"));
+    }
+
+    if let Some(speed) = request.speed && !(0.25..=4.0).contains(&speed) {
+        return Err(ApiError::invalid_request("`speed` must be between 0.25 and 4.0".to_string(), "speed"));
+    }
+
+    // Aborts the synthesis if the client disconnects and the handler is dropped.
+    let cancellation = CancellationToken::new();
+    let guard = cancellation.drop_guard();
+    let (waveform, stats, sample_rate) = state.synthesizer.run(move |synthesizer| {
+        // OpenAI voice names fall back to the active voice.
+        if synthesizer.voices().is_some_and(|voices| voices.contains(&request.voice)) {
+            synthesizer.set_voice(&request.voice)?;
+        }
+        let options = SynthesisOptions {
+            length_scale: request.speed
+                .map(|speed| synthesizer.model.config.inference.length_scale / speed),
+            cancellation: Some(cancellation),
+            ..Default::default()
+        };
+        let (waveform, stats) = synthesizer.synthesize_with_stats(&request.input, &options)?;
+        Ok((waveform, stats, synthesizer.sample_rate()))
+    })
+    .await?;
+    guard.disarm();
+
+    let bytes = format.output_format().encode(&waveform, sample_rate, SampleFormat::I16)?;
+
+    Ok(([
+        (header::CONTENT_TYPE, format.content_type().to_string()),
+        (header::HeaderName::from_static("server-timing"), server_timing(&stats)),
+    ], bytes).into_response())
+}
+
+/// Per-connection options of the WebSocket stream, from the query string.
+#[derive(Deserialize, Debug, Default)]
+pub struct StreamParams {
+    pub voice: Option<String>,
+    /// Encoding of every binary frame, `pcm` by default.
+    pub format: Option<String>,
+    pub speed: Option<f32>,
+}
+
+/// Sends every synthesized chunk to the socket as one binary frame, each a
+/// complete file in formats with a container.
+struct FrameSink {
+    sender: tokio::sync::mpsc::Sender<Message>,
+    format: OutputFormat,
+    sample_rate: u64,
+}
+
+impl AudioSink for FrameSink {
+    fn write_samples(&mut self, samples: &[f32]) -> Result<()> {
+        let frame = self.format.encode(samples, self.sample_rate, SampleFormat::I16)?;
+        // The receiver is gone once the client disconnected.
+        self.sender.blocking_send(Message::Binary(frame.into())).map_err(|_| PiperError::Cancelled)
+    }
+}
+
+async fn stream(
+    State(state): State<AppState>,
+    Query(params): Query<StreamParams>,
+    upgrade: WebSocketUpgrade,
+) -> std::result::Result<Response, ApiError> {
+    let format = match params.format.as_deref() {
+        None => ResponseFormat::Pcm,
+        format => ResponseFormat::parse(format)?,
+    };
+    if let Some(speed) = params.speed && !(0.25..=4.0).contains(&speed) {
+        return Err(ApiError::invalid_request("`speed` must be between 0.25 and 4.0".to_string(), "speed"));
+    }
+    Ok(upgrade.on_upgrade(move |socket| stream_socket(socket, state, params, format)))
+}
+
+fn event(event: serde_json::Value) -> Message {
+    Message::Text(event.to_string().into())
+}
+
+/// Speaks every text message in turn, framing the audio of each between a
+/// `start` and an `end` event.
+async fn stream_socket(mut socket: WebSocket, state: AppState, params: StreamParams, format: ResponseFormat) {
+    while let Some(Ok(message)) = socket.recv().await {
+        let text = match message {
+            Message::Text(text) => text.to_string(),
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        if text.trim().is_empty() {
+            continue;
+        }
+        let reply = match stream_utterance(&mut socket, &state, &params, format, text).await {
+            Ok(true) => event(serde_json::json!({ "type": "end" })),
+            Ok(false) => break,
+            Err(err) => event(serde_json::json!({ "type": "error", "message": err.to_string() })),
+        };
+        if socket.send(reply).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Streams the audio of `text` as it is synthesized, `false` once the client is gone.
+async fn stream_utterance(
+    socket: &mut WebSocket,
+    state: &AppState,
+    params: &StreamParams,
+    format: ResponseFormat,
+    text: String,
+) -> Result<bool> {
+    let (sender, mut frames) = tokio::sync::mpsc::channel(4);
+    let voice = params.voice.clone();
+    let speed = params.speed;
+    let cancellation = CancellationToken::new();
+    let guard = cancellation.drop_guard();
+    let synthesizer = state.synthesizer.clone();
+    let synthesis = tokio::spawn(async move {
+        synthesizer.run(move |synthesizer| {
+            if let Some(voice) = &voice
+                && synthesizer.voices().is_some_and(|voices| voices.contains(voice)) {
+                synthesizer.set_voice(voice)?;
+            }
+            let options = SynthesisOptions {
+                length_scale: speed.map(|speed| synthesizer.model.config.inference.length_scale / speed),
+                cancellation: Some(cancellation),
+                ..Default::default()
+            };
+            let sample_rate = synthesizer.sample_rate();
+            let start = event(serde_json::json!({
+                "type": "start",
+                "format": format.output_format().extension(),
+                "sample_rate": sample_rate,
+            }));
+            sender.blocking_send(start).map_err(|_| PiperError::Cancelled)?;
+            let mut sink = FrameSink { sender, format: format.output_format(), sample_rate };
+            synthesizer.synthesize_to_sink(&text, &options, &mut sink)
+        })
+        .await
+    });
+
+    while let Some(frame) = frames.recv().await {
+        if socket.send(frame).await.is_err() {
+            // Dropping the guard cancels the synthesis.
+            return Ok(false);
+        }
+    }
+    guard.disarm();
+    synthesis.await.map_err(|e| PiperError::TaskFailed(e.to_string()))??;
+    Ok(true)
+}
+
+/// Parameters of the legacy [MaryTTS](https://github.com/marytts/marytts)
+/// `/process` API, from the query string or a form body.
+#[derive(Deserialize, Debug)]
+pub struct MaryRequest {
+    #[serde(rename = "INPUT_TEXT")]
+    pub input_text: String,
+    #[serde(rename = "INPUT_TYPE")]
+    pub input_type: Option<String>,
+    #[serde(rename = "OUTPUT_TYPE")]
+    pub output_type: Option<String>,
+    #[serde(rename = "AUDIO")]
+    pub audio: Option<String>,
+    /// Accepted but unused, voices are only picked by `VOICE`.
+    #[serde(rename = "LOCALE")]
+    pub locale: Option<String>,
+    #[serde(rename = "VOICE")]
+    pub voice: Option<String>,
+}
+
+/// MaryTTS reports errors as plain text.
+type MaryResult<T> = std::result::Result<T, (StatusCode, String)>;
+
+fn mary_error(err: PiperError) -> (StatusCode, String) {
+    (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
+}
+
+async fn mary_process_query(State(state): State<AppState>, Query(request): Query<MaryRequest>) -> MaryResult<Response> {
+    mary_process(state, request).await
+}
+
+async fn mary_process_form(State(state): State<AppState>, Form(request): Form<MaryRequest>) -> MaryResult<Response> {
+    mary_process(state, request).await
+}
+
+/// Synthesizes plain text into a WAV file, the only input and output MaryTTS
+/// clients such as Home Assistant use.
+async fn mary_process(state: AppState, request: MaryRequest) -> MaryResult<Response> {
+    let bad_request = |message: String| Err((StatusCode::BAD_REQUEST, message));
+    match request.input_type.as_deref() {
+        None | Some("TEXT") => {}
+        Some(other) => return bad_request(format!("Unsupported INPUT_TYPE `{}`, expected `TEXT`", other)),
+    }
+    match request.output_type.as_deref() {
+        None | Some("AUDIO") => {}
+        Some(other) => return bad_request(format!("Unsupported OUTPUT_TYPE `{}`, expected `AUDIO`", other)),
+    }
+    match request.audio.as_deref() {
+        None | Some("WAVE_FILE") | Some("WAVE") => {}
+        Some(other) => return bad_request(format!("Unsupported AUDIO `{}`, expected `WAVE_FILE`", other)),
+    }
+    if request.input_text.trim().is_empty() {
+        return bad_request("INPUT_TEXT must not be empty".to_string());
+    }
+
+    let cancellation = CancellationToken::new();
+    let guard = cancellation.drop_guard();
+    let (waveform, sample_rate) = state.synthesizer.run(move |synthesizer| {
+        // MaryTTS voice names such as `cmu-slt-hsmm` fall back to the active voice.
+        if let Some(voice) = &request.voice
+            && synthesizer.voices().is_some_and(|voices| voices.contains(voice)) {
+            synthesizer.set_voice(voice)?;
+        }
+        let options = SynthesisOptions { cancellation: Some(cancellation), ..Default::default() };
+        Ok((synthesizer.synthesize_with(&request.input_text, &options)?, synthesizer.sample_rate()))
+    })
+    .await
+    .map_err(mary_error)?;
+    guard.disarm();
+
+    let bytes = OutputFormat::Wav.encode(&waveform, sample_rate, SampleFormat::I16).map_err(mary_error)?;
+    Ok(([(header::CONTENT_TYPE, "audio/x-wav")], bytes).into_response())
+}
+
+/// Locale of a voice config in MaryTTS' `en_US` form, read from disk for
+/// voices that are not loaded.
+fn config_locale(config: &str) -> Option<String> {
+    let config: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(config).ok()?).ok()?;
+    config["language"]["code"].as_str().map(str::to_string)
+}
+
+/// Name and locale of every selectable voice.
+async fn mary_voice_list(state: &AppState) -> MaryResult<Vec<(String, String)>> {
+    state.synthesizer.run(|synthesizer| {
+        let active = synthesizer.model.config.language.code.clone();
+        let voices = match synthesizer.voices() {
+            Some(voices) => voices.voices().into_iter()
+                .map(|name| {
+                    let locale = match synthesizer.voice() == Some(name) {
+                        true => Some(active.clone()),
+                        false => voices.files(name).and_then(|files| config_locale(&files.config)),
+                    };
+                    (name.to_string(), locale.unwrap_or_default())
+                })
+                .collect(),
+            None => vec![("piper".to_string(), active)],
+        };
+        Ok(voices)
+    })
+    .await
+    .map_err(mary_error)
+}
+
+/// One `name locale gender type` line per voice, Piper voices have no gender.
+async fn mary_voices(State(state): State<AppState>) -> MaryResult<String> {
+    Ok(mary_voice_list(&state).await?.into_iter()
+        .map(|(name, locale)| format!("{} {} unknown piper\n", name, locale))
+        .collect())
+}
+
+async fn mary_locales(State(state): State<AppState>) -> MaryResult<String> {
+    let mut locales: Vec<String> = mary_voice_list(&state).await?.into_iter()
+        .map(|(_, locale)| locale)
+        .filter(|locale| !locale.is_empty())
+        .collect();
+    locales.sort_unstable();
+    locales.dedup();
+    Ok(locales.into_iter().map(|locale| locale + "\n").collect())
+}
+
+async fn mary_version() -> String {
+    format!("Mary TTS server 5.2 (piper-tts-rust {})\n", env!("CARGO_PKG_VERSION"))
+}
+
+#[derive(Serialize, Debug)]
+struct MetricsBody {
+    pool_size: usize,
+    busy: usize,
+    requests: u64,
+    waited: u64,
+    mean_wait_ms: f64,
+    max_wait_ms: f64,
+}
+
+async fn metrics(State(state): State<AppState>) -> Json<MetricsBody> {
+    let metrics = state.synthesizer.metrics();
+    Json(MetricsBody {
+        pool_size: metrics.size,
+        busy: metrics.busy,
+        requests: metrics.requests,
+        waited: metrics.waited,
+        mean_wait_ms: metrics.mean_wait().as_secs_f64() * 1000.0,
+        max_wait_ms: metrics.max_wait.as_secs_f64() * 1000.0,
+    })
+}
+
+pub fn router(synthesizer: Synthesizer) -> Router {
+    pooled_router(AsyncSynthesizer::new(synthesizer))
+}
+
+/// Serves requests with every synthesizer of the pool, see [`AsyncSynthesizer::pooled`].
+pub fn pooled_router(synthesizer: AsyncSynthesizer) -> Router {
+    let state = AppState { synthesizer };
+
+    Router::new()
+        .route("/v1/audio/speech", post(speech))
+        .route("/v1/audio/stream", get(stream))
+        .route("/metrics", get(metrics))
+        .route("/process", get(mary_process_query).post(mary_process_form))
+        .route("/voices", get(mary_voices))
+        .route("/locales", get(mary_locales))
+        .route("/version", get(mary_version))
+        .with_state(state)
+}
+
+pub async fn serve(synthesizer: Synthesizer, addr: SocketAddr) -> Result<()> {
+    serve_pooled(AsyncSynthesizer::new(synthesizer), addr).await
+}
+
+pub async fn serve_pooled(synthesizer: AsyncSynthesizer, addr: SocketAddr) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tracing::info!("Listening on http://{}", listener.local_addr()?);
+    axum::serve(listener, pooled_router(synthesizer)).await?;
+    Ok(())
+}
@@ -0,0 +1,291 @@
+use std::str::FromStr;
+
+use ort::{
+    execution_providers::ExecutionProviderDispatch,
+    session::{builder::GraphOptimizationLevel, Session},
+};
+
+use crate::{
+    asset::Asset,
+    backend::Backend,
+    error::{PiperError, Result},
+};
+
+pub const DEFAULT_INTRA_THREADS: usize = 4;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum ExecutionProvider {
+    #[default]
+    Cpu,
+    #[cfg(feature = "coreml")]
+    CoreML,
+    /// NVIDIA TensorRT; built engines are persisted in `engine_cache_dir` when set,
+    /// since the first-run engine build can take minutes.
+    #[cfg(feature = "tensorrt")]
+    TensorRT {
+        device_id: i32,
+        engine_cache_dir: Option<std::path::PathBuf>,
+    },
+    /// Intel OpenVINO, `device_type` is an OpenVINO device such as `CPU`, `GPU` or `AUTO`.
+    #[cfg(feature = "openvino")]
+    OpenVINO {
+        device_type: String,
+    },
+    /// Tries each provider in `priority` order and falls back to the CPU
+    /// with a warning if none of them can create the session.
+    Auto {
+        priority: Vec<ExecutionProvider>,
+    },
+}
+
+impl ExecutionProvider {
+    /// Every hardware provider compiled into this build, fastest first.
+    pub fn default_priority() -> Vec<ExecutionProvider> {
+        vec![
+            #[cfg(feature = "tensorrt")]
+            ExecutionProvider::TensorRT {
+                device_id: 0,
+                engine_cache_dir: None,
+            },
+            #[cfg(feature = "coreml")]
+            ExecutionProvider::CoreML,
+            #[cfg(feature = "openvino")]
+            ExecutionProvider::OpenVINO {
+                device_type: "AUTO".to_string(),
+            },
+        ]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ExecutionProvider::Cpu => "cpu",
+            #[cfg(feature = "coreml")]
+            ExecutionProvider::CoreML => "coreml",
+            #[cfg(feature = "tensorrt")]
+            ExecutionProvider::TensorRT { .. } => "tensorrt",
+            #[cfg(feature = "openvino")]
+            ExecutionProvider::OpenVINO { .. } => "openvino",
+            ExecutionProvider::Auto { .. } => "auto",
+        }
+    }
+
+    #[cfg_attr(not(any(feature = "coreml", feature = "tensorrt", feature = "openvino")), allow(unused_variables))]
+    fn dispatch(&self, fp16: bool) -> Option<ExecutionProviderDispatch> {
+        match self {
+            ExecutionProvider::Cpu | ExecutionProvider::Auto { .. } => None,
+            #[cfg(feature = "coreml")]
+            ExecutionProvider::CoreML => {
+                use ort::execution_providers::{coreml::CoreMLComputeUnits, CoreMLExecutionProvider};
+
+                Some(CoreMLExecutionProvider::default()
+                    .with_compute_units(CoreMLComputeUnits::All)
+                    .with_low_precision_accumulation_on_gpu(fp16)
+                    .build()
+                    .error_on_failure())
+            }
+            #[cfg(feature = "tensorrt")]
+            ExecutionProvider::TensorRT { device_id, engine_cache_dir } => {
+                use ort::execution_providers::TensorRTExecutionProvider;
+
+                let mut provider = TensorRTExecutionProvider::default()
+                    .with_device_id(*device_id)
+                    .with_fp16(fp16);
+                if let Some(dir) = engine_cache_dir {
+                    let dir = dir.to_string_lossy();
+                    provider = provider
+                        .with_engine_cache(true)
+                        .with_engine_cache_path(&dir)
+                        .with_timing_cache(true)
+                        .with_timing_cache_path(&dir);
+                }
+                Some(provider.build().error_on_failure())
+            }
+            #[cfg(feature = "openvino")]
+            ExecutionProvider::OpenVINO { device_type } => {
+                use ort::execution_providers::OpenVINOExecutionProvider;
+
+                let mut provider = OpenVINOExecutionProvider::default()
+                    .with_device_type(device_type);
+                if fp16 {
+                    provider = provider.with_precision("FP16");
+                }
+                Some(provider.build().error_on_failure())
+            }
+        }
+    }
+}
+
+impl FromStr for ExecutionProvider {
+    type Err = PiperError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(priority) = s.strip_prefix("auto:") {
+            let priority = priority.split(',')
+                .map(|provider| provider.trim().parse())
+                .collect::<Result<Vec<ExecutionProvider>>>()?;
+            return Ok(ExecutionProvider::Auto { priority });
+        }
+
+        #[cfg(feature = "openvino")]
+        if let Some(device_type) = s.strip_prefix("openvino:") {
+            return Ok(ExecutionProvider::OpenVINO {
+                device_type: device_type.to_ascii_uppercase(),
+            });
+        }
+
+        match s.to_ascii_lowercase().as_str() {
+            "cpu" => Ok(ExecutionProvider::Cpu),
+            "auto" => Ok(ExecutionProvider::Auto {
+                priority: ExecutionProvider::default_priority(),
+            }),
+            #[cfg(feature = "coreml")]
+            "coreml" => Ok(ExecutionProvider::CoreML),
+            #[cfg(feature = "tensorrt")]
+            "tensorrt" => Ok(ExecutionProvider::TensorRT {
+                device_id: 0,
+                engine_cache_dir: None,
+            }),
+            #[cfg(feature = "openvino")]
+            "openvino" => Ok(ExecutionProvider::OpenVINO {
+                device_type: "CPU".to_string(),
+            }),
+            _ => Err(PiperError::InvalidConfig(format!("unknown execution provider `{}`", s))),
+        }
+    }
+}
+
+/// ONNX Runtime graph optimizations applied when a session is built.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OptimizationLevel {
+    Disable,
+    Basic,
+    Extended,
+    #[default]
+    All,
+}
+
+impl OptimizationLevel {
+    fn graph_level(self) -> GraphOptimizationLevel {
+        match self {
+            OptimizationLevel::Disable => GraphOptimizationLevel::Disable,
+            OptimizationLevel::Basic => GraphOptimizationLevel::Level1,
+            OptimizationLevel::Extended => GraphOptimizationLevel::Level2,
+            OptimizationLevel::All => GraphOptimizationLevel::Level3,
+        }
+    }
+}
+
+impl FromStr for OptimizationLevel {
+    type Err = PiperError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "disable" | "none" | "0" => Ok(OptimizationLevel::Disable),
+            "basic" | "1" => Ok(OptimizationLevel::Basic),
+            "extended" | "2" => Ok(OptimizationLevel::Extended),
+            "all" | "3" => Ok(OptimizationLevel::All),
+            _ => Err(PiperError::InvalidConfig(format!("unknown optimization level `{}`", s))),
+        }
+    }
+}
+
+/// Options shared by every ONNX session the crate creates.
+#[derive(Debug, Clone)]
+pub struct SessionOptions {
+    pub execution_provider: ExecutionProvider,
+    /// Memory-maps model files instead of letting ONNX Runtime read them, so
+    /// they are parsed straight from the page cache rather than a heap copy,
+    /// which lowers the startup peak when several large voices are loaded.
+    pub memory_map: bool,
+    /// Threads used within an operator, 0 lets ONNX Runtime pick one per core.
+    pub intra_threads: usize,
+    /// Threads running independent operators when `parallel_execution` is set,
+    /// 0 lets ONNX Runtime decide.
+    pub inter_threads: usize,
+    pub optimization_level: OptimizationLevel,
+    /// Runs independent branches of the graph concurrently instead of in order.
+    pub parallel_execution: bool,
+    /// Lets hardware providers compute in half precision: FP16 TensorRT
+    /// engines, OpenVINO `FP16` precision and CoreML fp16 accumulation on the
+    /// GPU. Ignored on the CPU.
+    pub fp16: bool,
+    /// Engine running the voices, see [`Backend`].
+    pub backend: Backend,
+    /// Seeds the noise the voices draw, so the same input always gives the
+    /// same audio. ONNX Runtime keeps drawing from one sequence per session,
+    /// so the voice is reloaded before every inference, which is slow.
+    pub seed: Option<u64>,
+}
+
+impl Default for SessionOptions {
+    fn default() -> Self {
+        Self {
+            execution_provider: ExecutionProvider::default(),
+            memory_map: false,
+            intra_threads: DEFAULT_INTRA_THREADS,
+            inter_threads: 0,
+            optimization_level: OptimizationLevel::default(),
+            parallel_execution: false,
+            fp16: false,
+            backend: Backend::default(),
+            seed: None,
+        }
+    }
+}
+
+fn build_with_provider(source: &Asset<'_>, provider: &ExecutionProvider, options: &SessionOptions) -> ort::Result<Session> {
+    Session::builder()
+        .and_then(|builder| builder.with_optimization_level(options.optimization_level.graph_level()))
+        .and_then(|builder| builder.with_intra_threads(options.intra_threads))
+        .and_then(|builder| match options.inter_threads {
+            0 => Ok(builder),
+            threads => builder.with_inter_threads(threads),
+        })
+        .and_then(|builder| builder.with_parallel_execution(options.parallel_execution))
+        .and_then(|builder| match provider.dispatch(options.fp16) {
+            Some(provider) => builder.with_execution_providers([provider]),
+            None => Ok(builder),
+        })
+        .and_then(|builder| match source {
+            Asset::Path(path) => builder.commit_from_file(path),
+            Asset::Bytes(bytes) => builder.commit_from_memory(bytes),
+        })
+}
+
+pub(crate) fn build_session(source: &Asset<'_>, options: &SessionOptions) -> Result<Session> {
+    // ONNX Runtime copies what it keeps, the mapping only has to outlive the build.
+    if let (Asset::Path(path), true) = (source, options.memory_map) {
+        return source.map("model", |bytes| build_session_from(&Asset::Bytes(bytes.into()), path, options))?;
+    }
+    build_session_from(source, source.name(), options)
+}
+
+fn build_session_from(source: &Asset<'_>, path: &str, options: &SessionOptions) -> Result<Session> {
+    let session = match &options.execution_provider {
+        ExecutionProvider::Auto { priority } => {
+            let mut session = None;
+            for provider in priority {
+                match build_with_provider(source, provider, options) {
+                    Ok(built) => {
+                        session = Some(built);
+                        break;
+                    }
+                    Err(err) => tracing::warn!(
+                        "execution provider `{}` unavailable for `{}`: {}",
+                        provider.name(), path, err
+                    ),
+                }
+            }
+            match session {
+                Some(session) => Ok(session),
+                None => {
+                    tracing::warn!("falling back to the CPU execution provider for `{}`", path);
+                    build_with_provider(source, &ExecutionProvider::Cpu, options)
+                }
+            }
+        }
+        provider => build_with_provider(source, provider, options),
+    };
+
+    session.map_err(|source| PiperError::ModelLoad { path: path.to_string(), source })
+}
@@ -0,0 +1,133 @@
+use std::fmt;
+
+use ort::tensor::TensorElementType;
+
+use crate::error::{PiperError, Result};
+
+/// Names of the session inputs and outputs the voice is run with. Standard
+/// Piper exports use `input`, `input_lengths`, `scales`, an optional `sid`
+/// and `output`; other exports are mapped by name hints and tensor types.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    pub input: String,
+    pub input_lengths: String,
+    pub scales: String,
+    pub sid: Option<String>,
+    pub output: String,
+    /// Per-phoneme durations, see [`PhonemeDuration`](crate::model_handler::PhonemeDuration).
+    pub durations: Option<String>,
+    /// Float inputs and outputs are `float16`, as in voices converted to half
+    /// precision. Needs the `fp16` feature.
+    pub half_precision: bool,
+}
+
+/// An input or output of a voice model as an inference backend reports it,
+/// `tensor` being the element type and rank of tensors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TensorInfo {
+    pub name: String,
+    pub tensor: Option<(TensorElementType, usize)>,
+}
+
+impl fmt::Display for TensorInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.tensor {
+            Some((element, rank)) => write!(f, "`{}` {} rank {}", self.name, element, rank),
+            None => write!(f, "`{}` (not a tensor)", self.name),
+        }
+    }
+}
+
+fn is_tensor(info: &TensorInfo, element: TensorElementType, rank: usize) -> bool {
+    info.tensor == Some((element, rank))
+}
+
+fn is_float(info: &TensorInfo) -> bool {
+    matches!(info.tensor, Some((TensorElementType::Float32 | TensorElementType::Float16, _)))
+}
+
+/// Picks the candidate called `name`, else the only one of the right type
+/// whose name contains a hint, else, with `by_type`, the only one of the
+/// right type.
+fn pick<'a>(
+    candidates: &[&'a TensorInfo],
+    name: &str,
+    hints: &[&str],
+    fits: &dyn Fn(&TensorInfo) -> bool,
+    by_type: bool,
+) -> Option<&'a str> {
+    if let Some(exact) = candidates.iter().find(|candidate| candidate.name == name) {
+        return Some(&exact.name);
+    }
+    let fitting: Vec<&str> = candidates.iter()
+        .filter(|candidate| fits(candidate))
+        .map(|candidate| candidate.name.as_str())
+        .collect();
+    let hinted: Vec<&str> = fitting.iter()
+        .copied()
+        .filter(|candidate| hints.iter().any(|hint| candidate.to_lowercase().contains(hint)))
+        .collect();
+    match (hinted.as_slice(), fitting.as_slice()) {
+        ([only], _) => Some(only),
+        ([], [only]) if by_type => Some(only),
+        _ => None,
+    }
+}
+
+fn describe(values: &[&TensorInfo]) -> String {
+    values.iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Signature {
+    pub(crate) fn detect(inputs: &[TensorInfo], outputs: &[TensorInfo]) -> Result<Self> {
+        let mut remaining: Vec<&TensorInfo> = inputs.iter().collect();
+        let all_inputs = describe(&remaining);
+        let mut take = |name: &str, hints: &[&str], fits: &dyn Fn(&TensorInfo) -> bool, by_type: bool| {
+            let found = pick(&remaining, name, hints, fits, by_type)?.to_string();
+            remaining.retain(|candidate| candidate.name != found);
+            Some(found)
+        };
+        let missing = |role: &str| PiperError::IncompatibleModel(format!("no `{}` input among {}", role, all_inputs));
+
+        let ids = |t: &TensorInfo| is_tensor(t, TensorElementType::Int64, 2);
+        let scales = |t: &TensorInfo| is_float(t) && t.tensor.is_some_and(|(_, rank)| rank == 1);
+        let lengths = |t: &TensorInfo| is_tensor(t, TensorElementType::Int64, 1);
+        let input = take("input", &["input", "phoneme", "text"], &ids, true).ok_or_else(|| missing("input"))?;
+        let scales = take("scales", &["scale"], &scales, true).ok_or_else(|| missing("scales"))?;
+        // Speaker ids have the same type as the lengths, only a name tells them apart.
+        let sid = take("sid", &["sid", "speaker"], &lengths, false);
+        let input_lengths = take("input_lengths", &["length", "len"], &lengths, true)
+            .ok_or_else(|| missing("input_lengths"))?;
+        let sid = sid.or_else(|| take("sid", &[], &lengths, true));
+        if !remaining.is_empty() {
+            return Err(PiperError::IncompatibleModel(format!("unexpected inputs {}", describe(&remaining))));
+        }
+
+        let durations = outputs.iter()
+            .map(|output| output.name.as_str())
+            .find(|name| name.to_lowercase().contains("duration"))
+            .map(str::to_string);
+        let audio_outputs: Vec<&TensorInfo> = outputs.iter()
+            .filter(|output| Some(&output.name) != durations.as_ref())
+            .collect();
+        let output = pick(&audio_outputs, "output", &["output", "audio", "wav"], &is_float, true)
+            .ok_or_else(|| {
+                let outputs: Vec<&TensorInfo> = outputs.iter().collect();
+                PiperError::IncompatibleModel(format!("no audio output among {}", describe(&outputs)))
+            })?
+            .to_string();
+
+        let half_precision = audio_outputs.iter()
+            .any(|info| info.name == output && matches!(info.tensor, Some((TensorElementType::Float16, _))));
+        if cfg!(not(feature = "fp16")) && half_precision {
+            return Err(PiperError::IncompatibleModel(
+                "the voice runs in float16, which needs the `fp16` feature".to_string()
+            ));
+        }
+
+        Ok(Self { input, input_lengths, scales, sid, output, durations, half_precision })
+    }
+}
@@ -0,0 +1,250 @@
+use std::{
+    io::{self, BufRead, Write},
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
+    thread,
+};
+
+use crate::{
+    cancellation::CancellationToken,
+    error::{PiperError, Result},
+    playback::PlaybackSink,
+    postprocess::{Gain, PostProcess},
+    synthesizer::{SynthesisOptions, Synthesizer},
+};
+
+type Output = Arc<Mutex<Box<dyn Write + Send>>>;
+
+/// Voice settings sent with `SET`, in speech-dispatcher's -100 to 100 ranges.
+#[derive(Debug, Clone, Copy)]
+struct Settings {
+    rate: i32,
+    pitch: i32,
+    volume: i32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self { rate: 0, pitch: 0, volume: 100 }
+    }
+}
+
+impl Settings {
+    fn set(&mut self, key: &str, value: &str) {
+        let Ok(value) = value.parse::<i32>() else {
+            return;
+        };
+        let value = value.clamp(-100, 100);
+        match key {
+            "rate" => self.rate = value,
+            "pitch" => self.pitch = value,
+            "volume" => self.volume = value,
+            _ => {}
+        }
+    }
+
+    /// A rate of 100 speaks three times as fast, -100 three times as slow.
+    fn options(&self, synthesizer: &Synthesizer, cancellation: CancellationToken) -> SynthesisOptions {
+        let length_scale = synthesizer.model.config.inference.length_scale / 3f32.powf(self.rate as f32 / 100.0);
+        SynthesisOptions {
+            length_scale: Some(length_scale),
+            cancellation: Some(cancellation),
+            ..Default::default()
+        }
+    }
+
+    /// Pitch maps to up to an octave either way, volume 100 to unity gain.
+    fn post_process(&self, base: &PostProcess) -> PostProcess {
+        let mut post_process = base.clone();
+        if self.pitch != 0 {
+            post_process.pitch_semitones = Some(base.pitch_semitones.unwrap_or(0.0) + self.pitch as f32 * 0.12);
+        }
+        if self.volume != 100 {
+            let base_gain = base.gain.map_or(1.0, |gain| gain.0);
+            post_process.gain = Some(Gain(base_gain * (self.volume + 100) as f32 / 200.0));
+        }
+        post_process
+    }
+}
+
+struct Job {
+    text: String,
+    settings: Settings,
+    cancellation: CancellationToken,
+}
+
+/// Interrupts the message being spoken on `STOP` and `PAUSE`.
+#[derive(Default)]
+struct Interrupt {
+    current: Mutex<CancellationToken>,
+    paused: AtomicBool,
+}
+
+fn send(output: &Output, reply: &str) -> Result<()> {
+    let mut output = output.lock().unwrap_or_else(|e| e.into_inner());
+    output.write_all(reply.as_bytes())?;
+    output.write_all(b"\n")?;
+    Ok(output.flush()?)
+}
+
+/// Lines up to the terminating `.`, with the leading dot of escaped `..`
+/// lines removed.
+fn read_block(input: &mut impl BufRead) -> Result<Vec<String>> {
+    let mut lines = Vec::new();
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Err(PiperError::Io(io::ErrorKind::UnexpectedEof.into()));
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        match line {
+            "." => return Ok(lines),
+            line => lines.push(line.strip_prefix("..").map_or_else(|| line.to_string(), |rest| format!(".{}", rest))),
+        }
+    }
+}
+
+/// Plain text of the SSML speech-dispatcher wraps messages in.
+fn ssml_text(ssml: &str) -> String {
+    let mut text = String::with_capacity(ssml.len());
+    let mut rest = ssml;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&rest[..start]);
+        match rest[start..].find('>') {
+            Some(end) => rest = &rest[start + end + 1..],
+            None => rest = "",
+        }
+    }
+    text.push_str(rest);
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn speak(synthesizer: &Synthesizer, device: Option<&str>, job: &Job) -> Result<()> {
+    let options = job.settings.options(synthesizer, job.cancellation.clone());
+    let mut sink = PlaybackSink::new(synthesizer.sample_rate(), device)?
+        .with_cancellation(job.cancellation.clone());
+    synthesizer.synthesize_to_sink(&job.text, &options, &mut sink)
+}
+
+/// Speaks queued messages one after another, reporting when each starts and ends.
+fn worker(mut synthesizer: Synthesizer, device: Option<String>, jobs: mpsc::Receiver<Job>, interrupt: Arc<Interrupt>, output: Output) {
+    let base = synthesizer.post_process().clone();
+    for job in jobs {
+        synthesizer = synthesizer.with_post_process(job.settings.post_process(&base));
+        let _ = send(&output, "701 BEGIN");
+        let event = match speak(&synthesizer, device.as_deref(), &job) {
+            Err(PiperError::Cancelled) if interrupt.paused.swap(false, Ordering::Relaxed) => "704 PAUSE",
+            Err(PiperError::Cancelled) => "703 STOP",
+            Err(err) => {
+                tracing::warn!("failed to speak message: {}", err);
+                "702 END"
+            }
+            Ok(()) => "702 END",
+        };
+        let _ = send(&output, event);
+    }
+}
+
+/// Runs a [speech-dispatcher](https://freebsoft.org/speechd) output module on
+/// stdin and stdout, playing messages on `device` or the default output.
+/// `load` is called on `INIT`, a failure is reported to speech-dispatcher.
+/// `voice` is the name listed by `LIST VOICES`.
+pub fn run_module(voice: &str, device: Option<String>, load: impl FnOnce() -> Result<Synthesizer>) -> Result<()> {
+    serve(voice, device, load, io::stdin().lock(), Box::new(io::stdout()))
+}
+
+fn serve(
+    voice: &str,
+    mut device: Option<String>,
+    load: impl FnOnce() -> Result<Synthesizer>,
+    mut input: impl BufRead,
+    output: Box<dyn Write + Send>,
+) -> Result<()> {
+    let output: Output = Arc::new(Mutex::new(output));
+    let interrupt = Arc::new(Interrupt::default());
+    let mut load = Some(load);
+    let mut jobs: Option<mpsc::Sender<Job>> = None;
+    let mut language = String::new();
+    let mut settings = Settings::default();
+
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let command = line.trim();
+        match command {
+            "INIT" => match load.take().map(|load| load()) {
+                Some(Ok(synthesizer)) => {
+                    language = synthesizer.model.config.language.code.replace('_', "-");
+                    let (sender, receiver) = mpsc::channel();
+                    let (device, interrupt, worker_output) = (device.take(), interrupt.clone(), output.clone());
+                    thread::spawn(move || worker(synthesizer, device, receiver, interrupt, worker_output));
+                    jobs = Some(sender);
+                    send(&output, "299-piper-tts-rust: voice loaded\n299 OK LOADED SUCCESSFULLY")?;
+                }
+                Some(Err(err)) => send(&output, &format!("399-piper-tts-rust: {}\n399 ERR CANT INIT MODULE", err))?,
+                None => send(&output, "399-piper-tts-rust: already initialized\n399 ERR CANT INIT MODULE")?,
+            },
+            "AUDIO" => {
+                send(&output, "207 OK RECEIVING AUDIO SETTINGS")?;
+                read_block(&mut input)?;
+                send(&output, "203 OK AUDIO INITIALIZED")?;
+            }
+            "LOGLEVEL" => {
+                send(&output, "207 OK RECEIVING LOGLEVEL SETTINGS")?;
+                read_block(&mut input)?;
+                send(&output, "203 OK LOGLEVEL SET")?;
+            }
+            "SET" => {
+                send(&output, "203 OK RECEIVING SETTINGS")?;
+                for setting in read_block(&mut input)? {
+                    if let Some((key, value)) = setting.split_once('=') {
+                        settings.set(key, value);
+                    }
+                }
+                send(&output, "203 OK SETTINGS RECEIVED")?;
+            }
+            "LIST VOICES" => send(&output, &format!("200-{}\t{}\tnone\n200 OK VOICE LIST SENT", voice, language))?,
+            "SPEAK" | "CHAR" | "KEY" | "SOUND_ICON" => {
+                send(&output, "202 OK RECEIVING MESSAGE")?;
+                let message = read_block(&mut input)?.join("\n");
+                let Some(jobs) = &jobs else {
+                    send(&output, "301 ERROR CANT SPEAK")?;
+                    continue;
+                };
+                let text = match command {
+                    "SPEAK" => ssml_text(&message),
+                    "KEY" => message.replace('_', " "),
+                    // No sound icons to play, the message still has to begin and end.
+                    "SOUND_ICON" => String::new(),
+                    _ => message,
+                };
+                let cancellation = CancellationToken::new();
+                *interrupt.current.lock().unwrap_or_else(|e| e.into_inner()) = cancellation.clone();
+                interrupt.paused.store(false, Ordering::Relaxed);
+                send(&output, "200 OK SPEAKING")?;
+                jobs.send(Job { text, settings, cancellation })
+                    .map_err(|_| PiperError::TaskFailed("speech worker stopped".to_string()))?;
+            }
+            "STOP" | "PAUSE" => {
+                interrupt.paused.store(command == "PAUSE", Ordering::Relaxed);
+                interrupt.current.lock().unwrap_or_else(|e| e.into_inner()).cancel();
+            }
+            "QUIT" => {
+                interrupt.current.lock().unwrap_or_else(|e| e.into_inner()).cancel();
+                send(&output, "210 OK QUIT")?;
+                return Ok(());
+            }
+            _ => send(&output, "300 ERR UNKNOWN COMMAND")?,
+        }
+    }
+}
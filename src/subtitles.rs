@@ -0,0 +1,90 @@
+use std::{fmt::Write, path::Path, str::FromStr, time::Duration};
+
+use crate::error::{PiperError, Result};
+
+/// A sentence and when it is spoken in the synthesized audio.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cue {
+    pub text: String,
+    pub start: Duration,
+    pub end: Duration,
+}
+
+/// A word and when it is spoken, for highlighting text during playback.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WordTiming {
+    pub word: String,
+    pub start: Duration,
+    pub end: Duration,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SubtitleFormat {
+    #[default]
+    Srt,
+    Vtt,
+}
+
+impl SubtitleFormat {
+    /// Guesses the format from a file extension, `None` for unknown ones.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        path.as_ref().extension()?.to_str()?.parse().ok()
+    }
+
+    pub fn render(&self, cues: &[Cue]) -> String {
+        match self {
+            SubtitleFormat::Srt => to_srt(cues),
+            SubtitleFormat::Vtt => to_vtt(cues),
+        }
+    }
+}
+
+impl FromStr for SubtitleFormat {
+    type Err = PiperError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "srt" => Ok(SubtitleFormat::Srt),
+            "vtt" | "webvtt" => Ok(SubtitleFormat::Vtt),
+            _ => Err(PiperError::InvalidConfig(format!("unknown subtitle format `{}`", s))),
+        }
+    }
+}
+
+fn timestamp(time: Duration, decimal_separator: char) -> String {
+    let millis = time.as_millis();
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        decimal_separator,
+        millis % 1000
+    )
+}
+
+/// SubRip subtitles, numbered from 1 with `,` before the milliseconds.
+pub fn to_srt(cues: &[Cue]) -> String {
+    let mut out = String::new();
+    for (i, cue) in cues.iter().enumerate() {
+        let _ = writeln!(out, "{}", i + 1);
+        let _ = writeln!(out, "{} --> {}", timestamp(cue.start, ','), timestamp(cue.end, ','));
+        let _ = writeln!(out, "{}\n", cue.text);
+    }
+    out
+}
+
+/// WebVTT subtitles. Blank lines would end a cue early, so line breaks in the
+/// text are collapsed, and `-->` is spelled out to keep it out of the timings.
+pub fn to_vtt(cues: &[Cue]) -> String {
+    let mut out = String::from("WEBVTT\n\n");
+    for cue in cues {
+        let text = cue.text.split_whitespace().collect::<Vec<_>>().join(" ")
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace("-->", "--&gt;");
+        let _ = writeln!(out, "{} --> {}", timestamp(cue.start, '.'), timestamp(cue.end, '.'));
+        let _ = writeln!(out, "{}\n", text);
+    }
+    out
+}
@@ -0,0 +1,880 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{Arc, PoisonError, RwLock},
+    time::{Duration, Instant},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    alignment::{ChunkAlignment, PhonemeTiming},
+    audio::AudioSink,
+    cancellation::CancellationToken,
+    error::{PiperError, Result},
+    markup::{CodeBlocks, Markup},
+    model_handler::{Inference, Model, PhonemeDuration},
+    pauses::PauseConfig,
+    postprocess::PostProcess,
+    subtitles::{Cue, WordTiming},
+    phoneme_gen::PhonemeGen,
+    phonemizer::{PhonemizedSentence, Phonemizer, PhonemizerConfig},
+    session::SessionOptions,
+    voice_manager::VoiceManager,
+};
+
+/// Per-call overrides, unset fields fall back to the voice config.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct SynthesisOptions {
+    pub noise_scale: Option<f32>,
+    pub length_scale: Option<f32>,
+    pub noise_w: Option<f32>,
+    /// Speaking rate, `1.25` speaks 25% faster. Divides the length scale.
+    pub rate: Option<Rate>,
+    /// Aborts the call with [`PiperError::Cancelled`](crate::PiperError::Cancelled) once cancelled.
+    #[serde(skip)]
+    pub cancellation: Option<CancellationToken>,
+}
+
+impl SynthesisOptions {
+    pub fn scales(&self, defaults: &Inference) -> Inference {
+        Inference {
+            noise_scale: self.noise_scale.unwrap_or(defaults.noise_scale),
+            length_scale: self.length_scale.unwrap_or(defaults.length_scale) / self.rate.map_or(1.0, |rate| rate.0),
+            noise_w: self.noise_w.unwrap_or(defaults.noise_w),
+        }
+    }
+}
+
+/// Speaking rate as a factor, parsed from `1.25` or a percentage such as `+25%` or `-10%`.
+/// Deserializes from a number, rejecting rates that aren't positive and finite.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(try_from = "f32", into = "f32")]
+pub struct Rate(pub f32);
+
+impl TryFrom<f32> for Rate {
+    type Error = PiperError;
+
+    fn try_from(rate: f32) -> Result<Self> {
+        match rate > 0.0 && rate.is_finite() {
+            true => Ok(Rate(rate)),
+            false => Err(PiperError::InvalidConfig(format!("invalid rate {}, expected a positive number", rate))),
+        }
+    }
+}
+
+impl From<Rate> for f32 {
+    fn from(rate: Rate) -> Self {
+        rate.0
+    }
+}
+
+impl FromStr for Rate {
+    type Err = PiperError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || PiperError::InvalidConfig(format!("invalid rate `{}`, expected e.g. `1.25` or `+25%`", s));
+        let s = s.trim();
+        let rate = match s.strip_suffix('%') {
+            Some(percent) => percent.trim().parse::<f32>().map(|percent| 1.0 + percent / 100.0),
+            None => s.parse::<f32>(),
+        };
+        rate.map_err(|_| invalid()).and_then(|rate| Rate::try_from(rate).map_err(|_| invalid()))
+    }
+}
+
+/// Synthesis progress, reported after phonemization and after every chunk of audio.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Progress {
+    pub sentences_completed: usize,
+    pub sentences_total: usize,
+    pub words_phonemized: usize,
+    pub audio_seconds: f64,
+}
+
+/// Where the time of one synthesis went, see [`Synthesizer::synthesize_with_stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SynthesisStats {
+    /// Markup stripping, normalization and phonemization of the whole text.
+    pub phonemization: Duration,
+    /// Voice inference and post-processing.
+    pub inference: Duration,
+    /// Duration of the returned audio.
+    pub audio: Duration,
+}
+
+impl SynthesisStats {
+    pub fn total(&self) -> Duration {
+        self.phonemization + self.inference
+    }
+
+    /// Synthesis time over audio duration, below 1 is faster than real time.
+    pub fn real_time_factor(&self) -> f64 {
+        self.total().as_secs_f64() / self.audio.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// Applies post-processing to each chunk on its way to the wrapped sink.
+/// With a fade-out the latest chunk is held back until the next one arrives,
+/// since only [`finish`](AudioSink::finish) tells which chunk is the last.
+struct PostProcessSink<'a, S: AudioSink> {
+    post_process: &'a PostProcess,
+    sample_rate: u64,
+    sink: &'a mut S,
+    started: bool,
+    held: Vec<f32>,
+}
+
+impl<S: AudioSink> AudioSink for PostProcessSink<'_, S> {
+    fn write_samples(&mut self, samples: &[f32]) -> Result<()> {
+        let mut processed = self.post_process.process_chunk(samples.to_vec(), self.sample_rate)?;
+        let sample_rate = self.post_process.output_sample_rate(self.sample_rate);
+        if !self.started && !processed.is_empty() {
+            self.post_process.fade_in(&mut processed, sample_rate);
+            self.started = true;
+        }
+        if self.post_process.fade_out.is_none() {
+            return self.sink.write_samples(&processed);
+        }
+        let previous = std::mem::replace(&mut self.held, processed);
+        self.sink.write_samples(&previous)
+    }
+
+    fn finish(&mut self) -> Result<()> {
+        let mut last = std::mem::take(&mut self.held);
+        self.post_process.fade_out(&mut last, self.post_process.output_sample_rate(self.sample_rate));
+        self.sink.write_samples(&last)?;
+        self.sink.finish()
+    }
+}
+
+/// Fades each chunk in and out before passing it on, see [`PostProcess::fade_chunks`].
+struct ChunkFadeSink<'a> {
+    post_process: &'a PostProcess,
+    sample_rate: u64,
+    sink: &'a mut dyn AudioSink,
+}
+
+impl AudioSink for ChunkFadeSink<'_> {
+    fn write_samples(&mut self, samples: &[f32]) -> Result<()> {
+        let mut chunk = samples.to_vec();
+        self.post_process.fade_in(&mut chunk, self.sample_rate);
+        self.post_process.fade_out(&mut chunk, self.sample_rate);
+        self.sink.write_samples(&chunk)
+    }
+}
+
+/// What one inference wrote to the sink.
+struct Chunk<'a> {
+    /// The `^...$` model input the audio was synthesized from.
+    model_input: &'a str,
+    /// Samples of speech, followed by `samples - speech_samples` of pause.
+    speech_samples: usize,
+    samples: usize,
+    /// Sentences finished by this chunk.
+    completed: &'a [PhonemizedSentence],
+    durations: Option<&'a [PhonemeDuration]>,
+}
+
+/// Called after every inference with the overall progress and the chunk
+/// just written.
+type ProgressCallback<'a> = dyn FnMut(Progress, &Chunk) + 'a;
+
+/// Start and end sample of every word in the chunk's speech, from the model's
+/// phoneme durations when they line up with the words, estimated otherwise.
+fn word_spans(chunk: &Chunk, separators: &[i64], pads: &[i64]) -> Vec<(usize, usize)> {
+    let words: Vec<&str> = chunk.model_input.split(|c: char| c.is_whitespace() || c == '^' || c == '$')
+        .filter(|word| !word.is_empty())
+        .collect();
+    if let Some(durations) = chunk.durations {
+        let mut spans = Vec::new();
+        let mut word: Option<(usize, usize)> = None;
+        let mut offset = 0;
+        for duration in durations {
+            let end = offset + duration.samples;
+            if separators.contains(&duration.id) {
+                spans.extend(word.take());
+            } else if pads.contains(&duration.id) {
+                if let Some((_, word_end)) = &mut word {
+                    *word_end = end;
+                }
+            } else {
+                word = Some((word.map_or(offset, |(start, _)| start), end));
+            }
+            offset = end;
+        }
+        spans.extend(word);
+        if spans.len() == words.len() {
+            return spans;
+        }
+    }
+    let weights: Vec<usize> = words.iter().map(|word| word.chars().count()).collect();
+    share_out(&weights, chunk.speech_samples)
+}
+
+/// Splits `total` samples into consecutive spans proportional to `weights`.
+fn share_out(weights: &[usize], total: usize) -> Vec<(usize, usize)> {
+    let sum = weights.iter().sum::<usize>().max(1);
+    let mut before = 0;
+    weights.iter()
+        .map(|weight| {
+            let start = before * total / sum;
+            before += weight;
+            (start, before * total / sum)
+        })
+        .collect()
+}
+
+/// Silence inserted between the clauses of a chunked sentence.
+const CLAUSE_PAUSE: Duration = Duration::from_millis(150);
+
+/// Text to waveform pipeline, generic over the [`Phonemizer`] front end.
+pub struct Synthesizer<P: Phonemizer = PhonemeGen> {
+    pub phonemizer: P,
+    pub model: Model,
+    max_clause_phonemes: Option<usize>,
+    pauses: PauseConfig,
+    markup: Markup,
+    code_blocks: CodeBlocks,
+    post_process: PostProcess,
+    voices: Option<VoiceManager>,
+    /// Name of `model` within `voices`.
+    voice: Option<String>,
+    /// Front ends declared by voice configs, loaded on first use.
+    voice_phonemizers: RwLock<HashMap<PhonemizerConfig, Arc<dyn Phonemizer + Send + Sync>>>,
+}
+
+// Shared as `Arc<Synthesizer>` across threads, keep it that way.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Synthesizer>();
+};
+
+impl<P: Phonemizer> Synthesizer<P> {
+    pub fn new(phonemizer: P, model: Model) -> Self {
+        Self {
+            phonemizer,
+            model,
+            max_clause_phonemes: None,
+            pauses: PauseConfig::default(),
+            markup: Markup::Plain,
+            code_blocks: CodeBlocks::default(),
+            post_process: PostProcess::default(),
+            voices: None,
+            voice: None,
+            voice_phonemizers: RwLock::default(),
+        }
+    }
+
+    /// Sets the post-processing applied to the synthesized audio.
+    pub fn with_post_process(mut self, post_process: PostProcess) -> Self {
+        self.post_process = post_process;
+        self
+    }
+
+    pub fn post_process(&self) -> &PostProcess {
+        &self.post_process
+    }
+
+    /// Lets [`set_voice`](Self::set_voice) switch between the voices of
+    /// `voices`, naming the synthesizer's current model `current`.
+    pub fn with_voices(mut self, voices: VoiceManager, current: impl Into<String>) -> Self {
+        self.voices = Some(voices);
+        self.voice = Some(current.into());
+        self
+    }
+
+    pub fn voices(&self) -> Option<&VoiceManager> {
+        self.voices.as_ref()
+    }
+
+    /// Name of the active voice, when a [`VoiceManager`] is set.
+    pub fn voice(&self) -> Option<&str> {
+        self.voice.as_deref()
+    }
+
+    /// Makes `name` the active voice, loading it if needed and handing the
+    /// previous one back to the [`VoiceManager`].
+    pub fn set_voice(&mut self, name: &str) -> Result<()> {
+        if self.voice.as_deref() == Some(name) {
+            return Ok(());
+        }
+        let voices = self.voices.as_mut()
+            .ok_or_else(|| PiperError::InvalidConfig("no voice manager set".to_string()))?;
+        let model = voices.take(name)?;
+        let previous = std::mem::replace(&mut self.model, model);
+        if let Some(previous_name) = self.voice.replace(name.to_string()) {
+            voices.release(previous_name, previous);
+        }
+        Ok(())
+    }
+
+    /// Runs the phonemizer and the voice once, see [`Model::warm_up`].
+    pub fn warm_up(&self) -> Result<()> {
+        match self.voice_phonemizer()? {
+            Some(phonemizer) => phonemizer.warm_up()?,
+            None => self.phonemizer.warm_up()?,
+        }
+        self.model.warm_up()
+    }
+
+    /// The front end the active voice's config declares, see [`PhonemizerConfig`].
+    fn voice_phonemizer(&self) -> Result<Option<Arc<dyn Phonemizer + Send + Sync>>> {
+        let Some(config) = &self.model.config.phonemizer else {
+            return Ok(None);
+        };
+        if let Some(phonemizer) = self.voice_phonemizers.read().unwrap_or_else(PoisonError::into_inner).get(config) {
+            return Ok(Some(phonemizer.clone()));
+        }
+        let session_options = self.voices.as_ref()
+            .map_or_else(SessionOptions::default, |voices| voices.session_options().clone());
+        let phonemizer: Arc<dyn Phonemizer + Send + Sync> = config.load(&session_options)?.into();
+        let mut phonemizers = self.voice_phonemizers.write().unwrap_or_else(PoisonError::into_inner);
+        Ok(Some(phonemizers.entry(config.clone()).or_insert(phonemizer).clone()))
+    }
+
+    fn phonemize(&self, text: &str, cancellation: Option<&CancellationToken>) -> Result<Vec<PhonemizedSentence>> {
+        let _span = tracing::debug_span!("phonemize", chars = text.len()).entered();
+        match self.voice_phonemizer()? {
+            Some(phonemizer) => phonemizer.phonemize_cancellable(text, cancellation),
+            None => self.phonemizer.phonemize_cancellable(text, cancellation),
+        }
+    }
+
+    /// Sets the silence inserted for `[pause]` markers and ellipses.
+    pub fn with_pauses(mut self, pauses: PauseConfig) -> Self {
+        self.pauses = pauses;
+        self
+    }
+
+    /// Reads input text as `markup`, stripping it down to plain sentences
+    /// before pause markers and normalization are handled.
+    pub fn with_markup(mut self, markup: Markup, code_blocks: CodeBlocks) -> Self {
+        self.markup = markup;
+        self.code_blocks = code_blocks;
+        self
+    }
+
+    fn silence(&self, duration: Duration) -> Vec<f32> {
+        vec![0.0; (self.voice_sample_rate() as f64 * duration.as_secs_f64()) as usize]
+    }
+
+    /// Synthesizes sentences longer than `max_phonemes` clause by clause, split at
+    /// commas and semicolons, which keeps inference memory and latency bounded.
+    pub fn with_clause_chunking(mut self, max_phonemes: usize) -> Self {
+        self.max_clause_phonemes = Some(max_phonemes);
+        self
+    }
+
+    /// Groups the clauses of `phonemes` into chunks of at most `max_phonemes`,
+    /// a single clause longer than that becomes a chunk of its own.
+    fn split_clauses(phonemes: &str, max_phonemes: usize) -> Vec<String> {
+        let mut chunks: Vec<String> = Vec::new();
+        let mut chunk = String::new();
+        for clause in phonemes.split_inclusive([',', ';', ':']) {
+            let clause_len = clause.chars().count();
+            if !chunk.is_empty() && chunk.chars().count() + clause_len > max_phonemes {
+                chunks.push(chunk.trim().to_string());
+                chunk.clear();
+            }
+            chunk.push_str(clause);
+        }
+        if !chunk.trim().is_empty() {
+            chunks.push(chunk.trim().to_string());
+        }
+        chunks
+    }
+
+    /// Splits a sentence at its synthesis join points, which are the clause
+    /// chunks of an over-long sentence and the punctuation mapped to a pause,
+    /// returning each piece with the silence that follows it.
+    fn join_points(&self, phonemes: &str) -> Vec<(String, Option<Duration>)> {
+        let too_long = self.max_clause_phonemes.is_some_and(|max| phonemes.chars().count() > max);
+        let clauses = match self.max_clause_phonemes {
+            Some(max_phonemes) if too_long => Self::split_clauses(phonemes, max_phonemes),
+            _ => vec![phonemes.to_string()],
+        };
+
+        let punctuation = &self.pauses.punctuation;
+        let mut pieces = Vec::new();
+        for (i, clause) in clauses.iter().enumerate() {
+            let last_clause = i + 1 == clauses.len();
+            let parts: Vec<&str> = clause.split_inclusive(|c| punctuation.contains_key(&c))
+                .map(str::trim)
+                .filter(|part| !part.is_empty())
+                .collect();
+            for (j, part) in parts.iter().enumerate() {
+                let last_part = j + 1 == parts.len();
+                let pause = part.chars().last()
+                    .and_then(|c| punctuation.get(&c).copied())
+                    .or((last_part && !last_clause).then_some(CLAUSE_PAUSE));
+                pieces.push((part.to_string(), pause));
+            }
+        }
+        pieces
+    }
+
+    fn voice_sample_rate(&self) -> u64 {
+        self.model.config.audio.sample_rate
+    }
+
+    /// Sample rate of the returned audio, the voice's own unless post-processing resamples it.
+    pub fn sample_rate(&self) -> u64 {
+        self.post_process.output_sample_rate(self.voice_sample_rate())
+    }
+
+    pub fn synthesize(&self, text: &str) -> Result<Vec<f32>> {
+        self.synthesize_with(text, &SynthesisOptions::default())
+    }
+
+    pub fn synthesize_with(&self, text: &str, options: &SynthesisOptions) -> Result<Vec<f32>> {
+        self.synthesize_with_progress(text, options, |_| {})
+    }
+
+    /// Like [`synthesize_with`](Self::synthesize_with), calling `on_progress`
+    /// once the text is phonemized and after every inference.
+    pub fn synthesize_with_progress(
+        &self,
+        text: &str,
+        options: &SynthesisOptions,
+        mut on_progress: impl FnMut(Progress),
+    ) -> Result<Vec<f32>> {
+        let mut waveform = Vec::new();
+        self.synthesize_to_sink_with_progress(text, options, true, &mut waveform, &mut |progress, _| on_progress(progress))?;
+        self.post_process.apply(waveform, self.voice_sample_rate())
+    }
+
+    /// Like [`synthesize_with`](Self::synthesize_with), also returning how
+    /// long phonemization and inference took.
+    pub fn synthesize_with_stats(&self, text: &str, options: &SynthesisOptions) -> Result<(Vec<f32>, SynthesisStats)> {
+        let start = Instant::now();
+        // Progress is first reported once the whole text is phonemized.
+        let mut phonemized = None;
+        let mut waveform = Vec::new();
+        self.synthesize_to_sink_with_progress(text, options, true, &mut waveform, &mut |_, _| {
+            phonemized.get_or_insert_with(Instant::now);
+        })?;
+        let waveform = self.post_process.apply(waveform, self.voice_sample_rate())?;
+
+        let phonemized = phonemized.unwrap_or(start);
+        let stats = SynthesisStats {
+            phonemization: phonemized - start,
+            inference: phonemized.elapsed(),
+            audio: Duration::from_secs_f64(waveform.len() as f64 / self.sample_rate() as f64),
+        };
+        Ok((waveform, stats))
+    }
+
+    /// Like [`synthesize_with`](Self::synthesize_with), also returning one
+    /// subtitle cue per sentence timed against the returned audio.
+    pub fn synthesize_with_cues(&self, text: &str, options: &SynthesisOptions) -> Result<(Vec<f32>, Vec<Cue>)> {
+        let sample_rate = self.voice_sample_rate() as f64;
+        let time_scale = self.post_process.time_scale();
+        let mut cues = Vec::new();
+        let mut speech_samples = 0;
+        let mut waveform = Vec::new();
+
+        self.synthesize_to_sink_with_progress(text, options, false, &mut waveform, &mut |progress, chunk| {
+            speech_samples += chunk.samples;
+            if let Some(sentence) = chunk.completed.last() {
+                let end = progress.audio_seconds * time_scale;
+                let start = end - speech_samples as f64 / sample_rate * time_scale;
+                cues.push(Cue {
+                    text: sentence.text.trim().to_string(),
+                    start: Duration::from_secs_f64(start.max(0.0)),
+                    end: Duration::from_secs_f64(end),
+                });
+                speech_samples = 0;
+            }
+        })?;
+
+        Ok((self.post_process.apply(waveform, self.voice_sample_rate())?, cues))
+    }
+
+    /// Like [`synthesize_with`](Self::synthesize_with), also returning when
+    /// every word is spoken. Voices exported with a `durations` output give
+    /// the timings predicted by the model, for others each chunk's duration
+    /// is shared out by the length of its words' phonemes.
+    pub fn synthesize_with_word_timings(
+        &self,
+        text: &str,
+        options: &SynthesisOptions,
+    ) -> Result<(Vec<f32>, Vec<WordTiming>)> {
+        let sample_rate = self.voice_sample_rate() as f64;
+        let time_scale = self.post_process.time_scale();
+        let to_time = |samples: usize| Duration::from_secs_f64(samples as f64 / sample_rate * time_scale);
+        let id_map = &self.model.config.phoneme_id_map;
+        let ids = |keys: &[&str]| -> Vec<i64> {
+            keys.iter().filter_map(|key| id_map.get(*key)).flatten().copied().collect()
+        };
+        let (separators, pads) = (ids(&[" ", "^", "$"]), ids(&["_"]));
+
+        let mut timings = Vec::new();
+        let mut spans: Vec<(usize, usize)> = Vec::new();
+        let mut waveform = Vec::new();
+        self.synthesize_to_sink_with_progress(text, options, false, &mut waveform, &mut |progress, chunk| {
+            let chunk_start = ((progress.audio_seconds * sample_rate).round() as usize).saturating_sub(chunk.samples);
+            spans.extend(word_spans(chunk, &separators, &pads).into_iter()
+                .map(|(start, end)| (chunk_start + start, chunk_start + end)));
+            let Some(sentence) = chunk.completed.last() else {
+                return;
+            };
+            let words: Vec<&str> = sentence.text.split_whitespace().collect();
+            // Numbers and abbreviations can phonemize to more or fewer
+            // words than written, those sentences are shared out by length.
+            if words.len() != spans.len() && let (Some(first), Some(last)) = (spans.first(), spans.last()) {
+                let weights: Vec<usize> = words.iter().map(|word| word.chars().count()).collect();
+                spans = share_out(&weights, last.1 - first.0).into_iter()
+                    .map(|(start, end)| (first.0 + start, first.0 + end))
+                    .collect();
+            }
+            timings.extend(words.iter().zip(&spans).map(|(word, &(start, end))| WordTiming {
+                word: word.to_string(),
+                start: to_time(start),
+                end: to_time(end),
+            }));
+            spans.clear();
+        })?;
+
+        Ok((self.post_process.apply(waveform, self.voice_sample_rate())?, timings))
+    }
+
+    /// Like [`synthesize_with`](Self::synthesize_with), also returning the
+    /// id and timing of every phoneme, one [`ChunkAlignment`] per inference.
+    /// Timings are the model's own for voices exported with a `durations`
+    /// output and an even split of each chunk otherwise.
+    pub fn synthesize_with_alignment(
+        &self,
+        text: &str,
+        options: &SynthesisOptions,
+    ) -> Result<(Vec<f32>, Vec<ChunkAlignment>)> {
+        let sample_rate = self.voice_sample_rate() as f64;
+        let time_scale = self.post_process.time_scale();
+        let mut chunks = Vec::new();
+        let mut waveform = Vec::new();
+        self.synthesize_to_sink_with_progress(text, options, true, &mut waveform, &mut |progress, chunk| {
+            if chunk.model_input.is_empty() {
+                return;
+            }
+            let start = ((progress.audio_seconds * sample_rate).round() as usize).saturating_sub(chunk.samples);
+            chunks.push((chunk.model_input.to_string(), start, chunk.speech_samples, chunk.durations.map(<[_]>::to_vec)));
+        })?;
+
+        let symbols: HashMap<i64, &str> = self.model.config.phoneme_id_map.iter()
+            .flat_map(|(symbol, ids)| ids.iter().map(move |&id| (id, symbol.as_str())))
+            .collect();
+        let to_seconds = |samples: usize| samples as f64 / sample_rate * time_scale;
+        let mut alignments = Vec::new();
+        for (phonemes, start, speech_samples, durations) in chunks {
+            let estimated = durations.is_none();
+            let durations = match durations {
+                Some(durations) => durations,
+                None => {
+                    let ids = self.model.ipa_string_to_phoneme_ids(&phonemes)?;
+                    let weights = vec![1; ids.len()];
+                    ids.into_iter().zip(share_out(&weights, speech_samples))
+                        .map(|(id, (start, end))| PhonemeDuration { id, samples: end - start })
+                        .collect()
+                }
+            };
+            let mut offset = start;
+            let timings = durations.iter()
+                .map(|duration| {
+                    let timing = PhonemeTiming {
+                        id: duration.id,
+                        phoneme: symbols.get(&duration.id).copied().unwrap_or_default().to_string(),
+                        start: to_seconds(offset),
+                        duration: to_seconds(duration.samples),
+                    };
+                    offset += duration.samples;
+                    timing
+                })
+                .collect();
+            alignments.push(ChunkAlignment {
+                phonemes,
+                start: to_seconds(start),
+                end: to_seconds(start + speech_samples),
+                estimated,
+                timings,
+            });
+        }
+
+        Ok((self.post_process.apply(waveform, self.voice_sample_rate())?, alignments))
+    }
+
+    /// Pushes the audio to `sink` after every inference, so the first
+    /// sentences can be played or written before the whole text is done.
+    /// Post-processing is applied to each chunk separately, apart from loudness
+    /// normalization which needs the whole waveform and is skipped.
+    pub fn synthesize_to_sink(
+        &self,
+        text: &str,
+        options: &SynthesisOptions,
+        sink: &mut impl AudioSink,
+    ) -> Result<()> {
+        if self.post_process.loudness.is_some() {
+            tracing::warn!("loudness normalization needs the whole waveform and is skipped when streaming audio");
+        }
+        let mut sink = PostProcessSink {
+            post_process: &self.post_process,
+            sample_rate: self.voice_sample_rate(),
+            sink,
+            started: false,
+            held: Vec::new(),
+        };
+        self.synthesize_to_sink_with_progress(text, options, true, &mut sink, &mut |_, _| {})?;
+        sink.finish()
+    }
+
+    /// `batch_sentences` lets consecutive sentences share an inference, it is
+    /// off while collecting timings so every sentence gets exact ones.
+    fn synthesize_to_sink_with_progress(
+        &self,
+        text: &str,
+        options: &SynthesisOptions,
+        batch_sentences: bool,
+        sink: &mut dyn AudioSink,
+        on_progress: &mut ProgressCallback,
+    ) -> Result<()> {
+        self.fading_chunks(sink, |sink| self.synthesize_segments(text, options, batch_sentences, sink, on_progress))
+    }
+
+    /// Runs `write` on `sink`, wrapped in a [`ChunkFadeSink`] when every chunk is faded.
+    fn fading_chunks<T>(&self, sink: &mut dyn AudioSink, write: impl FnOnce(&mut dyn AudioSink) -> Result<T>) -> Result<T> {
+        match self.post_process.fade_chunks {
+            true => write(&mut ChunkFadeSink {
+                post_process: &self.post_process,
+                sample_rate: self.voice_sample_rate(),
+                sink,
+            }),
+            false => write(sink),
+        }
+    }
+
+    fn synthesize_segments(
+        &self,
+        text: &str,
+        options: &SynthesisOptions,
+        batch_sentences: bool,
+        sink: &mut dyn AudioSink,
+        on_progress: &mut ProgressCallback,
+    ) -> Result<()> {
+        let _span = tracing::debug_span!("synthesize", chars = text.len()).entered();
+        let scales = options.scales(&self.model.config.inference);
+        let cancellation = options.cancellation.as_ref();
+
+        let text = self.markup.strip(text, self.code_blocks);
+        let mut segments = Vec::new();
+        for segment in self.pauses.split(&text) {
+            let sentences = match segment.text.trim().is_empty() {
+                true => Vec::new(),
+                false => self.phonemize(segment.text, cancellation)?,
+            };
+            segments.push((sentences, segment.pause));
+        }
+
+        let mut progress = Progress {
+            sentences_total: segments.iter().map(|(sentences, _)| sentences.len()).sum(),
+            words_phonemized: segments.iter()
+                .flat_map(|(sentences, _)| sentences)
+                .map(|sentence| sentence.text.split_whitespace().count())
+                .sum(),
+            ..Default::default()
+        };
+        on_progress(progress, &Chunk { model_input: "", speech_samples: 0, samples: 0, completed: &[], durations: None });
+
+        let sample_rate = self.voice_sample_rate() as f64;
+        let mut samples_written = 0;
+        for (sentences, pause) in &segments {
+            self.synthesize_sentences(sentences, &scales, cancellation, batch_sentences, sink, &mut |chunk| {
+                samples_written += chunk.samples;
+                progress.sentences_completed += chunk.completed.len();
+                progress.audio_seconds = samples_written as f64 / sample_rate;
+                on_progress(progress, chunk);
+            })?;
+            if let Some(pause) = pause {
+                let silence = self.silence(*pause);
+                samples_written += silence.len();
+                sink.write_samples(&silence)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Synthesizes independent texts, running those without pauses or join
+    /// points through batched inference, see [`Model::process_ipa_batch_with_scales`].
+    pub fn synthesize_batch(&self, texts: &[&str], options: &SynthesisOptions) -> Result<Vec<Vec<f32>>> {
+        let scales = options.scales(&self.model.config.inference);
+
+        let mut waveforms = vec![Vec::new(); texts.len()];
+        let mut batch: Vec<(usize, String)> = Vec::new();
+        for (i, text) in texts.iter().enumerate() {
+            let stripped = self.markup.strip(text, self.code_blocks);
+            let segments = self.pauses.split(&stripped);
+            let single_segment = matches!(segments.as_slice(), [segment] if segment.pause.is_none());
+            if single_segment {
+                let sentences = self.phonemize(&stripped, options.cancellation.as_ref())?;
+                if sentences.iter().all(|sentence| matches!(self.join_points(&sentence.phonemes).as_slice(), [(_, None)])) {
+                    let model_input: String = sentences.iter().map(PhonemizedSentence::to_model_input).collect();
+                    batch.push((i, model_input));
+                    continue;
+                }
+            }
+            self.synthesize_to_sink_with_progress(text, options, true, &mut waveforms[i], &mut |_, _| {})?;
+        }
+
+        if let Some(token) = &options.cancellation {
+            token.check()?;
+        }
+        let ipa_strings: Vec<&str> = batch.iter().map(|(_, model_input)| model_input.as_str()).collect();
+        let outputs = self.model.process_ipa_batch_with_scales(&ipa_strings, &scales)?;
+        for ((i, _), waveform) in batch.iter().zip(outputs) {
+            self.fading_chunks(&mut waveforms[*i], |sink| sink.write_samples(&waveform))?;
+        }
+        waveforms.into_iter()
+            .map(|waveform| self.post_process.apply(waveform, self.voice_sample_rate()))
+            .collect()
+    }
+
+    /// Synthesizes mixed-language `text` part by part with the voice `router`
+    /// picks for each part's language, the active voice reading the parts it
+    /// can't tell. Parts read by another voice are resampled to the active
+    /// voice's rate, and the active voice is active again afterwards.
+    #[cfg(feature = "detect-language")]
+    pub fn synthesize_routed(
+        &mut self,
+        text: &str,
+        options: &SynthesisOptions,
+        router: &crate::language::LanguageRouter,
+    ) -> Result<Vec<f32>> {
+        let active = self.voice.clone()
+            .ok_or_else(|| PiperError::InvalidConfig("language routing needs a voice manager".to_string()))?;
+        let sample_rate = self.sample_rate();
+
+        let mut waveform = Vec::new();
+        let mut result = Ok(());
+        for (voice, part) in router.route(text) {
+            result = self.set_voice(voice.unwrap_or(&active))
+                .and_then(|()| self.synthesize_with(part, options))
+                .and_then(|part| crate::audio::resample(&part, self.sample_rate(), sample_rate))
+                .map(|part| waveform.extend(part));
+            if result.is_err() {
+                break;
+            }
+        }
+        self.set_voice(&active)?;
+        result.map(|()| waveform)
+    }
+
+    /// Writes the audio of `sentences` to `sink`, calling `on_chunk` after
+    /// every inference.
+    fn synthesize_sentences(
+        &self,
+        sentences: &[PhonemizedSentence],
+        scales: &Inference,
+        cancellation: Option<&CancellationToken>,
+        batch_sentences: bool,
+        sink: &mut dyn AudioSink,
+        on_chunk: &mut dyn FnMut(&Chunk),
+    ) -> Result<()> {
+        let check_cancelled = || cancellation.map_or(Ok(()), CancellationToken::check);
+        let sentence_silence = self.pauses.sentence_silence;
+        let batch_sentences = batch_sentences && sentence_silence.is_none();
+
+        let mut batch = String::new();
+        let mut batch_start = 0;
+        for (index, sentence) in sentences.iter().enumerate() {
+            check_cancelled()?;
+            let pieces = self.join_points(&sentence.phonemes);
+            let whole = matches!(pieces.as_slice(), [(_, None)]);
+            if whole && (batch_sentences || batch.is_empty()) {
+                if batch.is_empty() {
+                    batch_start = index;
+                }
+                batch.push_str(&sentence.to_model_input());
+                continue;
+            }
+
+            if !batch.is_empty() {
+                let (mut waveform, durations) = self.model.process_ipa_string_with_durations(&batch, scales)?;
+                let speech_samples = waveform.len();
+                if let Some(silence) = sentence_silence {
+                    waveform.extend(self.silence(silence));
+                }
+                sink.write_samples(&waveform)?;
+                on_chunk(&Chunk {
+                    model_input: &batch,
+                    speech_samples,
+                    samples: waveform.len(),
+                    completed: &sentences[batch_start..index],
+                    durations: durations.as_deref(),
+                });
+                batch.clear();
+            }
+            if whole {
+                batch_start = index;
+                batch.push_str(&sentence.to_model_input());
+                continue;
+            }
+            let last_piece = pieces.len() - 1;
+            for (i, (piece, pause)) in pieces.into_iter().enumerate() {
+                check_cancelled()?;
+                let ipa_string = format!("^{}$", piece);
+                let (mut waveform, durations) = self.model.process_ipa_string_with_durations(&ipa_string, scales)?;
+                let speech_samples = waveform.len();
+                if let Some(pause) = pause.or(sentence_silence.filter(|_| i == last_piece)) {
+                    waveform.extend(self.silence(pause));
+                }
+                sink.write_samples(&waveform)?;
+                let completed = if i == last_piece { std::slice::from_ref(sentence) } else { &[] };
+                on_chunk(&Chunk {
+                    model_input: &ipa_string,
+                    speech_samples,
+                    samples: waveform.len(),
+                    completed,
+                    durations: durations.as_deref(),
+                });
+            }
+        }
+        if !batch.is_empty() {
+            check_cancelled()?;
+            let (mut waveform, durations) = self.model.process_ipa_string_with_durations(&batch, scales)?;
+            let speech_samples = waveform.len();
+            if let Some(silence) = sentence_silence {
+                waveform.extend(self.silence(silence));
+            }
+            sink.write_samples(&waveform)?;
+            on_chunk(&Chunk {
+                model_input: &batch,
+                speech_samples,
+                samples: waveform.len(),
+                completed: &sentences[batch_start..],
+                durations: durations.as_deref(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rates() {
+        assert_eq!("1.25".parse::<Rate>().unwrap(), Rate(1.25));
+        assert_eq!("+25%".parse::<Rate>().unwrap(), Rate(1.25));
+        assert_eq!("-10%".parse::<Rate>().unwrap(), Rate(0.9));
+        assert!("-100%".parse::<Rate>().is_err());
+        assert!("0".parse::<Rate>().is_err());
+    }
+
+    #[test]
+    fn deserialized_rates_are_validated() {
+        let options: SynthesisOptions = serde_json::from_str(r#"{"rate":2}"#).unwrap();
+        assert_eq!(options.rate, Some(Rate(2.0)));
+        for invalid in [r#"{"rate":0}"#, r#"{"rate":-1.5}"#] {
+            assert!(serde_json::from_str::<SynthesisOptions>(invalid).is_err());
+        }
+    }
+}
@@ -0,0 +1,124 @@
+use std::{collections::HashMap, path::Path, sync::LazyLock};
+
+use regex::{Captures, Regex};
+
+use crate::error::{PiperError, Result};
+
+use super::TextNormalizer;
+
+const DEFAULT_ABBREVIATIONS: &str = include_str!("abbreviations.txt");
+
+static TOKEN: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\S+").unwrap());
+
+/// Abbreviations of the embedded table, lowercased, that are as likely to end
+/// a sentence as to continue it, as in `Smith & Co.` or `and so on etc.`
+pub const TERMINAL_ABBREVIATIONS: [&str; 13] = [
+    "sr.", "jr.", "ave.", "rd.", "blvd.", "ln.", "inc.", "ltd.", "co.", "corp.", "bros.", "etc.", "est.",
+];
+
+/// Parses `abbreviation expansion...` lines, `#` starts a comment.
+pub fn parse_abbreviations(data: &str) -> HashMap<String, String> {
+    data.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once(char::is_whitespace))
+        .map(|(abbreviation, expansion)| (abbreviation.to_string(), expansion.trim().to_string()))
+        .collect()
+}
+
+/// The embedded English abbreviation table.
+pub fn default_abbreviations() -> HashMap<String, String> {
+    parse_abbreviations(DEFAULT_ABBREVIATIONS)
+}
+
+pub fn load_abbreviations(path: impl AsRef<Path>) -> Result<HashMap<String, String>> {
+    let path = path.as_ref();
+    let data = std::fs::read_to_string(path)
+        .map_err(|e| PiperError::AssetLoad {
+            what: "abbreviations",
+            path: path.display().to_string(),
+            reason: e.to_string(),
+        })?;
+    Ok(parse_abbreviations(&data))
+}
+
+fn swap_first_case(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) if first.is_uppercase() => first.to_lowercase().chain(chars).collect(),
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Whether the abbreviation `core` ends a sentence: it ends the text, or it can
+/// end a sentence and the next word is capitalized.
+fn ends_sentence(core: &str, text: &str, token_end: usize) -> bool {
+    match TOKEN.find_at(text, token_end) {
+        None => true,
+        Some(next) => TERMINAL_ABBREVIATIONS.contains(&core.to_lowercase().as_str())
+            && next.as_str().trim_start_matches(|c: char| !c.is_alphanumeric()).starts_with(char::is_uppercase),
+    }
+}
+
+/// Replaces whole-word abbreviations such as `Dr.` or `e.g.`, keeping the
+/// surrounding punctuation and the full stop of an abbreviation ending a sentence.
+pub fn expand_abbreviations(text: &str, normalizer: &TextNormalizer) -> String {
+    if normalizer.abbreviations.is_empty() {
+        return text.to_string();
+    }
+
+    TOKEN.replace_all(text, |captures: &Captures| {
+        let token = captures.get(0).unwrap();
+        let word = token.as_str();
+
+        let start = word.find(|c: char| c.is_alphanumeric()).unwrap_or(word.len());
+        let end = word.trim_end_matches([',', ';', ':', '!', '?', ')', ']', '"', '\'', '”', '’']).len().max(start);
+        let (prefix, core, suffix) = (&word[..start], &word[start..end], &word[end..]);
+
+        let expansion = normalizer.abbreviations.get(core)
+            .or_else(|| normalizer.abbreviations.get(&swap_first_case(core)));
+        match expansion {
+            Some(expansion) => {
+                let full_stop = if core.ends_with('.') && suffix.is_empty() && ends_sentence(core, text, token.end()) { "." } else { "" };
+                format!("{}{}{}{}", prefix, expansion, full_stop, suffix)
+            }
+            None => word.to_string(),
+        }
+    }).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::phoneme_gen::PhonemeGen;
+
+    fn normalize(text: &str) -> String {
+        expand_abbreviations(text, &TextNormalizer::default())
+    }
+
+    #[test]
+    fn expands_abbreviations() {
+        assert_eq!(normalize("Dr. Smith lives on Elm St. now"), "doctor Smith lives on Elm street now");
+        assert_eq!(normalize("(approx. 5)"), "(approximately 5)");
+    }
+
+    #[test]
+    fn keeps_the_full_stop_ending_the_text() {
+        assert_eq!(normalize("He works at Acme Inc."), "He works at Acme incorporated.");
+    }
+
+    #[test]
+    fn keeps_the_full_stop_before_a_new_sentence() {
+        assert_eq!(normalize("He works at Acme Inc. She does not."), "He works at Acme incorporated. She does not.");
+        assert_eq!(normalize("Smith & Co. sells tea."), "Smith & company sells tea.");
+        assert_eq!(normalize("Dr. Smith"), "doctor Smith");
+    }
+
+    #[test]
+    fn terminal_abbreviations_end_sentences_after_normalizing() {
+        let split = |text: &str| PhonemeGen::split_sentences(&TextNormalizer::default().normalize(text));
+        assert_eq!(split("He works at Acme Inc. She does not."), ["He works at Acme incorporated.", "She does not."]);
+        assert_eq!(split("apples, pears etc. Then we left."), ["apples, pears et cetera.", "Then we left."]);
+    }
+}
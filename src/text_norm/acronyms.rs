@@ -0,0 +1,128 @@
+use std::{collections::HashMap, sync::LazyLock};
+
+use regex::{Captures, Regex};
+
+use super::TextNormalizer;
+
+/// Whether an all-caps token is said letter by letter (`USB`) or as a word (`NASA`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AcronymReading {
+    Spell,
+    Word,
+}
+
+const LETTER_NAMES: [&str; 26] = [
+    "ay", "bee", "see", "dee", "ee", "eff", "gee", "aitch", "eye", "jay", "kay", "el", "em",
+    "en", "oh", "pee", "cue", "ar", "ess", "tee", "you", "vee", "double you", "ex", "why", "zee",
+];
+
+const ONSETS: [&str; 30] = [
+    "BL", "BR", "CH", "CL", "CR", "DR", "FL", "FR", "GL", "GR", "KL", "KN", "KR", "PH", "PL",
+    "PR", "SC", "SH", "SK", "SL", "SM", "SN", "SP", "ST", "SW", "TH", "TR", "TW", "WH", "WR",
+];
+
+static ACRONYM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b([A-Z]{2,})(s)?\b").unwrap());
+
+/// Acronyms the heuristic gets wrong.
+pub fn default_acronyms() -> HashMap<String, AcronymReading> {
+    let spelled = ["CEO", "CIA", "EU", "FAQ", "IEEE", "IOU", "OK", "UK", "UN", "USA"];
+    let words = ["GIF", "JPEG", "JSON", "PIN", "RAM", "ROM", "SIM", "SQL", "WIFI"];
+
+    spelled.iter().map(|acronym| (acronym.to_string(), AcronymReading::Spell))
+        .chain(words.iter().map(|acronym| (acronym.to_string(), AcronymReading::Word)))
+        .collect()
+}
+
+fn is_vowel(letter: u8, position: usize) -> bool {
+    matches!(letter, b'A' | b'E' | b'I' | b'O' | b'U') || (letter == b'Y' && position > 0)
+}
+
+/// Rough check whether an all-caps word can be read like a regular word: at least
+/// four letters, a legal initial consonant cluster and no runs of three consonants or vowels.
+fn is_pronounceable(word: &str) -> bool {
+    let letters = word.as_bytes();
+    if letters.len() < 4 || !letters.iter().enumerate().any(|(i, &letter)| is_vowel(letter, i)) {
+        return false;
+    }
+
+    let onset = letters.iter().enumerate().take_while(|&(i, &letter)| !is_vowel(letter, i)).count();
+    if onset > 2 || (onset == 2 && !ONSETS.contains(&&word[..2])) {
+        return false;
+    }
+
+    let mut consonants = 0;
+    let mut vowels = 0;
+    for (i, &letter) in letters.iter().enumerate() {
+        if is_vowel(letter, i) {
+            vowels += 1;
+            consonants = 0;
+        } else {
+            consonants += 1;
+            vowels = 0;
+        }
+        if consonants > 2 || vowels > 2 {
+            return false;
+        }
+    }
+    true
+}
+
+fn spell(word: &str, plural: bool) -> String {
+    let mut letters: Vec<String> = word.bytes()
+        .map(|letter| LETTER_NAMES[(letter - b'A') as usize].to_string())
+        .collect();
+    if plural && let Some(last) = letters.last_mut() {
+        last.push_str(if last.ends_with('s') { "es" } else { "s" });
+    }
+    letters.join(" ")
+}
+
+/// Spells out initialisms like `USB` or `HTML` and lowercases acronyms read
+/// as words. Text without any lowercase letters is taken as shouting and left alone.
+pub fn expand_acronyms(text: &str, normalizer: &TextNormalizer) -> String {
+    if !text.chars().any(char::is_lowercase) {
+        return text.to_string();
+    }
+
+    ACRONYM.replace_all(text, |captures: &Captures| {
+        let word = &captures[1];
+        let plural = captures.get(2).is_some();
+        let reading = normalizer.acronyms.get(word).copied().unwrap_or(
+            if is_pronounceable(word) { AcronymReading::Word } else { AcronymReading::Spell }
+        );
+
+        match reading {
+            AcronymReading::Spell => spell(word, plural),
+            AcronymReading::Word => captures[0].to_lowercase(),
+        }
+    }).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(text: &str) -> String {
+        expand_acronyms(text, &TextNormalizer::default())
+    }
+
+    #[test]
+    fn spelled_and_word_acronyms() {
+        assert_eq!(expand("a USB cable"), "a you ess bee cable");
+        assert_eq!(expand("an HTML page"), "an aitch tee em el page");
+        assert_eq!(expand("NASA launched"), "nasa launched");
+        assert_eq!(expand("the CEO and the JSON file"), "the see ee oh and the json file");
+        assert_eq!(expand("two URLs"), "two you ar els");
+    }
+
+    #[test]
+    fn acronyms_at_sentence_boundaries() {
+        assert_eq!(expand("I work at NASA. Plug in the USB. Then go."), "I work at nasa. Plug in the you ess bee. Then go.");
+        assert_eq!(expand("Is it HTML? Yes."), "Is it aitch tee em el? Yes.");
+    }
+
+    #[test]
+    fn shouting_is_left_alone() {
+        assert_eq!(expand("STOP THAT NOW"), "STOP THAT NOW");
+    }
+}
@@ -0,0 +1,172 @@
+use std::{collections::HashMap, sync::LazyLock};
+
+use regex::{Captures, Regex};
+
+use super::{
+    numbers::{cardinal, decimal, parse_grouped},
+    TextNormalizer,
+};
+
+/// How the amounts of a currency are read, `minor` being the singular and
+/// plural name of the hundredth unit (`cent`/`cents`), if there is one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Currency {
+    pub name: String,
+    pub plural: String,
+    pub minor: Option<(String, String)>,
+}
+
+impl Currency {
+    pub fn new(name: &str, plural: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            plural: plural.to_string(),
+            minor: None,
+        }
+    }
+
+    pub fn with_minor(mut self, name: &str, plural: &str) -> Self {
+        self.minor = Some((name.to_string(), plural.to_string()));
+        self
+    }
+}
+
+/// Currency symbols and ISO codes understood out of the box.
+pub fn default_currencies() -> HashMap<String, Currency> {
+    let dollar = Currency::new("dollar", "dollars").with_minor("cent", "cents");
+    let euro = Currency::new("euro", "euros").with_minor("cent", "cents");
+    let pound = Currency::new("pound", "pounds").with_minor("penny", "pence");
+    let yen = Currency::new("yen", "yen");
+    let rupee = Currency::new("rupee", "rupees").with_minor("paisa", "paise");
+    let ruble = Currency::new("ruble", "rubles").with_minor("kopek", "kopeks");
+    let won = Currency::new("won", "won");
+    let franc = Currency::new("franc", "francs").with_minor("centime", "centimes");
+
+    [
+        ("$", dollar.clone()), ("USD", dollar),
+        ("€", euro.clone()), ("EUR", euro),
+        ("£", pound.clone()), ("GBP", pound),
+        ("¥", yen.clone()), ("JPY", yen),
+        ("₹", rupee.clone()), ("INR", rupee),
+        ("₽", ruble.clone()), ("RUB", ruble),
+        ("₩", won.clone()), ("KRW", won),
+        ("CHF", franc),
+        ("¢", Currency::new("cent", "cents")),
+    ]
+    .into_iter()
+    .map(|(symbol, currency)| (symbol.to_string(), currency))
+    .collect()
+}
+
+const AMOUNT: &str = r"(\d{1,3}(?:,\d{3})+|\d+)(?:\.(\d+))?(?:\s?(thousand|million|billion|trillion|k\b|m\b|bn\b))?";
+
+static PREFIXED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(r"(\p{{Sc}}|\b[A-Z]{{3}})\s?{}", AMOUNT)).unwrap()
+});
+static SUFFIXED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(r"\b{}\s?(\p{{Sc}}|[A-Z]{{3}}\b)", AMOUNT)).unwrap()
+});
+
+fn scale_name(scale: &str) -> &str {
+    match scale {
+        "k" => "thousand",
+        "m" => "million",
+        "bn" => "billion",
+        scale => scale,
+    }
+}
+
+fn spoken_amount(currency: &Currency, whole: &str, fraction: Option<&str>, scale: Option<&str>) -> Option<String> {
+    let whole = parse_grouped(whole)?;
+
+    if let Some(scale) = scale {
+        let amount = match fraction {
+            Some(fraction) => decimal(whole, fraction),
+            None => cardinal(whole),
+        };
+        return Some(format!("{} {} {}", amount, scale_name(scale), currency.plural));
+    }
+
+    let major = |n: u64| format!("{} {}", cardinal(n), if n == 1 { &currency.name } else { &currency.plural });
+    let (Some((minor_name, minor_plural)), Some(fraction)) = (&currency.minor, fraction) else {
+        return Some(match fraction {
+            Some(fraction) => format!("{} {}", decimal(whole, fraction), currency.plural),
+            None => major(whole),
+        });
+    };
+    if fraction.len() > 2 {
+        return Some(format!("{} {}", decimal(whole, fraction), currency.plural));
+    }
+
+    let minor: u64 = format!("{:0<2}", fraction).parse().ok()?;
+    let minor = format!("{} {}", cardinal(minor), if minor == 1 { minor_name } else { minor_plural });
+    Some(match (whole, fraction.trim_matches('0').is_empty()) {
+        (_, true) => major(whole),
+        (0, false) => minor,
+        (_, false) => format!("{} and {}", major(whole), minor),
+    })
+}
+
+/// Expands `$5.99`, `€1,200`, `£3`, `5 EUR` and `$2.5 million` using the normalizer's currency table.
+pub fn expand_currency(text: &str, normalizer: &TextNormalizer) -> String {
+    let text = PREFIXED.replace_all(text, |captures: &Captures| {
+        normalizer.currencies.get(&captures[1])
+            .and_then(|currency| spoken_amount(
+                currency,
+                &captures[2],
+                captures.get(3).map(|m| m.as_str()),
+                captures.get(4).map(|m| m.as_str()),
+            ))
+            .unwrap_or_else(|| captures[0].to_string())
+    });
+
+    SUFFIXED.replace_all(&text, |captures: &Captures| {
+        normalizer.currencies.get(&captures[4])
+            .and_then(|currency| spoken_amount(
+                currency,
+                &captures[1],
+                captures.get(2).map(|m| m.as_str()),
+                captures.get(3).map(|m| m.as_str()),
+            ))
+            .unwrap_or_else(|| captures[0].to_string())
+    }).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(text: &str) -> String {
+        expand_currency(text, &TextNormalizer::default())
+    }
+
+    #[test]
+    fn amounts_with_minor_units() {
+        assert_eq!(expand("$1.05"), "one dollar and five cents");
+        assert_eq!(expand("$5.99"), "five dollars and ninety-nine cents");
+        assert_eq!(expand("£3.50"), "three pounds and fifty pence");
+        assert_eq!(expand("$0.01"), "one cent");
+        assert_eq!(expand("£0.01"), "one penny");
+    }
+
+    #[test]
+    fn singular_and_plural_amounts() {
+        assert_eq!(expand("$1"), "one dollar");
+        assert_eq!(expand("$2"), "two dollars");
+        assert_eq!(expand("$1.00"), "one dollar");
+        assert_eq!(expand("€1,200"), "one thousand two hundred euros");
+        assert_eq!(expand("5 EUR"), "five euros");
+        assert_eq!(expand("¥1"), "one yen");
+    }
+
+    #[test]
+    fn scaled_amounts() {
+        assert_eq!(expand("$2.5 million"), "two point five million dollars");
+        assert_eq!(expand("$3bn"), "three billion dollars");
+    }
+
+    #[test]
+    fn unknown_codes_are_left_alone() {
+        assert_eq!(expand("ABC 5"), "ABC 5");
+    }
+}
@@ -0,0 +1,167 @@
+use std::sync::LazyLock;
+
+use regex::{Captures, Regex};
+
+use super::{
+    numbers::{cardinal, ordinal, two_digits, year},
+    Clock, DateOrder, TextNormalizer,
+};
+
+const MONTHS: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June",
+    "July", "August", "September", "October", "November", "December",
+];
+const MONTH_PATTERN: &str =
+    "January|February|March|April|May|June|July|August|September|October|November|December|Jan|Feb|Mar|Apr|Jun|Jul|Aug|Sept|Sep|Oct|Nov|Dec";
+
+static ISO_DATE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(\d{4})-(\d{1,2})-(\d{1,2})\b").unwrap()
+});
+static NUMERIC_DATE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b(\d{1,2})/(\d{1,2})/(\d{4})\b").unwrap()
+});
+static MONTH_DAY: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(r"\b({})\.?\s+(\d{{1,2}})(?:st|nd|rd|th)?\b(?:,?\s+(\d{{4}})\b)?", MONTH_PATTERN)).unwrap()
+});
+static DAY_MONTH: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(r"\b(\d{{1,2}})(?:st|nd|rd|th)?\s+(?:of\s+)?({})\b\.?(?:,?\s+(\d{{4}})\b)?", MONTH_PATTERN)).unwrap()
+});
+static TIME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\b(\d{1,2}):(\d{2})(?:\s*([ap])(?:m\b|\.m\.))?").unwrap()
+});
+
+fn month_number(name: &str) -> Option<usize> {
+    MONTHS.iter().position(|month| month.starts_with(name)).map(|i| i + 1)
+}
+
+fn spoken_date(normalizer: &TextNormalizer, month: usize, day: u64, year_number: Option<u64>) -> Option<String> {
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+
+    let month = MONTHS[month - 1];
+    let mut spoken = match normalizer.date_order {
+        DateOrder::MonthDayYear => format!("{} {}", month, ordinal(day)),
+        DateOrder::DayMonthYear => format!("the {} of {}", ordinal(day), month),
+    };
+    if let Some(year_number) = year_number {
+        spoken.push_str(", ");
+        spoken.push_str(&year(year_number));
+    }
+    Some(spoken)
+}
+
+fn number(captures: &Captures, group: usize) -> Option<u64> {
+    captures.get(group).and_then(|m| m.as_str().parse().ok())
+}
+
+/// Expands `2024-05-01`, `05/01/2024`, `May 1st, 2024` and `1 May 2024`.
+pub fn expand_dates(text: &str, normalizer: &TextNormalizer) -> String {
+    let text = ISO_DATE.replace_all(text, |captures: &Captures| {
+        let (Some(year), Some(month), Some(day)) = (number(captures, 1), number(captures, 2), number(captures, 3)) else {
+            return captures[0].to_string();
+        };
+        spoken_date(normalizer, month as usize, day, Some(year)).unwrap_or_else(|| captures[0].to_string())
+    });
+
+    let text = NUMERIC_DATE.replace_all(&text, |captures: &Captures| {
+        let (Some(first), Some(second), Some(year)) = (number(captures, 1), number(captures, 2), number(captures, 3)) else {
+            return captures[0].to_string();
+        };
+        let (month, day) = match normalizer.date_order {
+            DateOrder::MonthDayYear => (first, second),
+            DateOrder::DayMonthYear => (second, first),
+        };
+        spoken_date(normalizer, month as usize, day, Some(year)).unwrap_or_else(|| captures[0].to_string())
+    });
+
+    let text = MONTH_DAY.replace_all(&text, |captures: &Captures| {
+        let (Some(month), Some(day)) = (month_number(&captures[1]), number(captures, 2)) else {
+            return captures[0].to_string();
+        };
+        spoken_date(normalizer, month, day, number(captures, 3)).unwrap_or_else(|| captures[0].to_string())
+    });
+
+    DAY_MONTH.replace_all(&text, |captures: &Captures| {
+        let (Some(day), Some(month)) = (number(captures, 1), month_number(&captures[2])) else {
+            return captures[0].to_string();
+        };
+        spoken_date(normalizer, month, day, number(captures, 3)).unwrap_or_else(|| captures[0].to_string())
+    }).into_owned()
+}
+
+/// Expands `14:30` and `2:30 pm`, reading 24-hour times according to `normalizer.clock`.
+pub fn expand_times(text: &str, normalizer: &TextNormalizer) -> String {
+    TIME.replace_all(text, |captures: &Captures| {
+        let (Some(hour), Some(minute)) = (number(captures, 1), number(captures, 2)) else {
+            return captures[0].to_string();
+        };
+        let meridiem = captures.get(3).map(|m| m.as_str().to_ascii_lowercase());
+        if minute > 59 || hour > 23 || (meridiem.is_some() && !(1..=12).contains(&hour)) {
+            return captures[0].to_string();
+        }
+
+        match (meridiem, normalizer.clock) {
+            (Some(meridiem), _) => twelve_hour(hour, minute, Some(&meridiem)),
+            (None, Clock::TwelveHour) if hour > 12 => twelve_hour(hour - 12, minute, Some("p")),
+            (None, Clock::TwelveHour) if hour == 0 => twelve_hour(12, minute, Some("a")),
+            (None, Clock::TwelveHour) => twelve_hour(hour, minute, None),
+            (None, Clock::TwentyFourHour) => match minute {
+                0 => format!("{} hundred", cardinal(hour)),
+                _ => format!("{} {}", cardinal(hour), two_digits(minute)),
+            },
+        }
+    }).into_owned()
+}
+
+fn twelve_hour(hour: u64, minute: u64, meridiem: Option<&str>) -> String {
+    let mut spoken = match (minute, meridiem) {
+        (0, None) => format!("{} o'clock", cardinal(hour)),
+        (0, Some(_)) => cardinal(hour),
+        _ => format!("{} {}", cardinal(hour), two_digits(minute)),
+    };
+    if let Some(meridiem) = meridiem {
+        spoken.push_str(&format!(" {} m", meridiem));
+    }
+    spoken
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iso_and_written_dates() {
+        let normalizer = TextNormalizer::default();
+        assert_eq!(expand_dates("on 2024-05-01", &normalizer), "on May first, twenty twenty-four");
+        assert_eq!(expand_dates("May 1st, 2024", &normalizer), "May first, twenty twenty-four");
+        assert_eq!(expand_dates("05/01/2024", &normalizer), "May first, twenty twenty-four");
+
+        let day_first = TextNormalizer { date_order: DateOrder::DayMonthYear, ..TextNormalizer::default() };
+        assert_eq!(expand_dates("05/01/2024", &day_first), "the fifth of January, twenty twenty-four");
+    }
+
+    #[test]
+    fn clock_times() {
+        let normalizer = TextNormalizer::default();
+        assert_eq!(expand_times("3:05 pm", &normalizer), "three oh five p m");
+        assert_eq!(expand_times("12:00", &normalizer), "twelve o'clock");
+        assert_eq!(expand_times("14:30", &normalizer), "two thirty p m");
+        assert_eq!(expand_times("0:15", &normalizer), "twelve fifteen a m");
+
+        let twenty_four_hour = TextNormalizer { clock: Clock::TwentyFourHour, ..TextNormalizer::default() };
+        assert_eq!(expand_times("14:30", &twenty_four_hour), "fourteen thirty");
+        assert_eq!(expand_times("12:00", &twenty_four_hour), "twelve hundred");
+    }
+
+    #[test]
+    fn invalid_dates_and_times_are_left_alone() {
+        let normalizer = TextNormalizer::default();
+        for text in ["2024-13-01", "2024-02-32", "13/45/2024", "May 40"] {
+            assert_eq!(expand_dates(text, &normalizer), text);
+        }
+        for text in ["25:00", "12:60", "13:05 pm", "0:30 am"] {
+            assert_eq!(expand_times(text, &normalizer), text);
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use std::{collections::HashMap, path::Path, str::FromStr};
+
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::error::{PiperError, Result};
+
+use super::TextNormalizer;
+
+const DEFAULT_EMOJI: &str = include_str!("emoji.txt");
+
+/// What becomes of emoji, which have no pronunciation of their own.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EmojiHandling {
+    /// Replaced with their description, emoji missing from the table are removed.
+    #[default]
+    Describe,
+    /// Removed.
+    Strip,
+}
+
+impl FromStr for EmojiHandling {
+    type Err = PiperError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "describe" => Ok(EmojiHandling::Describe),
+            "strip" => Ok(EmojiHandling::Strip),
+            _ => Err(PiperError::InvalidConfig(format!("unknown emoji handling `{}`", s))),
+        }
+    }
+}
+
+/// Skin tones, variation selectors and the keycap mark, which don't change what an emoji is.
+fn is_modifier(c: char) -> bool {
+    matches!(c, '\u{1F3FB}'..='\u{1F3FF}' | '\u{FE0E}' | '\u{FE0F}' | '\u{20E3}')
+}
+
+fn is_pictograph(c: char) -> bool {
+    matches!(
+        c,
+        '\u{1F000}'..='\u{1FAFF}' | '\u{2600}'..='\u{27BF}' | '\u{2B00}'..='\u{2BFF}' | '\u{2300}'..='\u{23FF}' | '\u{E0020}'..='\u{E007F}'
+    )
+}
+
+/// Table key of an emoji, without its modifiers.
+fn emoji_key(emoji: &str) -> String {
+    emoji.chars().filter(|&c| !is_modifier(c)).collect()
+}
+
+/// Parses `emoji description...` lines, `#` starts a comment.
+pub fn parse_emoji(data: &str) -> HashMap<String, String> {
+    data.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once(char::is_whitespace))
+        .map(|(emoji, description)| (emoji_key(emoji), description.trim().to_string()))
+        .collect()
+}
+
+/// The embedded table of common emoji.
+pub fn default_emoji() -> HashMap<String, String> {
+    parse_emoji(DEFAULT_EMOJI)
+}
+
+pub fn load_emoji(path: impl AsRef<Path>) -> Result<HashMap<String, String>> {
+    let path = path.as_ref();
+    let data = std::fs::read_to_string(path)
+        .map_err(|e| PiperError::AssetLoad {
+            what: "emoji descriptions",
+            path: path.display().to_string(),
+            reason: e.to_string(),
+        })?;
+    Ok(parse_emoji(&data))
+}
+
+/// Replaces emoji with their descriptions or removes them. A run of the same
+/// emoji is described once.
+pub fn expand_emoji(text: &str, normalizer: &TextNormalizer) -> String {
+    if text.is_ascii() {
+        return text.to_string();
+    }
+
+    let mut expanded = String::with_capacity(text.len());
+    let mut previous: Option<String> = None;
+    let mut after_emoji = false;
+    for grapheme in text.graphemes(true) {
+        let key = emoji_key(grapheme);
+        let description = match normalizer.emoji {
+            EmojiHandling::Describe => normalizer.emoji_descriptions.get(&key),
+            EmojiHandling::Strip => None,
+        };
+        let is_emoji = grapheme.chars().any(is_pictograph) || normalizer.emoji_descriptions.contains_key(&key);
+        if !is_emoji {
+            let space_before = expanded.is_empty() || expanded.ends_with(char::is_whitespace);
+            if grapheme.trim().is_empty() {
+                // Spacing around a removed emoji is not doubled.
+                if !(after_emoji && space_before) {
+                    expanded.push_str(grapheme);
+                }
+                continue;
+            }
+            if after_emoji {
+                if key.starts_with(char::is_alphanumeric) && !space_before {
+                    expanded.push(' ');
+                } else if key.starts_with(|c: char| c.is_ascii_punctuation()) && expanded.ends_with(' ') {
+                    expanded.pop();
+                }
+            }
+            // A keycap such as `1️⃣` is left as its digit.
+            expanded.push_str(&key);
+            previous = None;
+            after_emoji = false;
+            continue;
+        }
+        after_emoji = true;
+        if let Some(description) = description
+            && previous.as_ref() != Some(&key)
+        {
+            if !expanded.is_empty() && !expanded.ends_with(char::is_whitespace) {
+                expanded.push(' ');
+            }
+            expanded.push_str(description);
+            previous = Some(key);
+        }
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn describe(text: &str) -> String {
+        expand_emoji(text, &TextNormalizer::default())
+    }
+
+    fn strip(text: &str) -> String {
+        expand_emoji(text, &TextNormalizer::default().with_emoji(EmojiHandling::Strip))
+    }
+
+    #[test]
+    fn describes_emoji() {
+        assert_eq!(describe("Well done 🎉"), "Well done party popper");
+        assert_eq!(describe("great🔥"), "great fire");
+        assert_eq!(describe("👍🏽 ok"), "thumbs up ok");
+    }
+
+    #[test]
+    fn runs_are_described_once() {
+        assert_eq!(describe("great🔥🔥!"), "great fire!");
+        assert_eq!(describe("🔥🔥🔥 🎉🎉"), "fire party popper");
+    }
+
+    #[test]
+    fn zwj_sequences_are_one_emoji() {
+        assert_eq!(describe("Pride 🏳️‍🌈 month"), "Pride rainbow flag month");
+        assert_eq!(describe("❤️‍🔥"), "heart on fire");
+    }
+
+    #[test]
+    fn keycaps_become_digits() {
+        assert_eq!(describe("1️⃣"), "1");
+        assert_eq!(describe("Press 2️⃣ now"), "Press 2 now");
+    }
+
+    #[test]
+    fn spacing_around_removed_emoji() {
+        assert_eq!(strip("Well done 🎉 today"), "Well done today");
+        assert_eq!(strip("Well done 🎉!"), "Well done!");
+        assert_eq!(strip("a🎉b"), "a b");
+        assert_eq!(describe("unknown 🫨 emoji"), "unknown emoji");
+    }
+
+    #[test]
+    fn text_without_emoji_is_unchanged() {
+        assert_eq!(describe("Crème brûlée — 5 €"), "Crème brûlée — 5 €");
+        assert_eq!(strip("plain text"), "plain text");
+    }
+}
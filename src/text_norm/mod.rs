@@ -0,0 +1,144 @@
+//! Rewrites written forms such as abbreviations, acronyms, dates, times,
+//! amounts of money, measurements, Roman numerals and emoji into words the G2P can read.
+
+mod abbreviations;
+mod acronyms;
+mod currency;
+mod dates;
+mod emoji;
+mod numbers;
+mod roman;
+mod units;
+
+use std::{collections::HashMap, path::Path, sync::LazyLock};
+
+use regex::Regex;
+
+use crate::error::{PiperError, Result};
+
+pub use abbreviations::{default_abbreviations, load_abbreviations, parse_abbreviations, TERMINAL_ABBREVIATIONS};
+pub use acronyms::{default_acronyms, AcronymReading};
+pub use currency::{default_currencies, Currency};
+pub use emoji::{default_emoji, load_emoji, parse_emoji, EmojiHandling};
+pub use numbers::{cardinal, decimal, number, ordinal, year};
+pub use roman::parse_roman;
+pub use units::{default_units, Unit};
+
+/// Day/month order of numeric dates, which also decides how dates are read out
+/// (`May first` or `the first of May`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DateOrder {
+    #[default]
+    MonthDayYear,
+    DayMonthYear,
+}
+
+/// How times without an am/pm suffix are read: `14:30` → `two thirty p m` or `fourteen thirty`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Clock {
+    #[default]
+    TwelveHour,
+    TwentyFourHour,
+}
+
+#[derive(Debug, Clone)]
+pub struct TextNormalizer {
+    pub date_order: DateOrder,
+    pub clock: Clock,
+    /// Currency symbols and ISO codes (`$`, `EUR`) to how their amounts are read.
+    pub currencies: HashMap<String, Currency>,
+    /// Abbreviations (`Dr.`) to their spoken form, the embedded English table by default.
+    pub abbreviations: HashMap<String, String>,
+    /// All-caps words whose reading overrides the spell-or-word heuristic.
+    pub acronyms: HashMap<String, AcronymReading>,
+    /// Measurement unit symbols (`kg`, `°C`, `km/h`) to their spoken form.
+    pub units: HashMap<String, Unit>,
+    pub emoji: EmojiHandling,
+    /// Emoji to their description, the embedded table of common emoji by default.
+    pub emoji_descriptions: HashMap<String, String>,
+}
+
+impl Default for TextNormalizer {
+    fn default() -> Self {
+        Self {
+            date_order: DateOrder::default(),
+            clock: Clock::default(),
+            currencies: default_currencies(),
+            abbreviations: default_abbreviations(),
+            acronyms: default_acronyms(),
+            units: default_units(),
+            emoji: EmojiHandling::default(),
+            emoji_descriptions: default_emoji(),
+        }
+    }
+}
+
+static PHONEME_ESCAPE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[\[.*?\]\]").unwrap());
+
+impl TextNormalizer {
+    /// Locale defaults, `en-US` and `en-GB` style formats are known.
+    pub fn for_locale(locale: &str) -> Result<Self> {
+        match locale.to_ascii_lowercase().replace('_', "-").as_str() {
+            "en" | "en-us" => Ok(Self::default()),
+            "en-gb" | "en-au" | "en-ie" | "en-nz" | "en-in" => Ok(Self {
+                date_order: DateOrder::DayMonthYear,
+                clock: Clock::TwentyFourHour,
+                ..Self::default()
+            }),
+            _ => Err(PiperError::InvalidConfig(format!("unsupported locale `{}`", locale))),
+        }
+    }
+
+    /// Adds or replaces the reading of a currency symbol or code.
+    pub fn with_currency(mut self, symbol: &str, currency: Currency) -> Self {
+        self.currencies.insert(symbol.to_string(), currency);
+        self
+    }
+
+    /// Adds or replaces the reading of a unit symbol.
+    pub fn with_unit(mut self, symbol: &str, unit: Unit) -> Self {
+        self.units.insert(symbol.to_string(), unit);
+        self
+    }
+
+    /// Merges the abbreviations of a file into the table, overriding existing entries.
+    pub fn with_abbreviations_file(mut self, path: impl AsRef<Path>) -> Result<Self> {
+        self.abbreviations.extend(load_abbreviations(path)?);
+        Ok(self)
+    }
+
+    pub fn with_emoji(mut self, emoji: EmojiHandling) -> Self {
+        self.emoji = emoji;
+        self
+    }
+
+    /// Merges the emoji descriptions of a file into the table, overriding existing entries.
+    pub fn with_emoji_file(mut self, path: impl AsRef<Path>) -> Result<Self> {
+        self.emoji_descriptions.extend(load_emoji(path)?);
+        Ok(self)
+    }
+
+    /// Normalizes `text`, leaving `[[...]]` phoneme escapes untouched.
+    pub fn normalize(&self, text: &str) -> String {
+        let mut normalized = String::with_capacity(text.len());
+        let mut last = 0;
+        for escape in PHONEME_ESCAPE.find_iter(text) {
+            normalized.push_str(&self.normalize_segment(&text[last..escape.start()]));
+            normalized.push_str(escape.as_str());
+            last = escape.end();
+        }
+        normalized.push_str(&self.normalize_segment(&text[last..]));
+        normalized
+    }
+
+    fn normalize_segment(&self, text: &str) -> String {
+        let text = emoji::expand_emoji(text, self);
+        let text = abbreviations::expand_abbreviations(&text, self);
+        let text = roman::expand_roman_numerals(&text);
+        let text = currency::expand_currency(&text, self);
+        let text = dates::expand_times(&text, self);
+        let text = dates::expand_dates(&text, self);
+        let text = units::expand_units(&text, self);
+        acronyms::expand_acronyms(&text, self)
+    }
+}
@@ -0,0 +1,186 @@
+const ONES: [&str; 20] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten",
+    "eleven", "twelve", "thirteen", "fourteen", "fifteen", "sixteen", "seventeen", "eighteen", "nineteen",
+];
+const TENS: [&str; 10] = [
+    "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+const SCALES: [(u64, &str); 6] = [
+    (1_000_000_000_000_000_000, "quintillion"),
+    (1_000_000_000_000_000, "quadrillion"),
+    (1_000_000_000_000, "trillion"),
+    (1_000_000_000, "billion"),
+    (1_000_000, "million"),
+    (1_000, "thousand"),
+];
+
+fn below_thousand(n: u64, words: &mut Vec<String>) {
+    let hundreds = n / 100;
+    let rest = n % 100;
+    if hundreds > 0 {
+        words.push(format!("{} hundred", ONES[hundreds as usize]));
+    }
+    if rest == 0 {
+        return;
+    }
+    if rest < 20 {
+        words.push(ONES[rest as usize].to_string());
+    } else if rest.is_multiple_of(10) {
+        words.push(TENS[(rest / 10) as usize].to_string());
+    } else {
+        words.push(format!("{}-{}", TENS[(rest / 10) as usize], ONES[(rest % 10) as usize]));
+    }
+}
+
+/// `1234` → `one thousand two hundred thirty-four`.
+pub fn cardinal(n: u64) -> String {
+    if n == 0 {
+        return ONES[0].to_string();
+    }
+
+    let mut words = Vec::new();
+    let mut rest = n;
+    for (scale, name) in SCALES {
+        if rest >= scale {
+            below_thousand(rest / scale, &mut words);
+            words.push(name.to_string());
+            rest %= scale;
+        }
+    }
+    below_thousand(rest, &mut words);
+    words.join(" ")
+}
+
+/// `21` → `twenty-first`.
+pub fn ordinal(n: u64) -> String {
+    let words = cardinal(n);
+    let split = words.rfind([' ', '-']).map(|i| i + 1).unwrap_or(0);
+    let (head, last) = words.split_at(split);
+
+    let last = match last {
+        "one" => "first".to_string(),
+        "two" => "second".to_string(),
+        "three" => "third".to_string(),
+        "five" => "fifth".to_string(),
+        "eight" => "eighth".to_string(),
+        "nine" => "ninth".to_string(),
+        "twelve" => "twelfth".to_string(),
+        word if word.ends_with('y') => format!("{}ieth", &word[..word.len() - 1]),
+        word => format!("{}th", word),
+    };
+    format!("{}{}", head, last)
+}
+
+/// Reads years the way they are spoken: `1905` → `nineteen oh five`,
+/// `2024` → `twenty twenty-four`, `2005` → `two thousand five`.
+pub fn year(n: u64) -> String {
+    if !(1000..=9999).contains(&n) || (2000..2010).contains(&n) || n.is_multiple_of(1000) {
+        return cardinal(n);
+    }
+
+    let century = cardinal(n / 100);
+    match n % 100 {
+        0 => format!("{} hundred", century),
+        rest if rest < 10 => format!("{} oh {}", century, cardinal(rest)),
+        rest => format!("{} {}", century, cardinal(rest)),
+    }
+}
+
+/// Reads minutes and other two digit groups: `5` → `oh five`, `30` → `thirty`.
+pub fn two_digits(n: u64) -> String {
+    if n < 10 {
+        format!("oh {}", cardinal(n))
+    } else {
+        cardinal(n)
+    }
+}
+
+/// Reads a decimal digit by digit after the point: `3`, `"14"` → `three point one four`.
+pub fn decimal(whole: u64, fraction: &str) -> String {
+    let digits: Vec<&str> = fraction.chars()
+        .filter_map(|c| c.to_digit(10))
+        .map(|digit| ONES[digit as usize])
+        .collect();
+    format!("{} point {}", cardinal(whole), digits.join(" "))
+}
+
+/// Parses digits that may be grouped in thousands with commas: `1,200` or `1200`.
+pub fn parse_grouped(digits: &str) -> Option<u64> {
+    let mut groups = digits.split(',');
+    let first = groups.next()?;
+    let mut grouped = first.to_string();
+    for group in groups {
+        if first.len() > 3 || group.len() != 3 {
+            return None;
+        }
+        grouped.push_str(group);
+    }
+    match grouped.chars().all(|c| c.is_ascii_digit()) {
+        true => grouped.parse().ok(),
+        false => None,
+    }
+}
+
+/// Reads a written number with an optional minus sign, thousands grouped with
+/// commas and decimals: `-1,234.5` → `minus one thousand two hundred thirty-four point five`.
+pub fn number(text: &str) -> Option<String> {
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(digits) => ("minus ", digits),
+        None => ("", text),
+    };
+    let (whole, fraction) = match digits.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (digits, None),
+    };
+    let whole = parse_grouped(whole)?;
+    let amount = match fraction {
+        Some(fraction) if !fraction.is_empty() && fraction.chars().all(|c| c.is_ascii_digit()) => decimal(whole, fraction),
+        Some(_) => return None,
+        None => cardinal(whole),
+    };
+    Some(format!("{}{}", sign, amount))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cardinals() {
+        assert_eq!(cardinal(0), "zero");
+        assert_eq!(cardinal(13), "thirteen");
+        assert_eq!(cardinal(19), "nineteen");
+        assert_eq!(cardinal(40), "forty");
+        assert_eq!(cardinal(1_000_000), "one million");
+        assert_eq!(cardinal(1234), "one thousand two hundred thirty-four");
+        assert_eq!(cardinal(2_000_017), "two million seventeen");
+    }
+
+    #[test]
+    fn ordinals() {
+        assert_eq!(ordinal(1), "first");
+        assert_eq!(ordinal(12), "twelfth");
+        assert_eq!(ordinal(21), "twenty-first");
+        assert_eq!(ordinal(40), "fortieth");
+        assert_eq!(ordinal(100), "one hundredth");
+    }
+
+    #[test]
+    fn years() {
+        assert_eq!(year(1905), "nineteen oh five");
+        assert_eq!(year(2024), "twenty twenty-four");
+        assert_eq!(year(2005), "two thousand five");
+        assert_eq!(year(1900), "nineteen hundred");
+    }
+
+    #[test]
+    fn signed_grouped_and_decimal_numbers() {
+        assert_eq!(number("-5").as_deref(), Some("minus five"));
+        assert_eq!(number("1,000,000").as_deref(), Some("one million"));
+        assert_eq!(number("3.14").as_deref(), Some("three point one four"));
+        assert_eq!(number("-1,234.05").as_deref(), Some("minus one thousand two hundred thirty-four point zero five"));
+        for invalid in ["1,00", "1234,567", "", "1.", "1.2.3", "12a"] {
+            assert_eq!(number(invalid), None, "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use std::sync::LazyLock;
+
+use regex::{Captures, Regex};
+
+use super::numbers::{cardinal, ordinal};
+
+const NUMERALS: [(u64, &str); 13] = [
+    (1000, "M"), (900, "CM"), (500, "D"), (400, "CD"), (100, "C"), (90, "XC"),
+    (50, "L"), (40, "XL"), (10, "X"), (9, "IX"), (5, "V"), (4, "IV"), (1, "I"),
+];
+
+/// Words after which a numeral counts things and is read as a cardinal (`Chapter four`).
+const COUNTING_WORDS: [&str; 22] = [
+    "act", "appendix", "article", "book", "bowl", "chapter", "class", "episode", "grade", "level", "mark",
+    "part", "phase", "psalm", "round", "scene", "season", "section", "stage", "type", "volume", "war",
+];
+
+/// Capitalized words after which a numeral is not a number, as in `Size XL`.
+const NOT_NAMES: [&str; 6] = ["Hepatitis", "Model", "Plan", "Size", "The", "Vitamin"];
+
+/// Regnal names after which a numeral is an ordinal, as in `Henry VIII`.
+const REGNAL_NAMES: &[&str] = &[
+    "Afonso", "Alexander", "Alfonso", "Amenhotep", "Anne", "Baldwin", "Benedict", "Boniface", "Carlos",
+    "Casimir", "Catherine", "Charles", "Christian", "Clement", "Constantine", "Darius", "David", "Edmund",
+    "Edward", "Elizabeth", "Eric", "Ferdinand", "Francis", "Franz", "Frederick", "George", "Gregory",
+    "Gustav", "Haakon", "Harald", "Henri", "Henry", "Innocent", "Isabella", "Ivan", "James", "John",
+    "Joseph", "Juan", "Julius", "Karl", "Leo", "Leopold", "Louis", "Ludwig", "Magnus", "Malcolm", "Manuel",
+    "Mary", "Mehmed", "Murad", "Napoleon", "Nicholas", "Olaf", "Otto", "Paul", "Pedro", "Peter", "Philip",
+    "Pius", "Ptolemy", "Ramesses", "Richard", "Robert", "Rudolf", "Selim", "Sixtus", "Stephen", "Thutmose",
+    "Umberto", "Urban", "Valdemar", "Victor", "Wilhelm", "William",
+];
+
+/// Titles before a regnal name that make a lone `I` after it a numeral, as in
+/// `Queen Elizabeth I`, rather than the pronoun of `Peter I love you`.
+const REGNAL_TITLES: [&str; 14] = [
+    "Czar", "Duke", "Emperor", "Empress", "Kaiser", "King", "Pharaoh", "Pope", "Prince", "Princess",
+    "Queen", "Shah", "Sultan", "Tsar",
+];
+
+/// Regnal names followed by a numeral that is part of another name.
+const NOT_REGNAL: [&str; 1] = ["Malcolm X"];
+
+/// Largest numeral read after a name, keeping `Sony CD` from becoming a 400th.
+const MAX_REGNAL: u64 = 40;
+
+static NUMERAL_AFTER_WORD: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"\b([A-Za-z]+)(\s+)([IVXLCDM]+)\b").unwrap()
+});
+
+/// A realm after a regnal numeral, as in `Henry I of England`.
+static OF_REALM: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s+of\s+\p{Lu}").unwrap());
+
+/// Whether a lone `I` after `word` is a numeral: the word is a regnal name with
+/// a title before it or a realm after it, or the `War` of `World War I`.
+fn is_lone_numeral(word: &str, before: &str, after: &str) -> bool {
+    let previous = before.split_whitespace().last()
+        .map(|previous| previous.trim_matches(|c: char| !c.is_alphanumeric()))
+        .unwrap_or_default();
+    match word {
+        "War" => previous == "World",
+        _ => REGNAL_NAMES.contains(&word) && (REGNAL_TITLES.contains(&previous) || OF_REALM.is_match(after)),
+    }
+}
+
+fn to_roman(mut n: u64) -> String {
+    let mut roman = String::new();
+    for (value, numeral) in NUMERALS {
+        while n >= value {
+            roman.push_str(numeral);
+            n -= value;
+        }
+    }
+    roman
+}
+
+/// Parses canonical numerals only, so `IIII` or `VX` are rejected.
+pub fn parse_roman(numeral: &str) -> Option<u64> {
+    let value = |c: char| NUMERALS.iter().find(|(_, n)| n.len() == 1 && n.starts_with(c)).map(|&(v, _)| v);
+    let digits: Vec<u64> = numeral.chars().map(value).collect::<Option<_>>()?;
+
+    let mut total = 0;
+    for (i, &digit) in digits.iter().enumerate() {
+        match digits.get(i + 1) {
+            Some(&next) if next > digit => total -= digit as i64,
+            _ => total += digit as i64,
+        }
+    }
+    let total = u64::try_from(total).ok().filter(|&total| total > 0 && total < 4000)?;
+    (to_roman(total) == numeral).then_some(total)
+}
+
+/// Reads numerals after counting words as cardinals (`Chapter IV` → `Chapter four`),
+/// after regnal names as ordinals (`Henry VIII` → `Henry the eighth`) and other
+/// numerals of several letters after capitalized words as cardinals (`Apollo XI`
+/// → `Apollo eleven`). Numerals without such context are left alone since they
+/// are usually words or initialisms, and so are lone `V` and `X` after words
+/// that aren't regnal names, as in `Generation X`, and a lone `I` without a
+/// title or realm around the name, as in `Peter I love you`.
+pub fn expand_roman_numerals(text: &str) -> String {
+    NUMERAL_AFTER_WORD.replace_all(text, |captures: &Captures| {
+        let (word, space, numeral) = (&captures[1], &captures[2], &captures[3]);
+        let whole = captures.get(0).unwrap();
+        let regnal = REGNAL_NAMES.contains(&word) && !NOT_REGNAL.contains(&format!("{} {}", word, numeral).as_str());
+        let value = match parse_roman(numeral) {
+            Some(value) if numeral.len() > 1 || matches!(numeral, "V" | "X") => value,
+            Some(value) if numeral == "I" && is_lone_numeral(word, &text[..whole.start()], &text[whole.end()..]) => value,
+            _ => return captures[0].to_string(),
+        };
+
+        let is_name = word.chars().next().is_some_and(char::is_uppercase)
+            && word.chars().skip(1).all(char::is_lowercase)
+            && !NOT_NAMES.contains(&word);
+        if COUNTING_WORDS.contains(&word.to_lowercase().as_str()) {
+            format!("{}{}{}", word, space, cardinal(value))
+        } else if regnal && value <= MAX_REGNAL {
+            format!("{}{}the {}", word, space, ordinal(value))
+        } else if is_name && numeral.len() > 1 && value <= MAX_REGNAL {
+            format!("{}{}{}", word, space, cardinal(value))
+        } else {
+            captures[0].to_string()
+        }
+    }).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pronoun_i_is_left_alone() {
+        for text in ["Yesterday I went home.", "Then I said no.", "Maybe I will.", "Chapter I think"] {
+            assert_eq!(expand_roman_numerals(text), text);
+        }
+    }
+
+    #[test]
+    fn lone_i_after_regnal_names() {
+        assert_eq!(expand_roman_numerals("Queen Elizabeth I ruled"), "Queen Elizabeth the first ruled");
+        assert_eq!(expand_roman_numerals("Henry I of England"), "Henry the first of England");
+        assert_eq!(expand_roman_numerals("after World War I ended"), "after World War one ended");
+    }
+
+    #[test]
+    fn pronoun_i_after_given_names() {
+        for text in ["Peter I love you", "I met Mary I think", "John I said no", "Elizabeth I know", "the War I fought"] {
+            assert_eq!(expand_roman_numerals(text), text);
+        }
+    }
+
+    #[test]
+    fn names_and_counting_words() {
+        assert_eq!(expand_roman_numerals("Henry VIII"), "Henry the eighth");
+        assert_eq!(expand_roman_numerals("Chapter IV"), "Chapter four");
+        assert_eq!(expand_roman_numerals("Vitamin C"), "Vitamin C");
+    }
+
+    #[test]
+    fn ordinals_only_after_regnal_names() {
+        assert_eq!(expand_roman_numerals("Malcolm X spoke"), "Malcolm X spoke");
+        assert_eq!(expand_roman_numerals("Malcolm III of Scotland"), "Malcolm the third of Scotland");
+        assert_eq!(expand_roman_numerals("Generation X"), "Generation X");
+        assert_eq!(expand_roman_numerals("Planet X"), "Planet X");
+        assert_eq!(expand_roman_numerals("Apollo XI landed"), "Apollo eleven landed");
+        assert_eq!(expand_roman_numerals("Final Fantasy VII"), "Final Fantasy seven");
+        assert_eq!(expand_roman_numerals("Size XL"), "Size XL");
+    }
+}
@@ -0,0 +1,166 @@
+use std::{collections::HashMap, sync::LazyLock};
+
+use regex::{Captures, Regex};
+
+use super::{
+    numbers::number,
+    TextNormalizer,
+};
+
+/// Singular and plural spoken form of a measurement unit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unit {
+    pub name: String,
+    pub plural: String,
+}
+
+impl Unit {
+    pub fn new(name: &str, plural: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            plural: plural.to_string(),
+        }
+    }
+}
+
+/// Common SI, imperial and computing units by symbol.
+pub fn default_units() -> HashMap<String, Unit> {
+    let units: &[(&[&str], &str, &str)] = &[
+        (&["mm"], "millimeter", "millimeters"),
+        (&["cm"], "centimeter", "centimeters"),
+        (&["m"], "meter", "meters"),
+        (&["km"], "kilometer", "kilometers"),
+        (&["mg"], "milligram", "milligrams"),
+        (&["g"], "gram", "grams"),
+        (&["kg"], "kilogram", "kilograms"),
+        (&["ml", "mL"], "milliliter", "milliliters"),
+        (&["l", "L"], "liter", "liters"),
+        (&["ft"], "foot", "feet"),
+        (&["yd"], "yard", "yards"),
+        (&["mi"], "mile", "miles"),
+        (&["lb", "lbs"], "pound", "pounds"),
+        (&["oz"], "ounce", "ounces"),
+        (&["mph"], "mile per hour", "miles per hour"),
+        (&["kph"], "kilometer per hour", "kilometers per hour"),
+        (&["ms"], "millisecond", "milliseconds"),
+        (&["s", "sec"], "second", "seconds"),
+        (&["min"], "minute", "minutes"),
+        (&["h", "hr", "hrs"], "hour", "hours"),
+        (&["Hz"], "hertz", "hertz"),
+        (&["kHz"], "kilohertz", "kilohertz"),
+        (&["MHz"], "megahertz", "megahertz"),
+        (&["GHz"], "gigahertz", "gigahertz"),
+        (&["W"], "watt", "watts"),
+        (&["kW"], "kilowatt", "kilowatts"),
+        (&["kWh"], "kilowatt hour", "kilowatt hours"),
+        (&["V"], "volt", "volts"),
+        (&["mAh"], "milliamp hour", "milliamp hours"),
+        (&["KB", "kB"], "kilobyte", "kilobytes"),
+        (&["MB"], "megabyte", "megabytes"),
+        (&["GB"], "gigabyte", "gigabytes"),
+        (&["TB"], "terabyte", "terabytes"),
+        (&["m²"], "square meter", "square meters"),
+        (&["km²"], "square kilometer", "square kilometers"),
+        (&["m³"], "cubic meter", "cubic meters"),
+        (&["°C"], "degree Celsius", "degrees Celsius"),
+        (&["°F"], "degree Fahrenheit", "degrees Fahrenheit"),
+        (&["°"], "degree", "degrees"),
+        (&["%"], "percent", "percent"),
+    ];
+
+    units.iter()
+        .flat_map(|(symbols, name, plural)| symbols.iter().map(|symbol| (symbol.to_string(), Unit::new(name, plural))))
+        .collect()
+}
+
+static MEASUREMENT: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(^|[\s(])(-?(?:\d{1,3}(?:,\d{3})+|\d+)(?:\.\d+)?)\s?([A-Za-zµ°%²³]+)(?:/([A-Za-z²³]+))?").unwrap()
+});
+
+/// Expands `5kg`, `100 km/h` and `-20°C`. Compound units that aren't in the table
+/// are read as `<unit> per <unit>`, anything else with an unknown unit is left as is,
+/// as are decades such as `1990s` and single-letter units glued to a four-digit number.
+pub fn expand_units(text: &str, normalizer: &TextNormalizer) -> String {
+    MEASUREMENT.replace_all(text, |captures: &Captures| {
+        let unchanged = captures[0].to_string();
+        let end = captures.get(0).map(|m| m.end()).unwrap_or(text.len());
+        if text[end..].starts_with(char::is_alphanumeric) {
+            return unchanged;
+        }
+        let Some(amount) = number(&captures[2]) else {
+            return unchanged;
+        };
+        // `1990s`, `80s` or `1800h` glued together are decades and times, not measurements.
+        let glued = captures.get(2).unwrap().end() == captures.get(3).unwrap().start();
+        if glued && captures.get(4).is_none() && captures[3].len() == 1 {
+            let year_like = captures[2].len() == 4 && captures[2].bytes().all(|b| b.is_ascii_digit());
+            if &captures[3] == "s" || year_like {
+                return unchanged;
+            }
+        }
+        let singular = captures[2].trim_start_matches('-') == "1";
+
+        let unit_name = |unit: &Unit, singular: bool| if singular { unit.name.clone() } else { unit.plural.clone() };
+        let spoken_unit = match captures.get(4) {
+            Some(per) => {
+                let compound = format!("{}/{}", &captures[3], per.as_str());
+                match normalizer.units.get(&compound) {
+                    Some(unit) => unit_name(unit, singular),
+                    None => match (normalizer.units.get(&captures[3]), normalizer.units.get(per.as_str())) {
+                        (Some(unit), Some(per)) => format!("{} per {}", unit_name(unit, singular), per.name),
+                        _ => return unchanged,
+                    },
+                }
+            }
+            None => match normalizer.units.get(&captures[3]) {
+                Some(unit) => unit_name(unit, singular),
+                None => return unchanged,
+            },
+        };
+
+        format!("{}{} {}", &captures[1], amount, spoken_unit)
+    }).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(text: &str) -> String {
+        expand_units(text, &TextNormalizer::default())
+    }
+
+    #[test]
+    fn simple_and_compound_units() {
+        assert_eq!(expand("5kg"), "five kilograms");
+        assert_eq!(expand("5 km/h"), "five kilometers per hour");
+        assert_eq!(expand("100 km/h"), "one hundred kilometers per hour");
+        assert_eq!(expand("2 GB/s"), "two gigabytes per second");
+        assert_eq!(expand("20°C"), "twenty degrees Celsius");
+    }
+
+    #[test]
+    fn singular_and_plural_units() {
+        assert_eq!(expand("1 kg"), "one kilogram");
+        assert_eq!(expand("1 km/h"), "one kilometer per hour");
+        assert_eq!(expand("1.5 kg"), "one point five kilograms");
+        assert_eq!(expand("-1°C"), "minus one degree Celsius");
+        assert_eq!(expand("1,000 m"), "one thousand meters");
+    }
+
+    #[test]
+    fn unknown_units_are_left_alone() {
+        assert_eq!(expand("5 apples"), "5 apples");
+        assert_eq!(expand("3D"), "3D");
+    }
+
+    #[test]
+    fn decades_are_not_durations() {
+        assert_eq!(expand("Music of the 1990s"), "Music of the 1990s");
+        assert_eq!(expand("the 80s"), "the 80s");
+        assert_eq!(expand("the '80s"), "the '80s");
+        assert_eq!(expand("at 1800h"), "at 1800h");
+        assert_eq!(expand("80 s"), "eighty seconds");
+        assert_eq!(expand("5m"), "five meters");
+    }
+}
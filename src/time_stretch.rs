@@ -0,0 +1,134 @@
+use std::f32::consts::PI;
+
+use crate::{audio, error::Result};
+
+/// Range of tempo factors that still sound natural.
+pub const TEMPO_RANGE: std::ops::RangeInclusive<f32> = 0.5..=2.0;
+/// Pitch shifts up to an octave either way, the tempo range allows no more.
+pub const PITCH_RANGE: std::ops::RangeInclusive<f32> = -12.0..=12.0;
+/// Analysis frame, long enough to span a couple of pitch periods.
+const FRAME_SECONDS: f64 = 0.02;
+/// How far a frame may move from its nominal position to line up with the
+/// previous one.
+const TOLERANCE_SECONDS: f64 = 0.01;
+
+/// WSOLA time-stretch: changes the duration by `1 / tempo` without changing
+/// the pitch, so `tempo = 1.5` plays 50% faster. Frames are overlap-added at a
+/// fixed hop, each taken from near its nominal input position where it best
+/// continues the previous frame's waveform, which avoids the phasing of plain
+/// overlap-add.
+pub fn time_stretch(waveform: &[f32], sample_rate: u64, tempo: f32) -> Vec<f32> {
+    if waveform.is_empty() || tempo == 1.0 {
+        return waveform.to_vec();
+    }
+
+    let hop = ((FRAME_SECONDS * sample_rate as f64) as usize / 2).max(8);
+    let frame = 2 * hop;
+    let tolerance = (TOLERANCE_SECONDS * sample_rate as f64) as usize;
+    let analysis_hop = hop as f64 * tempo as f64;
+    // A periodic Hann window at 50% overlap sums to exactly one.
+    let window: Vec<f32> = (0..frame)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / frame as f32).cos())
+        .collect();
+
+    let out_len = (waveform.len() as f64 / tempo as f64).round() as usize;
+    let frames = out_len.div_ceil(hop) + 1;
+    // Leading `hop` zeros let the first output samples get a full overlap,
+    // `tolerance` more on both sides keep every candidate frame in bounds.
+    let front = tolerance + hop;
+    let last_start = tolerance + ((frames - 1) as f64 * analysis_hop).round() as usize + tolerance;
+    let back = (last_start + frame + hop).saturating_sub(front + waveform.len());
+    let input: Vec<f32> = std::iter::repeat_n(0.0, front)
+        .chain(waveform.iter().copied())
+        .chain(std::iter::repeat_n(0.0, back))
+        .collect();
+
+    let mut output = vec![0.0; (frames - 1) * hop + frame];
+    let mut previous = tolerance;
+    for k in 0..frames {
+        let nominal = tolerance + (k as f64 * analysis_hop).round() as usize;
+        let start = match k {
+            0 => nominal,
+            _ => {
+                let natural = &input[previous + hop..previous + hop + frame];
+                (nominal - tolerance..=nominal + tolerance)
+                    .map(|candidate| (candidate, correlation(natural, &input[candidate..candidate + frame])))
+                    .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                    .map_or(nominal, |(candidate, _)| candidate)
+            }
+        };
+        let out = &mut output[k * hop..k * hop + frame];
+        for ((sample, &value), &weight) in out.iter_mut().zip(&input[start..start + frame]).zip(&window) {
+            *sample += value * weight;
+        }
+        previous = start;
+    }
+
+    output.drain(..hop);
+    output.truncate(out_len);
+    output
+}
+
+/// Shifts the pitch by `semitones` keeping the duration: the audio is
+/// stretched by the pitch ratio, then resampled back to its length.
+pub fn pitch_shift(waveform: &[f32], sample_rate: u64, semitones: f32) -> Result<Vec<f32>> {
+    if waveform.is_empty() || semitones == 0.0 {
+        return Ok(waveform.to_vec());
+    }
+    let ratio = 2f64.powf(semitones as f64 / 12.0);
+    // Rounding the intermediate rate to 50 Hz (a few cents at most) keeps the
+    // FFT resampler's rate ratio small.
+    let stretched_rate = ((sample_rate as f64 * ratio / 50.0).round() as u64 * 50).max(50);
+    let stretched = time_stretch(waveform, sample_rate, (sample_rate as f64 / stretched_rate as f64) as f32);
+    audio::resample(&stretched, stretched_rate, sample_rate)
+}
+
+fn correlation(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u64 = 16000;
+
+    fn tone(len: usize) -> Vec<f32> {
+        (0..len).map(|i| (2.0 * PI * 220.0 * i as f32 / SAMPLE_RATE as f32).sin() * 0.5).collect()
+    }
+
+    #[test]
+    fn output_length_follows_tempo() {
+        let waveform = tone(SAMPLE_RATE as usize);
+        for tempo in [0.5, 1.0, 2.0] {
+            let stretched = time_stretch(&waveform, SAMPLE_RATE, tempo);
+            let expected = waveform.len() as f32 / tempo;
+            assert!((stretched.len() as f32 - expected).abs() <= 1.0, "tempo {}: {} samples", tempo, stretched.len());
+        }
+    }
+
+    #[test]
+    fn unit_tempo_passes_through() {
+        let waveform = tone(1000);
+        assert_eq!(time_stretch(&waveform, SAMPLE_RATE, 1.0), waveform);
+    }
+
+    #[test]
+    fn stretching_keeps_the_level() {
+        let waveform = tone(SAMPLE_RATE as usize);
+        let stretched = time_stretch(&waveform, SAMPLE_RATE, 0.5);
+        let peak = stretched.iter().fold(0.0f32, |peak, sample| peak.max(sample.abs()));
+        assert!((0.4..=0.6).contains(&peak), "peak {}", peak);
+    }
+
+    #[test]
+    fn empty_and_short_inputs() {
+        for tempo in [0.5, 1.0, 2.0] {
+            assert!(time_stretch(&[], SAMPLE_RATE, tempo).is_empty());
+            for len in [1, 2, 7, 100] {
+                let stretched = time_stretch(&tone(len), SAMPLE_RATE, tempo);
+                assert_eq!(stretched.len(), (len as f32 / tempo).round() as usize);
+            }
+        }
+    }
+}
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+
+use crate::alignment::ChunkAlignment;
+
+/// The 15 mouth shapes of the Oculus lip-sync set, serialized under their
+/// Oculus names (`sil`, `PP`, `FF`, ..., `U`).
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Viseme {
+    #[serde(rename = "sil")]
+    Sil,
+    #[serde(rename = "PP")]
+    Pp,
+    #[serde(rename = "FF")]
+    Ff,
+    #[serde(rename = "TH")]
+    Th,
+    #[serde(rename = "DD")]
+    Dd,
+    #[serde(rename = "kk")]
+    Kk,
+    #[serde(rename = "CH")]
+    Ch,
+    #[serde(rename = "SS")]
+    Ss,
+    #[serde(rename = "nn")]
+    Nn,
+    #[serde(rename = "RR")]
+    Rr,
+    #[serde(rename = "aa")]
+    Aa,
+    E,
+    I,
+    O,
+    U,
+}
+
+impl Viseme {
+    /// The viseme of an IPA phoneme, `None` for symbols that only modify the
+    /// phoneme before or after them (padding, stress, length, diacritics).
+    pub fn from_phoneme(phoneme: &str) -> Option<Self> {
+        let c = phoneme.chars().next()?;
+        let viseme = match c {
+            '_' | 'ˈ' | 'ˌ' | 'ː' | 'ˑ' | '\u{0300}'..='\u{036f}' => return None,
+            'p' | 'b' | 'm' | 'ɱ' => Viseme::Pp,
+            'f' | 'v' | 'ʋ' => Viseme::Ff,
+            'θ' | 'ð' => Viseme::Th,
+            't' | 'd' | 'ɾ' | 'ʈ' | 'ɖ' => Viseme::Dd,
+            'k' | 'g' | 'ɡ' | 'q' | 'x' | 'ɣ' | 'χ' | 'h' | 'ɦ' | 'ʔ' => Viseme::Kk,
+            'ʃ' | 'ʒ' | 'ɕ' | 'ʑ' | 'ç' | 'ʝ' => Viseme::Ch,
+            's' | 'z' | 'ʂ' | 'ʐ' => Viseme::Ss,
+            'n' | 'l' | 'ɲ' | 'ŋ' | 'ɳ' | 'ɫ' | 'ʎ' | 'ɭ' => Viseme::Nn,
+            'r' | 'ɹ' | 'ɻ' | 'ʁ' | 'ʀ' | 'ɚ' | 'ɝ' => Viseme::Rr,
+            'a' | 'ɑ' | 'æ' | 'ʌ' | 'ɐ' | 'ɶ' => Viseme::Aa,
+            'e' | 'ɛ' | 'ə' | 'ɜ' | 'ɘ' | 'ø' | 'œ' => Viseme::E,
+            'i' | 'ɪ' | 'j' | 'y' | 'ʏ' | 'ɨ' => Viseme::I,
+            'o' | 'ɔ' | 'ɒ' | 'ɤ' => Viseme::O,
+            'u' | 'ʊ' | 'w' | 'ɯ' | 'ʉ' => Viseme::U,
+            _ => Viseme::Sil,
+        };
+        Some(viseme)
+    }
+
+    /// Position in the Oculus viseme list, `sil` being 0.
+    pub fn index(self) -> u8 {
+        self as u8
+    }
+}
+
+/// A viseme held from `start` to `end`, in seconds from the start of the audio.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct VisemeEvent {
+    pub viseme: Viseme,
+    pub start: f64,
+    pub end: f64,
+}
+
+/// Gaps shorter than this between chunks are rounding, not pauses.
+const GAP_SECONDS: f64 = 1e-6;
+
+/// Turns phoneme timings into a gapless viseme stream, merging repeats and
+/// filling the pauses between chunks with `sil`.
+pub fn visemes(alignment: &[ChunkAlignment]) -> Vec<VisemeEvent> {
+    let mut events: Vec<VisemeEvent> = Vec::new();
+    for chunk in alignment {
+        let mut current = Viseme::Sil;
+        for timing in &chunk.timings {
+            current = Viseme::from_phoneme(&timing.phoneme).unwrap_or(current);
+            if let Some(last) = events.last().copied() && timing.start - last.end > GAP_SECONDS {
+                push(&mut events, Viseme::Sil, last.end, timing.start);
+            }
+            push(&mut events, current, timing.start, timing.start + timing.duration);
+        }
+    }
+    events
+}
+
+fn push(events: &mut Vec<VisemeEvent>, viseme: Viseme, start: f64, end: f64) {
+    match events.last_mut() {
+        Some(last) if last.viseme == viseme => last.end = end,
+        _ => events.push(VisemeEvent { viseme, start, end }),
+    }
+}
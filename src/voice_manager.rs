@@ -0,0 +1,114 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::{
+    error::{PiperError, Result},
+    model_handler::Model,
+    session::SessionOptions,
+};
+
+/// Most voices kept loaded at once unless configured otherwise.
+pub const DEFAULT_MAX_LOADED: usize = 2;
+
+/// Model and config paths of a registered voice.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VoiceFiles {
+    pub model: String,
+    pub config: String,
+}
+
+/// Voices known by name, loaded on first use and evicted least recently used
+/// first once more than `max_loaded` are in memory. Used through
+/// [`Synthesizer::with_voices`](crate::Synthesizer::with_voices), which holds
+/// the active voice while the manager keeps the idle ones.
+pub struct VoiceManager {
+    voices: HashMap<String, VoiceFiles>,
+    /// Loaded voices not in use, most recently used first.
+    idle: VecDeque<(String, Model)>,
+    max_loaded: usize,
+    session_options: SessionOptions,
+}
+
+impl Default for VoiceManager {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_LOADED)
+    }
+}
+
+impl VoiceManager {
+    /// `max_loaded` counts the active voice too, so it is at least 1.
+    pub fn new(max_loaded: usize) -> Self {
+        Self {
+            voices: HashMap::new(),
+            idle: VecDeque::new(),
+            max_loaded: max_loaded.max(1),
+            session_options: SessionOptions::default(),
+        }
+    }
+
+    /// Session options the voices are loaded with.
+    pub fn with_session_options(mut self, session_options: SessionOptions) -> Self {
+        self.session_options = session_options;
+        self
+    }
+
+    /// Registers a voice, the config defaulting to `<model>.json`.
+    pub fn with_voice(mut self, name: impl Into<String>, model: impl Into<String>, config: Option<String>) -> Self {
+        self.add_voice(name, model, config);
+        self
+    }
+
+    /// Registers a voice, replacing any registered under the same name.
+    pub fn add_voice(&mut self, name: impl Into<String>, model: impl Into<String>, config: Option<String>) {
+        let name = name.into();
+        let model = model.into();
+        let config = config.unwrap_or_else(|| format!("{}.json", model));
+        self.idle.retain(|(loaded, _)| *loaded != name);
+        self.voices.insert(name, VoiceFiles { model, config });
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.voices.contains_key(name)
+    }
+
+    pub fn files(&self, name: &str) -> Option<&VoiceFiles> {
+        self.voices.get(name)
+    }
+
+    /// Names of the registered voices, sorted.
+    pub fn voices(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.voices.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    /// Names of the idle voices still in memory, most recently used first.
+    pub fn idle(&self) -> impl Iterator<Item = &str> {
+        self.idle.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub fn max_loaded(&self) -> usize {
+        self.max_loaded
+    }
+
+    pub fn session_options(&self) -> &SessionOptions {
+        &self.session_options
+    }
+
+    /// Takes the voice out of the manager, loading it if it is not in memory.
+    pub fn take(&mut self, name: &str) -> Result<Model> {
+        if let Some(i) = self.idle.iter().position(|(loaded, _)| loaded == name)
+            && let Some((_, model)) = self.idle.remove(i) {
+            return Ok(model);
+        }
+        let files = self.voices.get(name)
+            .ok_or_else(|| PiperError::InvalidConfig(format!("unknown voice `{}`", name)))?;
+        Model::with_options(&files.model, &files.config, &self.session_options)
+    }
+
+    /// Returns a voice no longer in use, evicting the least recently used
+    /// ones beyond `max_loaded`.
+    pub fn release(&mut self, name: impl Into<String>, model: Model) {
+        self.idle.push_front((name.into(), model));
+        self.idle.truncate(self.max_loaded - 1);
+    }
+}
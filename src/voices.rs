@@ -0,0 +1,103 @@
+use std::{
+    env,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::error::{PiperError, Result};
+
+/// A voice found on disk, an `.onnx` model with its `.onnx.json` config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalVoice {
+    /// The model's file name without `.onnx`, e.g. `en_US-norman-medium`.
+    pub name: String,
+    pub model: PathBuf,
+    pub config: PathBuf,
+}
+
+/// Suffix of quantized copies of a voice, e.g. `en_US-norman-medium.int8.onnx`.
+const QUANTIZED_SUFFIX: &str = ".int8";
+
+/// The config of `model`, `<model>.json`. A quantized `<name>.int8.onnx`
+/// without a config of its own shares `<name>.onnx.json` with the original.
+pub fn config_path(model: &Path) -> PathBuf {
+    let config = PathBuf::from(format!("{}.json", model.display()));
+    let original = model.to_str()
+        .and_then(|model| model.strip_suffix(".onnx"))
+        .and_then(|stem| stem.strip_suffix(QUANTIZED_SUFFIX))
+        .map(|stem| PathBuf::from(format!("{}.onnx.json", stem)));
+    match original {
+        Some(original) if !config.is_file() && original.is_file() => original,
+        _ => config,
+    }
+}
+
+/// `./voices`, where `download` puts voices, then `$XDG_DATA_HOME/piper`
+/// (`~/.local/share/piper` when unset).
+pub fn default_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![PathBuf::from("voices")];
+    let data_home = env::var_os("XDG_DATA_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".local/share")));
+    dirs.extend(data_home.map(|dir| dir.join("piper")));
+    dirs
+}
+
+/// Every voice under `dirs` and their subdirectories, sorted by name. When
+/// two share a name the one from the earlier directory is kept.
+pub fn discover(dirs: &[PathBuf]) -> Vec<LocalVoice> {
+    let mut voices = Vec::new();
+    for dir in dirs {
+        scan(dir, &mut voices);
+    }
+    voices.sort_by(|a, b| a.name.cmp(&b.name));
+    voices.dedup_by(|b, a| a.name == b.name);
+    voices
+}
+
+fn scan(dir: &Path, voices: &mut Vec<LocalVoice>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut paths: Vec<PathBuf> = entries.filter_map(|entry| entry.ok().map(|entry| entry.path())).collect();
+    paths.sort();
+    for path in paths {
+        if path.is_dir() {
+            scan(&path, voices);
+            continue;
+        }
+        let Some(name) = path.file_name().and_then(|name| name.to_str()).and_then(|name| name.strip_suffix(".onnx")) else {
+            continue;
+        };
+        let config = config_path(&path);
+        if config.is_file() {
+            voices.push(LocalVoice { name: name.to_string(), model: path.clone(), config });
+        }
+    }
+}
+
+/// The voice called `name` under `dirs`.
+pub fn find(name: &str, dirs: &[PathBuf]) -> Option<LocalVoice> {
+    discover(dirs).into_iter().find(|voice| voice.name == name)
+}
+
+/// Model and config paths for `model`, which is either a path to an `.onnx`
+/// file or the name of a voice under `dirs`. `config` overrides the
+/// config path, which otherwise is found by [`config_path`].
+pub fn resolve(model: &str, config: Option<&str>, dirs: &[PathBuf]) -> Result<(String, String)> {
+    let config_for = |model: &str| config.map_or_else(
+        || config_path(Path::new(model)).to_string_lossy().into_owned(),
+        str::to_string,
+    );
+    if Path::new(model).is_file() {
+        return Ok((model.to_string(), config_for(model)));
+    }
+    let voice = find(model, dirs).ok_or_else(|| {
+        let searched: Vec<String> = dirs.iter().map(|dir| dir.display().to_string()).collect();
+        PiperError::InvalidConfig(format!("no model file or voice named `{}` in {}", model, searched.join(", ")))
+    })?;
+    let model = voice.model.to_string_lossy().into_owned();
+    let config = config.map_or_else(|| voice.config.to_string_lossy().into_owned(), str::to_string);
+    Ok((model, config))
+}